@@ -0,0 +1,33 @@
+// Timings for creating, reading back, and converting large literals. Run with
+// `cargo bench --bench literal` and with `--features rayon` to compare the parallel versions.
+use anyhow::Result;
+
+const ELEMENTS: usize = 1 << 28;
+const ITERATIONS: usize = 5;
+
+fn bench<T>(name: &str, mut f: impl FnMut() -> Result<T>) -> Result<()> {
+    // Warmup run, this also makes sure that the memory has been paged in.
+    f()?;
+    let start = std::time::Instant::now();
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(f()?);
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    let gb_per_s = (ELEMENTS * 4) as f64 / elapsed.as_secs_f64() / 1e9;
+    println!("{name:<12} {elapsed:>10.2?} {gb_per_s:>8.2} GB/s");
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    println!("rayon feature enabled: {}", cfg!(feature = "rayon"));
+    let data: Vec<f32> = (0..ELEMENTS).map(|i| i as f32).collect();
+    bench("vec1", || Ok(xla::Literal::vec1(&data)))?;
+    let literal = xla::Literal::vec1(&data);
+    bench("to_vec", || Ok(literal.to_vec::<f32>()?))?;
+    let mut dst = xla::Literal::create_from_shape(xla::PrimitiveType::F32, &[ELEMENTS])?;
+    bench("copy_raw_from", || Ok(dst.copy_raw_from(&data)?))?;
+    bench("to_bf16", || Ok(literal.to_bf16()?))?;
+    let bf16 = literal.to_bf16()?;
+    bench("upcast_to_f32", || Ok(bf16.upcast_to_f32()?))?;
+    Ok(())
+}
@@ -1,9 +1,10 @@
 extern crate bindgen;
 
+use sha2::{Digest, Sha256};
 use std::env;
 use std::fs;
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 enum OS {
@@ -32,6 +33,9 @@ fn get_download_url(os: OS) -> &'static str {
         OS::Linux if cfg!(feature = "cuda") && cfg!(target_arch = "x86_64") => {
             "https://github.com/elixir-nx/xla/releases/download/v0.8.0/xla_extension-0.8.0-x86_64-linux-gnu-cuda12.tar.gz"
         }
+        OS::Linux if cfg!(feature = "rocm") && cfg!(target_arch = "x86_64") => {
+            "https://github.com/elixir-nx/xla/releases/download/v0.8.0/xla_extension-0.8.0-x86_64-linux-gnu-rocm.tar.gz"
+        }
         OS::Linux if cfg!(feature = "tpu") && cfg!(target_arch = "x86_64") => {
             "https://github.com/elixir-nx/xla/releases/download/v0.8.0/xla_extension-0.8.0-x86_64-linux-gnu-tpu.tar.gz"
         }
@@ -51,6 +55,75 @@ fn get_download_url(os: OS) -> &'static str {
     }
 }
 
+fn download<P: AsRef<Path>>(url: &str, dst: P) {
+    let dst = dst.as_ref();
+    // Download to a temporary file first so that an interrupted download does not get picked up
+    // by the next build.
+    let tmp_path = dst.with_extension("partial");
+    let response =
+        ureq::get(url).call().unwrap_or_else(|e| panic!("Failed to download {url}: {e}"));
+    let mut file = fs::File::create(&tmp_path).expect("Failed to create XLA extension archive");
+    std::io::copy(&mut response.into_reader(), &mut file)
+        .expect("Failed to download XLA extension");
+    fs::rename(&tmp_path, dst).expect("Failed to write XLA extension archive");
+}
+
+fn sha256<P: AsRef<Path>>(path: P) -> String {
+    let file = fs::File::open(path.as_ref()).expect("Failed to open XLA extension archive");
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = reader.read(&mut buf).expect("Failed to read XLA extension archive");
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Extract a .tar.gz archive, stripping the top-level directory, i.e. the equivalent of
+// `tar -xzf archive -C dst --strip-components=1`. The archive is extracted to a temporary
+// directory first which is then renamed so that an interrupted extraction does not get picked
+// up by the next build.
+fn extract<P: AsRef<Path>, Q: AsRef<Path>>(archive: P, dst: Q) {
+    let final_dst = dst.as_ref();
+    let mut tmp_dst = final_dst.as_os_str().to_owned();
+    tmp_dst.push(".partial");
+    let tmp_dst = PathBuf::from(tmp_dst);
+    if tmp_dst.exists() {
+        fs::remove_dir_all(&tmp_dst).expect("Failed to remove partial XLA extension directory");
+    }
+    let dst = tmp_dst.as_path();
+    fs::create_dir_all(dst).expect("Failed to create XLA extension directory");
+    let file = fs::File::open(archive.as_ref()).expect("Failed to open XLA extension archive");
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(BufReader::new(file)));
+    archive.set_preserve_permissions(true);
+    for entry in archive.entries().expect("Failed to read XLA extension archive") {
+        let mut entry = entry.expect("Failed to read XLA extension archive");
+        let path = entry.path().expect("Invalid path in XLA extension archive").into_owned();
+        let path: PathBuf = path.components().skip(1).collect();
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        if path.components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+            panic!("Unexpected path in XLA extension archive {}", path.display())
+        }
+        let path = dst.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create XLA extension directory");
+        }
+        entry.unpack(&path).expect("Failed to extract XLA extension");
+    }
+    // The destination may exist as an empty directory, `rename` only replaces empty directories
+    // on some platforms.
+    if final_dst.exists() {
+        fs::remove_dir(final_dst).expect("Failed to replace the XLA extension directory");
+    }
+    fs::rename(dst, final_dst).expect("Failed to move the XLA extension directory");
+}
+
 fn make_shared_lib<P: AsRef<Path>>(os: OS, xla_dir: P) {
     println!("cargo:rerun-if-changed=xla_rs/xla_rs.cc");
     println!("cargo:rerun-if-changed=xla_rs/xla_rs.h");
@@ -78,40 +151,40 @@ fn env_var_rerun(name: &str) -> Option<String> {
 }
 
 fn main() {
+    // Only the types that do not depend on the C++ library are built without the `ffi` feature.
+    if env::var("CARGO_FEATURE_FFI").is_err() {
+        return;
+    }
+    if env::var("CARGO_CFG_TARGET_ARCH").as_deref() == Ok("wasm32") {
+        panic!("The XLA library is not available on wasm32, disable the default features and enable the `stub` feature instead")
+    }
     let os = OS::get();
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     let xla_dir = env_var_rerun("XLA_EXTENSION_DIR")
         .map_or_else(|| out_path.join("xla_extension"), PathBuf::from);
 
     if !xla_dir.exists() || fs::read_dir(&xla_dir).unwrap().next().is_none() {
-        let download_path = out_path.join("xla_extension.tar.gz");
-        if !download_path.exists() {
-            let download_url = get_download_url(os);
-
-            Command::new("curl")
-                .arg("-L")
-                .arg("-o")
-                .arg(&download_path)
-                .arg(download_url)
-                .status()
-                .expect("Failed to download XLA extension");
+        // A pre-downloaded archive can be provided for offline or sandboxed builds.
+        let archive_path = match env_var_rerun("XLA_EXTENSION_ARCHIVE") {
+            Some(archive_path) => PathBuf::from(archive_path),
+            None => {
+                let download_path = out_path.join("xla_extension.tar.gz");
+                if !download_path.exists() {
+                    download(get_download_url(os), &download_path)
+                }
+                download_path
+            }
+        };
+        if let Some(expected) = env_var_rerun("XLA_EXTENSION_SHA256") {
+            let sha256 = sha256(&archive_path);
+            if !sha256.eq_ignore_ascii_case(expected.trim()) {
+                panic!(
+                    "Checksum mismatch for {}, expected {expected}, got {sha256}",
+                    archive_path.display()
+                )
+            }
         }
-
-        Command::new("mkdir")
-            .arg("-p")
-            .arg(&xla_dir)
-            .status()
-            .expect("Failed to create XLA extension directory");
-
-        Command::new("tar")
-            .arg("-xzvf")
-            .arg(&download_path)
-            .arg("-C")
-            .arg(&xla_dir)
-            .arg("--strip-components=1")
-            .status()
-            .expect("Failed to extract XLA extension");
-
+        extract(&archive_path, &xla_dir);
         env::set_var("XLA_EXTENSION_DIR", &xla_dir);
     }
 
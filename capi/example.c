@@ -0,0 +1,77 @@
+// Compile and run examples/fn_hlo.txt through the C API, from the root of the repo:
+//   cargo build --release -p xla-capi
+//   cc capi/example.c -Icapi -Ltarget/release -lxla_rs -o /tmp/xla_rs_example
+//   LD_LIBRARY_PATH=target/release /tmp/xla_rs_example examples/fn_hlo.txt
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "xla_rs.h"
+
+static int check(xla_rs_status *status) {
+  if (status == NULL) return 0;
+  fprintf(stderr, "error: %s\n", xla_rs_status_message(status));
+  xla_rs_status_free(status);
+  return 1;
+}
+
+static char *read_file(const char *path, size_t *len) {
+  FILE *file = fopen(path, "rb");
+  if (file == NULL) return NULL;
+  fseek(file, 0, SEEK_END);
+  *len = ftell(file);
+  fseek(file, 0, SEEK_SET);
+  char *data = malloc(*len);
+  if (data != NULL && fread(data, 1, *len, file) != *len) {
+    free(data);
+    data = NULL;
+  }
+  fclose(file);
+  return data;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s fn_hlo.txt\n", argv[0]);
+    return 1;
+  }
+  size_t len = 0;
+  char *hlo = read_file(argv[1], &len);
+  if (hlo == NULL) {
+    fprintf(stderr, "cannot read %s\n", argv[1]);
+    return 1;
+  }
+
+  xla_rs_client *client = NULL;
+  xla_rs_executable *exe = NULL;
+  if (check(xla_rs_client_cpu(&client))) return 1;
+  if (check(xla_rs_client_compile_hlo(client, (const uint8_t *)hlo, len,
+                                      XLA_RS_HLO_TEXT, &exe)))
+    return 1;
+  free(hlo);
+  // The executable keeps the client alive.
+  xla_rs_client_free(client);
+
+  const int32_t f32 = 11;
+  const int64_t dims[2] = {2, 2};
+  const float x_data[4] = {1, 2, 3, 4};
+  const float y_data[4] = {1, 1, 1, 1};
+  xla_rs_literal *x = NULL, *y = NULL;
+  if (check(xla_rs_literal_create(f32, dims, 2, x_data, sizeof(x_data), &x))) return 1;
+  if (check(xla_rs_literal_create(f32, dims, 2, y_data, sizeof(y_data), &y))) return 1;
+
+  const xla_rs_literal *args[2] = {x, y};
+  xla_rs_literal **outputs = NULL;
+  size_t num_outputs = 0;
+  if (check(xla_rs_executable_execute(exe, args, 2, &outputs, &num_outputs))) return 1;
+  for (size_t i = 0; i < num_outputs; ++i) {
+    float values[4];
+    if (check(xla_rs_literal_copy_to(outputs[i], values, sizeof(values)))) return 1;
+    printf("output %zu: %f %f %f %f\n", i, values[0], values[1], values[2], values[3]);
+  }
+
+  xla_rs_literals_free(outputs, num_outputs);
+  xla_rs_literal_free(x);
+  xla_rs_literal_free(y);
+  xla_rs_executable_free(exe);
+  return 0;
+}
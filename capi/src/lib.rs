@@ -0,0 +1,2 @@
+//! The C API of the xla crate, see `xla_rs.h` for the declarations.
+pub use xla::capi::*;
@@ -0,0 +1,73 @@
+// C API of the xla crate, the functions are exported by the libraries built from this
+// directory with `cargo build --release`, i.e. `libxla_rs.so` and `libxla_rs.a`.
+//
+// All handles are owned by the caller and released with the matching `_free` function. An
+// executable keeps its client alive, so the client can be released first. Fallible functions
+// return NULL on success, otherwise a status holding an error message that has to be released
+// with `xla_rs_status_free`.
+//
+// Handles are not thread safe. A client and the executables compiled with it must all be used
+// and released on the thread that created the client. Literals and statuses can be moved to
+// other threads but must not be used by multiple threads at the same time.
+#ifndef XLA_RS_H
+#define XLA_RS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct xla_rs_status xla_rs_status;
+typedef struct xla_rs_client xla_rs_client;
+typedef struct xla_rs_executable xla_rs_executable;
+typedef struct xla_rs_literal xla_rs_literal;
+
+// The serialization formats accepted by `xla_rs_client_compile_hlo`.
+#define XLA_RS_HLO_TEXT 0
+#define XLA_RS_HLO_PROTO 1
+#define XLA_RS_HLO_PROTO_TEXT 2
+
+const char *xla_rs_status_message(const xla_rs_status *status);
+void xla_rs_status_free(xla_rs_status *status);
+
+xla_rs_status *xla_rs_client_cpu(xla_rs_client **out);
+xla_rs_status *xla_rs_client_gpu(double memory_fraction, bool preallocate,
+                                 xla_rs_client **out);
+void xla_rs_client_free(xla_rs_client *client);
+size_t xla_rs_client_device_count(const xla_rs_client *client);
+xla_rs_status *xla_rs_client_compile_hlo(const xla_rs_client *client,
+                                         const uint8_t *data, size_t len,
+                                         int format, xla_rs_executable **out);
+
+void xla_rs_executable_free(xla_rs_executable *executable);
+// Runs on the first device, tuple results are returned as separate outputs. The
+// output array is released with `xla_rs_literals_free`.
+xla_rs_status *xla_rs_executable_execute(const xla_rs_executable *executable,
+                                         const xla_rs_literal *const *args,
+                                         size_t num_args,
+                                         xla_rs_literal ***outputs,
+                                         size_t *num_outputs);
+void xla_rs_literals_free(xla_rs_literal **literals, size_t len);
+
+// `primitive_type` is the numeric value of the `PrimitiveType` enum from
+// `xla_data.proto`, e.g. 11 for F32.
+xla_rs_status *xla_rs_literal_create(int32_t primitive_type, const int64_t *dims,
+                                     size_t rank, const void *data,
+                                     size_t size_in_bytes, xla_rs_literal **out);
+void xla_rs_literal_free(xla_rs_literal *literal);
+xla_rs_status *xla_rs_literal_shape(const xla_rs_literal *literal,
+                                    int32_t *primitive_type, size_t *rank);
+xla_rs_status *xla_rs_literal_dims(const xla_rs_literal *literal, int64_t *dims,
+                                   size_t rank);
+size_t xla_rs_literal_size_bytes(const xla_rs_literal *literal);
+xla_rs_status *xla_rs_literal_copy_to(const xla_rs_literal *literal, void *dst,
+                                      size_t size_in_bytes);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,26 @@
+# Runs a small HLO module through the python bindings, build them first with
+# `maturin develop` from this directory.
+import numpy as np
+import xla
+
+HLO = """
+HloModule add_one, entry_computation_layout={(f32[2,3]{1,0})->f32[2,3]{1,0}}
+
+ENTRY main {
+  x = f32[2,3]{1,0} parameter(0)
+  one = f32[] constant(1)
+  ones = f32[2,3]{1,0} broadcast(one), dimensions={}
+  ROOT sum = f32[2,3]{1,0} add(x, ones)
+}
+"""
+
+client = xla.PjRtClient.cpu()
+print(f"platform: {client.platform_name}, devices: {client.device_count}")
+exe = client.compile(xla.XlaComputation.from_hlo_text(HLO))
+x = np.arange(6, dtype=np.float32).reshape(2, 3)
+(result,) = exe.execute([x])
+assert result.shape == [2, 3] and result.element_type == "F32"
+np.testing.assert_allclose(np.asarray(result), x + 1)
+(result,) = exe.execute([xla.Literal(x)])
+np.testing.assert_allclose(result.to_numpy(), x + 1)
+print("ok")
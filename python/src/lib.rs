@@ -0,0 +1,6 @@
+use pyo3::prelude::*;
+
+#[pymodule]
+fn xla(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    ::xla::python::register(m)
+}
@@ -0,0 +1,81 @@
+//! Conversions from Arrow arrays and record batches to literals.
+//!
+//! This module is only available with the `arrow` feature. Only primitive numeric arrays without
+//! null values are supported. The values are copied directly from the Arrow buffers into the
+//! literal, without going through an intermediate vector.
+use crate::{ElementType, Error, Literal, Result};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::DataType;
+
+fn element_type(data_type: &DataType) -> Result<ElementType> {
+    let ty = match data_type {
+        DataType::Int8 => ElementType::S8,
+        DataType::Int16 => ElementType::S16,
+        DataType::Int32 => ElementType::S32,
+        DataType::Int64 => ElementType::S64,
+        DataType::UInt8 => ElementType::U8,
+        DataType::UInt16 => ElementType::U16,
+        DataType::UInt32 => ElementType::U32,
+        DataType::UInt64 => ElementType::U64,
+        DataType::Float16 => ElementType::F16,
+        DataType::Float32 => ElementType::F32,
+        DataType::Float64 => ElementType::F64,
+        data_type => Err(Error::Arrow(format!("unsupported data type {data_type}")))?,
+    };
+    Ok(ty)
+}
+
+// Call `f` on the raw bytes of the values of a primitive array.
+fn with_array_bytes<R>(
+    array: &dyn Array,
+    f: impl FnOnce(ElementType, &[u8]) -> Result<R>,
+) -> Result<R> {
+    let ty = element_type(array.data_type())?;
+    if array.null_count() > 0 {
+        Err(Error::Arrow(format!("the array contains {} null values", array.null_count())))?
+    }
+    let data = array.to_data();
+    let size = ty.element_size_in_bytes();
+    let start = data.offset() * size;
+    let bytes = &data.buffers()[0].as_slice()[start..start + data.len() * size];
+    f(ty, bytes)
+}
+
+/// Copy a primitive Arrow array into a rank 1 literal.
+pub fn literal_from_array(array: &dyn Array) -> Result<Literal> {
+    with_array_bytes(array, |ty, bytes| {
+        Literal::create_from_shape_and_untyped_data(ty, &[array.len()], bytes)
+    })
+}
+
+/// Copy some columns of a record batch into a rank 2 literal with dimensions
+/// `(num_rows, columns.len())`. All the columns must have the same data type.
+pub fn literal_from_columns(batch: &RecordBatch, columns: &[&str]) -> Result<Literal> {
+    let arrays = columns
+        .iter()
+        .map(|name| {
+            batch.column_by_name(name).ok_or_else(|| Error::Arrow(format!("no column {name}")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let ty = match arrays.first() {
+        None => Err(Error::Arrow("no columns to convert".to_string()))?,
+        Some(array) => element_type(array.data_type())?,
+    };
+    let (nrows, ncols) = (batch.num_rows(), arrays.len());
+    let size = ty.element_size_in_bytes();
+    let mut literal = Literal::create_from_shape(ty.primitive_type(), &[nrows, ncols])?;
+    let dst = literal.raw_bytes_mut();
+    for (col, (name, array)) in columns.iter().zip(arrays.iter()).enumerate() {
+        with_array_bytes(array.as_ref(), |array_ty, bytes| {
+            if array_ty != ty {
+                Err(Error::Arrow(format!("column {name} has type {array_ty:?}, expected {ty:?}")))?
+            }
+            for (row, value) in bytes.chunks_exact(size).enumerate() {
+                let offset = (row * ncols + col) * size;
+                dst[offset..offset + size].copy_from_slice(value)
+            }
+            Ok(())
+        })?
+    }
+    Ok(literal)
+}
@@ -0,0 +1,107 @@
+//! Measuring the latency of a computation in a reproducible way.
+//!
+//! [`run`] compiles a computation, performs some warmup executions, and then times each of the
+//! following executions. The time for an execution includes waiting for all of its outputs to be
+//! ready on the device but does not include transferring the arguments, these are passed as
+//! device buffers. The number of floating point operations is taken from the compiler cost
+//! analysis when available.
+use crate::{PjRtBuffer, PjRtClient, Result, XlaComputation};
+use std::time::{Duration, Instant};
+
+/// The options used by [`run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchOptions {
+    /// The number of executions performed before starting the measurements.
+    pub warmup_iterations: usize,
+    /// The number of timed executions, at least one execution is always timed.
+    pub iterations: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self { warmup_iterations: 5, iterations: 100 }
+    }
+}
+
+/// The latency statistics returned by [`run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub iterations: usize,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// The number of floating point operations for a single execution, as estimated by the
+    /// compiler.
+    pub flops: Option<f64>,
+}
+
+impl BenchReport {
+    /// The achieved floating point operations per second based on the mean latency.
+    pub fn flops_per_second(&self) -> Option<f64> {
+        let flops = self.flops?;
+        let secs = self.mean.as_secs_f64();
+        if secs > 0. {
+            Some(flops / secs)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} iterations, mean {:.2?}, p50 {:.2?}, p99 {:.2?}, min {:.2?}, max {:.2?}",
+            self.iterations, self.mean, self.p50, self.p99, self.min, self.max
+        )?;
+        if let Some(flops_per_second) = self.flops_per_second() {
+            write!(f, ", {:.2} GFLOP/s", flops_per_second / 1e9)?
+        }
+        Ok(())
+    }
+}
+
+// The value at quantile `q` of some sorted durations, using the nearest rank.
+fn quantile(sorted: &[Duration], q: f64) -> Duration {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Compile `computation` on `client` and time its executions on `args`.
+pub fn run(
+    client: &PjRtClient,
+    computation: &XlaComputation,
+    args: &[&PjRtBuffer],
+    options: &BenchOptions,
+) -> Result<BenchReport> {
+    let iterations = options.iterations.max(1);
+    let exe = client.compile(computation)?;
+    let run_once = || -> Result<Duration> {
+        let start = Instant::now();
+        let outputs = exe.execute_b(args)?;
+        for output in outputs.iter().flatten() {
+            output.block_until_ready()?
+        }
+        Ok(start.elapsed())
+    };
+    for _ in 0..options.warmup_iterations {
+        run_once()?;
+    }
+    let mut durations = (0..iterations).map(|_| run_once()).collect::<Result<Vec<_>>>()?;
+    durations.sort();
+    let total: Duration = durations.iter().sum();
+    // Not all the backends implement the cost analysis.
+    let flops = exe.cost_analysis().ok().and_then(|cost| cost.get("flops").copied());
+    Ok(BenchReport {
+        iterations,
+        mean: total / iterations as u32,
+        p50: quantile(&durations, 0.5),
+        p99: quantile(&durations, 0.99),
+        min: durations[0],
+        max: durations[durations.len() - 1],
+        flops,
+    })
+}
@@ -0,0 +1,243 @@
+//! Padding of variable length sequences to a fixed set of lengths.
+//!
+//! Compiling a computation for each possible sequence length is not practical, instead the
+//! inputs are padded to the smallest of a few bucket lengths and one executable is compiled per
+//! bucket upfront. A padding mask tells the computation which positions are valid.
+//!
+//! ```ignore
+//! let buckets = xla::bucketing::Buckets::powers_of_two(16, 512)?;
+//! let exe = xla::bucketing::BucketedExecutable::new(&client, buckets, 0, |builder, len| {
+//!     let tokens = builder.parameter(0, xla::ElementType::S32, &[len as i64], "tokens")?;
+//!     let mask = builder.parameter(1, xla::ElementType::Pred, &[len as i64], "mask")?;
+//!     builder.build(&model(&tokens, &mask)?)
+//! })?;
+//! // The tokens are padded to 32 and a mask with 20 valid positions is passed.
+//! let outputs = exe.execute_with_mask(&[tokens_of_len_20])?;
+//! ```
+use crate::{
+    ElementType, Error, Literal, NativeType, PjRtBuffer, PjRtClient, PjRtLoadedExecutable, Result,
+    XlaBuilder, XlaComputation,
+};
+
+/// A sorted set of sequence lengths that the inputs are padded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buckets {
+    lengths: Vec<usize>,
+}
+
+impl Buckets {
+    /// Buckets with the given lengths, duplicates are removed.
+    pub fn new(lengths: &[usize]) -> Result<Self> {
+        let mut lengths = lengths.to_vec();
+        lengths.sort_unstable();
+        lengths.dedup();
+        if lengths.is_empty() || lengths[0] == 0 {
+            let dims = lengths.iter().map(|l| *l as i64).collect();
+            Err(Error::InvalidDims { dims, msg: "bucket lengths must be positive and non-empty" })?
+        }
+        Ok(Self { lengths })
+    }
+
+    /// The powers of two between `min` and `max`, `max` is always included so that sequences of
+    /// up to `max` elements fit in a bucket.
+    pub fn powers_of_two(min: usize, max: usize) -> Result<Self> {
+        let mut lengths: Vec<usize> =
+            std::iter::successors(Some(min.max(1).next_power_of_two()), |l| l.checked_mul(2))
+                .take_while(|l| *l < max)
+                .collect();
+        lengths.push(max);
+        Self::new(&lengths)
+    }
+
+    pub fn lengths(&self) -> &[usize] {
+        &self.lengths
+    }
+
+    /// The longest sequence length that fits in a bucket.
+    pub fn max_len(&self) -> usize {
+        self.lengths[self.lengths.len() - 1]
+    }
+
+    /// The index of the smallest bucket that can hold a sequence of length `len`.
+    pub fn index(&self, len: usize) -> Result<usize> {
+        match self.lengths.binary_search(&len) {
+            Ok(index) => Ok(index),
+            Err(index) if index < self.lengths.len() => Ok(index),
+            Err(_) => Err(Error::SequenceTooLong { len, max: self.max_len() }),
+        }
+    }
+
+    /// The length of the smallest bucket that can hold a sequence of length `len`.
+    pub fn bucket_len(&self, len: usize) -> Result<usize> {
+        Ok(self.lengths[self.index(len)?])
+    }
+}
+
+/// Pad `literal` with zeros along dimension `dim` so that this dimension has size `len`.
+pub fn pad_literal(literal: &Literal, dim: usize, len: usize) -> Result<Literal> {
+    let shape = literal.array_shape()?;
+    let dims: Vec<usize> = shape.dims().iter().map(|d| *d as usize).collect();
+    if dim >= dims.len() {
+        Err(Error::IndexOutOfBounds { index: dim as i64, rank: dims.len() })?
+    }
+    if dims[dim] > len {
+        Err(Error::SequenceTooLong { len: dims[dim], max: len })?
+    }
+    let ty = shape.ty();
+    let outer: usize = dims[..dim].iter().product();
+    // The number of bytes in a single position of the padded dimension.
+    let inner = dims[dim + 1..].iter().product::<usize>() * ty.element_size_in_bytes();
+    let data = literal.untyped_data();
+    let mut padded = vec![0u8; outer * len * inner];
+    if dims[dim] * inner > 0 {
+        for (src, dst) in data.chunks(dims[dim] * inner).zip(padded.chunks_mut(len * inner)) {
+            dst[..src.len()].copy_from_slice(src)
+        }
+    }
+    let mut padded_dims = dims;
+    padded_dims[dim] = len;
+    Literal::create_from_shape_and_untyped_data(ty, &padded_dims, &padded)
+}
+
+/// A `PRED` vector of length `bucket_len` where only the first `len` positions are true.
+pub fn padding_mask(len: usize, bucket_len: usize) -> Result<Literal> {
+    batch_padding_mask(&[len], bucket_len)?.reshape(&[bucket_len as i64])
+}
+
+/// A `PRED` array of shape `(lens.len(), bucket_len)` where row `i` has `lens[i]` leading true
+/// positions.
+pub fn batch_padding_mask(lens: &[usize], bucket_len: usize) -> Result<Literal> {
+    let mut mask = vec![0u8; lens.len() * bucket_len];
+    for (&len, row) in lens.iter().zip(mask.chunks_mut(bucket_len.max(1))) {
+        if len > bucket_len {
+            Err(Error::SequenceTooLong { len, max: bucket_len })?
+        }
+        row[..len].fill(1)
+    }
+    Literal::create_from_shape_and_untyped_data(ElementType::Pred, &[lens.len(), bucket_len], &mask)
+}
+
+/// A batch of token sequences padded to a common length, e.g. the ids returned by a tokenizer.
+#[derive(Debug)]
+pub struct TokenBatch {
+    /// The tokens, of shape `(batch_size, len)`.
+    pub tokens: Literal,
+    /// The padding mask of shape `(batch_size, len)`, see [`batch_padding_mask`].
+    pub mask: Literal,
+    /// The length of each sequence before padding.
+    pub lens: Vec<usize>,
+}
+
+impl TokenBatch {
+    /// Pad the `sequences` with `pad_id` to the length of the longest one, or to the length of
+    /// the smallest bucket that holds it when `buckets` is set. The tokens are converted to `T`,
+    /// usually `i32` or `i64`.
+    pub fn new<T, S>(sequences: &[S], pad_id: u32, buckets: Option<&Buckets>) -> Result<Self>
+    where
+        T: NativeType + TryFrom<u32>,
+        S: AsRef<[u32]>,
+    {
+        let lens: Vec<usize> = sequences.iter().map(|s| s.as_ref().len()).collect();
+        let max_len = lens.iter().copied().max().unwrap_or(0);
+        let len = match buckets {
+            Some(buckets) => buckets.bucket_len(max_len)?,
+            None => max_len,
+        };
+        let convert = |token: u32| {
+            T::try_from(token).map_err(|_| Error::TokenOutOfRange { token, ty: T::TY })
+        };
+        let pad = convert(pad_id)?;
+        let mut tokens = vec![pad; sequences.len() * len];
+        for (sequence, row) in sequences.iter().zip(tokens.chunks_mut(len.max(1))) {
+            for (&token, dst) in sequence.as_ref().iter().zip(row.iter_mut()) {
+                *dst = convert(token)?
+            }
+        }
+        let tokens = Literal::vec1(&tokens).reshape(&[sequences.len() as i64, len as i64])?;
+        let mask = batch_padding_mask(&lens, len)?;
+        Ok(Self { tokens, mask, lens })
+    }
+}
+
+/// One executable per bucket, the arguments are padded to the bucket of their sequence length
+/// before running the matching executable.
+pub struct BucketedExecutable {
+    buckets: Buckets,
+    seq_dim: usize,
+    exes: Vec<PjRtLoadedExecutable>,
+}
+
+impl BucketedExecutable {
+    /// Compile the computations returned by `build` for each bucket length. The arguments are
+    /// padded along dimension `seq_dim`.
+    pub fn new<F>(client: &PjRtClient, buckets: Buckets, seq_dim: usize, build: F) -> Result<Self>
+    where
+        F: Fn(&XlaBuilder, usize) -> Result<XlaComputation>,
+    {
+        let exes = buckets
+            .lengths()
+            .iter()
+            .map(|&len| {
+                let builder = XlaBuilder::new(&format!("bucket_{len}"));
+                client.compile(&build(&builder, len)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { buckets, seq_dim, exes })
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    /// The bucket length and the executable used for sequences of length `len`.
+    pub fn executable(&self, len: usize) -> Result<(usize, &PjRtLoadedExecutable)> {
+        let index = self.buckets.index(len)?;
+        Ok((self.buckets.lengths[index], &self.exes[index]))
+    }
+
+    // The common sequence length of the arguments and the arguments padded to their bucket.
+    fn pad_args(&self, args: &[Literal]) -> Result<(usize, usize, Vec<Literal>)> {
+        let lens = args
+            .iter()
+            .map(|arg| {
+                let dims = arg.array_shape()?.dims().to_vec();
+                match dims.get(self.seq_dim) {
+                    Some(len) => Ok(*len as usize),
+                    None => Err(Error::IndexOutOfBounds {
+                        index: self.seq_dim as i64,
+                        rank: dims.len(),
+                    }),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let len = match lens.first() {
+            None => Err(Error::EmptyOpList { op: "bucketed execute" })?,
+            Some(len) => *len,
+        };
+        if lens.iter().any(|l| *l != len) {
+            let dims = lens.iter().map(|l| *l as i64).collect();
+            Err(Error::InvalidDims { dims, msg: "the arguments have different sequence lengths" })?
+        }
+        let bucket_len = self.buckets.bucket_len(len)?;
+        let args = args
+            .iter()
+            .map(|arg| pad_literal(arg, self.seq_dim, bucket_len))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((len, bucket_len, args))
+    }
+
+    /// Pad the arguments, which must share the same sequence length, and run the executable of
+    /// their bucket.
+    pub fn execute(&self, args: &[Literal]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let (_, bucket_len, args) = self.pad_args(args)?;
+        self.executable(bucket_len)?.1.execute::<Literal>(&args)
+    }
+
+    /// Like [`BucketedExecutable::execute`] but a [`padding_mask`] is passed as an additional
+    /// last argument.
+    pub fn execute_with_mask(&self, args: &[Literal]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let (len, bucket_len, mut args) = self.pad_args(args)?;
+        args.push(padding_mask(len, bucket_len)?);
+        self.executable(bucket_len)?.1.execute::<Literal>(&args)
+    }
+}
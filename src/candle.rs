@@ -0,0 +1,168 @@
+//! Conversions between candle tensors and literals or device buffers.
+//!
+//! This module is only available with the `candle` feature. For tensors stored on the CPU, the
+//! data is read directly from the tensor storage when the tensor is contiguous so the only copy
+//! is the one into the literal or the device buffer. With the `cuda` feature, a tensor stored on
+//! the CUDA device of a GPU client is not copied at all: [`buffer_from_tensor`] creates a view on
+//! the tensor memory and keeps the tensor storage alive until the buffer is dropped. The candle
+//! device is synchronized first so that the kernels writing the tensor have completed before XLA
+//! reads it. Tensors stored on other candle devices are first copied to the CPU.
+//!
+//! The conversions from buffers to tensors always go through host memory, candle does not
+//! provide a way to create a CUDA tensor backed by memory that it has not allocated.
+use crate::{ElementType, Error, Literal, PjRtBuffer, PjRtClient, PjRtDevice, Result};
+use candle_core::{CpuStorage, DType, Device, Storage, Tensor};
+
+fn element_type(dtype: DType) -> ElementType {
+    match dtype {
+        DType::U8 => ElementType::U8,
+        DType::U32 => ElementType::U32,
+        DType::I64 => ElementType::S64,
+        DType::BF16 => ElementType::Bf16,
+        DType::F16 => ElementType::F16,
+        DType::F32 => ElementType::F32,
+        DType::F64 => ElementType::F64,
+    }
+}
+
+fn dtype(ty: ElementType) -> Result<DType> {
+    let dtype = match ty {
+        ElementType::U8 => DType::U8,
+        ElementType::U32 => DType::U32,
+        ElementType::S64 => DType::I64,
+        ElementType::Bf16 => DType::BF16,
+        ElementType::F16 => DType::F16,
+        ElementType::F32 => DType::F32,
+        ElementType::F64 => DType::F64,
+        ty => Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "candle" })?,
+    };
+    Ok(dtype)
+}
+
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+// Call `f` on the raw bytes of `tensor` in row major order.
+fn with_tensor_bytes<R>(tensor: &Tensor, f: impl FnOnce(&[u8]) -> Result<R>) -> Result<R> {
+    if !tensor.device().is_cpu() {
+        return with_tensor_bytes(&tensor.to_device(&Device::Cpu)?, f);
+    }
+    let (storage, layout) = tensor.storage_and_layout();
+    let (start, end) = match layout.contiguous_offsets() {
+        Some(offsets) => offsets,
+        None => {
+            drop(storage);
+            return with_tensor_bytes(&tensor.contiguous()?, f);
+        }
+    };
+    let bytes = match &*storage {
+        Storage::Cpu(CpuStorage::U8(v)) => as_bytes(&v[start..end]),
+        Storage::Cpu(CpuStorage::U32(v)) => as_bytes(&v[start..end]),
+        Storage::Cpu(CpuStorage::I64(v)) => as_bytes(&v[start..end]),
+        Storage::Cpu(CpuStorage::BF16(v)) => as_bytes(&v[start..end]),
+        Storage::Cpu(CpuStorage::F16(v)) => as_bytes(&v[start..end]),
+        Storage::Cpu(CpuStorage::F32(v)) => as_bytes(&v[start..end]),
+        Storage::Cpu(CpuStorage::F64(v)) => as_bytes(&v[start..end]),
+        _ => unreachable!("the tensor has been moved to the cpu"),
+    };
+    f(bytes)
+}
+
+/// Copy a candle tensor into a literal with the same element type and dimensions.
+pub fn literal_from_tensor(tensor: &Tensor) -> Result<Literal> {
+    let ty = element_type(tensor.dtype());
+    with_tensor_bytes(tensor, |bytes| {
+        Literal::create_from_shape_and_untyped_data(ty, tensor.dims(), bytes)
+    })
+}
+
+/// Create a device buffer from a candle tensor, see the [module documentation](self) for when
+/// the tensor memory is used without copying it. If the device is not specified, the default
+/// device of the client is used.
+pub fn buffer_from_tensor(
+    client: &PjRtClient,
+    tensor: &Tensor,
+    device: Option<&PjRtDevice>,
+) -> Result<PjRtBuffer> {
+    #[cfg(feature = "cuda")]
+    if let Some(buffer) = cuda_buffer_view(client, tensor, device)? {
+        return Ok(buffer);
+    }
+    let ty = element_type(tensor.dtype());
+    with_tensor_bytes(tensor, |bytes| {
+        client.buffer_from_host_raw_bytes(ty, bytes, tensor.dims(), device)
+    })
+}
+
+// A buffer that is a view on the memory of a CUDA tensor, `None` is returned when the tensor is
+// not stored on the CUDA device used by the buffer.
+#[cfg(feature = "cuda")]
+fn cuda_buffer_view(
+    client: &PjRtClient,
+    tensor: &Tensor,
+    device: Option<&PjRtDevice>,
+) -> Result<Option<PjRtBuffer>> {
+    use candle_core::cuda_backend::{cudarc::driver::DevicePtr, CudaStorageSlice};
+    use candle_core::DeviceLocation;
+
+    let gpu_id = match tensor.device().location() {
+        DeviceLocation::Cuda { gpu_id } => gpu_id,
+        _ => return Ok(None),
+    };
+    let hardware_id = match device {
+        Some(device) => Some(device.local_hardware_id()),
+        None => client.devices().first().map(|d| d.local_hardware_id()),
+    };
+    let on_gpu_client = matches!(client.platform_name().as_str(), "cuda" | "gpu");
+    if !on_gpu_client || hardware_id != Some(gpu_id) {
+        return Ok(None);
+    }
+    let tensor = tensor.contiguous()?;
+    // XLA does not use the candle stream, wait for the pending kernels writing to the tensor.
+    tensor.device().synchronize()?;
+    let ptr = {
+        let (storage, layout) = tensor.storage_and_layout();
+        let slice = match &*storage {
+            Storage::Cuda(storage) => &storage.slice,
+            _ => return Ok(None),
+        };
+        let base = match slice {
+            CudaStorageSlice::U8(v) => *v.device_ptr(),
+            CudaStorageSlice::U32(v) => *v.device_ptr(),
+            CudaStorageSlice::I64(v) => *v.device_ptr(),
+            CudaStorageSlice::BF16(v) => *v.device_ptr(),
+            CudaStorageSlice::F16(v) => *v.device_ptr(),
+            CudaStorageSlice::F32(v) => *v.device_ptr(),
+            CudaStorageSlice::F64(v) => *v.device_ptr(),
+        };
+        base + (layout.start_offset() * tensor.dtype().size_in_bytes()) as u64
+    };
+    let ty = element_type(tensor.dtype());
+    let dims = tensor.dims().to_vec();
+    // The tensor is moved into the deleter so that its storage outlives the buffer.
+    let buffer = unsafe {
+        client.buffer_from_device_pointer(
+            ptr as *mut libc::c_void,
+            ty,
+            &dims,
+            device,
+            move || drop(tensor),
+        )?
+    };
+    Ok(Some(buffer))
+}
+
+/// Create a candle tensor on `device` from an array literal.
+pub fn tensor_from_literal(literal: &Literal, device: &Device) -> Result<Tensor> {
+    let shape = literal.array_shape()?;
+    let dims: Vec<_> = shape.dims().iter().map(|d| *d as usize).collect();
+    let tensor =
+        Tensor::from_raw_buffer(&literal.untyped_data(), dtype(shape.ty())?, &dims, device)?;
+    Ok(tensor)
+}
+
+/// Copy a device buffer back to the host and create a candle tensor on `device` from it.
+pub fn tensor_from_buffer(buffer: &PjRtBuffer, device: &Device) -> Result<Tensor> {
+    tensor_from_literal(&buffer.to_literal_sync()?, device)
+}
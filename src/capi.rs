@@ -0,0 +1,385 @@
+//! A C API exposing client creation, compilation of serialized HLO modules, and execution, so
+//! that applications written in other languages can embed this crate rather than the raw
+//! xla_extension API.
+//!
+//! The declarations are in `capi/xla_rs.h` and the `capi` directory builds a shared and a static
+//! library exporting these functions. All the handles are owned by the caller and have to be
+//! released with the matching `_free` function, handles do not borrow from each other: an
+//! executable keeps its client alive so the client handle can be freed first.
+//!
+//! The handles are not thread safe: a client and the executables compiled with it share a
+//! reference count that is not atomic, so these handles must all be used and freed from the
+//! thread that created the client. Literal and status handles do not reference a client and can
+//! be moved to other threads, as long as each handle is only used by one thread at a time.
+//!
+//! Fallible functions return a null status on success, otherwise the status holds an error
+//! message and has to be freed with [`xla_rs_status_free`]. Rust panics are caught and reported
+//! as errors rather than unwinding into the caller.
+use crate::{
+    Error, HloModuleProto, Literal, PjRtClient, PjRtLoadedExecutable, PrimitiveType, Result, Shape,
+    XlaComputation,
+};
+use std::ffi::{c_char, c_int, c_void, CString};
+
+/// The HLO module is in the HLO text format.
+pub const XLA_RS_HLO_TEXT: c_int = 0;
+/// The HLO module is a binary serialized `HloModuleProto`.
+pub const XLA_RS_HLO_PROTO: c_int = 1;
+/// The HLO module is a `HloModuleProto` in the protobuf text format.
+pub const XLA_RS_HLO_PROTO_TEXT: c_int = 2;
+
+/// An error returned to the C caller.
+pub struct Status(CString);
+
+fn status(err: String) -> *mut Status {
+    let bytes: Vec<u8> = err.bytes().filter(|b| *b != 0).collect();
+    Box::into_raw(Box::new(Status(CString::new(bytes).unwrap_or_default())))
+}
+
+// Run `f`, converting both its errors and its panics to a status.
+fn guard<F: FnOnce() -> Result<()>>(f: F) -> *mut Status {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(Ok(())) => std::ptr::null_mut(),
+        Ok(Err(err)) => status(err.to_string()),
+        Err(panic) => {
+            let msg = match panic.downcast_ref::<&str>() {
+                Some(msg) => msg.to_string(),
+                None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+            };
+            status(format!("panic in the xla C API: {msg}"))
+        }
+    }
+}
+
+fn non_null<T>(ptr: *const T, name: &'static str) -> Result<()> {
+    if ptr.is_null() {
+        Err(Error::CApiNullPointer { name })?
+    }
+    Ok(())
+}
+
+// Store a value for the caller, `out` has already been checked to be non-null.
+unsafe fn write_out<T>(out: *mut *mut T, value: T) {
+    *out = Box::into_raw(Box::new(value))
+}
+
+unsafe fn free<T>(ptr: *mut T) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(ptr))
+    }
+}
+
+/// The error message of a status, the string is owned by the status.
+///
+/// # Safety
+/// `status` must be a status returned by this API that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_status_message(status: *const Status) -> *const c_char {
+    if status.is_null() {
+        return std::ptr::null();
+    }
+    (*status).0.as_ptr()
+}
+
+/// # Safety
+/// `status` must be null or a status returned by this API that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_status_free(status: *mut Status) {
+    free(status)
+}
+
+/// Create a client for the host CPU.
+///
+/// # Safety
+/// `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_client_cpu(out: *mut *mut PjRtClient) -> *mut Status {
+    guard(|| {
+        non_null(out, "out")?;
+        write_out(out, PjRtClient::cpu()?);
+        Ok(())
+    })
+}
+
+/// Create a client for the GPU devices, see [`PjRtClient::gpu`].
+///
+/// # Safety
+/// `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_client_gpu(
+    memory_fraction: f64,
+    preallocate: bool,
+    out: *mut *mut PjRtClient,
+) -> *mut Status {
+    guard(|| {
+        non_null(out, "out")?;
+        write_out(out, PjRtClient::gpu(memory_fraction, preallocate)?);
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `client` must be null or a client returned by this API that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_client_free(client: *mut PjRtClient) {
+    free(client)
+}
+
+/// The number of devices of the client, zero for a null client.
+///
+/// # Safety
+/// `client` must be null or a client returned by this API that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_client_device_count(client: *const PjRtClient) -> usize {
+    match client.as_ref() {
+        None => 0,
+        Some(client) => client.device_count(),
+    }
+}
+
+/// Parse a serialized HLO module and compile it, `format` is one of the `XLA_RS_HLO_*`
+/// constants.
+///
+/// # Safety
+/// `client` must be a client returned by this API, `data` must point to `len` readable bytes,
+/// and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_client_compile_hlo(
+    client: *const PjRtClient,
+    data: *const u8,
+    len: usize,
+    format: c_int,
+    out: *mut *mut PjRtLoadedExecutable,
+) -> *mut Status {
+    guard(|| {
+        non_null(client, "client")?;
+        non_null(data, "data")?;
+        non_null(out, "out")?;
+        let data = std::slice::from_raw_parts(data, len);
+        let proto = match format {
+            XLA_RS_HLO_TEXT => HloModuleProto::parse_and_return_unverified_module(data)?,
+            XLA_RS_HLO_PROTO => HloModuleProto::parse_proto(data, true)?,
+            XLA_RS_HLO_PROTO_TEXT => HloModuleProto::parse_proto(data, false)?,
+            format => Err(Error::CApiInvalidArgument { name: "format", value: format as i64 })?,
+        };
+        let executable = (*client).compile(&XlaComputation::from_proto(&proto))?;
+        write_out(out, executable);
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `executable` must be null or an executable returned by this API that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_executable_free(executable: *mut PjRtLoadedExecutable) {
+    free(executable)
+}
+
+/// Run an executable on the first device and copy the results back to the host. The outputs
+/// are written to a newly allocated array of `*num_outputs` literals that has to be released
+/// with [`xla_rs_literals_free`], when the computation returns a tuple its elements are
+/// returned as separate outputs.
+///
+/// # Safety
+/// `executable` must be an executable returned by this API, `args` must point to `num_args`
+/// valid literals, and `outputs` and `num_outputs` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_executable_execute(
+    executable: *const PjRtLoadedExecutable,
+    args: *const *const Literal,
+    num_args: usize,
+    outputs: *mut *mut *mut Literal,
+    num_outputs: *mut usize,
+) -> *mut Status {
+    guard(|| {
+        non_null(executable, "executable")?;
+        non_null(outputs, "outputs")?;
+        non_null(num_outputs, "num_outputs")?;
+        let args: &[*const Literal] = if num_args == 0 {
+            &[]
+        } else {
+            non_null(args, "args")?;
+            std::slice::from_raw_parts(args, num_args)
+        };
+        let args = args
+            .iter()
+            .map(|arg| match arg.as_ref() {
+                None => Err(Error::CApiNullPointer { name: "args" }),
+                Some(arg) => Ok(arg),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let buffers = (*executable).execute::<&Literal>(&args)?;
+        let mut literals = vec![];
+        for buffer in buffers.into_iter().next().unwrap_or_default() {
+            let literal = buffer.to_literal_sync()?;
+            match literal.shape()? {
+                Shape::Tuple(_) => literals.extend(literal.to_tuple()?),
+                _ => literals.push(literal),
+            }
+        }
+        let literals: Box<[*mut Literal]> =
+            literals.into_iter().map(|l| Box::into_raw(Box::new(l))).collect();
+        *num_outputs = literals.len();
+        *outputs = Box::into_raw(literals) as *mut *mut Literal;
+        Ok(())
+    })
+}
+
+/// Release an array of literals returned by [`xla_rs_executable_execute`] together with the
+/// literals that it contains.
+///
+/// # Safety
+/// `literals` and `len` must be the values returned by [`xla_rs_executable_execute`].
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literals_free(literals: *mut *mut Literal, len: usize) {
+    if literals.is_null() {
+        return;
+    }
+    let literals = Box::from_raw(std::ptr::slice_from_raw_parts_mut(literals, len));
+    for literal in literals.iter() {
+        free(*literal)
+    }
+}
+
+/// Create an array literal by copying `size_in_bytes` bytes from `data`, `primitive_type` is
+/// the numeric value of the `PrimitiveType` enum from `xla_data.proto`.
+///
+/// # Safety
+/// `dims` must point to `rank` values, `data` to `size_in_bytes` readable bytes, and `out`
+/// must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literal_create(
+    primitive_type: i32,
+    dims: *const i64,
+    rank: usize,
+    data: *const c_void,
+    size_in_bytes: usize,
+    out: *mut *mut Literal,
+) -> *mut Status {
+    guard(|| {
+        non_null(out, "out")?;
+        let ty = match num_traits::FromPrimitive::from_i32(primitive_type) {
+            Some(ty) => PrimitiveType::element_type(ty)?,
+            None => Err(Error::UnexpectedElementType(primitive_type))?,
+        };
+        let dims: &[i64] = if rank == 0 {
+            &[]
+        } else {
+            non_null(dims, "dims")?;
+            std::slice::from_raw_parts(dims, rank)
+        };
+        let dims = dims
+            .iter()
+            .map(|d| {
+                usize::try_from(*d)
+                    .map_err(|_| Error::CApiInvalidArgument { name: "dims", value: *d })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let data: &[u8] = if size_in_bytes == 0 {
+            &[]
+        } else {
+            non_null(data, "data")?;
+            std::slice::from_raw_parts(data as *const u8, size_in_bytes)
+        };
+        write_out(out, Literal::create_from_shape_and_untyped_data(ty, &dims, data)?);
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `literal` must be null or a literal returned by this API that has not been freed, literals
+/// returned by [`xla_rs_executable_execute`] are released with [`xla_rs_literals_free`].
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literal_free(literal: *mut Literal) {
+    free(literal)
+}
+
+/// The element type and rank of an array literal, the element type is written as the numeric
+/// value of the `PrimitiveType` enum.
+///
+/// # Safety
+/// `literal` must be a literal returned by this API, `primitive_type` and `rank` must be
+/// valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literal_shape(
+    literal: *const Literal,
+    primitive_type: *mut i32,
+    rank: *mut usize,
+) -> *mut Status {
+    guard(|| {
+        non_null(literal, "literal")?;
+        non_null(primitive_type, "primitive_type")?;
+        non_null(rank, "rank")?;
+        let shape = (*literal).array_shape()?;
+        *primitive_type = shape.primitive_type() as i32;
+        *rank = shape.dims().len();
+        Ok(())
+    })
+}
+
+/// Write the dimensions of an array literal to `dims`, which must have room for `rank` values
+/// as returned by [`xla_rs_literal_shape`].
+///
+/// # Safety
+/// `literal` must be a literal returned by this API and `dims` must point to `rank` writable
+/// values.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literal_dims(
+    literal: *const Literal,
+    dims: *mut i64,
+    rank: usize,
+) -> *mut Status {
+    guard(|| {
+        non_null(literal, "literal")?;
+        let shape = (*literal).array_shape()?;
+        if shape.dims().len() != rank {
+            Err(Error::CApiInvalidArgument { name: "rank", value: rank as i64 })?
+        }
+        if rank > 0 {
+            non_null(dims, "dims")?;
+            std::slice::from_raw_parts_mut(dims, rank).copy_from_slice(shape.dims())
+        }
+        Ok(())
+    })
+}
+
+/// The size of the data of a literal in bytes, zero for a null literal.
+///
+/// # Safety
+/// `literal` must be null or a literal returned by this API that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literal_size_bytes(literal: *const Literal) -> usize {
+    match literal.as_ref() {
+        None => 0,
+        Some(literal) => literal.size_bytes(),
+    }
+}
+
+/// Copy the data of an array literal to `dst`, `size_in_bytes` has to be the value returned by
+/// [`xla_rs_literal_size_bytes`].
+///
+/// # Safety
+/// `literal` must be a literal returned by this API and `dst` must point to `size_in_bytes`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn xla_rs_literal_copy_to(
+    literal: *const Literal,
+    dst: *mut c_void,
+    size_in_bytes: usize,
+) -> *mut Status {
+    guard(|| {
+        non_null(literal, "literal")?;
+        let literal = &*literal;
+        // Tuple literals have no data of their own.
+        literal.array_shape()?;
+        if literal.size_bytes() != size_in_bytes {
+            Err(Error::CApiInvalidArgument { name: "size_in_bytes", value: size_in_bytes as i64 })?
+        }
+        if size_in_bytes > 0 {
+            non_null(dst, "dst")?;
+            let dst = std::slice::from_raw_parts_mut(dst as *mut u8, size_in_bytes);
+            dst.copy_from_slice(&literal.untyped_data())
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,278 @@
+//! Saving and restoring device buffers.
+//!
+//! A checkpoint is a zip archive holding a set of named arrays. For each array, the archive
+//! contains a `{name}/metadata` entry with the element type and dimensions, followed by the raw
+//! little-endian data split into `{name}/{chunk_index}` entries of at most
+//! [`SaveOptions::chunk_size_in_bytes`] bytes. The chunks can optionally be compressed with
+//! deflate or zstd. Checkpoint files that have been compressed as a whole with zstd, e.g. before
+//! being sent over the network, are detected via the zstd magic number and can be loaded directly,
+//! they are decompressed to a temporary file rather than in memory.
+//!
+//! In a multi-host setting, each process saves the buffers that it can address to its own shard,
+//! [`shard_path`] returns a naming scheme for these shards so that each process can find its
+//! shard again when restoring.
+use crate::serialize::convert_values;
+use crate::{ElementType, Endianness, Error, PjRtBuffer, PjRtClient, PjRtDevice, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+const METADATA: &str = "metadata";
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The options used by [`save`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// The maximum size of each data chunk in the archive.
+    pub chunk_size_in_bytes: usize,
+    /// Compress the data chunks with deflate.
+    pub compress: bool,
+    /// Compress the data chunks with zstd at the given level, this takes precedence over
+    /// `compress`.
+    pub zstd_level: Option<i32>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self { chunk_size_in_bytes: 64 << 20, compress: false, zstd_level: None }
+    }
+}
+
+/// The element type and dimensions of an array stored in a checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayInfo {
+    pub ty: ElementType,
+    pub dims: Vec<usize>,
+    chunk_count: usize,
+}
+
+impl ArrayInfo {
+    fn to_metadata(&self) -> String {
+        let dims = self.dims.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+        format!("ty: {:?}\ndims: {dims}\nchunks: {}\n", self.ty, self.chunk_count)
+    }
+
+    fn parse(name: &str, metadata: &str) -> Result<Self> {
+        let err = |msg: &str| Error::Checkpoint(format!("invalid metadata for {name}: {msg}"));
+        let mut fields = HashMap::new();
+        for line in metadata.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                fields.insert(key.trim(), value.trim());
+            }
+        }
+        let field = |key: &str| fields.get(key).copied().ok_or_else(|| err(&format!("no {key}")));
+        let ty = parse_element_type(field("ty")?).ok_or_else(|| err("unknown element type"))?;
+        let dims = match field("dims")? {
+            "" => vec![],
+            dims => dims
+                .split(',')
+                .map(|d| d.parse::<usize>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| err("dims"))?,
+        };
+        let chunk_count = field("chunks")?.parse::<usize>().map_err(|_| err("chunks"))?;
+        let size_in_bytes =
+            dims.iter().try_fold(ty.element_size_in_bytes(), |acc, d| acc.checked_mul(*d));
+        if size_in_bytes.is_none() {
+            Err(err("the array size overflows"))?
+        }
+        Ok(Self { ty, dims, chunk_count })
+    }
+
+    /// The size of the array data in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        // Cannot overflow, this is checked when parsing the metadata.
+        self.dims.iter().product::<usize>() * self.ty.element_size_in_bytes()
+    }
+}
+
+fn parse_element_type(s: &str) -> Option<ElementType> {
+    let ty = match s {
+        "Pred" => ElementType::Pred,
+        "S8" => ElementType::S8,
+        "S16" => ElementType::S16,
+        "S32" => ElementType::S32,
+        "S64" => ElementType::S64,
+        "U8" => ElementType::U8,
+        "U16" => ElementType::U16,
+        "U32" => ElementType::U32,
+        "U64" => ElementType::U64,
+        "F16" => ElementType::F16,
+        "Bf16" => ElementType::Bf16,
+        "F32" => ElementType::F32,
+        "F64" => ElementType::F64,
+        "C64" => ElementType::C64,
+        "C128" => ElementType::C128,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// The path of the checkpoint shard written by process `process_index` out of `process_count`.
+pub fn shard_path<P: AsRef<Path>>(dir: P, process_index: usize, process_count: usize) -> PathBuf {
+    dir.as_ref().join(format!("shard-{process_index:05}-of-{process_count:05}.ckpt"))
+}
+
+/// Copy some named buffers to the host and save them in a checkpoint file. The file is first
+/// written under a temporary name and then renamed so that an interrupted save never leaves a
+/// truncated checkpoint at `path`.
+pub fn save<S: AsRef<str>, P: AsRef<Path>>(
+    path: P,
+    buffers: &[(S, &PjRtBuffer)],
+    options: &SaveOptions,
+) -> Result<()> {
+    if options.chunk_size_in_bytes == 0 {
+        Err(Error::Checkpoint("chunk size cannot be zero".to_string()))?
+    }
+    let path = path.as_ref();
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let mut zip = zip::ZipWriter::new(BufWriter::new(File::create(&tmp_path)?));
+    let (method, level) = match options.zstd_level {
+        Some(level) => (zip::CompressionMethod::Zstd, Some(level)),
+        None if options.compress => (zip::CompressionMethod::Deflated, None),
+        None => (zip::CompressionMethod::Stored, None),
+    };
+    let file_options = zip::write::FileOptions::default()
+        .compression_method(method)
+        .compression_level(level)
+        .large_file(true);
+    for (name, buffer) in buffers.iter() {
+        let name = name.as_ref();
+        let literal = buffer.to_literal_sync()?;
+        let shape = literal.array_shape()?;
+        let mut data = literal.untyped_data();
+        convert_values(&mut data, shape.ty(), Endianness::Little);
+        let info = ArrayInfo {
+            ty: shape.ty(),
+            dims: shape.dims().iter().map(|d| *d as usize).collect(),
+            chunk_count: data.chunks(options.chunk_size_in_bytes).len(),
+        };
+        zip.start_file(format!("{name}/{METADATA}"), file_options)?;
+        zip.write_all(info.to_metadata().as_bytes())?;
+        for (chunk_index, chunk) in data.chunks(options.chunk_size_in_bytes).enumerate() {
+            zip.start_file(format!("{name}/{chunk_index}"), file_options)?;
+            zip.write_all(chunk)?;
+        }
+    }
+    zip.finish()?.flush()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+trait ReadSeek: Read + Seek {}
+impl<R: Read + Seek> ReadSeek for R {}
+
+// A decompressed copy of a checkpoint file, the file is removed when dropped.
+struct TempFile {
+    path: PathBuf,
+    file: BufReader<File>,
+}
+
+impl TempFile {
+    // Decompress a zstd stream to a new temporary file, streaming avoids holding the whole
+    // checkpoint in memory.
+    fn decompress<R: Read>(reader: R) -> Result<Self> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let index = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let name = format!("xla-checkpoint-{}-{index}.tmp", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+        // Constructed right away so that the file gets removed on errors.
+        let mut tmp = Self { path, file: BufReader::new(file) };
+        let mut writer = BufWriter::new(tmp.file.get_mut());
+        std::io::copy(&mut zstd::Decoder::new(reader)?, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        tmp.file.rewind()?;
+        Ok(tmp)
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Open a checkpoint file, files compressed with zstd are decompressed to a temporary file.
+fn open<P: AsRef<Path>>(path: P) -> Result<zip::ZipArchive<Box<dyn ReadSeek>>> {
+    let mut file = BufReader::new(File::open(path.as_ref())?);
+    let mut magic = [0u8; 4];
+    let is_zstd = match file.read_exact(&mut magic) {
+        Ok(()) => magic == ZSTD_MAGIC,
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => false,
+        Err(err) => Err(err)?,
+    };
+    file.rewind()?;
+    let reader: Box<dyn ReadSeek> =
+        if is_zstd { Box::new(TempFile::decompress(file)?) } else { Box::new(file) };
+    Ok(zip::ZipArchive::new(reader)?)
+}
+
+fn read_metadata<R: Read + Seek>(zip: &mut zip::ZipArchive<R>) -> Result<Vec<(String, ArrayInfo)>> {
+    let names: Vec<String> = zip
+        .file_names()
+        .filter_map(|f| f.strip_suffix(&format!("/{METADATA}")).map(|n| n.to_string()))
+        .collect();
+    let mut infos = Vec::with_capacity(names.len());
+    for name in names.into_iter() {
+        let mut metadata = String::new();
+        zip.by_name(&format!("{name}/{METADATA}"))?.read_to_string(&mut metadata)?;
+        let info = ArrayInfo::parse(&name, &metadata)?;
+        infos.push((name, info))
+    }
+    Ok(infos)
+}
+
+/// The names, element types, and dimensions of the arrays stored in a checkpoint file, without
+/// reading the data.
+pub fn read_info<P: AsRef<Path>>(path: P) -> Result<HashMap<String, ArrayInfo>> {
+    Ok(read_metadata(&mut open(path)?)?.into_iter().collect())
+}
+
+/// Load all the arrays from a checkpoint file onto a device, the default device is used if
+/// `device` is `None`.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    client: &PjRtClient,
+    device: Option<&PjRtDevice>,
+) -> Result<HashMap<String, PjRtBuffer>> {
+    let mut zip = open(path)?;
+    let mut buffers = HashMap::new();
+    for (name, info) in read_metadata(&mut zip)?.into_iter() {
+        let size_in_bytes = info.size_in_bytes();
+        // The buffer grows as the chunks are read so that corrupted metadata does not trigger a
+        // huge allocation, reading stops one byte past the expected size.
+        let mut data = vec![];
+        for chunk_index in 0..info.chunk_count {
+            let remaining = (size_in_bytes + 1).saturating_sub(data.len());
+            let chunk = zip.by_name(&format!("{name}/{chunk_index}"))?;
+            chunk.take(remaining as u64).read_to_end(&mut data)?;
+        }
+        if data.len() != size_in_bytes {
+            Err(Error::Checkpoint(format!(
+                "unexpected data size for {name}, expected {size_in_bytes}, got {}",
+                data.len()
+            )))?
+        }
+        convert_values(&mut data, info.ty, Endianness::Little);
+        let buffer = client.buffer_from_host_raw_bytes(info.ty, &data, &info.dims, device)?;
+        buffers.insert(name, buffer);
+    }
+    Ok(buffers)
+}
@@ -0,0 +1,109 @@
+//! Decoding algorithms built as XLA control flow.
+use crate::{ElementType, Result, Shape, XlaBuilder, XlaOp};
+
+/// The configuration used by [`beam_search`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeamSearchConfig {
+    /// The number of beams kept at each step.
+    pub beam_size: usize,
+    /// The length of the generated sequences, including the prompt.
+    pub max_len: usize,
+    /// Once a beam has generated this token, it only gets extended with this same token and its
+    /// score does not change anymore.
+    pub eos_token: Option<i32>,
+}
+
+/// Beam search decoding.
+///
+/// - `prompt` is a `S32` node of shape `(max_len,)` that holds the prompt tokens, the values
+///   after the prompt are ignored.
+/// - `prompt_len` is a scalar `S32` node with the number of tokens in the prompt.
+/// - `step` builds the next token logits. It is called once when building the loop body, with
+///   the current token sequences of shape `(beam_size, max_len)` and with the scalar index of the
+///   position to predict, it should return logits of shape `(beam_size, vocab_size)`.
+///
+/// The whole decoding runs in a single while loop on the device. This returns the token
+/// sequences of shape `(beam_size, max_len)` and their log-probability scores of shape
+/// `(beam_size,)`, both sorted by decreasing score.
+pub fn beam_search<F>(
+    prompt: &XlaOp,
+    prompt_len: &XlaOp,
+    config: &BeamSearchConfig,
+    step: F,
+) -> Result<(XlaOp, XlaOp)>
+where
+    F: Fn(&XlaOp, &XlaOp) -> Result<XlaOp>,
+{
+    let beam_size = config.beam_size as i64;
+    let max_len = config.max_len as i64;
+    let state_shape = Shape::tuple(vec![
+        Shape::array::<i32>(vec![]),
+        Shape::array::<i32>(vec![beam_size, max_len]),
+        Shape::array::<f32>(vec![beam_size]),
+        Shape::array_with_type(ElementType::Pred, vec![beam_size]),
+    ]);
+
+    let cond = {
+        let builder = XlaBuilder::new("beam_search_cond");
+        let state = builder.parameter_s(0, &state_shape, "state")?;
+        state.get_tuple_element(0)?.lt(&builder.c0(max_len as i32)?)?.build()?
+    };
+
+    let body = {
+        let builder = XlaBuilder::new("beam_search_body");
+        let state = builder.parameter_s(0, &state_shape, "state")?;
+        let pos = state.get_tuple_element(0)?;
+        let tokens = state.get_tuple_element(1)?;
+        let scores = state.get_tuple_element(2)?;
+        let finished = state.get_tuple_element(3)?;
+
+        let log_probs = step(&tokens, &pos)?.convert(crate::PrimitiveType::F32)?.log_softmax(-1)?;
+        let vocab_size = log_probs.dims()?[1] as i64;
+        let log_probs = match config.eos_token {
+            None => log_probs,
+            Some(eos_token) => {
+                // Finished beams can only be extended with the eos token, at no cost.
+                let dims = [beam_size, vocab_size];
+                let iota = builder.iota(ElementType::S32, &dims, 1)?;
+                let zero = builder.zero(ElementType::F32)?.broadcast(&dims)?;
+                let min_value = builder.min_finite_value(ElementType::F32)?.broadcast(&dims)?;
+                let eos_row = iota.eq(&builder.c0(eos_token)?)?.select(&zero, &min_value)?;
+                let finished = finished.broadcast_in_dim(&dims, &[0])?;
+                finished.select(&eos_row, &log_probs)?
+            }
+        };
+        let candidates = (log_probs + scores.reshape(&[beam_size, 1])?)?;
+        let candidates = candidates.reshape(&[beam_size * vocab_size])?;
+        let top = candidates.top_k(beam_size)?;
+        let new_scores = top.get_tuple_element(0)?;
+        let indexes = top.get_tuple_element(1)?;
+        let vocab_size_op = builder.c0(vocab_size as i32)?;
+        let beam_indexes = (&indexes / &vocab_size_op)?;
+        let new_tokens = indexes.rem_(&vocab_size_op)?;
+
+        let tokens = tokens.take(&beam_indexes, 0)?;
+        let zero = builder.zero(ElementType::S32)?;
+        let update = new_tokens.reshape(&[beam_size, 1])?;
+        let tokens = tokens.dynamic_update_slice(&update, &[&zero, &pos])?;
+        let finished = finished.take(&beam_indexes, 0)?;
+        let finished = match config.eos_token {
+            None => finished,
+            Some(eos_token) => finished.or(&new_tokens.eq(&builder.c0(eos_token)?)?)?,
+        };
+        let pos = (pos + builder.c0(1i32)?)?;
+        builder.tuple(&[pos, tokens, new_scores, finished])?.build()?
+    };
+
+    let builder = prompt.builder();
+    let tokens = prompt.broadcast(&[beam_size])?;
+    // Only the first beam is active initially so that the first step does not select the same
+    // token multiple times.
+    let iota = builder.iota(ElementType::S32, &[beam_size], 0)?;
+    let zero = builder.zero(ElementType::F32)?.broadcast(&[beam_size])?;
+    let min_value = builder.min_finite_value(ElementType::F32)?.broadcast(&[beam_size])?;
+    let scores = iota.eq(&builder.c0(0i32)?)?.select(&zero, &min_value)?;
+    let finished = iota.lt(&builder.c0(0i32)?)?;
+    let init = builder.tuple(&[prompt_len.clone(), tokens, scores, finished])?;
+    let state = XlaOp::while_(cond, body, init)?;
+    Ok((state.get_tuple_element(1)?, state.get_tuple_element(2)?))
+}
@@ -0,0 +1,224 @@
+//! Zero-copy exchange of device arrays with other libraries using the DLPack C ABI.
+//!
+//! [`PjRtClient::buffer_from_dlpack`] creates a buffer that is a view on the memory of a
+//! `DLManagedTensor` produced by another library, e.g. PyTorch, and [`PjRtBuffer::into_dlpack`]
+//! hands the memory of a buffer over to another library. Only dense row-major arrays stored on
+//! the device of the client can be exchanged.
+//!
+//! DLPack does not carry any stream information: the producer has to make sure that the data is
+//! ready before handing a tensor over, e.g. by synchronizing its CUDA stream.
+//! [`PjRtBuffer::into_dlpack`] waits for the buffer to be ready before exporting it.
+use crate::{ElementType, Error, PjRtBuffer, PjRtClient, PjRtDevice, Result, Shape};
+
+/// The `kDLCPU` device type.
+pub const DL_CPU: i32 = 1;
+/// The `kDLCUDA` device type.
+pub const DL_CUDA: i32 = 2;
+/// The `kDLROCM` device type.
+pub const DL_ROCM: i32 = 10;
+
+const DL_INT: u8 = 0;
+const DL_UINT: u8 = 1;
+const DL_FLOAT: u8 = 2;
+const DL_BFLOAT: u8 = 4;
+const DL_COMPLEX: u8 = 5;
+const DL_BOOL: u8 = 6;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DLDevice {
+    pub device_type: i32,
+    pub device_id: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DLDataType {
+    pub code: u8,
+    pub bits: u8,
+    pub lanes: u16,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLTensor {
+    pub data: *mut libc::c_void,
+    pub device: DLDevice,
+    pub ndim: i32,
+    pub dtype: DLDataType,
+    pub shape: *mut i64,
+    /// The strides in number of elements, null for a compact row-major tensor.
+    pub strides: *mut i64,
+    pub byte_offset: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLManagedTensor {
+    pub dl_tensor: DLTensor,
+    pub manager_ctx: *mut libc::c_void,
+    /// Releases the tensor, this is called by the consumer once it does not use the data anymore.
+    pub deleter: Option<unsafe extern "C" fn(*mut DLManagedTensor)>,
+}
+
+impl DLDataType {
+    /// The DLPack data type for an element type.
+    pub fn from_element_type(ty: ElementType) -> Self {
+        let code = match ty {
+            ElementType::Pred => DL_BOOL,
+            ElementType::S8 | ElementType::S16 | ElementType::S32 | ElementType::S64 => DL_INT,
+            ElementType::U8 | ElementType::U16 | ElementType::U32 | ElementType::U64 => DL_UINT,
+            ElementType::F16 | ElementType::F32 | ElementType::F64 => DL_FLOAT,
+            ElementType::Bf16 => DL_BFLOAT,
+            ElementType::C64 | ElementType::C128 => DL_COMPLEX,
+        };
+        let bits = (ty.element_size_in_bytes() * 8) as u8;
+        Self { code, bits, lanes: 1 }
+    }
+
+    /// The element type for this DLPack data type, vector types with multiple lanes are not
+    /// supported.
+    pub fn element_type(&self) -> Result<ElementType> {
+        let ty = match (self.code, self.bits, self.lanes) {
+            (DL_BOOL, 8, 1) => ElementType::Pred,
+            (DL_INT, 8, 1) => ElementType::S8,
+            (DL_INT, 16, 1) => ElementType::S16,
+            (DL_INT, 32, 1) => ElementType::S32,
+            (DL_INT, 64, 1) => ElementType::S64,
+            (DL_UINT, 8, 1) => ElementType::U8,
+            (DL_UINT, 16, 1) => ElementType::U16,
+            (DL_UINT, 32, 1) => ElementType::U32,
+            (DL_UINT, 64, 1) => ElementType::U64,
+            (DL_FLOAT, 16, 1) => ElementType::F16,
+            (DL_FLOAT, 32, 1) => ElementType::F32,
+            (DL_FLOAT, 64, 1) => ElementType::F64,
+            (DL_BFLOAT, 16, 1) => ElementType::Bf16,
+            (DL_COMPLEX, 64, 1) => ElementType::C64,
+            (DL_COMPLEX, 128, 1) => ElementType::C128,
+            _ => Err(Error::DlPack(format!("unsupported data type {self:?}")))?,
+        };
+        Ok(ty)
+    }
+}
+
+impl PjRtClient {
+    // The DLPack device type for the devices of this client.
+    fn dlpack_device_type(&self) -> Result<i32> {
+        match self.platform_name().as_str() {
+            "cpu" => Ok(DL_CPU),
+            "cuda" | "gpu" => Ok(DL_CUDA),
+            "rocm" => Ok(DL_ROCM),
+            platform => Err(Error::DlPack(format!("no dlpack device type for {platform}"))),
+        }
+    }
+
+    /// Create a buffer that is a view on the memory of a DLPack tensor, no data is copied. The
+    /// tensor has to be a dense row-major array stored on `device`, or on the default device if
+    /// `device` is `None`. Its deleter is called once the buffer is not used anymore, or right
+    /// away if an error is returned.
+    ///
+    /// # Safety
+    /// `tensor` has to point to a valid `DLManagedTensor` which data is ready to be read, the
+    /// ownership of this tensor is transferred to this function.
+    pub unsafe fn buffer_from_dlpack(
+        &self,
+        tensor: *mut DLManagedTensor,
+        device: Option<&PjRtDevice>,
+    ) -> Result<PjRtBuffer> {
+        // Calls the deleter when dropped, including when an error is returned.
+        struct Owner(*mut DLManagedTensor);
+        unsafe impl Send for Owner {}
+        impl Drop for Owner {
+            fn drop(&mut self) {
+                if let Some(deleter) = unsafe { (*self.0).deleter } {
+                    unsafe { deleter(self.0) }
+                }
+            }
+        }
+
+        let owner = Owner(tensor);
+        let dl_tensor = &(*tensor).dl_tensor;
+        let ty = dl_tensor.dtype.element_type()?;
+        let dims: &[i64] = if dl_tensor.ndim == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(dl_tensor.shape, dl_tensor.ndim as usize)
+        };
+        if !dl_tensor.strides.is_null() {
+            let strides = std::slice::from_raw_parts(dl_tensor.strides, dims.len());
+            let mut expected = 1;
+            for (dim, stride) in dims.iter().zip(strides.iter()).rev() {
+                // The stride of a dimension of size 1 does not matter.
+                if *dim != 1 && *stride != expected {
+                    Err(Error::DlPack(format!("non row-major strides {strides:?} for {dims:?}")))?
+                }
+                expected *= dim
+            }
+        }
+        let (device_type, device_id) = match device {
+            Some(device) => (self.dlpack_device_type()?, device.local_hardware_id() as i32),
+            None => match self.devices().first() {
+                Some(device) => (self.dlpack_device_type()?, device.local_hardware_id() as i32),
+                None => Err(Error::DlPack("the client has no device".to_string()))?,
+            },
+        };
+        let device_matches = dl_tensor.device.device_type == device_type
+            && (device_type == DL_CPU || dl_tensor.device.device_id == device_id);
+        if !device_matches {
+            Err(Error::DlPack(format!(
+                "the tensor is stored on {:?}, expected device type {device_type} id {device_id}",
+                dl_tensor.device
+            )))?
+        }
+        let data = (dl_tensor.data as *mut u8).add(dl_tensor.byte_offset as usize);
+        let dims: Vec<usize> = dims.iter().map(|d| *d as usize).collect();
+        self.buffer_from_device_pointer(data as *mut libc::c_void, ty, &dims, device, move || {
+            drop(owner)
+        })
+    }
+}
+
+// The state of a buffer exported with `PjRtBuffer::into_dlpack`.
+struct ExportedBuffer {
+    _buffer: PjRtBuffer,
+    shape: Vec<i64>,
+}
+
+unsafe extern "C" fn exported_buffer_deleter(tensor: *mut DLManagedTensor) {
+    let tensor = Box::from_raw(tensor);
+    drop(Box::from_raw(tensor.manager_ctx as *mut ExportedBuffer))
+}
+
+impl PjRtBuffer {
+    /// Hand the memory of an array buffer over to another library as a DLPack tensor, the buffer
+    /// is kept alive until the deleter of the returned tensor is called. This waits for the
+    /// buffer to be ready. The deleter has to be called on the thread that created the client of
+    /// this buffer, as for all the other uses of the client.
+    pub fn into_dlpack(self) -> Result<*mut DLManagedTensor> {
+        let shape = match self.on_device_shape()? {
+            Shape::Array(shape) => shape,
+            shape => Err(Error::NotAnArray { expected: None, got: shape })?,
+        };
+        self.block_until_ready()?;
+        let data = self.unsafe_device_pointer()?;
+        let device_type = self.client().dlpack_device_type()?;
+        let device_id =
+            if device_type == DL_CPU { 0 } else { self.device().local_hardware_id() as i32 };
+        let mut ctx = Box::new(ExportedBuffer { shape: shape.dims().to_vec(), _buffer: self });
+        let dl_tensor = DLTensor {
+            data,
+            device: DLDevice { device_type, device_id },
+            ndim: ctx.shape.len() as i32,
+            dtype: DLDataType::from_element_type(shape.ty()),
+            shape: ctx.shape.as_mut_ptr(),
+            strides: std::ptr::null_mut(),
+            byte_offset: 0,
+        };
+        let tensor = DLManagedTensor {
+            dl_tensor,
+            manager_ctx: Box::into_raw(ctx) as *mut libc::c_void,
+            deleter: Some(exported_buffer_deleter),
+        };
+        Ok(Box::into_raw(Box::new(tensor)))
+    }
+}
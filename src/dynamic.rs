@@ -0,0 +1,179 @@
+//! Executables for computations with symbolic dimensions.
+//!
+//! A [`DynamicExecutable`] holds a computation whose parameters use symbolic dimensions, see
+//! [`XlaBuilder::parameter_dyn`]. When running it, the symbols are bound to the sizes of the
+//! actual inputs and the computation is built and compiled for these sizes if no such variant is
+//! available yet. Compiled variants are kept in a cache of bounded size, the least recently used
+//! variant is evicted when a new one has to be added to a full cache.
+//!
+//! ```ignore
+//! let mut exe = xla::dynamic::DynamicExecutable::new(
+//!     &client,
+//!     vec![(xla::ElementType::F32, vec!["batch".into(), 128.into()])],
+//!     4,
+//!     |_builder, xs| xs[0].reduce_sum(&[1], false),
+//! );
+//! let batch8 = exe.execute(&[xla::Literal::vec1(&[0f32; 8 * 128]).reshape(&[8, 128])?])?;
+//! let batch3 = exe.execute(&[xla::Literal::vec1(&[0f32; 3 * 128]).reshape(&[3, 128])?])?;
+//! assert_eq!(exe.compilations(), 2);
+//! ```
+use crate::{
+    ArrayShape, Dim, ElementType, Error, IntoExecuteArg, Literal, PjRtBuffer, PjRtClient,
+    PjRtLoadedExecutable, Result, XlaBuilder, XlaOp,
+};
+use std::collections::BTreeMap;
+
+/// The sizes bound to each symbol for a given set of inputs.
+pub type SymbolBindings = BTreeMap<String, i64>;
+
+/// A computation with symbolic dimensions compiled on demand for each set of concrete sizes, see
+/// the [module documentation](self).
+pub struct DynamicExecutable<F> {
+    client: PjRtClient,
+    parameters: Vec<(ElementType, Vec<Dim>)>,
+    build: F,
+    capacity: usize,
+    // The compiled variants, the most recently used one is last.
+    variants: Vec<(SymbolBindings, PjRtLoadedExecutable)>,
+    compilations: usize,
+}
+
+impl<F> DynamicExecutable<F>
+where
+    F: Fn(&XlaBuilder, &[XlaOp]) -> Result<XlaOp>,
+{
+    /// Create an executable for the computation returned by `build`, the computation is given a
+    /// parameter node for each of the element type and dimensions in `parameters`. At most
+    /// `capacity` compiled variants are kept, a capacity of zero is treated as one.
+    pub fn new(
+        client: &PjRtClient,
+        parameters: Vec<(ElementType, Vec<Dim>)>,
+        capacity: usize,
+        build: F,
+    ) -> Self {
+        Self {
+            client: client.clone(),
+            parameters,
+            build,
+            capacity: capacity.max(1),
+            variants: vec![],
+            compilations: 0,
+        }
+    }
+
+    /// Bind the symbols used by the parameters to the sizes of the input shapes.
+    pub fn bind(&self, shapes: &[ArrayShape]) -> Result<SymbolBindings> {
+        if shapes.len() != self.parameters.len() {
+            Err(Error::WrongArgumentCount { expected: self.parameters.len(), got: shapes.len() })?
+        }
+        let mut bindings = SymbolBindings::new();
+        for (index, ((ty, dims), shape)) in self.parameters.iter().zip(shapes.iter()).enumerate() {
+            if shape.ty() != *ty {
+                Err(Error::ParameterTypeMismatch { index, expected: *ty, got: shape.ty() })?
+            }
+            if dims.len() != shape.dims().len() {
+                Err(Error::InvalidDims {
+                    dims: shape.dims().to_vec(),
+                    msg: "the rank does not match the parameter",
+                })?
+            }
+            for (dim, &size) in dims.iter().zip(shape.dims().iter()) {
+                match dim {
+                    Dim::Static(expected) if *expected != size => Err(Error::InvalidDims {
+                        dims: shape.dims().to_vec(),
+                        msg: "a static dimension does not match the parameter",
+                    })?,
+                    Dim::Static(_) => {}
+                    Dim::Sym(name) => match bindings.insert(name.clone(), size) {
+                        Some(previous) if previous != size => Err(Error::InvalidSymbol {
+                            name: name.clone(),
+                            msg: "the inputs use different sizes for the symbol",
+                        })?,
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// The variant compiled for the input shapes, compiling it if needed.
+    pub fn executable(&mut self, shapes: &[ArrayShape]) -> Result<&PjRtLoadedExecutable> {
+        let bindings = self.bind(shapes)?;
+        match self.variants.iter().position(|(b, _)| *b == bindings) {
+            Some(index) => {
+                let variant = self.variants.remove(index);
+                self.variants.push(variant)
+            }
+            None => {
+                let exe = self.compile(&bindings)?;
+                if self.variants.len() >= self.capacity {
+                    self.variants.remove(0);
+                }
+                self.variants.push((bindings, exe))
+            }
+        }
+        Ok(&self.variants[self.variants.len() - 1].1)
+    }
+
+    fn compile(&mut self, bindings: &SymbolBindings) -> Result<PjRtLoadedExecutable> {
+        let builder = XlaBuilder::new("dynamic");
+        for (name, size) in bindings.iter() {
+            builder.set_symbol_size(name, *size)?
+        }
+        let parameters = self
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, (ty, dims))| builder.parameter_dyn(i as i64, *ty, dims, &format!("p{i}")))
+            .collect::<Result<Vec<_>>>()?;
+        let root = (self.build)(&builder, &parameters)?;
+        let exe = self.client.compile(&builder.build(&root)?)?;
+        self.compilations += 1;
+        Ok(exe)
+    }
+
+    /// Run the variant matching the shapes of some host arguments.
+    pub fn execute<L: IntoExecuteArg>(&mut self, args: &[L]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let args = args.iter().map(|x| x.to_literal()).collect::<Result<Vec<_>>>()?;
+        let shapes = args.iter().map(|x| x.array_shape()).collect::<Result<Vec<_>>>()?;
+        let args: Vec<&Literal> = args.iter().map(|x| x.as_ref()).collect();
+        self.executable(&shapes)?.execute(&args)
+    }
+
+    /// Run the variant matching the shapes of some device buffers.
+    pub fn execute_b<L: std::borrow::Borrow<PjRtBuffer>>(
+        &mut self,
+        args: &[L],
+    ) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let shapes = args
+            .iter()
+            .map(|x| ArrayShape::try_from(&x.borrow().on_device_shape()?))
+            .collect::<Result<Vec<_>>>()?;
+        self.executable(&shapes)?.execute_b(args)
+    }
+
+    /// The number of compiled variants currently cached.
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// The maximum number of compiled variants that are cached.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of compilations so far, including the ones for evicted variants.
+    pub fn compilations(&self) -> usize {
+        self.compilations
+    }
+
+    /// Drop all the compiled variants.
+    pub fn clear(&mut self) {
+        self.variants.clear()
+    }
+}
@@ -1,3 +1,70 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Whether a backtrace is captured when the XLA library returns an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BacktraceCapture {
+    /// Follow the `RUST_LIB_BACKTRACE` and `RUST_BACKTRACE` environment variables, see
+    /// [`Backtrace::capture`].
+    #[default]
+    Env,
+    Always,
+    /// Never capture a backtrace, this makes errors cheap when they are used for control flow.
+    Never,
+}
+
+static BACKTRACE_CAPTURE: AtomicU8 = AtomicU8::new(BacktraceCapture::Env as u8);
+
+/// Set whether backtraces are captured for the errors returned by the XLA library, this applies
+/// process wide.
+pub fn set_backtrace_capture(capture: BacktraceCapture) {
+    BACKTRACE_CAPTURE.store(capture as u8, Ordering::Relaxed)
+}
+
+pub fn backtrace_capture() -> BacktraceCapture {
+    match BACKTRACE_CAPTURE.load(Ordering::Relaxed) {
+        1 => BacktraceCapture::Always,
+        2 => BacktraceCapture::Never,
+        _ => BacktraceCapture::Env,
+    }
+}
+
+/// The backtrace attached to [`Error::XlaError`]. The symbols are only resolved when the
+/// backtrace is displayed.
+pub struct XlaBacktrace(Backtrace);
+
+impl XlaBacktrace {
+    #[cfg_attr(not(feature = "ffi"), allow(dead_code))]
+    pub(crate) fn capture() -> Self {
+        let backtrace = match backtrace_capture() {
+            BacktraceCapture::Env => Backtrace::capture(),
+            BacktraceCapture::Always => Backtrace::force_capture(),
+            BacktraceCapture::Never => Backtrace::disabled(),
+        };
+        Self(backtrace)
+    }
+
+    pub fn status(&self) -> BacktraceStatus {
+        self.0.status()
+    }
+
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for XlaBacktrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl std::fmt::Display for XlaBacktrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
 /// Main library error type.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -7,7 +74,7 @@ pub enum Error {
 
     /// Error from the xla C++ library.
     #[error("xla error {msg}\n{backtrace}")]
-    XlaError { msg: String, backtrace: String },
+    XlaError { msg: String, backtrace: XlaBacktrace },
 
     #[error("unexpected element type {0}")]
     UnexpectedElementType(i32),
@@ -30,6 +97,9 @@ pub enum Error {
     #[error("element type mismatch, on-device: {on_device:?}, on-host: {on_host:?}")]
     ElementTypeMismatch { on_device: crate::ElementType, on_host: crate::ElementType },
 
+    #[error("the {ty:?} data is not aligned on {align} bytes")]
+    MisalignedData { ty: crate::ElementType, align: usize },
+
     #[error("unsupported element type for {op}: {ty:?}")]
     UnsupportedElementType { ty: crate::PrimitiveType, op: &'static str },
 
@@ -50,18 +120,133 @@ pub enum Error {
     #[error("npy/npz error {0}")]
     Npy(String),
 
+    #[error("serialized literal error {0}")]
+    SerializedLiteral(String),
+
+    #[error("invalid sparse matrix, {0}")]
+    InvalidSparseMatrix(String),
+
+    #[error("a pjrt plugin for {device_type} has already been loaded from {loaded:?}, cannot load {path:?}")]
+    PluginAlreadyLoaded {
+        device_type: String,
+        loaded: std::path::PathBuf,
+        path: std::path::PathBuf,
+    },
+
+    #[error("no backend available, {}", failures.join(", "))]
+    NoBackendAvailable { failures: Vec<String> },
+
+    /// A buffer or device from one client was used with a different client.
+    #[error("{what} belongs to {got} but was used with {expected}")]
+    ClientMismatch { what: String, expected: String, got: String },
+
+    #[error("unexpected number of arguments, expected: {expected}, got: {got}")]
+    WrongArgumentCount { expected: usize, got: usize },
+
+    #[error("{priority} execution priority is not supported on {platform}")]
+    UnsupportedExecutePriority { priority: String, platform: String },
+
+    #[error("parameter {index} expects element type {expected:?}, got {got:?}")]
+    ParameterTypeMismatch { index: usize, expected: crate::ElementType, got: crate::ElementType },
+
+    #[error("parameter {index} is bound more than once")]
+    DuplicateParameterBinding { index: usize },
+
+    #[error("{count} client(s) are still alive at shutdown")]
+    ClientsStillAlive { count: usize },
+
+    /// Ops created by different builders cannot be combined.
+    #[error("cannot combine ops created by different builders")]
+    BuilderMismatch,
+
+    #[error("shape mismatch, expected: {expected:?}, got: {got:?}")]
+    ShapeMismatch { expected: crate::Shape, got: crate::Shape },
+
+    #[error("invalid collective op configuration, {msg}")]
+    InvalidCollective { msg: String },
+
+    #[error("invalid device mesh, {msg}")]
+    InvalidMesh { msg: String },
+
+    #[error("unsupported sharding type {0}, e.g. a tuple or manual sharding")]
+    UnsupportedShardingType(i32),
+
+    #[error("replicated shards of element {start:?} have different values")]
+    ShardMismatch { start: Vec<usize> },
+
+    #[error("the job queue of executor worker {worker} is full")]
+    ExecutorQueueFull { worker: usize },
+
+    #[error("executor worker {worker} has stopped")]
+    ExecutorStopped { worker: usize },
+
+    #[error("{name} on executor worker {worker} has not completed after {elapsed:?}")]
+    ExecutionTimeout { name: String, worker: usize, elapsed: std::time::Duration },
+
+    #[error("{name} on executor worker {worker} panicked")]
+    ExecutorJobPanicked { name: String, worker: usize },
+
+    #[error("not a tuple, got: {got:?}")]
+    NotATuple { got: crate::Shape },
+
+    #[error("checkpoint error {0}")]
+    Checkpoint(String),
+
+    #[error("saved model error {0}")]
+    SavedModel(String),
+
+    #[error("dlpack error {0}")]
+    DlPack(String),
+
+    #[error("numa error {0}")]
+    Numa(String),
+
     /// I/O error.
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
     /// Zip file format error.
+    #[cfg(feature = "ffi")]
     #[error(transparent)]
     Zip(#[from] zip::result::ZipError),
 
+    /// Error when converting Arrow data.
+    #[cfg(feature = "arrow")]
+    #[error("arrow error {0}")]
+    Arrow(String),
+
+    /// Error when converting ndarray arrays.
+    #[cfg(feature = "ndarray")]
+    #[error(transparent)]
+    Ndarray(#[from] ndarray::ShapeError),
+
+    /// A computation did not match its reference implementation.
+    #[cfg(feature = "ndarray")]
+    #[error("mismatch with the reference implementation on case {case}, {msg}")]
+    ReferenceMismatch { case: usize, msg: String },
+
+    /// Error from the candle library.
+    #[cfg(feature = "candle")]
+    #[error(transparent)]
+    Candle(#[from] candle_core::Error),
+
+    /// Error from the tch library.
+    #[cfg(feature = "tch")]
+    #[error(transparent)]
+    Tch(#[from] ::tch::TchError),
+
     /// Integer parse error.
     #[error(transparent)]
     ParseInt(#[from] std::num::ParseIntError),
 
+    /// Integer conversion error.
+    #[error(transparent)]
+    TryFromInt(#[from] std::num::TryFromIntError),
+
+    /// A string passed to the C api contains an interior nul byte.
+    #[error(transparent)]
+    Nul(#[from] std::ffi::NulError),
+
     #[error("cannot create literal with shape {ty:?} {dims:?} from bytes data with len {data_len_in_bytes}")]
     CannotCreateLiteralWithData {
         data_len_in_bytes: usize,
@@ -69,6 +254,46 @@ pub enum Error {
         dims: Vec<usize>,
     },
 
+    #[error("cannot append {len} elements at position {position} to a kv-cache of capacity {capacity} without wrapping around")]
+    KvCacheWrapAround { len: usize, position: usize, capacity: usize },
+
+    #[error("{op} requires at least one node")]
+    EmptyOpList { op: &'static str },
+
+    #[error("cannot allocate {len} bytes of host memory")]
+    HostAllocationFailed { len: usize },
+
+    #[error("all rows must have the same number of columns, got rows of lengths {row_lens:?}")]
+    RaggedRows { row_lens: Vec<usize> },
+
+    #[error("invalid dimensions {dims:?}, {msg}")]
+    InvalidDims { dims: Vec<i64>, msg: &'static str },
+
+    #[error("invalid dropout rate {0}, expected a value in [0, 1)")]
+    InvalidDropoutRate(f64),
+
+    /// A variance scaling initializer has not been replaced by the equivalent distribution.
+    #[error("initializer {0} has not been resolved to a distribution")]
+    UnresolvedInit(String),
+
+    #[error("sequence of length {len} does not fit in the maximum length {max}")]
+    SequenceTooLong { len: usize, max: usize },
+
+    #[error("token {token} cannot be represented as {ty:?}")]
+    TokenOutOfRange { token: u32, ty: crate::ElementType },
+
+    #[error("invalid symbolic dimension {name}, {msg}")]
+    InvalidSymbol { name: String, msg: &'static str },
+
+    #[error("the buffer has been deleted or donated")]
+    BufferDeleted,
+
+    #[error("null pointer passed to the C API for {name}")]
+    CApiNullPointer { name: &'static str },
+
+    #[error("invalid value {value} passed to the C API for {name}")]
+    CApiInvalidArgument { name: &'static str, value: i64 },
+
     #[error("invalid dimensions in matmul, lhs: {lhs_dims:?}, rhs: {rhs_dims:?}, {msg}")]
     MatMulIncorrectDims { lhs_dims: Vec<i64>, rhs_dims: Vec<i64>, msg: &'static str },
 }
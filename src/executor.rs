@@ -0,0 +1,342 @@
+//! Per-device worker threads to launch computations concurrently.
+//!
+//! Clients, buffers, and executables cannot be shared between threads, so by default a single
+//! thread performs the host side work, transfers and launches, for all the devices. An
+//! [`Executor`] instead owns one worker thread per device. Each worker builds its own state on
+//! its thread with the `init` function, typically a client and the compiled executables, and
+//! then runs the jobs submitted for its device in submission order.
+//!
+//! The queue of each worker is bounded: [`Executor::launch`] blocks when the queue of the target
+//! worker is full, whereas [`Executor::try_launch`] returns [`Error::ExecutorQueueFull`] so that
+//! the caller can shed load.
+//!
+//! When [`ExecutorOptions::deadline`] is set, a watchdog thread logs a warning with the name of
+//! the job, see [`Executor::launch_named`], for each job that runs for longer than the deadline,
+//! and [`Launch::wait`] returns [`Error::ExecutionTimeout`] for such jobs. XLA does not provide
+//! a way to interrupt a running computation so the worker remains busy with the job, later jobs
+//! on this worker only start once it completes.
+//!
+//! ```ignore
+//! let executor = Executor::new(2, ExecutorOptions::default(), |device| {
+//!     let client = xla::PjRtClient::cpu()?;
+//!     let exe = client.compile(&build_model()?)?;
+//!     Ok((client, exe, device))
+//! })?;
+//! let launch = executor.launch(1, |(_client, exe, _device)| {
+//!     let outputs = exe.execute::<xla::Literal>(&[])?;
+//!     outputs[0][0].to_literal_sync()?.to_vec::<f32>()
+//! })?;
+//! let result = launch.wait()?;
+//! ```
+use crate::{Error, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// The name used for the jobs submitted without a name.
+const DEFAULT_JOB_NAME: &str = "job";
+
+struct Job<S> {
+    name: String,
+    started: Arc<OnceLock<Instant>>,
+    run: Box<dyn FnOnce(&mut S) + Send>,
+}
+
+// The job currently running on a worker, as monitored by the watchdog.
+struct InFlight {
+    name: String,
+    started: Instant,
+    reported: bool,
+}
+
+type SharedInFlight = Arc<Mutex<Option<InFlight>>>;
+
+/// The options used by [`Executor::new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutorOptions {
+    /// The number of jobs that can be queued for each worker before [`Executor::launch`] blocks.
+    pub queue_capacity: usize,
+    /// The maximum duration of a job once it has started running, the watchdog is disabled when
+    /// not specified.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for ExecutorOptions {
+    fn default() -> Self {
+        Self { queue_capacity: 16, deadline: None }
+    }
+}
+
+/// A job submitted to an [`Executor`], use [`Launch::wait`] to get its result.
+pub struct Launch<R> {
+    worker: usize,
+    name: String,
+    deadline: Option<Duration>,
+    started: Arc<OnceLock<Instant>>,
+    receiver: Receiver<Result<R>>,
+}
+
+impl<R> Launch<R> {
+    /// The index of the worker running the job.
+    pub fn worker(&self) -> usize {
+        self.worker
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Block until the job has completed and return its result. If the executor has a deadline,
+    /// this returns [`Error::ExecutionTimeout`] once the job has been running for longer than
+    /// the deadline.
+    pub fn wait(self) -> Result<R> {
+        let stopped = || Error::ExecutorStopped { worker: self.worker };
+        let deadline = match self.deadline {
+            None => return self.receiver.recv().map_err(|_| stopped())?,
+            Some(deadline) => deadline,
+        };
+        loop {
+            // While the job is queued, the deadline is checked again once it has started.
+            let timeout = match self.started.get() {
+                None => deadline,
+                Some(started) => match deadline.checked_sub(started.elapsed()) {
+                    Some(timeout) => timeout,
+                    None => Err(Error::ExecutionTimeout {
+                        name: self.name.clone(),
+                        worker: self.worker,
+                        elapsed: started.elapsed(),
+                    })?,
+                },
+            };
+            match self.receiver.recv_timeout(timeout) {
+                Ok(result) => return result,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => Err(stopped())?,
+            }
+        }
+    }
+}
+
+struct Worker<S> {
+    sender: Option<SyncSender<Job<S>>>,
+    // The number of jobs queued or running on this worker.
+    pending: Arc<AtomicUsize>,
+    in_flight: SharedInFlight,
+    handle: Option<JoinHandle<()>>,
+}
+
+// Run the jobs sent to a worker until the sender is dropped.
+fn run_worker<S>(
+    state: &mut S,
+    receiver: Receiver<Job<S>>,
+    pending: &AtomicUsize,
+    in_flight: &Mutex<Option<InFlight>>,
+) {
+    for job in receiver {
+        let started = *job.started.get_or_init(Instant::now);
+        let job_in_flight = InFlight { name: job.name, started, reported: false };
+        *in_flight.lock().unwrap_or_else(|e| e.into_inner()) = Some(job_in_flight);
+        // The job catches the panics of the submitted closure so this always gets reached.
+        (job.run)(state);
+        *in_flight.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Report the jobs running for longer than `deadline` until `stop` is disconnected.
+fn run_watchdog(deadline: Duration, in_flight: Vec<SharedInFlight>, stop: Receiver<()>) {
+    let period = (deadline / 4).max(Duration::from_millis(10));
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(period) {
+        for (worker, in_flight) in in_flight.iter().enumerate() {
+            let mut in_flight = in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(job) = in_flight.as_mut() {
+                let elapsed = job.started.elapsed();
+                if elapsed > deadline && !job.reported {
+                    log::warn!(
+                        "{} on executor worker {worker} has not completed after {elapsed:?}",
+                        job.name
+                    );
+                    job.reported = true
+                }
+            }
+        }
+    }
+}
+
+/// A set of worker threads, one per device, see the [module documentation](self).
+pub struct Executor<S> {
+    workers: Vec<Worker<S>>,
+    deadline: Option<Duration>,
+    // Dropping the sender stops the watchdog thread.
+    watchdog: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl<S: 'static> Executor<S> {
+    /// Start `num_workers` worker threads, `init` is called on each worker thread with the index
+    /// of the worker to create its state. This returns the first error returned by `init`.
+    pub fn new<F>(num_workers: usize, options: ExecutorOptions, init: F) -> Result<Self>
+    where
+        F: Fn(usize) -> Result<S> + Send + Sync + 'static,
+    {
+        let init = Arc::new(init);
+        let mut executor = Self {
+            workers: Vec::with_capacity(num_workers),
+            deadline: options.deadline,
+            watchdog: None,
+        };
+        for index in 0..num_workers {
+            let (sender, receiver) = mpsc::sync_channel::<Job<S>>(options.queue_capacity);
+            let (ready_sender, ready_receiver) = mpsc::channel();
+            let pending = Arc::new(AtomicUsize::new(0));
+            let in_flight = Arc::new(Mutex::new(None));
+            let init = init.clone();
+            let (worker_pending, worker_in_flight) = (pending.clone(), in_flight.clone());
+            let body = move || {
+                let mut state = match init(index) {
+                    Ok(state) => state,
+                    Err(err) => {
+                        let _ = ready_sender.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = ready_sender.send(Ok(()));
+                run_worker(&mut state, receiver, &worker_pending, &worker_in_flight)
+            };
+            let handle =
+                std::thread::Builder::new().name(format!("xla-executor-{index}")).spawn(body)?;
+            executor.workers.push(Worker {
+                sender: Some(sender),
+                pending,
+                in_flight,
+                handle: Some(handle),
+            });
+            // On errors, the workers that have already been started are stopped when `executor`
+            // is dropped.
+            ready_receiver.recv().map_err(|_| Error::ExecutorStopped { worker: index })??
+        }
+        if let Some(deadline) = options.deadline {
+            let in_flight = executor.workers.iter().map(|w| w.in_flight.clone()).collect();
+            let (stop, stop_receiver) = mpsc::channel();
+            let handle = std::thread::Builder::new()
+                .name("xla-executor-watchdog".to_string())
+                .spawn(move || run_watchdog(deadline, in_flight, stop_receiver))?;
+            executor.watchdog = Some((stop, handle))
+        }
+        Ok(executor)
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn worker(&self, worker: usize) -> Result<&Worker<S>> {
+        self.workers
+            .get(worker)
+            .ok_or(Error::IndexOutOfBounds { index: worker as i64, rank: self.workers.len() })
+    }
+
+    /// The number of jobs that are queued or running on a worker.
+    pub fn pending(&self, worker: usize) -> Result<usize> {
+        Ok(self.worker(worker)?.pending.load(Ordering::SeqCst))
+    }
+
+    fn submit<R, F>(&self, worker: usize, name: &str, f: F, block: bool) -> Result<Launch<R>>
+    where
+        F: FnOnce(&mut S) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let w = self.worker(worker)?;
+        let sender = w.sender.as_ref().ok_or(Error::ExecutorStopped { worker })?;
+        let (result_sender, receiver) = mpsc::channel();
+        let started = Arc::new(OnceLock::new());
+        let job_name = name.to_string();
+        let job = Job {
+            name: name.to_string(),
+            started: started.clone(),
+            run: Box::new(move |state| {
+                // A panicking job is reported to its caller, the worker keeps running the
+                // following jobs.
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(state)))
+                    .unwrap_or_else(|_| Err(Error::ExecutorJobPanicked { name: job_name, worker }));
+                let _ = result_sender.send(result);
+            }),
+        };
+        w.pending.fetch_add(1, Ordering::SeqCst);
+        let sent = if block {
+            sender.send(job).map_err(|_| Error::ExecutorStopped { worker })
+        } else {
+            sender.try_send(job).map_err(|err| match err {
+                TrySendError::Full(_) => Error::ExecutorQueueFull { worker },
+                TrySendError::Disconnected(_) => Error::ExecutorStopped { worker },
+            })
+        };
+        if let Err(err) = sent {
+            w.pending.fetch_sub(1, Ordering::SeqCst);
+            Err(err)?
+        }
+        Ok(Launch { worker, name: name.to_string(), deadline: self.deadline, started, receiver })
+    }
+
+    /// Queue a job on a worker, this blocks while the queue of the worker is full. This returns
+    /// [`Error::IndexOutOfBounds`] if `worker` is not smaller than [`Executor::num_workers`].
+    pub fn launch<R, F>(&self, worker: usize, f: F) -> Result<Launch<R>>
+    where
+        F: FnOnce(&mut S) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.submit(worker, DEFAULT_JOB_NAME, f, true)
+    }
+
+    /// Same as [`Executor::launch`] but with a name for the job, e.g. the name of the module
+    /// being executed. This name is used when reporting that the job exceeded the deadline.
+    pub fn launch_named<R, F>(&self, worker: usize, name: &str, f: F) -> Result<Launch<R>>
+    where
+        F: FnOnce(&mut S) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.submit(worker, name, f, true)
+    }
+
+    /// Queue a job on a worker, this returns [`Error::ExecutorQueueFull`] rather than blocking
+    /// when the queue of the worker is full.
+    pub fn try_launch<R, F>(&self, worker: usize, f: F) -> Result<Launch<R>>
+    where
+        F: FnOnce(&mut S) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.submit(worker, DEFAULT_JOB_NAME, f, false)
+    }
+
+    /// Queue a job on the worker with the fewest pending jobs, this blocks while the queue of
+    /// this worker is full.
+    pub fn launch_any<R, F>(&self, f: F) -> Result<Launch<R>>
+    where
+        F: FnOnce(&mut S) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let worker = (0..self.workers.len())
+            .min_by_key(|w| self.workers[*w].pending.load(Ordering::SeqCst))
+            .ok_or(Error::ExecutorStopped { worker: 0 })?;
+        self.submit(worker, DEFAULT_JOB_NAME, f, true)
+    }
+}
+
+impl<S> Drop for Executor<S> {
+    // Stop the workers once they have run all their queued jobs.
+    fn drop(&mut self) {
+        if let Some((stop, handle)) = self.watchdog.take() {
+            drop(stop);
+            let _ = handle.join();
+        }
+        for worker in self.workers.iter_mut() {
+            worker.sender.take();
+        }
+        for worker in self.workers.iter_mut() {
+            if let Some(handle) = worker.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
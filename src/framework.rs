@@ -0,0 +1,76 @@
+//! Traits for higher level frameworks built on top of this crate.
+//!
+//! A framework, e.g. an autograd library, can be written against [`TensorBackend`] and
+//! [`DeviceArray`] rather than against [`PjRtClient`] and [`PjRtBuffer`] directly. The framework
+//! can then be tested against a mock backend that runs on the host without compiling anything.
+//! The trait is not named `Backend` to avoid confusion with [`crate::Backend`], which selects
+//! the PjRt plugin used by a client.
+use crate::{
+    ArrayElement, ArrayShape, Literal, PjRtBuffer, PjRtClient, PjRtLoadedExecutable, Result,
+    XlaComputation,
+};
+
+/// An array stored on the device of some [`TensorBackend`].
+pub trait DeviceArray {
+    /// The element type and dimensions of the array.
+    fn array_shape(&self) -> Result<ArrayShape>;
+
+    /// Copy the array back to the host.
+    fn to_literal(&self) -> Result<Literal>;
+
+    /// Copy the array back to the host as a flat vector.
+    fn to_vec<T: ArrayElement>(&self) -> Result<Vec<T>> {
+        self.to_literal()?.to_vec::<T>()
+    }
+}
+
+/// Something that can store arrays and run computations on them.
+pub trait TensorBackend {
+    type Array: DeviceArray;
+    type Executable;
+
+    /// Transfer some host data to the device, the dimensions have to match the number of
+    /// elements in `data`.
+    fn array_from_host<T: ArrayElement>(&self, data: &[T], dims: &[usize]) -> Result<Self::Array>;
+
+    /// Transfer a literal to the device.
+    fn array_from_literal(&self, literal: &Literal) -> Result<Self::Array>;
+
+    fn compile(&self, computation: &XlaComputation) -> Result<Self::Executable>;
+
+    /// Run an executable, the outputs of the computation are returned.
+    fn execute(&self, exe: &Self::Executable, args: &[&Self::Array]) -> Result<Vec<Self::Array>>;
+}
+
+impl DeviceArray for PjRtBuffer {
+    fn array_shape(&self) -> Result<ArrayShape> {
+        ArrayShape::try_from(&self.on_device_shape()?)
+    }
+
+    fn to_literal(&self) -> Result<Literal> {
+        self.to_literal_sync()
+    }
+}
+
+impl TensorBackend for PjRtClient {
+    type Array = PjRtBuffer;
+    type Executable = PjRtLoadedExecutable;
+
+    fn array_from_host<T: ArrayElement>(&self, data: &[T], dims: &[usize]) -> Result<PjRtBuffer> {
+        self.buffer_from_host_buffer(data, dims, None)
+    }
+
+    fn array_from_literal(&self, literal: &Literal) -> Result<PjRtBuffer> {
+        self.buffer_from_host_literal(None, literal)
+    }
+
+    fn compile(&self, computation: &XlaComputation) -> Result<PjRtLoadedExecutable> {
+        PjRtClient::compile(self, computation)
+    }
+
+    fn execute(&self, exe: &PjRtLoadedExecutable, args: &[&PjRtBuffer]) -> Result<Vec<PjRtBuffer>> {
+        // Only the outputs for the first replica are returned.
+        let mut outputs = exe.execute_b(args)?;
+        Ok(if outputs.is_empty() { vec![] } else { outputs.remove(0) })
+    }
+}
@@ -0,0 +1,183 @@
+//! Key/value caches for auto-regressive decoding.
+//!
+//! A [`KvCache`] holds preallocated device buffers for the keys and the values of an attention
+//! layer. New entries are written in place on the device using `dynamic_update_slice`, so the
+//! cache never has to be copied back to the host. Once the cache is full, new entries overwrite
+//! the oldest ones, i.e. the cache behaves as a ring buffer over the sequence dimension.
+//!
+//! [`append_op`] can also be used directly to perform the update as part of a larger
+//! computation.
+use crate::{
+    ArrayShape, ElementType, Error, PjRtBuffer, PjRtClient, PjRtDevice, PjRtLoadedExecutable,
+    Result, Shape, XlaBuilder, XlaOp,
+};
+use std::collections::HashMap;
+
+/// A node where `update` has been written in `cache` starting at position `index` on the
+/// dimension `seq_dim`, `index` is a scalar `S32` node.
+pub fn append_op(cache: &XlaOp, update: &XlaOp, index: &XlaOp, seq_dim: i64) -> Result<XlaOp> {
+    let rank = cache.rank()?;
+    let zero = cache.builder().zero(ElementType::S32)?;
+    let start_indices: Vec<&XlaOp> =
+        (0..rank as i64).map(|i| if i == seq_dim { index } else { &zero }).collect();
+    cache.dynamic_update_slice(update, &start_indices)
+}
+
+/// Preallocated key/value buffers, see the [module documentation](self).
+pub struct KvCache {
+    client: PjRtClient,
+    ty: ElementType,
+    dims: Vec<usize>,
+    seq_dim: usize,
+    keys: PjRtBuffer,
+    values: PjRtBuffer,
+    position: usize,
+    // The append executables, indexed by the number of elements to append.
+    append_exes: HashMap<usize, PjRtLoadedExecutable>,
+}
+
+impl KvCache {
+    /// Allocate zero-initialized key and value buffers of shape `dims` on the target device,
+    /// `seq_dim` is the index of the sequence dimension in `dims`, the size of this dimension is
+    /// the cache capacity and has to be positive.
+    pub fn new(
+        client: &PjRtClient,
+        ty: ElementType,
+        dims: &[usize],
+        seq_dim: usize,
+        device: Option<&PjRtDevice>,
+    ) -> Result<Self> {
+        if seq_dim >= dims.len() {
+            Err(Error::IndexOutOfBounds { index: seq_dim as i64, rank: dims.len() })?
+        }
+        if dims[seq_dim] == 0 {
+            Err(Error::InvalidDims {
+                dims: dims.iter().map(|d| *d as i64).collect(),
+                msg: "the kv-cache capacity must be positive",
+            })?
+        }
+        let zeros = vec![0u8; dims.iter().product::<usize>() * ty.element_size_in_bytes()];
+        let keys = client.buffer_from_host_raw_bytes(ty, &zeros, dims, device)?;
+        let values = client.buffer_from_host_raw_bytes(ty, &zeros, dims, device)?;
+        Ok(Self {
+            client: client.clone(),
+            ty,
+            dims: dims.to_vec(),
+            seq_dim,
+            keys,
+            values,
+            position: 0,
+            append_exes: HashMap::new(),
+        })
+    }
+
+    /// The maximum number of elements that can be stored on the sequence dimension.
+    pub fn capacity(&self) -> usize {
+        self.dims[self.seq_dim]
+    }
+
+    /// The number of valid elements in the cache.
+    pub fn len(&self) -> usize {
+        usize::min(self.position, self.capacity())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == 0
+    }
+
+    /// The total number of elements that have been appended since the cache was created or
+    /// reset, including the ones that have been evicted.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The index in the cache where the next element will be written.
+    pub fn write_index(&self) -> usize {
+        self.position % self.capacity()
+    }
+
+    /// The buffer holding the keys, the valid entries are given by [`KvCache::valid_mask`].
+    pub fn keys(&self) -> &PjRtBuffer {
+        &self.keys
+    }
+
+    /// The buffer holding the values, the valid entries are given by [`KvCache::valid_mask`].
+    pub fn values(&self) -> &PjRtBuffer {
+        &self.values
+    }
+
+    /// For each index on the sequence dimension, whether the cache holds a valid element.
+    pub fn valid_mask(&self) -> Vec<bool> {
+        let len = self.len();
+        (0..self.capacity()).map(|i| i < len).collect()
+    }
+
+    /// Forget about all the elements in the cache, the buffers are not cleared but the stale
+    /// values are excluded by [`KvCache::valid_mask`].
+    pub fn reset(&mut self) {
+        self.position = 0
+    }
+
+    fn compile_append(&mut self, len: usize) -> Result<()> {
+        if !self.append_exes.contains_key(&len) {
+            let builder = XlaBuilder::new("kv_cache_append");
+            let cache_dims: Vec<i64> = self.dims.iter().map(|d| *d as i64).collect();
+            let mut update_dims = cache_dims.clone();
+            update_dims[self.seq_dim] = len as i64;
+            let cache = builder.parameter(0, self.ty, &cache_dims, "cache")?;
+            let update = builder.parameter(1, self.ty, &update_dims, "update")?;
+            let index = builder.parameter(2, ElementType::S32, &[], "index")?;
+            let op = append_op(&cache, &update, &index, self.seq_dim as i64)?;
+            let exe = self.client.compile(&op.build()?)?;
+            self.append_exes.insert(len, exe);
+        }
+        Ok(())
+    }
+
+    /// Append some new keys and values, these buffers have the same shape as the cache except on
+    /// the sequence dimension. Appends that would straddle the end of the ring buffer return an
+    /// error, this never happens when appending one element at a time.
+    pub fn append(&mut self, keys: &PjRtBuffer, values: &PjRtBuffer) -> Result<()> {
+        let key_shape = keys.on_device_shape()?;
+        let len = match &key_shape {
+            Shape::Array(shape) if shape.dims().len() == self.dims.len() => {
+                shape.dims()[self.seq_dim] as usize
+            }
+            _ => {
+                Err(Error::NotAnArray { expected: Some(self.dims.len()), got: key_shape.clone() })?
+            }
+        };
+        let mut expected_dims: Vec<i64> = self.dims.iter().map(|d| *d as i64).collect();
+        expected_dims[self.seq_dim] = len as i64;
+        let expected = Shape::Array(ArrayShape::new_with_type(self.ty, expected_dims));
+        if key_shape != expected {
+            Err(Error::ShapeMismatch { expected: expected.clone(), got: key_shape })?
+        }
+        let value_shape = values.on_device_shape()?;
+        if value_shape != expected {
+            Err(Error::ShapeMismatch { expected, got: value_shape })?
+        }
+        let capacity = self.capacity();
+        let write_index = self.write_index();
+        if write_index + len > capacity {
+            Err(Error::KvCacheWrapAround { len, position: write_index, capacity })?
+        }
+        let index = self.client.buffer_from_host_buffer(&[write_index as i32], &[], None)?;
+        self.compile_append(len)?;
+        let exe = &self.append_exes[&len];
+        let new_keys = exe.execute_b(&[&self.keys, keys, &index])?;
+        let new_values = exe.execute_b(&[&self.values, values, &index])?;
+        self.keys = single_output(new_keys)?;
+        self.values = single_output(new_values)?;
+        self.position += len;
+        Ok(())
+    }
+}
+
+fn single_output(outputs: Vec<Vec<PjRtBuffer>>) -> Result<PjRtBuffer> {
+    let got = outputs.first().map_or(0, |v| v.len());
+    match outputs.into_iter().next().and_then(|v| v.into_iter().next()) {
+        Some(buffer) => Ok(buffer),
+        None => Err(Error::UnexpectedNumberOfElemsInTuple { expected: 1, got }),
+    }
+}
@@ -33,13 +33,89 @@
 //! let result = result[0][0].to_literal_sync()?.to_vec::<f32>()?;
 //! ```
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "ffi")]
+pub mod bench;
+#[cfg(feature = "ffi")]
+pub mod bucketing;
+#[cfg(feature = "ffi")]
 mod c_lib;
+#[cfg(feature = "candle")]
+pub mod candle;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "ffi")]
+pub mod checkpoint;
+#[cfg(feature = "ffi")]
+pub mod decode;
+#[cfg(feature = "ffi")]
+pub mod dlpack;
+#[cfg(feature = "ffi")]
+pub mod dynamic;
 mod error;
+pub mod executor;
+#[cfg(feature = "ffi")]
+pub mod framework;
+#[cfg(feature = "ffi")]
+pub mod kv_cache;
+#[cfg(feature = "ffi")]
+mod logging;
+#[cfg(feature = "ffi")]
+pub mod mesh;
+#[cfg(feature = "ffi")]
+pub mod metrics;
+#[cfg(feature = "ffi")]
+pub mod nn;
+#[cfg(feature = "ffi")]
 mod npy;
+pub mod numa;
+#[cfg(feature = "ffi")]
+mod prng;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "ffi")]
+mod runtime;
+#[cfg(feature = "ffi")]
+pub mod sampling;
+#[cfg(feature = "ffi")]
+pub mod saved_model;
+#[cfg(feature = "ffi")]
+mod serialize;
+#[cfg(feature = "ffi")]
+pub mod sparse;
+#[cfg(feature = "stub")]
+pub mod stub;
+#[cfg(feature = "tch")]
+pub mod tch;
+#[cfg(feature = "ffi")]
+pub mod testing;
+pub mod types;
+#[cfg(feature = "ffi")]
 mod wrappers;
-pub use error::{Error, Result};
+pub use error::{
+    backtrace_capture, set_backtrace_capture, BacktraceCapture, Error, Result, XlaBacktrace,
+};
+#[cfg(feature = "ffi")]
+pub use logging::{clear_log_handler, forward_logs_to_log_crate, set_log_handler, LogRecord};
+#[cfg(feature = "ffi")]
 pub use npy::FromRawBytes;
+#[cfg(feature = "ffi")]
+pub use prng::PrngKey;
+#[cfg(feature = "ffi")]
+pub use runtime::{init, shutdown, InitOptions, LogOutput};
+#[cfg(feature = "ffi")]
+pub use serialize::Endianness;
+#[cfg(all(feature = "stub", not(feature = "ffi")))]
+pub use stub::{
+    Literal, NativeType, PjRtBuffer, PjRtClient, PjRtDevice, PjRtLoadedExecutable, XlaBuilder,
+    XlaComputation, XlaOp,
+};
+pub use types::{ArrayElement, ArrayShape, Bf16, ElementType, PrimitiveType, Shape, F16};
+#[cfg(feature = "ffi")]
 pub use wrappers::*;
+#[cfg(feature = "ffi")]
+pub use xla_derive::XlaState;
 
 #[derive(Debug, Copy, Clone)]
 pub enum TfLogLevel {
@@ -58,6 +134,25 @@ impl TfLogLevel {
             Self::Fatal => "3",
         }
     }
+
+    #[cfg(feature = "ffi")]
+    fn from_severity(severity: i32) -> Self {
+        match severity {
+            i32::MIN..=0 => Self::Info,
+            1 => Self::Warning,
+            2 => Self::Error,
+            _ => Self::Fatal,
+        }
+    }
+
+    /// The `log` crate level used when forwarding messages with this severity.
+    pub fn as_log_level(&self) -> log::Level {
+        match self {
+            Self::Info => log::Level::Info,
+            Self::Warning => log::Level::Warn,
+            Self::Error | Self::Fatal => log::Level::Error,
+        }
+    }
 }
 
 pub fn set_tf_min_log_level(log_level: TfLogLevel) {
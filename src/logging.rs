@@ -0,0 +1,93 @@
+//! Forwarding of the XLA/TSL C++ logs to Rust.
+//!
+//! By default the C++ library writes its log messages (warnings, `VLOG` output, etc.) directly
+//! to stderr. Once a handler is registered with [`set_log_handler`], these messages are routed
+//! to this handler instead. [`forward_logs_to_log_crate`] installs a handler that emits the
+//! messages via the [`log`](https://docs.rs/log) crate, which makes them available to `tracing`
+//! subscribers through `tracing-log`.
+use crate::{c_lib, TfLogLevel};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A single log message emitted by the C++ library.
+#[derive(Debug, Clone, Copy)]
+pub struct LogRecord<'a> {
+    pub level: TfLogLevel,
+    /// The `VLOG` level of the message, 0 for the messages that are not verbose. TSL does not
+    /// record this level so `Info` messages get the highest `VLOG` level enabled for their file.
+    pub verbosity: i32,
+    pub file: &'a str,
+    pub line: i32,
+    pub message: &'a str,
+}
+
+impl LogRecord<'_> {
+    /// The `log` crate level used when forwarding this message: verbose messages are emitted
+    /// at the `Debug` level for a verbosity of 1 and at the `Trace` level above.
+    pub fn log_level(&self) -> log::Level {
+        match (self.level, self.verbosity) {
+            (TfLogLevel::Info, 1) => log::Level::Debug,
+            (TfLogLevel::Info, v) if v > 1 => log::Level::Trace,
+            (level, _) => level.as_log_level(),
+        }
+    }
+}
+
+type LogHandler = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+static LOG_HANDLER: RwLock<Option<LogHandler>> = RwLock::new(None);
+
+// Serializes the registrations. The C++ sink mutex is taken when registering and while a message
+// is sent, so the handler lock must never be held when calling into the C++ library.
+static REGISTRATION: Mutex<()> = Mutex::new(());
+
+unsafe extern "C" fn log_trampoline(
+    severity: i32,
+    verbosity: i32,
+    file: *const std::ffi::c_char,
+    line: i32,
+    message: *const std::ffi::c_char,
+) {
+    let file = std::ffi::CStr::from_ptr(file).to_string_lossy();
+    let message = std::ffi::CStr::from_ptr(message).to_string_lossy();
+    let record = LogRecord {
+        level: TfLogLevel::from_severity(severity),
+        verbosity,
+        file: &file,
+        line,
+        message: &message,
+    };
+    let handler = LOG_HANDLER.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(handler) = handler {
+        handler(&record)
+    }
+}
+
+/// Route the C++ log messages to `handler` rather than to stderr. This replaces any previously
+/// registered handler.
+pub fn set_log_handler<F: Fn(&LogRecord) + Send + Sync + 'static>(handler: F) {
+    let _registration = REGISTRATION.lock().unwrap_or_else(|e| e.into_inner());
+    *LOG_HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(handler));
+    unsafe { c_lib::set_log_callback(Some(log_trampoline)) }
+}
+
+/// Remove the registered handler, the C++ log messages are written to stderr again.
+pub fn clear_log_handler() {
+    let _registration = REGISTRATION.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe { c_lib::set_log_callback(None) }
+    *LOG_HANDLER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Emit the C++ log messages through the `log` crate using the `xla` target, see
+/// [`LogRecord::log_level`] for the level mapping.
+pub fn forward_logs_to_log_crate() {
+    set_log_handler(|record| {
+        log::log!(
+            target: "xla",
+            record.log_level(),
+            "{}:{}] {}",
+            record.file,
+            record.line,
+            record.message
+        )
+    })
+}
@@ -0,0 +1,399 @@
+//! Logical device meshes and partition specs.
+//!
+//! A [`Mesh`] arranges some devices in a multi-dimensional grid where each axis has a name, e.g.
+//! a `data` axis used for data parallelism and a `model` axis used for model parallelism. A
+//! [`PartitionSpec`] then describes how each dimension of an array is split over the mesh axes,
+//! and [`Mesh::op_sharding`] turns both into the [`OpSharding`] expected by the XLA compiler.
+//! [`Literal::shard`] and [`assemble_from_shards`] move the data between a full host array and
+//! its per-device shards, and [`crate::XlaOp::with_sharding`] attaches a sharding to a node of a
+//! computation.
+//!
+//! ```ignore
+//! let client = xla::PjRtClient::cpu()?;
+//! let mesh = Mesh::new([("data", 2), ("model", 4)], &client.devices())?;
+//! // Split the rows over the data axis, the columns over the model axis.
+//! let sharding = mesh.op_sharding(&PartitionSpec::new([Some("data"), Some("model")]))?;
+//! ```
+use crate::{Error, Literal, PjRtBuffer, PjRtDevice, Result};
+
+/// The kind of sharding applied to an op.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpShardingType {
+    /// The full value is available on every device.
+    Replicated = 0,
+    /// The value is only available on a single device.
+    Maximal = 1,
+    /// The value is split in tiles over the devices.
+    Other = 3,
+}
+
+/// The sharding of an op, this mirrors the `OpSharding` proto used by XLA.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OpSharding {
+    pub ty: OpShardingType,
+    /// The number of tiles along each dimension of the value. When `replicate_on_last_tile_dim`
+    /// is set, the last dimension is the number of replicas of each tile.
+    pub tile_assignment_dimensions: Vec<i64>,
+    /// The device ids holding each tile, in row major order over the tile dimensions.
+    pub tile_assignment_devices: Vec<i64>,
+    pub replicate_on_last_tile_dim: bool,
+}
+
+impl OpSharding {
+    /// A value replicated on all the devices.
+    pub fn replicated() -> Self {
+        Self {
+            ty: OpShardingType::Replicated,
+            tile_assignment_dimensions: vec![],
+            tile_assignment_devices: vec![],
+            replicate_on_last_tile_dim: false,
+        }
+    }
+
+    /// A value placed on a single device.
+    pub fn maximal(device_id: i64) -> Self {
+        Self {
+            ty: OpShardingType::Maximal,
+            tile_assignment_dimensions: vec![1],
+            tile_assignment_devices: vec![device_id],
+            replicate_on_last_tile_dim: false,
+        }
+    }
+
+    /// The number of tiles along each dimension of the value, excluding the replication
+    /// dimension.
+    pub fn tile_dims(&self) -> &[i64] {
+        let dims = &self.tile_assignment_dimensions;
+        if self.replicate_on_last_tile_dim {
+            &dims[..dims.len() - 1]
+        } else {
+            dims
+        }
+    }
+}
+
+/// The HLO text representation of the sharding, e.g. `{devices=[2,1]0,1}`.
+impl std::fmt::Display for OpSharding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |v: &[i64]| v.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        match self.ty {
+            OpShardingType::Replicated => write!(f, "{{replicated}}"),
+            OpShardingType::Maximal => {
+                write!(f, "{{maximal device={}}}", self.tile_assignment_devices[0])
+            }
+            OpShardingType::Other => {
+                write!(
+                    f,
+                    "{{devices=[{}]{}",
+                    join(&self.tile_assignment_dimensions),
+                    join(&self.tile_assignment_devices)
+                )?;
+                if self.replicate_on_last_tile_dim {
+                    write!(f, " last_tile_dim_replicate")?
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// How each dimension of an array is split over the axes of a [`Mesh`]. A dimension can be split
+/// over zero, one, or multiple mesh axes, the array is replicated over the mesh axes that are not
+/// used.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PartitionSpec(Vec<Vec<String>>);
+
+impl PartitionSpec {
+    /// A spec where each dimension is either split over a single mesh axis or not split.
+    pub fn new<I, S>(dims: I) -> Self
+    where
+        I: IntoIterator<Item = Option<S>>,
+        S: Into<String>,
+    {
+        Self(dims.into_iter().map(|axis| axis.into_iter().map(Into::into).collect()).collect())
+    }
+
+    /// A spec where each dimension is split over some mesh axes, the major axis first.
+    pub fn with_axes<I, J, S>(dims: I) -> Self
+    where
+        I: IntoIterator<Item = J>,
+        J: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self(dims.into_iter().map(|axes| axes.into_iter().map(Into::into).collect()).collect())
+    }
+
+    /// A spec for an array of rank `rank` that is replicated on all the devices.
+    pub fn replicated(rank: usize) -> Self {
+        Self(vec![vec![]; rank])
+    }
+
+    pub fn rank(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The mesh axes used to split dimension `dim`.
+    pub fn axes(&self, dim: usize) -> &[String] {
+        &self.0[dim]
+    }
+}
+
+/// The part of an array held by a device of a [`Mesh`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Shard {
+    pub device_id: usize,
+    /// The index in the full array of the first element of the shard.
+    pub start: Vec<usize>,
+    pub dims: Vec<usize>,
+}
+
+impl Shard {
+    // The contiguous runs of elements of the shard in a row major array of dimensions `dims`,
+    // as (offset in the array, offset in the shard, length) triples counted in elements.
+    pub(crate) fn runs(&self, dims: &[usize]) -> Vec<(usize, usize, usize)> {
+        let rank = dims.len();
+        if rank == 0 {
+            return vec![(0, 0, 1)];
+        }
+        let row_len = self.dims[rank - 1];
+        let num_rows: usize = self.dims[..rank - 1].iter().product();
+        if row_len == 0 || num_rows == 0 {
+            return vec![];
+        }
+        let mut strides = vec![1; rank];
+        for i in (0..rank - 1).rev() {
+            strides[i] = strides[i + 1] * dims[i + 1]
+        }
+        let mut index = vec![0; rank - 1];
+        let mut runs = Vec::with_capacity(num_rows);
+        for row in 0..num_rows {
+            let offset: usize =
+                (0..rank - 1).map(|d| (self.start[d] + index[d]) * strides[d]).sum();
+            runs.push((offset + self.start[rank - 1], row * row_len, row_len));
+            for d in (0..rank - 1).rev() {
+                index[d] += 1;
+                if index[d] < self.dims[d] {
+                    break;
+                }
+                index[d] = 0
+            }
+        }
+        runs
+    }
+}
+
+/// Some devices arranged in a grid with named axes, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mesh {
+    axis_names: Vec<String>,
+    shape: Vec<usize>,
+    // The device ids in row major order over the mesh axes.
+    device_ids: Vec<usize>,
+}
+
+impl Mesh {
+    /// A mesh with the specified axes laid out over `devices` in row major order. The number of
+    /// devices must match the product of the axis sizes.
+    pub fn new<I, S>(axes: I, devices: &[PjRtDevice]) -> Result<Self>
+    where
+        I: IntoIterator<Item = (S, usize)>,
+        S: Into<String>,
+    {
+        Self::from_device_ids(axes, devices.iter().map(|d| d.id()).collect())
+    }
+
+    /// Same as [`Mesh::new`] but using device ids rather than devices.
+    pub fn from_device_ids<I, S>(axes: I, device_ids: Vec<usize>) -> Result<Self>
+    where
+        I: IntoIterator<Item = (S, usize)>,
+        S: Into<String>,
+    {
+        let (axis_names, shape): (Vec<String>, Vec<usize>) =
+            axes.into_iter().map(|(name, size)| (name.into(), size)).unzip();
+        if axis_names.is_empty() {
+            Err(Error::InvalidMesh { msg: "no axes".to_string() })?
+        }
+        for (i, (name, size)) in axis_names.iter().zip(shape.iter()).enumerate() {
+            if *size == 0 {
+                Err(Error::InvalidMesh { msg: format!("axis {name} has size 0") })?
+            }
+            if axis_names[..i].contains(name) {
+                Err(Error::InvalidMesh { msg: format!("duplicate axis {name}") })?
+            }
+        }
+        let num_devices: usize = shape.iter().product();
+        if device_ids.len() != num_devices {
+            Err(Error::InvalidMesh {
+                msg: format!("expected {num_devices} devices, got {}", device_ids.len()),
+            })?
+        }
+        for (i, id) in device_ids.iter().enumerate() {
+            if device_ids[..i].contains(id) {
+                Err(Error::InvalidMesh { msg: format!("duplicate device {id}") })?
+            }
+        }
+        Ok(Self { axis_names, shape, device_ids })
+    }
+
+    pub fn axis_names(&self) -> &[String] {
+        &self.axis_names
+    }
+
+    /// The size of each axis.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// The device ids in row major order over the mesh axes.
+    pub fn device_ids(&self) -> &[usize] {
+        &self.device_ids
+    }
+
+    pub fn num_devices(&self) -> usize {
+        self.device_ids.len()
+    }
+
+    /// The index of an axis from its name.
+    pub fn axis_index(&self, name: &str) -> Result<usize> {
+        self.axis_names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| Error::InvalidMesh { msg: format!("unknown axis {name}") })
+    }
+
+    pub fn axis_size(&self, name: &str) -> Result<usize> {
+        Ok(self.shape[self.axis_index(name)?])
+    }
+
+    // The indexes of the mesh axes used by each dimension of `spec`, an axis can only be used
+    // once.
+    fn spec_axes(&self, spec: &PartitionSpec) -> Result<Vec<Vec<usize>>> {
+        let mut used = vec![];
+        for dim in 0..spec.rank() {
+            for axis in spec.axes(dim) {
+                let index = self.axis_index(axis)?;
+                if used.contains(&index) {
+                    Err(Error::InvalidMesh { msg: format!("axis {axis} is used multiple times") })?
+                }
+                used.push(index)
+            }
+        }
+        (0..spec.rank())
+            .map(|dim| spec.axes(dim).iter().map(|axis| self.axis_index(axis)).collect())
+            .collect()
+    }
+
+    /// The number of tiles along each dimension of an array partitioned with `spec`.
+    pub fn tile_dims(&self, spec: &PartitionSpec) -> Result<Vec<usize>> {
+        (0..spec.rank())
+            .map(|dim| spec.axes(dim).iter().map(|axis| self.axis_size(axis)).product())
+            .collect()
+    }
+
+    /// The shard held by each device when an array of dimensions `dims` is partitioned with
+    /// `spec`, in the order of [`Mesh::device_ids`]. Each dimension has to be divisible by its
+    /// number of tiles. Replicated shards appear once per device.
+    pub fn shards(&self, spec: &PartitionSpec, dims: &[usize]) -> Result<Vec<Shard>> {
+        if spec.rank() != dims.len() {
+            Err(Error::InvalidMesh {
+                msg: format!("partition spec of rank {} for dims {dims:?}", spec.rank()),
+            })?
+        }
+        let tile_dims = self.tile_dims(spec)?;
+        for (dim, (size, tiles)) in dims.iter().zip(tile_dims.iter()).enumerate() {
+            if size % tiles != 0 {
+                Err(Error::InvalidMesh {
+                    msg: format!("dimension {dim} of size {size} is not divisible by {tiles}"),
+                })?
+            }
+        }
+        let shard_dims: Vec<usize> =
+            dims.iter().zip(tile_dims.iter()).map(|(size, tiles)| size / tiles).collect();
+        let axes = self.spec_axes(spec)?;
+        let mut mesh_index = vec![0; self.shape.len()];
+        let mut shards = Vec::with_capacity(self.device_ids.len());
+        for &device_id in self.device_ids.iter() {
+            let start = axes
+                .iter()
+                .zip(shard_dims.iter())
+                .map(|(axes, size)| {
+                    let tile = axes.iter().fold(0, |acc, a| acc * self.shape[*a] + mesh_index[*a]);
+                    tile * size
+                })
+                .collect();
+            shards.push(Shard { device_id, start, dims: shard_dims.clone() });
+            for a in (0..self.shape.len()).rev() {
+                mesh_index[a] += 1;
+                if mesh_index[a] < self.shape[a] {
+                    break;
+                }
+                mesh_index[a] = 0
+            }
+        }
+        Ok(shards)
+    }
+
+    /// The sharding of an array partitioned over this mesh with `spec`. The array is replicated
+    /// over the mesh axes that are not used by `spec`.
+    pub fn op_sharding(&self, spec: &PartitionSpec) -> Result<OpSharding> {
+        // The mesh axes in the order used for the tile assignment: the axes used by each array
+        // dimension followed by the unused axes.
+        let mut order: Vec<usize> = self.spec_axes(spec)?.into_iter().flatten().collect();
+        let num_used = order.len();
+        let unused: Vec<usize> = (0..self.shape.len()).filter(|i| !order.contains(i)).collect();
+        order.extend(unused);
+        if num_used == 0 {
+            return Ok(OpSharding::replicated());
+        }
+        let mut tile_dims: Vec<i64> = self.tile_dims(spec)?.into_iter().map(|d| d as i64).collect();
+        let replicas: usize = order[num_used..].iter().map(|i| self.shape[*i]).product();
+        if replicas > 1 {
+            tile_dims.push(replicas as i64)
+        }
+        Ok(OpSharding {
+            ty: OpShardingType::Other,
+            tile_assignment_dimensions: tile_dims,
+            tile_assignment_devices: self.transposed_device_ids(&order),
+            replicate_on_last_tile_dim: replicas > 1,
+        })
+    }
+
+    // The device ids in row major order over the mesh axes permuted by `order`.
+    fn transposed_device_ids(&self, order: &[usize]) -> Vec<i64> {
+        let rank = self.shape.len();
+        let mut strides = vec![1; rank];
+        for i in (0..rank - 1).rev() {
+            strides[i] = strides[i + 1] * self.shape[i + 1]
+        }
+        let mut index = vec![0; rank];
+        let mut ids = Vec::with_capacity(self.device_ids.len());
+        for _ in 0..self.device_ids.len() {
+            let offset: usize = order.iter().zip(index.iter()).map(|(a, i)| strides[*a] * i).sum();
+            ids.push(self.device_ids[offset] as i64);
+            // Increment the index over the permuted axes, the last one first.
+            for (pos, axis) in order.iter().enumerate().rev() {
+                index[pos] += 1;
+                if index[pos] < self.shape[*axis] {
+                    break;
+                }
+                index[pos] = 0
+            }
+        }
+        ids
+    }
+}
+
+/// Copy the per-device buffers of an array partitioned with `spec` back to the host and assemble
+/// them into a single literal, see [`Literal::unshard`]. The buffers must be in the order of
+/// [`Mesh::device_ids`] and each one must be stored on the corresponding device.
+pub fn assemble_from_shards(
+    shards: &[PjRtBuffer],
+    mesh: &Mesh,
+    spec: &PartitionSpec,
+) -> Result<Literal> {
+    let shards = shards
+        .iter()
+        .map(|buffer| Ok((buffer.device().id(), buffer.to_literal_sync()?)))
+        .collect::<Result<Vec<_>>>()?;
+    Literal::unshard(&shards, mesh, spec)
+}
@@ -0,0 +1,69 @@
+//! Hooks to collect metrics about compilations, executions, and host/device transfers.
+//!
+//! A type implementing the [`Metrics`] trait can be registered via [`set_metrics`], its methods
+//! then get called each time one of the monitored events happens. All the methods have a default
+//! implementation that does nothing so that only the relevant ones have to be implemented, e.g.
+//! to feed a Prometheus exporter.
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The direction of a data transfer between the host and a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferDirection {
+    HostToDevice,
+    DeviceToHost,
+    DeviceToDevice,
+}
+
+pub trait Metrics: Send + Sync {
+    /// Called once a computation has been compiled, `module_name` is the computation name.
+    fn on_compile_finished(&self, _duration: Duration, _module_name: &str) {}
+
+    /// Called once all the outputs of an execution are ready, the duration is measured from the
+    /// start of the execute call so it includes the transfer of literal arguments but not the
+    /// transfer of the results back to the host. As executions are asynchronous, this may be
+    /// called after the execute call has returned and from a runtime thread.
+    fn on_execute_finished(&self, _duration: Duration) {}
+
+    /// Called for each transfer of `bytes` bytes between the host and a device.
+    fn on_transfer(&self, _bytes: usize, _direction: TransferDirection) {}
+}
+
+static METRICS: RwLock<Option<Arc<dyn Metrics>>> = RwLock::new(None);
+
+/// Register the metrics hooks, this replaces any previously registered hooks.
+pub fn set_metrics(metrics: Arc<dyn Metrics>) {
+    let mut current = METRICS.write().unwrap_or_else(|e| e.into_inner());
+    *current = Some(metrics);
+}
+
+/// Remove the registered metrics hooks.
+pub fn clear_metrics() {
+    let mut current = METRICS.write().unwrap_or_else(|e| e.into_inner());
+    *current = None;
+}
+
+// The hooks are cloned out of the lock before being called so that a hook can register other
+// hooks, or take some time, without blocking the other threads.
+fn with_metrics<F: FnOnce(&dyn Metrics)>(f: F) {
+    let metrics = METRICS.read().ok().and_then(|metrics| metrics.clone());
+    if let Some(metrics) = metrics {
+        f(metrics.as_ref())
+    }
+}
+
+pub(crate) fn compile_finished(duration: Duration, module_name: &str) {
+    with_metrics(|m| m.on_compile_finished(duration, module_name))
+}
+
+pub(crate) fn execute_finished(duration: Duration) {
+    with_metrics(|m| m.on_execute_finished(duration))
+}
+
+pub(crate) fn transfer(bytes: usize, direction: TransferDirection) {
+    with_metrics(|m| m.on_transfer(bytes, direction))
+}
+
+pub(crate) fn is_enabled() -> bool {
+    METRICS.read().map_or(false, |m| m.is_some())
+}
@@ -0,0 +1,130 @@
+//! Dynamic loss scaling for mixed precision training.
+//!
+//! Gradients computed in half precision can underflow to zero. The loss is multiplied by a large
+//! scale before computing the gradients, and the gradients are divided by the same scale before
+//! updating the parameters. When some gradients overflow, the update is skipped and the scale is
+//! reduced, the scale grows again after a number of steps without overflow.
+//!
+//! The scale and the number of steps since the last overflow are carried between steps as a
+//! [`LossScale`] state, e.g. passed as parameters and returned as outputs of the training step
+//! computation.
+use crate::{ElementType, Literal, PrimitiveType, Result, XlaBuilder, XlaOp};
+
+/// The hyper-parameters of the dynamic loss scaling, these match the PyTorch `GradScaler`
+/// defaults.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradScaler {
+    /// The scale used for the first step.
+    pub init_scale: f32,
+    /// The factor applied to the scale after `growth_interval` steps without overflow.
+    pub growth_factor: f32,
+    /// The factor applied to the scale when some gradients overflow.
+    pub backoff_factor: f32,
+    /// The number of consecutive steps without overflow before growing the scale.
+    pub growth_interval: i32,
+}
+
+impl Default for GradScaler {
+    fn default() -> Self {
+        Self { init_scale: 65536., growth_factor: 2., backoff_factor: 0.5, growth_interval: 2000 }
+    }
+}
+
+/// The state of the dynamic loss scaling, see [`GradScaler::state_parameters`] and
+/// [`GradScaler::update_state`].
+#[derive(Debug, Clone)]
+pub struct LossScale {
+    /// A `F32` scalar with the current scale.
+    pub scale: XlaOp,
+    /// A `S32` scalar with the number of consecutive steps without overflow, the scale grows
+    /// when it reaches [`GradScaler::growth_interval`].
+    pub growth_tracker: XlaOp,
+}
+
+impl GradScaler {
+    /// The initial values of the scale and of the growth tracker.
+    pub fn initial_state(&self) -> (Literal, Literal) {
+        (Literal::scalar(self.init_scale), Literal::scalar(0i32))
+    }
+
+    /// Create the parameters holding the scale and the growth tracker with the parameter
+    /// numbers `param_index` and `param_index + 1`.
+    pub fn state_parameters(&self, builder: &XlaBuilder, param_index: i64) -> Result<LossScale> {
+        let scale = builder.parameter(param_index, ElementType::F32, &[], "loss_scale")?;
+        let growth_tracker =
+            builder.parameter(param_index + 1, ElementType::S32, &[], "growth_tracker")?;
+        Ok(LossScale { scale, growth_tracker })
+    }
+
+    /// Multiply `loss` by the current scale, the result is a `F32` node so that the scaled loss
+    /// does not overflow in half precision.
+    pub fn scale_loss(&self, loss: &XlaOp, state: &LossScale) -> Result<XlaOp> {
+        loss.convert(PrimitiveType::F32)? * &state.scale
+    }
+
+    /// Divide the gradients of a scaled loss by the current scale. The division is performed in
+    /// `F32` and the result converted back to the gradient type, so that dividing by a large
+    /// scale does not flush half precision gradients to zero. This also returns a scalar `PRED`
+    /// node that is true when all the unscaled gradients are finite.
+    pub fn unscale<B: std::borrow::Borrow<XlaOp>>(
+        &self,
+        grads: &[B],
+        state: &LossScale,
+    ) -> Result<(Vec<XlaOp>, XlaOp)> {
+        let inv_scale = (state.scale.builder().one(ElementType::F32)? / &state.scale)?;
+        let mut finite = state.scale.builder().one(ElementType::Pred)?;
+        let mut unscaled = Vec::with_capacity(grads.len());
+        for grad in grads.iter() {
+            let grad = grad.borrow();
+            let ty = grad.ty()?;
+            let grad = (grad.convert(PrimitiveType::F32)? * &inv_scale)?;
+            finite = finite.logical_and(&grad.all_finite()?)?;
+            unscaled.push(grad.convert(ty)?)
+        }
+        Ok((unscaled, finite))
+    }
+
+    /// The state for the next step: the scale is multiplied by `backoff_factor` when `finite`
+    /// is false, and by `growth_factor` after `growth_interval` consecutive finite steps.
+    pub fn update_state(&self, state: &LossScale, finite: &XlaOp) -> Result<LossScale> {
+        let builder = state.scale.builder();
+        let zero = builder.zero(ElementType::S32)?;
+        let tracker = (&state.growth_tracker + builder.one(ElementType::S32)?)?;
+        let grow = tracker.ge(&builder.c0(self.growth_interval)?)?;
+        let grown = (&state.scale * builder.c0(self.growth_factor)?)?;
+        let reduced = (&state.scale * builder.c0(self.backoff_factor)?)?;
+        let scale = finite.select(&grow.select(&grown, &state.scale)?, &reduced)?;
+        let growth_tracker = finite.select(&grow.select(&zero, &tracker)?, &zero)?;
+        Ok(LossScale { scale, growth_tracker })
+    }
+
+    /// Unscale the gradients of a scaled loss and apply `update` to the parameters only when all
+    /// the gradients are finite, the parameters are returned unchanged otherwise. This returns
+    /// the new parameters and the next loss scaling state.
+    ///
+    /// `update` runs in a conditional branch: it is called once with the builder of the branch,
+    /// the parameters, and the unscaled gradients, and must return new parameters with the same
+    /// shapes, e.g. `p - lr * g` for a plain SGD step.
+    pub fn step<F>(
+        &self,
+        state: &LossScale,
+        params: &[XlaOp],
+        grads: &[XlaOp],
+        update: F,
+    ) -> Result<(Vec<XlaOp>, LossScale)>
+    where
+        F: Fn(&XlaBuilder, &[XlaOp], &[XlaOp]) -> Result<Vec<XlaOp>>,
+    {
+        let (grads, finite) = self.unscale(grads, state)?;
+        let n = params.len();
+        let mut operands = params.to_vec();
+        operands.extend(grads);
+        let params = state.scale.builder().cond(
+            &finite,
+            |builder, operands| update(builder, &operands[..n], &operands[n..]),
+            |_, operands| Ok(operands[..n].to_vec()),
+            &operands,
+        )?;
+        Ok((params, self.update_state(state, &finite)?))
+    }
+}
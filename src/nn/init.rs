@@ -0,0 +1,179 @@
+//! Parameter initialization.
+//!
+//! An [`Init`] value can either generate the initial parameters on the host as a [`Literal`]
+//! using a Rust random number generator, or as a node that generates them on the device using a
+//! [`PrngKey`].
+//!
+//! The fan-in and fan-out used by the variance scaling schemes follow the JAX conventions: the
+//! last dimension is the output dimension, the second to last is the input dimension, and the
+//! remaining dimensions form the receptive field, e.g. `(kernel_h, kernel_w, in, out)` for
+//! convolutions.
+use crate::{ElementType, Error, Literal, PrngKey, Result, XlaBuilder, XlaOp};
+use rand::Rng;
+
+// Values of a truncated normal distribution are restricted to this many standard deviations.
+const TRUNCATION: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Init {
+    Constant(f64),
+    Uniform {
+        lo: f64,
+        hi: f64,
+    },
+    Normal {
+        mean: f64,
+        stdev: f64,
+    },
+    /// A normal distribution where values are restricted to two standard deviations around the
+    /// mean.
+    TruncatedNormal {
+        mean: f64,
+        stdev: f64,
+    },
+    /// Glorot/Xavier uniform, bounds are `±sqrt(6 / (fan_in + fan_out))`.
+    XavierUniform,
+    /// Glorot/Xavier normal, the standard deviation is `sqrt(2 / (fan_in + fan_out))`.
+    XavierNormal,
+    /// He/Kaiming uniform, bounds are `±sqrt(6 / fan_in)`.
+    KaimingUniform,
+    /// He/Kaiming normal, the standard deviation is `sqrt(2 / fan_in)`.
+    KaimingNormal,
+}
+
+/// The fan-in and fan-out for a parameter with the given dimensions.
+pub fn fans(dims: &[usize]) -> (usize, usize) {
+    match dims {
+        [] => (1, 1),
+        [d] => (*d, *d),
+        [receptive_field @ .., fan_in, fan_out] => {
+            let receptive_field = receptive_field.iter().product::<usize>();
+            (fan_in * receptive_field, fan_out * receptive_field)
+        }
+    }
+}
+
+// Box-Muller transform.
+fn sample_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1: f64 = 1. - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * u2).cos()
+}
+
+fn check_float(ty: ElementType) -> Result<()> {
+    match ty {
+        ElementType::F16 | ElementType::Bf16 | ElementType::F32 | ElementType::F64 => Ok(()),
+        ty => Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "init" }),
+    }
+}
+
+impl Init {
+    /// Replace the variance scaling schemes by the equivalent uniform or normal distribution.
+    fn resolve(&self, dims: &[usize]) -> Self {
+        let (fan_in, fan_out) = fans(dims);
+        let (fan_in, fan_out) = (fan_in as f64, fan_out as f64);
+        match *self {
+            Self::XavierUniform => {
+                let bound = (6. / (fan_in + fan_out)).sqrt();
+                Self::Uniform { lo: -bound, hi: bound }
+            }
+            Self::XavierNormal => {
+                Self::Normal { mean: 0., stdev: (2. / (fan_in + fan_out)).sqrt() }
+            }
+            Self::KaimingUniform => {
+                let bound = (6. / fan_in).sqrt();
+                Self::Uniform { lo: -bound, hi: bound }
+            }
+            Self::KaimingNormal => Self::Normal { mean: 0., stdev: (2. / fan_in).sqrt() },
+            init => init,
+        }
+    }
+
+    /// Generate the initial values on the host.
+    pub fn literal<R: Rng>(&self, rng: &mut R, ty: ElementType, dims: &[usize]) -> Result<Literal> {
+        check_float(ty)?;
+        let element_count = dims.iter().product::<usize>();
+        let values: Vec<f64> = match self.resolve(dims) {
+            Self::Constant(v) => vec![v; element_count],
+            Self::Uniform { lo, hi } => {
+                (0..element_count).map(|_| lo + (hi - lo) * rng.gen::<f64>()).collect()
+            }
+            Self::Normal { mean, stdev } => {
+                (0..element_count).map(|_| mean + stdev * sample_normal(rng)).collect()
+            }
+            Self::TruncatedNormal { mean, stdev } => (0..element_count)
+                .map(|_| loop {
+                    let v = sample_normal(rng);
+                    if v.abs() <= TRUNCATION {
+                        break mean + stdev * v;
+                    }
+                })
+                .collect(),
+            init @ (Self::XavierUniform
+            | Self::XavierNormal
+            | Self::KaimingUniform
+            | Self::KaimingNormal) => Err(Error::UnresolvedInit(format!("{init:?}")))?,
+        };
+        let dims: Vec<i64> = dims.iter().map(|d| *d as i64).collect();
+        let literal = Literal::vec1(&values).reshape(&dims)?;
+        if ty == ElementType::F64 {
+            Ok(literal)
+        } else {
+            literal.convert(ty.primitive_type())
+        }
+    }
+
+    /// A node generating the initial values on the device, the values only depend on `key`.
+    pub fn op(
+        &self,
+        key: &PrngKey,
+        builder: &XlaBuilder,
+        ty: ElementType,
+        dims: &[usize],
+    ) -> Result<XlaOp> {
+        check_float(ty)?;
+        let dims_i64: Vec<i64> = dims.iter().map(|d| *d as i64).collect();
+        let sample_ty = if ty == ElementType::F64 { ElementType::F64 } else { ElementType::F32 };
+        let cst = |v: f64| builder.c0(v)?.convert(sample_ty.primitive_type());
+        let values = match self.resolve(dims) {
+            Self::Constant(v) => cst(v)?.broadcast(&dims_i64)?,
+            Self::Uniform { lo, hi } => {
+                let u = key.uniform(builder, sample_ty, &dims_i64)?;
+                ((u * cst(hi - lo)?)? + cst(lo)?)?
+            }
+            Self::Normal { mean, stdev } => {
+                let n = key.normal(builder, sample_ty, &dims_i64)?;
+                ((n * cst(stdev)?)? + cst(mean)?)?
+            }
+            Self::TruncatedNormal { mean, stdev } => {
+                // Inverse transform sampling: map uniform values to the cdf range covered by the
+                // truncated distribution, and apply the inverse cdf.
+                let sqrt2 = std::f64::consts::SQRT_2;
+                let cdf = |x: f64| 0.5 * (1. + erf(x / sqrt2));
+                let (lo, hi) = (cdf(-TRUNCATION), cdf(TRUNCATION));
+                let u = key.uniform(builder, sample_ty, &dims_i64)?;
+                let u = ((u * cst(hi - lo)?)? + cst(lo)?)?;
+                let n = (((u * cst(2.)?)? - cst(1.)?)?.erf_inv()? * cst(sqrt2)?)?;
+                let n = n.clamp(&cst(-TRUNCATION)?, &cst(TRUNCATION)?)?;
+                ((n * cst(stdev)?)? + cst(mean)?)?
+            }
+            init @ (Self::XavierUniform
+            | Self::XavierNormal
+            | Self::KaimingUniform
+            | Self::KaimingNormal) => Err(Error::UnresolvedInit(format!("{init:?}")))?,
+        };
+        values.convert(ty.primitive_type())
+    }
+}
+
+// The error function, using the Abramowitz and Stegun approximation 7.1.26 which has a maximum
+// error of 1.5e-7.
+fn erf(x: f64) -> f64 {
+    let sign = x.signum();
+    let x = x.abs();
+    let t = 1. / (1. + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    sign * (1. - poly * (-x * x).exp())
+}
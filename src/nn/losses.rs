@@ -0,0 +1,67 @@
+//! Loss functions.
+//!
+//! All the losses return a scalar node holding the loss averaged over all the examples. The
+//! computations are performed in a numerically stable way, e.g. the cross-entropy uses a
+//! log-softmax rather than taking the logarithm of a softmax.
+use crate::{Result, XlaOp};
+
+/// Select `log_probs[..., labels[...]]` where `log_probs` has shape `(..., num_classes)` and
+/// `labels` has shape `(...)`.
+fn gather_labels(log_probs: &XlaOp, labels: &XlaOp) -> Result<XlaOp> {
+    let dims = labels.dims()?;
+    let num_examples = dims.iter().product::<usize>() as i64;
+    let num_classes = *log_probs.dims()?.last().unwrap_or(&1) as i64;
+    let log_probs = log_probs.reshape(&[num_examples, num_classes])?;
+    let labels = labels.reshape(&[num_examples, 1])?;
+    let rows = log_probs.builder().iota(labels.ty()?.element_type()?, &[num_examples, 1], 0)?;
+    let indices = rows.concat_in_dim(&[labels], 1)?;
+    log_probs.gather(&indices, &[], &[0, 1], &[0, 1], Some(1), &[1, 1])
+}
+
+/// The cross-entropy between the distribution given by `logits` of shape `(..., num_classes)`
+/// and the integer `labels` of shape `(...)`.
+///
+/// With a non-zero `label_smoothing`, the target distribution puts a weight of
+/// `1 - label_smoothing` on the label and spreads `label_smoothing` uniformly over all the
+/// classes.
+pub fn cross_entropy(logits: &XlaOp, labels: &XlaOp, label_smoothing: f64) -> Result<XlaOp> {
+    let builder = logits.builder();
+    let ty = logits.ty()?;
+    let log_probs = logits.log_softmax(-1)?;
+    let nll = gather_labels(&log_probs, labels)?.neg()?;
+    let loss = if label_smoothing > 0. {
+        let num_examples = nll.dims()?[0] as i64;
+        let num_classes = *log_probs.dims()?.last().unwrap_or(&1) as i64;
+        let log_probs = log_probs.reshape(&[num_examples, num_classes])?;
+        let smooth = log_probs.reduce_mean(&[1], false)?.neg()?;
+        let eps = builder.c0(label_smoothing)?.convert(ty)?;
+        let one_minus_eps = builder.c0(1. - label_smoothing)?.convert(ty)?;
+        ((nll * one_minus_eps)? + (smooth * eps)?)?
+    } else {
+        nll
+    };
+    loss.reduce_mean(&[0], false)
+}
+
+/// The mean squared error between `pred` and `target`.
+pub fn mse(pred: &XlaOp, target: &XlaOp) -> Result<XlaOp> {
+    let diff = (pred - target)?;
+    let dims: Vec<i64> = (0..diff.rank()? as i64).collect();
+    (&diff * &diff)?.reduce_mean(&dims, false)
+}
+
+/// The Huber loss between `pred` and `target`, this is quadratic for absolute differences below
+/// `delta` and linear above.
+pub fn huber(pred: &XlaOp, target: &XlaOp, delta: f64) -> Result<XlaOp> {
+    let builder = pred.builder();
+    let ty = pred.ty()?;
+    let abs_diff = (pred - target)?.abs()?;
+    let delta = builder.c0(delta)?.convert(ty)?;
+    let half = builder.c0(0.5f64)?.convert(ty)?;
+    // Clamping the quadratic part avoids computing a large square for outliers.
+    let quadratic = abs_diff.min(&delta)?;
+    let linear = (&abs_diff - &quadratic)?;
+    let loss = (((&quadratic * &quadratic)? * half)? + (linear * delta)?)?;
+    let dims: Vec<i64> = (0..loss.rank()? as i64).collect();
+    loss.reduce_mean(&dims, false)
+}
@@ -0,0 +1,272 @@
+//! Neural network building blocks composed from [`XlaOp`] nodes.
+pub mod amp;
+pub mod init;
+pub mod losses;
+
+use crate::{
+    ArrayShape, ElementType, Error, Literal, PrngKey, RandomAlgorithm, Result, XlaBuilder, XlaOp,
+};
+
+/// Scaled dot-product attention, `softmax(q.kᵀ * scale).v`.
+///
+/// - `q` has shape `(..., q_len, head_dim)`.
+/// - `k` has shape `(..., kv_len, head_dim)`.
+/// - `v` has shape `(..., kv_len, v_dim)`.
+/// - `mask` is an optional `PRED` node, positions where the mask is false are not attended to.
+///   Its shape must match the trailing dimensions of `(..., q_len, kv_len)`, e.g. a causal mask
+///   can be passed as a `(q_len, kv_len)` node.
+/// - `scale` defaults to `1/sqrt(head_dim)`.
+///
+/// The leading dimensions of `q`, `k`, and `v` are used as batch dimensions and the result has
+/// shape `(..., q_len, v_dim)`. The softmax subtracts the row maximum so that large scores do not
+/// overflow. The graph follows the canonical attention pattern so that backends with a fused
+/// attention rewriter, e.g. the cudnn one on GPUs, can recognize it.
+pub fn sdpa(
+    q: &XlaOp,
+    k: &XlaOp,
+    v: &XlaOp,
+    mask: Option<&XlaOp>,
+    scale: Option<f64>,
+) -> Result<XlaOp> {
+    let builder = q.builder();
+    let ty = q.ty()?;
+    let q_dims = q.dims()?;
+    let rank = q_dims.len() as i64;
+    let head_dim = q_dims.last().copied().unwrap_or(1);
+    let scale = scale.unwrap_or_else(|| 1. / (head_dim as f64).sqrt());
+    let batch_dims: Vec<i64> = (0..rank - 2).collect();
+    let scores = q.dot_general(k, &[rank - 1], &[rank - 1], &batch_dims, &batch_dims)?;
+    let scores = (scores * builder.c0(scale)?.convert(ty)?)?;
+    let scores = match mask {
+        None => scores,
+        Some(mask) => {
+            let score_dims: Vec<i64> = scores.dims()?.iter().map(|d| *d as i64).collect();
+            let mask_rank = mask.rank()? as i64;
+            let bcast_dims: Vec<i64> = (rank - mask_rank..rank).collect();
+            let mask = mask.broadcast_in_dim(&score_dims, &bcast_dims)?;
+            let min_value = builder.min_finite_value(ty.element_type()?)?;
+            let min_value = min_value.broadcast(&score_dims)?;
+            mask.select(&scores, &min_value)?
+        }
+    };
+    let probs = scores.softmax(-1)?;
+    probs.dot_general(v, &[rank - 1], &[rank - 2], &batch_dims, &batch_dims)
+}
+
+/// Rotary positional embeddings (RoPE).
+///
+/// The rotation angles are computed once on the host when creating this value and cached for
+/// positions up to `max_seq_len`, [`RotaryEmbedding::apply`] then only embeds the required rows
+/// as constants.
+#[derive(Debug, Clone)]
+pub struct RotaryEmbedding {
+    head_dim: usize,
+    max_seq_len: usize,
+    interleaved: bool,
+    cos: Vec<f32>,
+    sin: Vec<f32>,
+}
+
+impl RotaryEmbedding {
+    /// Precompute the angles for a head dimension `head_dim`, which has to be even, and for
+    /// positions from 0 to `max_seq_len` (exclusive), `base` is typically 10000. An error is
+    /// returned if `head_dim` is odd.
+    ///
+    /// When `interleaved` is true the rotation applies to the pairs of adjacent elements `(x[2i],
+    /// x[2i+1])` as in the original RoPE implementation, otherwise it applies to the pairs
+    /// `(x[i], x[i + head_dim/2])` as in GPT-NeoX.
+    pub fn new(head_dim: usize, max_seq_len: usize, base: f64, interleaved: bool) -> Result<Self> {
+        if head_dim % 2 != 0 {
+            Err(Error::InvalidDims {
+                dims: vec![head_dim as i64],
+                msg: "the rotary embedding head dimension must be even",
+            })?
+        }
+        let half_dim = head_dim / 2;
+        let mut cos = Vec::with_capacity(max_seq_len * half_dim);
+        let mut sin = Vec::with_capacity(max_seq_len * half_dim);
+        for pos in 0..max_seq_len {
+            for i in 0..half_dim {
+                let inv_freq = 1. / base.powf(2. * i as f64 / head_dim as f64);
+                let angle = pos as f64 * inv_freq;
+                cos.push(angle.cos() as f32);
+                sin.push(angle.sin() as f32);
+            }
+        }
+        Ok(Self { head_dim, max_seq_len, interleaved, cos, sin })
+    }
+
+    pub fn head_dim(&self) -> usize {
+        self.head_dim
+    }
+
+    pub fn max_seq_len(&self) -> usize {
+        self.max_seq_len
+    }
+
+    fn table(&self, x: &XlaOp, table: &[f32], offset: usize, seq_len: usize) -> Result<XlaOp> {
+        let half_dim = self.head_dim / 2;
+        if offset + seq_len > self.max_seq_len {
+            Err(Error::IndexOutOfBounds {
+                index: (offset + seq_len) as i64,
+                rank: self.max_seq_len,
+            })?
+        }
+        let table = &table[offset * half_dim..(offset + seq_len) * half_dim];
+        let table = Literal::vec1(table).reshape(&[seq_len as i64, half_dim as i64])?;
+        x.builder().constant_literal(&table)?.convert(x.ty()?)
+    }
+
+    /// Apply the rotary embeddings to `x` which has shape `(..., seq_len, head_dim)`, the
+    /// first element on the sequence dimension has position `offset`.
+    pub fn apply(&self, x: &XlaOp, offset: usize) -> Result<XlaOp> {
+        let dims: Vec<i64> = x.dims()?.iter().map(|d| *d as i64).collect();
+        let rank = dims.len();
+        if rank < 2 || dims[rank - 1] != self.head_dim as i64 {
+            Err(Error::UnexpectedNumberOfDims { expected: 2, got: rank, dims: dims.clone() })?
+        }
+        let seq_len = dims[rank - 2] as usize;
+        let half_dim = (self.head_dim / 2) as i64;
+        let cos = self.table(x, &self.cos, offset, seq_len)?;
+        let sin = self.table(x, &self.sin, offset, seq_len)?;
+        let mut half_dims = dims.clone();
+        half_dims[rank - 1] = half_dim;
+        let bcast = [rank as i64 - 2, rank as i64 - 1];
+        let cos = cos.broadcast_in_dim(&half_dims, &bcast)?;
+        let sin = sin.broadcast_in_dim(&half_dims, &bcast)?;
+        let (x0, x1) = if self.interleaved {
+            let mut pair_dims = half_dims.clone();
+            pair_dims.push(2);
+            let x = x.reshape(&pair_dims)?;
+            (x.at(0, -1)?, x.at(1, -1)?)
+        } else {
+            (x.slice_in_dim1(0, half_dim, -1)?, x.slice_in_dim1(half_dim, 2 * half_dim, -1)?)
+        };
+        let r0 = ((&x0 * &cos)? - (&x1 * &sin)?)?;
+        let r1 = ((&x0 * &sin)? + (&x1 * &cos)?)?;
+        if self.interleaved {
+            let mut pair_dims = half_dims.clone();
+            pair_dims.push(1);
+            let r0 = r0.reshape(&pair_dims)?;
+            let r1 = r1.reshape(&pair_dims)?;
+            r0.concat_in_dim(&[r1], -1)?.reshape(&dims)
+        } else {
+            r0.concat_in_dim(&[r1], -1)
+        }
+    }
+}
+
+/// The sinusoidal positional encodings from "Attention Is All You Need" as a constant node of
+/// shape `(seq_len, dim)`, starting at position `offset`.
+///
+/// Even indexes on the last dimension hold `sin(pos / 10000^(i/dim))` and odd indexes hold the
+/// associated cosine.
+pub fn sinusoidal_positional_encoding(
+    builder: &XlaBuilder,
+    offset: usize,
+    seq_len: usize,
+    dim: usize,
+    ty: ElementType,
+) -> Result<XlaOp> {
+    let mut values = Vec::with_capacity(seq_len * dim);
+    for pos in offset..offset + seq_len {
+        for i in 0..dim {
+            let angle = pos as f64 / 10000f64.powf((i - i % 2) as f64 / dim as f64);
+            let value = if i % 2 == 0 { angle.sin() } else { angle.cos() };
+            values.push(value as f32)
+        }
+    }
+    let values = Literal::vec1(&values).reshape(&[seq_len as i64, dim as i64])?;
+    builder.constant_literal(&values)?.convert(ty.primitive_type())
+}
+
+/// The global L2 norm of a list of nodes, e.g. the gradients for all the parameters of a model.
+/// The norm is computed as a `F32` scalar whatever the element types of the nodes.
+pub fn global_norm<B: std::borrow::Borrow<XlaOp>>(xs: &[B]) -> Result<XlaOp> {
+    let mut sum_squares: Option<XlaOp> = None;
+    for x in xs.iter() {
+        let x = x.borrow().convert(crate::PrimitiveType::F32)?;
+        let dims: Vec<i64> = (0..x.rank()? as i64).collect();
+        let s = (&x * &x)?.reduce_sum(&dims, false)?;
+        sum_squares = Some(match sum_squares {
+            None => s,
+            Some(acc) => (acc + s)?,
+        })
+    }
+    match sum_squares {
+        Some(sum_squares) => sum_squares.sqrt(),
+        None => Err(Error::EmptyOpList { op: "global_norm" }),
+    }
+}
+
+/// Rescale the nodes so that their global L2 norm is at most `max_norm`, nodes are left unchanged
+/// if the global norm is already below `max_norm`. This returns the rescaled nodes as well as the
+/// global norm before clipping.
+pub fn clip_by_global_norm<B: std::borrow::Borrow<XlaOp>>(
+    xs: &[B],
+    max_norm: f64,
+) -> Result<(Vec<XlaOp>, XlaOp)> {
+    let norm = global_norm(xs)?;
+    let max_norm = norm.builder().c0(max_norm as f32)?;
+    let scale = (&max_norm / norm.max(&max_norm)?)?;
+    let clipped = xs
+        .iter()
+        .map(|x| {
+            let x = x.borrow();
+            x * scale.convert(x.ty()?)?
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((clipped, norm))
+}
+
+// Zero the elements of `x` where `bits`, some uniformly distributed `U32` values with the same
+// shape as `x`, are below `rate * 2^32` and rescale the remaining ones by `1 / (1 - rate)`.
+fn dropout_with_bits(x: &XlaOp, rate: f64, bits: &XlaOp) -> Result<XlaOp> {
+    let builder = x.builder();
+    let ty = x.ty()?;
+    let threshold = (rate * (1u64 << 32) as f64) as u32;
+    let keep = bits.ge(&builder.c0(threshold)?)?;
+    let scale = builder.c0(1. / (1. - rate))?.convert(ty)?;
+    let zeros = x.zeros_like()?;
+    keep.select(&(x * scale)?, &zeros)
+}
+
+fn check_dropout_rate(rate: f64) -> Result<()> {
+    if !(0. ..1.).contains(&rate) {
+        Err(Error::InvalidDropoutRate(rate))?
+    }
+    Ok(())
+}
+
+/// Randomly zero the elements of `x` with probability `rate`, the other elements are scaled by
+/// `1 / (1 - rate)` so that the expected value is unchanged. `rate` has to be in `[0, 1)`.
+///
+/// The mask only depends on `key`, this returns the output as well as the key to use for the
+/// next random op so that a key is never used twice. As the key is embedded in the graph, the
+/// mask is the same for each execution of the resulting computation, see
+/// [`dropout_with_state`] to get a new mask on each execution.
+pub fn dropout(x: &XlaOp, rate: f64, key: &PrngKey) -> Result<(XlaOp, PrngKey)> {
+    check_dropout_rate(rate)?;
+    let (next_key, key) = key.split();
+    let dims: Vec<i64> = x.dims()?.iter().map(|d| *d as i64).collect();
+    let bits = key.bits(x.builder(), ElementType::U32, &dims)?;
+    Ok((dropout_with_bits(x, rate, &bits)?, next_key))
+}
+
+/// A version of [`dropout`] where the generator state is a `U64` node of shape `(2,)`, e.g. a
+/// parameter of a training step initialized with [`PrngKey::state`]. This returns the output and
+/// the next generator state, which can be returned by the computation and passed back on the
+/// following execution so that each step uses a different mask.
+pub fn dropout_with_state(
+    x: &XlaOp,
+    rate: f64,
+    state: &XlaOp,
+    algorithm: RandomAlgorithm,
+) -> Result<(XlaOp, XlaOp)> {
+    check_dropout_rate(rate)?;
+    let dims: Vec<i64> = x.dims()?.iter().map(|d| *d as i64).collect();
+    let shape = ArrayShape::new_with_type(ElementType::U32, dims);
+    let result = state.rng_bit_generator(algorithm, &shape)?;
+    let bits = result.get_tuple_element(1)?;
+    Ok((dropout_with_bits(x, rate, &bits)?, result.get_tuple_element(0)?))
+}
@@ -0,0 +1,194 @@
+//! NUMA topology and thread placement.
+//!
+//! On multi-socket machines, accessing memory attached to another socket is noticeably slower.
+//! [`NodeBinding`] restricts the current thread to the cpus of a single NUMA node and optionally
+//! places its memory allocations on that node. Threads inherit the cpu affinity and the memory
+//! policy of the thread that creates them, this is what [`crate::PjRtClient::cpu_with_options`]
+//! relies on to bind the threads of a CPU client. This is only supported on Linux, the topology
+//! is read from `/sys/devices/system/node`.
+use crate::{Error, Result};
+
+/// Where the memory allocated by a bound thread is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumaMemoryPolicy {
+    /// Keep the current policy, by default memory is allocated on the node of the cpu that first
+    /// touches it.
+    #[default]
+    Default,
+    /// Allocate on the node, falling back to the other nodes when it is out of memory.
+    Preferred,
+    /// Only allocate on the node, allocations fail when it is out of memory.
+    Bind,
+}
+
+// Parse a cpu or node list as used by sysfs, e.g. `0-3,8,10-11`.
+fn parse_list(list: &str) -> Result<Vec<usize>> {
+    let mut ids = vec![];
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => ids.extend(start.parse::<usize>()?..=end.parse::<usize>()?),
+            None => ids.push(range.parse()?),
+        }
+    }
+    Ok(ids)
+}
+
+fn read_list(path: &str) -> Result<Vec<usize>> {
+    let list = std::fs::read_to_string(path)
+        .map_err(|err| Error::Numa(format!("cannot read {path}: {err}")))?;
+    parse_list(&list)
+}
+
+/// The ids of the online NUMA nodes.
+pub fn nodes() -> Result<Vec<usize>> {
+    read_list("/sys/devices/system/node/online")
+}
+
+/// The ids of the cpus that belong to `node`.
+pub fn node_cpus(node: usize) -> Result<Vec<usize>> {
+    let cpus = read_list(&format!("/sys/devices/system/node/node{node}/cpulist"))?;
+    if cpus.is_empty() {
+        Err(Error::Numa(format!("node {node} has no cpus")))?
+    }
+    Ok(cpus)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use crate::{Error, Result};
+
+    const MPOL_PREFERRED: i32 = 1;
+    const MPOL_BIND: i32 = 2;
+    // The number of nodes that a node mask can hold, this is the kernel's default maximum.
+    const MAX_NODES: usize = 1024;
+    const BITS_PER_WORD: usize = libc::c_ulong::BITS as usize;
+
+    fn last_error(what: &str) -> Error {
+        Error::Numa(format!("{what} failed: {}", std::io::Error::last_os_error()))
+    }
+
+    pub(super) struct Saved {
+        cpus: libc::cpu_set_t,
+        mode: i32,
+        nodemask: Vec<libc::c_ulong>,
+    }
+
+    pub(super) fn save() -> Result<Saved> {
+        let mut cpus: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        if unsafe { libc::sched_getaffinity(0, size, &mut cpus) } != 0 {
+            Err(last_error("sched_getaffinity"))?
+        }
+        let mut mode = 0i32;
+        let mut nodemask = vec![0 as libc::c_ulong; MAX_NODES / BITS_PER_WORD];
+        let status = unsafe {
+            libc::syscall(
+                libc::SYS_get_mempolicy,
+                &mut mode as *mut i32,
+                nodemask.as_mut_ptr(),
+                MAX_NODES as libc::c_ulong,
+                std::ptr::null_mut::<libc::c_void>(),
+                0 as libc::c_ulong,
+            )
+        };
+        if status != 0 {
+            Err(last_error("get_mempolicy"))?
+        }
+        Ok(Saved { cpus, mode, nodemask })
+    }
+
+    fn set_mempolicy(mode: i32, nodemask: &[libc::c_ulong]) -> Result<()> {
+        // The kernel reads `maxnode - 1` bits from the mask.
+        let maxnode = (nodemask.len() * BITS_PER_WORD + 1) as libc::c_ulong;
+        let status =
+            unsafe { libc::syscall(libc::SYS_set_mempolicy, mode, nodemask.as_ptr(), maxnode) };
+        if status != 0 {
+            Err(last_error("set_mempolicy"))?
+        }
+        Ok(())
+    }
+
+    pub(super) fn restore(saved: &Saved) -> Result<()> {
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        if unsafe { libc::sched_setaffinity(0, size, &saved.cpus) } != 0 {
+            Err(last_error("sched_setaffinity"))?
+        }
+        set_mempolicy(saved.mode, &saved.nodemask)
+    }
+
+    pub(super) fn bind(node: usize, cpus: &[usize], policy: super::NumaMemoryPolicy) -> Result<()> {
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                Err(Error::Numa(format!("cpu {cpu} of node {node} is out of the cpu set range")))?
+            }
+            unsafe { libc::CPU_SET(cpu, &mut set) }
+        }
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        if unsafe { libc::sched_setaffinity(0, size, &set) } != 0 {
+            Err(last_error("sched_setaffinity"))?
+        }
+        let mode = match policy {
+            super::NumaMemoryPolicy::Default => return Ok(()),
+            super::NumaMemoryPolicy::Preferred => MPOL_PREFERRED,
+            super::NumaMemoryPolicy::Bind => MPOL_BIND,
+        };
+        let mut nodemask = vec![0 as libc::c_ulong; node / BITS_PER_WORD + 1];
+        nodemask[node / BITS_PER_WORD] |= 1 << (node % BITS_PER_WORD);
+        set_mempolicy(mode, &nodemask)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use crate::{Error, Result};
+
+    pub(super) struct Saved;
+
+    pub(super) fn save() -> Result<Saved> {
+        Err(Error::Numa("thread binding is only supported on linux".to_string()))
+    }
+
+    pub(super) fn restore(_saved: &Saved) -> Result<()> {
+        Ok(())
+    }
+
+    pub(super) fn bind(_: usize, _: &[usize], _: super::NumaMemoryPolicy) -> Result<()> {
+        save().map(|_| ())
+    }
+}
+
+/// Restricts the current thread to the cpus of a NUMA node, and possibly its memory allocations
+/// to this node. The previous cpu affinity and memory policy are restored when this is dropped.
+pub struct NodeBinding {
+    node: usize,
+    saved: sys::Saved,
+    // The binding applies to the current thread so it must be dropped on this thread.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl NodeBinding {
+    /// Bind the current thread to `node`.
+    pub fn bind_current_thread(node: usize, policy: NumaMemoryPolicy) -> Result<Self> {
+        let cpus = node_cpus(node)?;
+        let saved = sys::save()?;
+        if let Err(err) = sys::bind(node, &cpus, policy) {
+            // Undo a partially applied binding, the original error is the one worth reporting.
+            let _ = sys::restore(&saved);
+            Err(err)?
+        }
+        Ok(Self { node, saved, _not_send: std::marker::PhantomData })
+    }
+
+    pub fn node(&self) -> usize {
+        self.node
+    }
+}
+
+impl Drop for NodeBinding {
+    fn drop(&mut self) {
+        if let Err(err) = sys::restore(&self.saved) {
+            log::warn!("cannot restore the thread placement after numa node {}: {err}", self.node)
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Deterministic random number generation based on explicit keys.
+//!
+//! Similar to `jax.random`, a [`PrngKey`] holds the state of the random number generator on the
+//! host. Keys are never updated in place: new independent keys are derived with
+//! [`PrngKey::split`] or [`PrngKey::fold_in`], and each key is used to generate the random
+//! values for a single node. The generated values only depend on the key so computations are
+//! reproducible across executions and across processes.
+//!
+//! ```ignore
+//! let key = xla::PrngKey::new(42);
+//! let (key, subkey) = key.split();
+//! let x = subkey.uniform(&builder, xla::ElementType::F32, &[2, 3])?;
+//! ```
+use crate::{ArrayShape, ElementType, Error, RandomAlgorithm, Result, XlaBuilder, XlaOp};
+
+/// A key for the random number generator, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PrngKey {
+    state: [u64; 2],
+    algorithm: RandomAlgorithm,
+}
+
+// The splitmix64 finalizer, used to derive new keys on the host.
+fn mix64(v: u64) -> u64 {
+    let v = v.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let v = (v ^ (v >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let v = (v ^ (v >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    v ^ (v >> 31)
+}
+
+// Mixed in the keys derived by `split`, so that they never match the keys derived by `fold_in`.
+const SPLIT_TAG: u64 = 0x73_706c_6974;
+
+impl PrngKey {
+    // Two keys derived from the same key with different `data` or `tag` are always different,
+    // as `mix64` is a bijection.
+    fn derive(&self, data: u64, tag: u64) -> Self {
+        let s0 = mix64(self.state[0] ^ mix64(data));
+        let s1 = mix64(self.state[1].wrapping_add(s0) ^ tag);
+        Self { state: [s0, s1], algorithm: self.algorithm }
+    }
+
+    /// Create a new key from a seed, using the three-fry algorithm.
+    pub fn new(seed: u64) -> Self {
+        Self { state: [mix64(seed), mix64(!seed)], algorithm: RandomAlgorithm::ThreeFry }
+    }
+
+    /// Create a key from a raw generator state.
+    pub fn from_state(state: [u64; 2]) -> Self {
+        Self { state, algorithm: RandomAlgorithm::ThreeFry }
+    }
+
+    /// The same key using a different random algorithm.
+    pub fn with_algorithm(self, algorithm: RandomAlgorithm) -> Self {
+        Self { algorithm, ..self }
+    }
+
+    /// The raw generator state.
+    pub fn state(&self) -> [u64; 2] {
+        self.state
+    }
+
+    pub fn algorithm(&self) -> RandomAlgorithm {
+        self.algorithm
+    }
+
+    /// Derive a new key from this key and some data, e.g. a step number.
+    pub fn fold_in(&self, data: u64) -> Self {
+        self.derive(data, 0)
+    }
+
+    /// Split this key in two new independent keys, these are also independent from the keys
+    /// returned by [`PrngKey::fold_in`].
+    pub fn split(&self) -> (Self, Self) {
+        (self.derive(0, SPLIT_TAG), self.derive(1, SPLIT_TAG))
+    }
+
+    /// Split this key in `n` new independent keys, the first two are the ones returned by
+    /// [`PrngKey::split`].
+    pub fn split_n(&self, n: usize) -> Vec<Self> {
+        (0..n as u64).map(|i| self.derive(i, SPLIT_TAG)).collect()
+    }
+
+    /// A node holding the generator state for this key.
+    pub fn state_op(&self, builder: &XlaBuilder) -> Result<XlaOp> {
+        builder.constant_r1(&self.state)
+    }
+
+    /// A node with random bits, `ty` has to be an unsigned integer type.
+    pub fn bits(&self, builder: &XlaBuilder, ty: ElementType, dims: &[i64]) -> Result<XlaOp> {
+        match ty {
+            ElementType::U8 | ElementType::U16 | ElementType::U32 | ElementType::U64 => {}
+            ty => Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "bits" })?,
+        }
+        let shape = ArrayShape::new_with_type(ty, dims.to_vec());
+        let state = self.state_op(builder)?;
+        state.rng_bit_generator(self.algorithm, &shape)?.get_tuple_element(1)
+    }
+
+    /// A node with values sampled uniformly in `[0, 1)`, `ty` has to be `F32` or `F64`.
+    pub fn uniform(&self, builder: &XlaBuilder, ty: ElementType, dims: &[i64]) -> Result<XlaOp> {
+        // Use the high bits for the mantissa of a float in [1, 2) and shift it back to [0, 1).
+        let bits = match ty {
+            ElementType::F32 => {
+                let bits = self.bits(builder, ElementType::U32, dims)?;
+                let bits = bits.shift_right_logical(&builder.c0(9u32)?)?;
+                bits.or(&builder.c0(0x3f80_0000u32)?)?
+            }
+            ElementType::F64 => {
+                let bits = self.bits(builder, ElementType::U64, dims)?;
+                let bits = bits.shift_right_logical(&builder.c0(12u64)?)?;
+                bits.or(&builder.c0(0x3ff0_0000_0000_0000u64)?)?
+            }
+            ty => Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "uniform" })?,
+        };
+        bits.bitcast_convert(ty.primitive_type())? - builder.one(ty)?
+    }
+
+    /// A node with values sampled from a normal distribution with mean 0 and standard
+    /// deviation 1, `ty` has to be `F32` or `F64`.
+    pub fn normal(&self, builder: &XlaBuilder, ty: ElementType, dims: &[i64]) -> Result<XlaOp> {
+        // Box-Muller transform.
+        let (k1, k2) = self.split();
+        let one = builder.one(ty)?;
+        let u1 = (&one - k1.uniform(builder, ty, dims)?)?;
+        let u2 = k2.uniform(builder, ty, dims)?;
+        let two_pi = builder.c0(2. * std::f64::consts::PI)?.convert(ty.primitive_type())?;
+        let minus_two = builder.c0(-2f64)?.convert(ty.primitive_type())?;
+        let radius = (u1.log()? * minus_two)?.sqrt()?;
+        radius * (u2 * two_pi)?.cos()?
+    }
+}
@@ -0,0 +1,255 @@
+//! Python bindings, these expose clients, computations, literals, and executables so that
+//! compiled artifacts can be exercised from Python.
+//!
+//! The extension module itself is built from the `python` directory with `maturin develop`, it
+//! calls [`register`] to populate the module:
+//!
+//! ```python
+//! import numpy as np
+//! import xla
+//!
+//! client = xla.PjRtClient.cpu()
+//! exe = client.compile(xla.XlaComputation.from_text_file("model.hlo"))
+//! outputs = exe.execute([np.ones((2, 3), dtype=np.float32)])
+//! print(outputs[0].to_numpy())
+//! ```
+//!
+//! Clients and literals are tied to the thread that created them, using them from another
+//! Python thread raises an exception.
+use crate::{
+    ArrayElement, ElementType, Error, HloModuleProto, Literal, PjRtClient, PjRtLoadedExecutable,
+    XlaComputation,
+};
+use numpy::{
+    PyArray1, PyArrayDescrMethods, PyArrayMethods, PyReadonlyArrayDyn, PyUntypedArray,
+    PyUntypedArrayMethods,
+};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> Self {
+        PyRuntimeError::new_err(err.to_string())
+    }
+}
+
+// Calls `$f::<T>($args)` with `T` the rust type corresponding to the element type `$ty`.
+macro_rules! dispatch_element_type {
+    ($ty:expr, $f:ident($($args:expr),*)) => {
+        match $ty {
+            ElementType::U8 => $f::<u8>($($args),*),
+            ElementType::U16 => $f::<u16>($($args),*),
+            ElementType::U32 => $f::<u32>($($args),*),
+            ElementType::U64 => $f::<u64>($($args),*),
+            ElementType::S8 => $f::<i8>($($args),*),
+            ElementType::S16 => $f::<i16>($($args),*),
+            ElementType::S32 => $f::<i32>($($args),*),
+            ElementType::S64 => $f::<i64>($($args),*),
+            ElementType::F32 => $f::<f32>($($args),*),
+            ElementType::F64 => $f::<f64>($($args),*),
+            ty => Err(PyRuntimeError::new_err(format!("unsupported element type {ty:?}"))),
+        }
+    };
+}
+
+fn array_to_literal<T: ArrayElement + numpy::Element>(
+    array: &Bound<'_, PyAny>,
+) -> PyResult<Literal> {
+    let array = array.extract::<PyReadonlyArrayDyn<'_, T>>()?;
+    // Iterating over the view yields the elements in row major order whatever its strides.
+    let data: Vec<T> = array.as_array().iter().copied().collect();
+    let mut literal = Literal::create_from_shape(T::TY.primitive_type(), array.shape())?;
+    literal.copy_raw_from(&data)?;
+    Ok(literal)
+}
+
+fn literal_to_array<'py, T: ArrayElement + numpy::Element>(
+    py: Python<'py>,
+    literal: &Literal,
+    dims: Vec<usize>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let array = PyArray1::from_vec(py, literal.to_vec::<T>()?).reshape(dims)?;
+    Ok(array.into_any())
+}
+
+// The element type corresponding to a numpy dtype, only the types listed in
+// `dispatch_element_type` are supported.
+fn numpy_element_type(array: &Bound<'_, PyUntypedArray>) -> PyResult<ElementType> {
+    let py = array.py();
+    let dtype = array.dtype();
+    let candidates = [
+        (numpy::dtype::<u8>(py), ElementType::U8),
+        (numpy::dtype::<u16>(py), ElementType::U16),
+        (numpy::dtype::<u32>(py), ElementType::U32),
+        (numpy::dtype::<u64>(py), ElementType::U64),
+        (numpy::dtype::<i8>(py), ElementType::S8),
+        (numpy::dtype::<i16>(py), ElementType::S16),
+        (numpy::dtype::<i32>(py), ElementType::S32),
+        (numpy::dtype::<i64>(py), ElementType::S64),
+        (numpy::dtype::<f32>(py), ElementType::F32),
+        (numpy::dtype::<f64>(py), ElementType::F64),
+    ];
+    match candidates.iter().find(|(candidate, _)| dtype.is_equiv_to(candidate)) {
+        Some((_, ty)) => Ok(*ty),
+        None => Err(PyRuntimeError::new_err(format!("unsupported numpy dtype {dtype}"))),
+    }
+}
+
+// Convert a `Literal` or a numpy array argument to a literal.
+fn to_literal(arg: &Bound<'_, PyAny>) -> PyResult<Literal> {
+    if let Ok(literal) = arg.extract::<PyRef<'_, PyLiteral>>() {
+        return Ok(literal.0.clone());
+    }
+    let array = arg.extract::<Bound<'_, PyUntypedArray>>()?;
+    dispatch_element_type!(numpy_element_type(&array)?, array_to_literal(arg))
+}
+
+/// A client for a device, see [`PjRtClient`].
+#[pyclass(name = "PjRtClient", unsendable)]
+pub struct PyPjRtClient(pub PjRtClient);
+
+#[pymethods]
+impl PyPjRtClient {
+    #[staticmethod]
+    fn cpu() -> PyResult<Self> {
+        Ok(Self(PjRtClient::cpu()?))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (memory_fraction=0.95, preallocate=false))]
+    fn gpu(memory_fraction: f64, preallocate: bool) -> PyResult<Self> {
+        Ok(Self(PjRtClient::gpu(memory_fraction, preallocate)?))
+    }
+
+    #[getter]
+    fn platform_name(&self) -> String {
+        self.0.platform_name()
+    }
+
+    #[getter]
+    fn device_count(&self) -> usize {
+        self.0.device_count()
+    }
+
+    fn compile(&self, computation: &PyXlaComputation) -> PyResult<PyPjRtLoadedExecutable> {
+        Ok(PyPjRtLoadedExecutable(self.0.compile(&computation.0)?))
+    }
+}
+
+/// A computation loaded from a HLO module, see [`XlaComputation`].
+#[pyclass(name = "XlaComputation", unsendable)]
+pub struct PyXlaComputation(pub XlaComputation);
+
+#[pymethods]
+impl PyXlaComputation {
+    #[staticmethod]
+    fn from_hlo_text(text: &str) -> PyResult<Self> {
+        let proto = HloModuleProto::parse_and_return_unverified_module(text.as_bytes())?;
+        Ok(Self(XlaComputation::from_proto(&proto)))
+    }
+
+    #[staticmethod]
+    fn from_text_file(path: PathBuf) -> PyResult<Self> {
+        let proto = HloModuleProto::from_text_file(path)?;
+        Ok(Self(XlaComputation::from_proto(&proto)))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (path, binary=true))]
+    fn from_proto_file(path: PathBuf, binary: bool) -> PyResult<Self> {
+        let proto = HloModuleProto::from_proto_file(path, binary)?;
+        Ok(Self(XlaComputation::from_proto(&proto)))
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.0.name()
+    }
+
+    fn fingerprint(&self) -> PyResult<String> {
+        Ok(self.0.fingerprint()?)
+    }
+}
+
+/// A host value, literals can be created from and converted to numpy arrays.
+#[pyclass(name = "Literal", unsendable)]
+pub struct PyLiteral(pub Literal);
+
+#[pymethods]
+impl PyLiteral {
+    /// Copy a numpy array, or any value accepted by `numpy.asarray`.
+    #[new]
+    fn new(array: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let numpy = array.py().import("numpy")?;
+        let array = numpy.call_method1("ascontiguousarray", (array,))?;
+        Ok(Self(to_literal(&array)?))
+    }
+
+    fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let shape = self.0.array_shape()?;
+        let dims = shape.dims().iter().map(|d| *d as usize).collect();
+        dispatch_element_type!(shape.ty(), literal_to_array(py, &self.0, dims))
+    }
+
+    /// Support for `numpy.asarray(literal)`, a copy is always made.
+    #[pyo3(signature = (dtype=None, copy=None))]
+    fn __array__<'py>(
+        &self,
+        py: Python<'py>,
+        dtype: Option<Bound<'py, PyAny>>,
+        copy: Option<bool>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let _ = copy;
+        let array = self.to_numpy(py)?;
+        match dtype {
+            None => Ok(array),
+            Some(dtype) => array.call_method1("astype", (dtype,)),
+        }
+    }
+
+    #[getter]
+    fn shape(&self) -> PyResult<Vec<i64>> {
+        Ok(self.0.array_shape()?.dims().to_vec())
+    }
+
+    #[getter]
+    fn element_type(&self) -> PyResult<String> {
+        Ok(format!("{:?}", self.0.array_shape()?.ty()))
+    }
+
+    fn __repr__(&self) -> String {
+        match self.0.shape() {
+            Ok(shape) => format!("Literal({shape:?})"),
+            Err(err) => format!("Literal(<{err}>)"),
+        }
+    }
+}
+
+/// A compiled computation, see [`PjRtLoadedExecutable`].
+#[pyclass(name = "PjRtLoadedExecutable", unsendable)]
+pub struct PyPjRtLoadedExecutable(pub PjRtLoadedExecutable);
+
+#[pymethods]
+impl PyPjRtLoadedExecutable {
+    /// Run the executable on the first device, the arguments are literals or numpy arrays and
+    /// the outputs are returned as literals.
+    fn execute(&self, args: Vec<Bound<'_, PyAny>>) -> PyResult<Vec<PyLiteral>> {
+        let args = args.iter().map(to_literal).collect::<PyResult<Vec<_>>>()?;
+        let outputs = self.0.execute::<Literal>(&args)?;
+        let outputs = match outputs.into_iter().next() {
+            Some(outputs) => outputs,
+            None => return Ok(vec![]),
+        };
+        outputs.iter().map(|buffer| Ok(PyLiteral(buffer.to_literal_sync()?))).collect()
+    }
+}
+
+/// Add the classes of this module to a Python module.
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyPjRtClient>()?;
+    m.add_class::<PyXlaComputation>()?;
+    m.add_class::<PyLiteral>()?;
+    m.add_class::<PyPjRtLoadedExecutable>()?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Process wide initialization and shutdown.
+//!
+//! Using [`init`] and [`shutdown`] is optional, they are mostly useful when this crate is
+//! embedded in a shared library that can be unloaded. [`init`] sets the log sink, the error
+//! backtraces, and the host buffer allocator, and initializes the TSL runtime: the logging and
+//! `XLA_FLAGS` settings are read from the environment at this point rather than lazily from
+//! whichever thread logs or compiles first. After [`shutdown`], the C++ library does not hold
+//! any callback pointing into Rust code anymore.
+//!
+//! [`shutdown`] cannot tear down the CUDA contexts. XLA caches one stream executor per device
+//! for the lifetime of the process, together with its CUDA context, and provides no way to
+//! release them: destroying the contexts behind these executors would crash the next client
+//! created in the process. The other device resources are released when the last clone of
+//! each client is dropped, and [`shutdown`] reports an error if some clients are still alive.
+//!
+//! Calls can be nested, e.g. by multiple plugins using this crate, only the first [`init`] call
+//! applies its options and only the matching last [`shutdown`] call tears things down.
+use crate::{Error, Result, TfLogLevel};
+use std::sync::Mutex;
+
+/// Where the C++ log messages are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogOutput {
+    /// Keep the default behavior of writing to stderr.
+    #[default]
+    Stderr,
+    /// Forward the messages to the `log` crate, see [`crate::forward_logs_to_log_crate`].
+    LogCrate,
+    /// Drop all the messages.
+    Discard,
+}
+
+/// The options used by [`init`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InitOptions {
+    /// Sets `TF_CPP_MIN_LOG_LEVEL`, this has to be done before the first client is created to
+    /// have an effect.
+    pub min_log_level: Option<TfLogLevel>,
+    pub log_output: LogOutput,
+    /// Whether backtraces are captured for the errors returned by the XLA library, see
+    /// [`crate::set_backtrace_capture`].
+    pub backtrace_capture: Option<crate::BacktraceCapture>,
+    /// The allocator used for the host buffers that are not page-locked and for the storage of
+    /// literals, see [`crate::HostBufferAllocator`].
+    pub host_buffer_allocator: Option<&'static dyn crate::HostBufferAllocator>,
+}
+
+// The number of `init` calls that have not been matched by a `shutdown` call yet.
+static INIT_COUNT: Mutex<usize> = Mutex::new(0);
+
+/// Initialize the TSL runtime and apply the options, only the outermost call has an effect. An
+/// invalid `XLA_FLAGS` environment variable aborts the process here rather than on the first
+/// compilation.
+pub fn init(options: InitOptions) -> Result<()> {
+    let mut init_count = INIT_COUNT.lock().unwrap_or_else(|e| e.into_inner());
+    if *init_count == 0 {
+        if let Some(min_log_level) = options.min_log_level {
+            crate::set_tf_min_log_level(min_log_level)
+        }
+        match options.log_output {
+            LogOutput::Stderr => {}
+            LogOutput::LogCrate => crate::forward_logs_to_log_crate(),
+            LogOutput::Discard => crate::set_log_handler(|_| {}),
+        }
+        if let Some(capture) = options.backtrace_capture {
+            crate::set_backtrace_capture(capture)
+        }
+        if let Some(allocator) = options.host_buffer_allocator {
+            crate::set_host_buffer_allocator(allocator)
+        }
+        unsafe { crate::c_lib::tsl_runtime_init() }
+    }
+    *init_count += 1;
+    Ok(())
+}
+
+/// Undo the matching [`init`] call. The outermost call unregisters the log handler, the metrics
+/// hooks, and the host buffer allocator, it returns an error if some clients have not been
+/// dropped. CUDA contexts are not released, see the [module documentation](self).
+pub fn shutdown() -> Result<()> {
+    let mut init_count = INIT_COUNT.lock().unwrap_or_else(|e| e.into_inner());
+    match *init_count {
+        0 => return Ok(()),
+        1 => {
+            crate::clear_log_handler();
+            crate::metrics::clear_metrics();
+            crate::clear_host_buffer_allocator();
+        }
+        _ => {}
+    }
+    *init_count -= 1;
+    if *init_count == 0 {
+        let count = crate::wrappers::live_client_count();
+        if count > 0 {
+            Err(Error::ClientsStillAlive { count })?
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Token sampling from logits, to be used in decoding loops.
+//!
+//! These helpers operate on logits of shape `(..., vocab_size)` and return the sampled token
+//! indexes as a `S32` node of shape `(...)`, so that the whole decoding step can run on the
+//! device.
+use crate::{ElementType, PrngKey, Result, XlaOp};
+
+/// The configuration used by [`sample`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplingConfig {
+    /// The logits are divided by the temperature before sampling.
+    pub temperature: f64,
+    /// Only sample from the `top_k` most likely tokens.
+    pub top_k: Option<usize>,
+    /// Only sample from the smallest set of most likely tokens which cumulative probability
+    /// exceeds `top_p`, a.k.a. nucleus sampling.
+    pub top_p: Option<f64>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self { temperature: 1.0, top_k: None, top_p: None }
+    }
+}
+
+/// Greedy decoding, select the most likely token.
+pub fn argmax(logits: &XlaOp) -> Result<XlaOp> {
+    logits.argmax(-1, false)
+}
+
+/// Divide the logits by `temperature`.
+pub fn apply_temperature(logits: &XlaOp, temperature: f64) -> Result<XlaOp> {
+    let temperature = logits.builder().c0(temperature)?.convert(logits.ty()?)?;
+    logits / temperature
+}
+
+fn mask_below(logits: &XlaOp, threshold: &XlaOp) -> Result<XlaOp> {
+    let dims: Vec<i64> = logits.dims()?.iter().map(|d| *d as i64).collect();
+    let min_value = logits.builder().min_finite_value(logits.ty()?.element_type()?)?;
+    let min_value = min_value.broadcast(&dims)?;
+    logits.ge(threshold)?.select(logits, &min_value)
+}
+
+/// Set the logits that are not in the `k` largest ones to the lowest finite value.
+pub fn top_k_filter(logits: &XlaOp, k: usize) -> Result<XlaOp> {
+    let values = logits.top_k(k as i64)?.get_tuple_element(0)?;
+    let kth_value = values.slice_in_dim1(k as i64 - 1, k as i64, -1)?;
+    mask_below(logits, &kth_value)
+}
+
+/// Only keep the logits for the smallest set of most likely tokens which cumulative probability
+/// exceeds `p`, the other logits are set to the lowest finite value. The most likely token is
+/// always kept.
+pub fn top_p_filter(logits: &XlaOp, p: f64) -> Result<XlaOp> {
+    let vocab_size = *logits.dims()?.last().unwrap_or(&1) as i64;
+    let sorted = logits.top_k(vocab_size)?.get_tuple_element(0)?;
+    let probs = sorted.softmax(-1)?;
+    let cumulative = (probs.cumsum(-1)? - &probs)?;
+    let p = logits.builder().c0(p)?.convert(logits.ty()?)?;
+    let keep = cumulative.lt(&p)?;
+    let dims: Vec<i64> = sorted.dims()?.iter().map(|d| *d as i64).collect();
+    let max_value = logits.builder().max_value(logits.ty()?.element_type()?)?.broadcast(&dims)?;
+    let threshold = keep.select(&sorted, &max_value)?.reduce_min(&[-1], true)?;
+    mask_below(logits, &threshold)
+}
+
+/// Sample a token using the temperature, top-k, and top-p filters from `config`. The sampling
+/// uses the Gumbel-max trick with random values generated from `key`.
+pub fn sample(logits: &XlaOp, key: &PrngKey, config: &SamplingConfig) -> Result<XlaOp> {
+    let builder = logits.builder();
+    let ty = logits.ty()?;
+    let mut logits = apply_temperature(logits, config.temperature)?;
+    if let Some(k) = config.top_k {
+        logits = top_k_filter(&logits, k)?
+    }
+    if let Some(p) = config.top_p {
+        logits = top_p_filter(&logits, p)?
+    }
+    let dims: Vec<i64> = logits.dims()?.iter().map(|d| *d as i64).collect();
+    let uniform = key.uniform(builder, ElementType::F32, &dims)?.convert(ty)?;
+    let gumbel = uniform.log()?.neg()?.log()?.neg()?;
+    argmax(&(logits + gumbel)?)
+}
@@ -0,0 +1,421 @@
+//! Loading TensorFlow SavedModels whose functions have already been lowered to HLO.
+//!
+//! The xla_extension library does not include the TensorFlow graph to HLO converter, so only a
+//! subset of SavedModels can be served: the ones where the HLO for each function was generated
+//! at export time and stored in the `assets` directory of the model. With TensorFlow, this HLO
+//! can be obtained from a `tf.function(jit_compile=True)` via
+//! `fn.experimental_get_compiler_ir(*args)(stage="hlo_serialized")` and written to
+//! `assets/{function_name}.hlo.pb`. Text modules, `stage="hlo"`, can be written to
+//! `assets/{function_name}.hlo` instead.
+//!
+//! The signatures of the serving meta graph are read from `saved_model.pb`, a function stored
+//! under the name of a signature, e.g. `assets/serving_default.hlo.pb`, is checked against this
+//! signature when loading the model: the parameters are matched to the signature inputs by name
+//! when possible and by key order otherwise, and their types and shapes have to agree.
+use crate::{ElementType, Error, HloModuleProto, Result, Shape, XlaComputation};
+use std::collections::HashMap;
+use std::path::Path;
+
+const SAVED_MODEL_PB: &str = "saved_model.pb";
+const ASSETS_DIR: &str = "assets";
+/// The tag of the meta graph used for serving, `tf.saved_model.SERVING`.
+const SERVE_TAG: &str = "serve";
+
+/// An input or an output of a [`Signature`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorSpec {
+    /// The key of the tensor in the signature.
+    pub key: String,
+    /// The name of the tensor in the TensorFlow graph, e.g. `serving_default_x:0`.
+    pub name: String,
+    /// `None` for the TensorFlow types that have no XLA counterpart, e.g. strings.
+    pub ty: Option<ElementType>,
+    /// `None` when the rank is unknown, the unknown dimensions are -1.
+    pub dims: Option<Vec<i64>>,
+}
+
+/// A signature of the serving meta graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// E.g. `tensorflow/serving/predict`.
+    pub method_name: String,
+    /// The inputs in the order of the parameters of the HLO function with the same name as the
+    /// signature if there is one, sorted by key otherwise.
+    pub inputs: Vec<TensorSpec>,
+    /// The outputs sorted by key.
+    pub outputs: Vec<TensorSpec>,
+}
+
+/// The HLO functions and the signatures of a SavedModel, see [`load`].
+#[derive(Debug)]
+pub struct SavedModel {
+    tags: Vec<String>,
+    signatures: HashMap<String, Signature>,
+    functions: HashMap<String, HloModuleProto>,
+}
+
+impl SavedModel {
+    /// The tags of the meta graph that has been loaded.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// The names of the signatures of the model, in alphabetical order.
+    pub fn signature_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.signatures.keys().map(|s| s.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    /// The signature `name`.
+    pub fn signature(&self, name: &str) -> Result<&Signature> {
+        self.signatures
+            .get(name)
+            .ok_or_else(|| Error::SavedModel(format!("no signature named {name} in the model")))
+    }
+
+    /// The names of the functions available in the model, in alphabetical order.
+    pub fn function_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.functions.keys().map(|s| s.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    /// The HLO module for the function `name`.
+    pub fn proto(&self, name: &str) -> Result<&HloModuleProto> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| Error::SavedModel(format!("no function named {name} in the model")))
+    }
+
+    /// A computation for the function `name`, ready to be compiled.
+    pub fn computation(&self, name: &str) -> Result<XlaComputation> {
+        Ok(XlaComputation::from_proto(self.proto(name)?))
+    }
+}
+
+/// Load the serving signatures and the HLO functions from the SavedModel stored in `dir`. An
+/// error is returned if the model does not have a meta graph tagged `serve`, if it does not
+/// contain any function lowered to HLO, or if a function does not match the signature with the
+/// same name.
+pub fn load<P: AsRef<Path>>(dir: P) -> Result<SavedModel> {
+    let dir = dir.as_ref();
+    let saved_model_pb = dir.join(SAVED_MODEL_PB);
+    if !saved_model_pb.is_file() {
+        Err(Error::SavedModel(format!("{dir:?} does not contain a {SAVED_MODEL_PB} file")))?
+    }
+    let meta_graphs = parse_saved_model(&std::fs::read(&saved_model_pb)?)?;
+    let (tags, mut signatures) =
+        match meta_graphs.into_iter().find(|(tags, _)| tags.iter().any(|t| t == SERVE_TAG)) {
+            Some(meta_graph) => meta_graph,
+            None => Err(Error::SavedModel(format!(
+                "{saved_model_pb:?} does not contain a meta graph tagged {SERVE_TAG}"
+            )))?,
+        };
+    let mut functions = HashMap::new();
+    let assets = dir.join(ASSETS_DIR);
+    if assets.is_dir() {
+        for entry in std::fs::read_dir(&assets)? {
+            let path = entry?.path();
+            let file_name = match path.file_name().and_then(|f| f.to_str()) {
+                Some(file_name) => file_name,
+                None => continue,
+            };
+            let (name, proto) = if let Some(name) = file_name.strip_suffix(".hlo.pb") {
+                (name, HloModuleProto::from_proto_file(&path, true)?)
+            } else if let Some(name) = file_name.strip_suffix(".hlo") {
+                (name, HloModuleProto::from_text_file(&path)?)
+            } else {
+                continue;
+            };
+            if let Some(signature) = signatures.get_mut(name) {
+                check_signature(name, signature, &proto)?
+            }
+            functions.insert(name.to_string(), proto);
+        }
+    }
+    if functions.is_empty() {
+        Err(Error::SavedModel(format!(
+            "{dir:?} does not contain any function lowered to HLO, converting TensorFlow graphs is not supported"
+        )))?
+    }
+    Ok(SavedModel { tags, signatures, functions })
+}
+
+// The index of the input of `signature` matching a parameter name. The parameter can be named
+// after the input key or after the graph tensor, e.g. `serving_default_x` for
+// `serving_default_x:0`.
+fn input_for_parameter(signature: &Signature, name: &str, parameter_name: &str) -> Option<usize> {
+    if parameter_name.is_empty() {
+        return None;
+    }
+    let prefixed = format!("{name}_{parameter_name}");
+    signature.inputs.iter().position(|input| {
+        let tensor = input.name.split(':').next().unwrap_or_default();
+        input.key == parameter_name || tensor == parameter_name || tensor == prefixed
+    })
+}
+
+// Check that the parameters of the HLO function match the signature inputs and reorder the inputs
+// in parameter order. Parameters are mapped to inputs by name when all of them can be, otherwise
+// the inputs are taken in key order which is the order in which TensorFlow flattens the keyword
+// arguments of a signature. The types and the known dimensions have to match.
+fn check_signature(name: &str, signature: &mut Signature, proto: &HloModuleProto) -> Result<()> {
+    let program_shape = match proto.host_program_shape()? {
+        Some(program_shape) => program_shape,
+        None => return Ok(()),
+    };
+    let parameters = program_shape.parameters;
+    if parameters.len() != signature.inputs.len() {
+        Err(Error::SavedModel(format!(
+            "the function {name} has {} parameters but its signature has {} inputs",
+            parameters.len(),
+            signature.inputs.len()
+        )))?
+    }
+    let by_name: Option<Vec<usize>> = program_shape
+        .parameter_names
+        .iter()
+        .map(|parameter_name| input_for_parameter(signature, name, parameter_name))
+        .collect();
+    let order = match by_name {
+        Some(order) if order.len() == parameters.len() && is_permutation(&order) => order,
+        _ => (0..parameters.len()).collect(),
+    };
+    let inputs: Vec<TensorSpec> = order.iter().map(|i| signature.inputs[*i].clone()).collect();
+    for (parameter, input) in parameters.iter().zip(inputs.iter()) {
+        let (ty, dims) = match parameter {
+            Shape::Array(shape) => (Some(shape.ty()), Some(shape.dims())),
+            _ => (None, None),
+        };
+        let ty_matches = input.ty.is_none() || ty == input.ty;
+        let dims_match = match (&input.dims, dims) {
+            (None, _) => true,
+            (Some(expected), Some(dims)) => {
+                expected.len() == dims.len()
+                    && expected.iter().zip(dims.iter()).all(|(e, d)| *e < 0 || e == d)
+            }
+            (Some(_), None) => false,
+        };
+        if !ty_matches || !dims_match {
+            Err(Error::SavedModel(format!(
+                "the parameter for the input {} of {name} is {parameter:?}, expected {:?} {:?}",
+                input.key, input.ty, input.dims
+            )))?
+        }
+    }
+    signature.inputs = inputs;
+    Ok(())
+}
+
+fn is_permutation(order: &[usize]) -> bool {
+    let mut seen = vec![false; order.len()];
+    order.iter().all(|i| *i < seen.len() && !std::mem::replace(&mut seen[*i], true))
+}
+
+// A minimal reader for the protobuf wire format, only the fields of the SavedModel protos that
+// are used above are decoded and the other ones are skipped.
+enum ProtoField<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct ProtoReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.data.split_first().ok_or_else(truncated)?;
+            self.data = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::SavedModel("invalid varint in the SavedModel proto".to_string()))
+    }
+
+    fn skip(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            Err(truncated())?
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, ProtoField<'a>)>> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = match key & 7 {
+            0 => ProtoField::Varint(self.varint()?),
+            1 => {
+                self.skip(8)?;
+                ProtoField::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                ProtoField::Bytes(self.skip(len)?)
+            }
+            5 => {
+                self.skip(4)?;
+                ProtoField::Fixed
+            }
+            wire_type => Err(Error::SavedModel(format!(
+                "unsupported wire type {wire_type} in the SavedModel proto"
+            )))?,
+        };
+        Ok(Some((key >> 3, field)))
+    }
+}
+
+fn truncated() -> Error {
+    Error::SavedModel("truncated SavedModel proto".to_string())
+}
+
+fn proto_string(bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| Error::SavedModel("invalid string in the SavedModel proto".to_string()))
+}
+
+// The `tensorflow.DataType` enum.
+fn element_type(dtype: u64) -> Option<ElementType> {
+    let ty = match dtype {
+        1 => ElementType::F32,
+        2 => ElementType::F64,
+        3 => ElementType::S32,
+        4 => ElementType::U8,
+        5 => ElementType::S16,
+        6 => ElementType::S8,
+        8 => ElementType::C64,
+        9 => ElementType::S64,
+        10 => ElementType::Pred,
+        14 => ElementType::Bf16,
+        17 => ElementType::U16,
+        18 => ElementType::C128,
+        19 => ElementType::F16,
+        22 => ElementType::U32,
+        23 => ElementType::U64,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+// `SavedModel.meta_graphs` returned as the tags and the signatures of each meta graph.
+type MetaGraph = (Vec<String>, HashMap<String, Signature>);
+
+fn parse_saved_model(data: &[u8]) -> Result<Vec<MetaGraph>> {
+    let mut reader = ProtoReader::new(data);
+    let mut meta_graphs = vec![];
+    while let Some((number, field)) = reader.next_field()? {
+        if let (2, ProtoField::Bytes(bytes)) = (number, field) {
+            meta_graphs.push(parse_meta_graph(bytes)?)
+        }
+    }
+    Ok(meta_graphs)
+}
+
+fn parse_meta_graph(data: &[u8]) -> Result<MetaGraph> {
+    let mut reader = ProtoReader::new(data);
+    let mut tags = vec![];
+    let mut signatures = HashMap::new();
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            // MetaInfoDef, the tags are the field 4.
+            (1, ProtoField::Bytes(bytes)) => {
+                let mut reader = ProtoReader::new(bytes);
+                while let Some((number, field)) = reader.next_field()? {
+                    if let (4, ProtoField::Bytes(tag)) = (number, field) {
+                        tags.push(proto_string(tag)?)
+                    }
+                }
+            }
+            (5, ProtoField::Bytes(bytes)) => {
+                let (key, value) = parse_map_entry(bytes)?;
+                signatures.insert(key, parse_signature(value)?);
+            }
+            _ => {}
+        }
+    }
+    Ok((tags, signatures))
+}
+
+// A map entry is a message with the key as field 1 and the value as field 2.
+fn parse_map_entry(data: &[u8]) -> Result<(String, &[u8])> {
+    let mut reader = ProtoReader::new(data);
+    let mut key = String::new();
+    let mut value: &[u8] = &[];
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (1, ProtoField::Bytes(bytes)) => key = proto_string(bytes)?,
+            (2, ProtoField::Bytes(bytes)) => value = bytes,
+            _ => {}
+        }
+    }
+    Ok((key, value))
+}
+
+fn parse_signature(data: &[u8]) -> Result<Signature> {
+    let mut reader = ProtoReader::new(data);
+    let mut signature = Signature { method_name: String::new(), inputs: vec![], outputs: vec![] };
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (1, ProtoField::Bytes(bytes)) => signature.inputs.push(parse_tensor_info(bytes)?),
+            (2, ProtoField::Bytes(bytes)) => signature.outputs.push(parse_tensor_info(bytes)?),
+            (3, ProtoField::Bytes(bytes)) => signature.method_name = proto_string(bytes)?,
+            _ => {}
+        }
+    }
+    signature.inputs.sort_by(|a, b| a.key.cmp(&b.key));
+    signature.outputs.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(signature)
+}
+
+// A `map<string, TensorInfo>` entry.
+fn parse_tensor_info(data: &[u8]) -> Result<TensorSpec> {
+    let (key, value) = parse_map_entry(data)?;
+    let mut reader = ProtoReader::new(value);
+    let mut spec = TensorSpec { key, name: String::new(), ty: None, dims: None };
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (1, ProtoField::Bytes(bytes)) => spec.name = proto_string(bytes)?,
+            (2, ProtoField::Varint(dtype)) => spec.ty = element_type(dtype),
+            (3, ProtoField::Bytes(bytes)) => spec.dims = parse_tensor_shape(bytes)?,
+            _ => {}
+        }
+    }
+    Ok(spec)
+}
+
+// A `TensorShapeProto`, `None` is returned when the rank is unknown.
+fn parse_tensor_shape(data: &[u8]) -> Result<Option<Vec<i64>>> {
+    let mut reader = ProtoReader::new(data);
+    let mut dims = vec![];
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (2, ProtoField::Bytes(bytes)) => {
+                let mut size = 0;
+                let mut reader = ProtoReader::new(bytes);
+                while let Some((number, field)) = reader.next_field()? {
+                    if let (1, ProtoField::Varint(v)) = (number, field) {
+                        size = v as i64
+                    }
+                }
+                dims.push(size)
+            }
+            (3, ProtoField::Varint(unknown_rank)) if unknown_rank != 0 => return Ok(None),
+            _ => {}
+        }
+    }
+    Ok(Some(dims))
+}
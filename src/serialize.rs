@@ -0,0 +1,306 @@
+//! A portable binary format for literals.
+//!
+//! Contrary to [`Literal::as_bytes`] which exposes the raw memory of a literal, this format
+//! starts with a versioned header and records the byte order used for the values, so that a
+//! literal written on a little endian machine such as x86 can be read back on a big endian one.
+//!
+//! The layout is the following, all the integers use the byte order given in the header:
+//! - the magic string `XLALIT`, the format version (a single byte, currently 1), and the byte
+//!   order (a single byte, 0 for little endian and 1 for big endian).
+//! - the value, a tag byte which is 0 for arrays and 1 for tuples. Arrays are followed by the
+//!   primitive type as a `u32`, the rank as a `u32`, each dimension as a `u64`, and the values
+//!   in row major order. Tuples are followed by the number of elements as a `u32` and the
+//!   elements.
+//!
+//! The whole stream can also be compressed with zstd, compressed streams are detected via the
+//! zstd magic number when reading them back.
+use crate::{ElementType, Error, Literal, PrimitiveType, Result, Shape};
+use std::io::{Read, Write};
+
+const MAGIC: &[u8] = b"XLALIT";
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const VERSION: u8 = 1;
+const ARRAY_TAG: u8 = 0;
+const TUPLE_TAG: u8 = 1;
+// The maximum nesting of tuples, deeper literals are rejected rather than overflowing the stack.
+const MAX_TUPLE_DEPTH: usize = 64;
+
+/// The byte order used to serialize multi-byte values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// The byte order of the target architecture.
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            Self::Big
+        } else {
+            Self::Little
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Little => 0,
+            Self::Big => 1,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self> {
+        match b {
+            0 => Ok(Self::Little),
+            1 => Ok(Self::Big),
+            b => Err(Error::SerializedLiteral(format!("invalid byte order {b}"))),
+        }
+    }
+}
+
+// The size of the scalars that have to be byte swapped, complex values are made of two floats.
+fn swap_size(ty: ElementType) -> usize {
+    match ty {
+        ElementType::C64 | ElementType::C128 => ty.element_size_in_bytes() / 2,
+        _ => ty.element_size_in_bytes(),
+    }
+}
+
+// Convert some values between the native byte order and `endianness`, in place.
+pub(crate) fn convert_values(data: &mut [u8], ty: ElementType, endianness: Endianness) {
+    let size = swap_size(ty);
+    if endianness != Endianness::native() && size > 1 {
+        data.chunks_exact_mut(size).for_each(|c| c.reverse())
+    }
+}
+
+struct Writer<'a, W: Write> {
+    writer: &'a mut W,
+    endianness: Endianness,
+}
+
+impl<W: Write> Writer<'_, W> {
+    fn u32(&mut self, v: u32) -> Result<()> {
+        let bytes = match self.endianness {
+            Endianness::Little => v.to_le_bytes(),
+            Endianness::Big => v.to_be_bytes(),
+        };
+        Ok(self.writer.write_all(&bytes)?)
+    }
+
+    fn u64(&mut self, v: u64) -> Result<()> {
+        let bytes = match self.endianness {
+            Endianness::Little => v.to_le_bytes(),
+            Endianness::Big => v.to_be_bytes(),
+        };
+        Ok(self.writer.write_all(&bytes)?)
+    }
+
+    fn literal(&mut self, literal: &Literal) -> Result<()> {
+        match literal.shape()? {
+            Shape::Tuple(elems) => {
+                self.writer.write_all(&[TUPLE_TAG])?;
+                self.u32(elems.len() as u32)?;
+                for index in 0..elems.len() {
+                    self.literal(&literal.tuple_element(index)?)?
+                }
+            }
+            Shape::Array(shape) => {
+                self.writer.write_all(&[ARRAY_TAG])?;
+                self.u32(shape.primitive_type() as u32)?;
+                self.u32(shape.dims().len() as u32)?;
+                for &dim in shape.dims() {
+                    self.u64(dim as u64)?
+                }
+                let bytes = literal.as_bytes()?;
+                if self.endianness == Endianness::native() {
+                    self.writer.write_all(bytes)?
+                } else {
+                    let mut bytes = bytes.to_vec();
+                    convert_values(&mut bytes, shape.ty(), self.endianness);
+                    self.writer.write_all(&bytes)?
+                }
+            }
+            shape @ Shape::Unsupported(_) => {
+                Err(Error::SerializedLiteral(format!("unsupported shape {shape:?}")))?
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a, R: Read> {
+    reader: &'a mut R,
+    endianness: Endianness,
+}
+
+impl<R: Read> Reader<'_, R> {
+    fn u8(&mut self) -> Result<u8> {
+        let mut bytes = [0u8; 1];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(match self.endianness {
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(match self.endianness {
+            Endianness::Little => u64::from_le_bytes(bytes),
+            Endianness::Big => u64::from_be_bytes(bytes),
+        })
+    }
+
+    fn literal(&mut self, depth: usize) -> Result<Literal> {
+        match self.u8()? {
+            ARRAY_TAG => {
+                let ty = self.u32()?;
+                let ty = match num_traits::FromPrimitive::from_u32(ty) {
+                    Some(ty) => PrimitiveType::element_type(ty)?,
+                    None => Err(Error::UnexpectedElementType(ty as i32))?,
+                };
+                let rank = self.u32()?;
+                let dims = (0..rank)
+                    .map(|_| {
+                        let dim = self.u64()?;
+                        usize::try_from(dim).map_err(|_| {
+                            Error::SerializedLiteral(format!("dimension {dim} is too large"))
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let len =
+                    dims.iter().try_fold(ty.element_size_in_bytes(), |acc, d| acc.checked_mul(*d));
+                let len = match len {
+                    Some(len) => len,
+                    None => Err(Error::SerializedLiteral(format!("dimensions {dims:?} overflow")))?,
+                };
+                // The buffer grows as the data is read so that a corrupted header does not
+                // trigger a huge allocation.
+                let mut data = vec![];
+                self.reader.by_ref().take(len as u64).read_to_end(&mut data)?;
+                if data.len() != len {
+                    Err(Error::SerializedLiteral(format!(
+                        "expected {len} bytes of data, got {}",
+                        data.len()
+                    )))?
+                }
+                convert_values(&mut data, ty, self.endianness);
+                Literal::from_bytes(ty, &dims, &data)
+            }
+            TUPLE_TAG => {
+                if depth >= MAX_TUPLE_DEPTH {
+                    Err(Error::SerializedLiteral(format!(
+                        "tuples are nested more than {MAX_TUPLE_DEPTH} levels deep"
+                    )))?
+                }
+                let len = self.u32()?;
+                // Not preallocated so that a corrupted length does not trigger a huge allocation.
+                let mut elems = vec![];
+                for _ in 0..len {
+                    elems.push(self.literal(depth + 1)?)
+                }
+                Ok(Literal::tuple(elems))
+            }
+            tag => Err(Error::SerializedLiteral(format!("invalid tag {tag}"))),
+        }
+    }
+}
+
+impl Literal {
+    /// Write the literal in a portable binary format: a versioned header followed by the shapes
+    /// and values, all written with the byte order `endianness`.
+    pub fn write_serialized<W: Write>(&self, writer: &mut W, endianness: Endianness) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, endianness.to_byte()])?;
+        Writer { writer, endianness }.literal(self)
+    }
+
+    /// The literal in the portable format, see [`Literal::write_serialized`].
+    pub fn to_serialized_bytes(&self, endianness: Endianness) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.write_serialized(&mut bytes, endianness)?;
+        Ok(bytes)
+    }
+
+    /// Write the literal in the same format as [`Literal::write_serialized`], compressed with
+    /// zstd at the given level, 0 selects the zstd default level.
+    pub fn write_serialized_compressed<W: Write>(
+        &self,
+        writer: &mut W,
+        endianness: Endianness,
+        level: i32,
+    ) -> Result<()> {
+        let mut encoder = zstd::Encoder::new(writer, level)?;
+        self.write_serialized(&mut encoder, endianness)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// The literal in the portable format compressed with zstd, see
+    /// [`Literal::write_serialized_compressed`].
+    pub fn to_serialized_bytes_compressed(
+        &self,
+        endianness: Endianness,
+        level: i32,
+    ) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.write_serialized_compressed(&mut bytes, endianness, level)?;
+        Ok(bytes)
+    }
+
+    /// Read a literal written with [`Literal::write_serialized`] or
+    /// [`Literal::write_serialized_compressed`], whatever the byte order used when writing it.
+    pub fn read_serialized<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 6];
+        reader.read_exact(&mut magic)?;
+        if magic.starts_with(ZSTD_MAGIC) {
+            let zstd_header = magic;
+            let mut decoder = zstd::Decoder::new(zstd_header.as_slice().chain(reader))?;
+            decoder.read_exact(&mut magic)?;
+            Self::read_uncompressed(magic, &mut decoder)
+        } else {
+            Self::read_uncompressed(magic, reader)
+        }
+    }
+
+    fn read_uncompressed<R: Read>(magic: [u8; 6], reader: &mut R) -> Result<Self> {
+        if magic != MAGIC {
+            Err(Error::SerializedLiteral("magic string mismatch".to_string()))?
+        }
+        let mut version_and_endianness = [0u8; 2];
+        reader.read_exact(&mut version_and_endianness)?;
+        let [version, endianness] = version_and_endianness;
+        if version == 0 || version > VERSION {
+            Err(Error::SerializedLiteral(format!("unsupported version {version}")))?
+        }
+        let endianness = Endianness::from_byte(endianness)?;
+        Reader { reader, endianness }.literal(0)
+    }
+
+    /// Read a literal from some bytes produced by [`Literal::to_serialized_bytes`] or
+    /// [`Literal::to_serialized_bytes_compressed`], the bytes must not contain any trailing data.
+    pub fn from_serialized_bytes(bytes: &[u8]) -> Result<Self> {
+        let decompressed;
+        let mut reader = if bytes.starts_with(ZSTD_MAGIC) {
+            decompressed = zstd::decode_all(bytes)?;
+            decompressed.as_slice()
+        } else {
+            bytes
+        };
+        let mut magic = [0u8; 6];
+        reader.read_exact(&mut magic)?;
+        let literal = Self::read_uncompressed(magic, &mut reader)?;
+        if !reader.is_empty() {
+            Err(Error::SerializedLiteral(format!("{} trailing bytes", reader.len())))?
+        }
+        Ok(literal)
+    }
+}
@@ -0,0 +1,248 @@
+//! Sparse matrices in the coordinate (COO) and compressed sparse row (CSR) formats.
+//!
+//! XLA has no sparse array type, a sparse matrix is represented by three dense arrays holding the
+//! row indices, the column indices, and the values of the non-zero elements. These arrays can be
+//! uploaded as is rather than densifying the matrix on the host, and [`sparse_dense_matmul`]
+//! multiplies such a matrix with a dense one by gathering the rows of the dense matrix and
+//! summing the products over the sparse rows.
+use crate::{
+    ElementType, Error, Literal, NativeType, PjRtBuffer, PjRtClient, PjRtDevice, Result,
+    XlaBuilder, XlaOp,
+};
+
+fn check_indices(indices: &[i64], size: usize, what: &str) -> Result<()> {
+    if let Some(index) = indices.iter().find(|&&i| i < 0 || i as usize >= size) {
+        Err(Error::InvalidSparseMatrix(format!("{what} index {index} is out of bounds ({size})")))?
+    }
+    Ok(())
+}
+
+/// A sparse matrix in the coordinate format: the non-zero element `i` is at row `rows[i]` and
+/// column `cols[i]` and has value `values[i]`. Elements with the same position are summed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CooMatrix<T> {
+    shape: (usize, usize),
+    rows: Vec<i64>,
+    cols: Vec<i64>,
+    values: Vec<T>,
+}
+
+impl<T: NativeType> CooMatrix<T> {
+    /// A matrix with `shape.0` rows and `shape.1` columns, this checks that there is one row
+    /// index and one column index per value and that these indices are in bounds.
+    pub fn new(
+        shape: (usize, usize),
+        rows: Vec<i64>,
+        cols: Vec<i64>,
+        values: Vec<T>,
+    ) -> Result<Self> {
+        if rows.len() != values.len() || cols.len() != values.len() {
+            Err(Error::InvalidSparseMatrix(format!(
+                "got {} row indices, {} column indices, and {} values",
+                rows.len(),
+                cols.len(),
+                values.len()
+            )))?
+        }
+        check_indices(&rows, shape.0, "row")?;
+        check_indices(&cols, shape.1, "column")?;
+        Ok(Self { shape, rows, cols, values })
+    }
+
+    /// The number of rows and columns of the matrix.
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// The number of stored elements.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The row index of each stored element.
+    pub fn rows(&self) -> &[i64] {
+        &self.rows
+    }
+
+    /// The column index of each stored element.
+    pub fn cols(&self) -> &[i64] {
+        &self.cols
+    }
+
+    /// The value of each stored element.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// The row indices, column indices, and values as literals, in this order.
+    pub fn to_literals(&self) -> [Literal; 3] {
+        [Literal::vec1(&self.rows), Literal::vec1(&self.cols), Literal::vec1(&self.values)]
+    }
+
+    /// Transfer the row indices, column indices, and values to a device, in this order. The
+    /// default device is used if `device` is `None`.
+    pub fn to_buffers(
+        &self,
+        client: &PjRtClient,
+        device: Option<&PjRtDevice>,
+    ) -> Result<Vec<PjRtBuffer>> {
+        let nnz = [self.nnz()];
+        Ok(vec![
+            client.buffer_from_host_buffer(&self.rows, &nnz, device)?,
+            client.buffer_from_host_buffer(&self.cols, &nnz, device)?,
+            client.buffer_from_host_buffer(&self.values, &nnz, device)?,
+        ])
+    }
+
+    /// Embed the matrix as constants in a computation.
+    pub fn constant(&self, builder: &XlaBuilder) -> Result<SparseOp> {
+        Ok(SparseOp {
+            shape: self.shape,
+            rows: builder.c1(&self.rows)?,
+            cols: builder.c1(&self.cols)?,
+            values: builder.c1(&self.values)?,
+        })
+    }
+}
+
+/// A sparse matrix in the compressed sparse row format: the elements of row `r` are stored at
+/// the positions `row_offsets[r]..row_offsets[r + 1]` of `cols` and `values`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsrMatrix<T> {
+    shape: (usize, usize),
+    row_offsets: Vec<i64>,
+    cols: Vec<i64>,
+    values: Vec<T>,
+}
+
+impl<T: NativeType> CsrMatrix<T> {
+    /// A matrix with `shape.0` rows and `shape.1` columns, `row_offsets` must have `shape.0 + 1`
+    /// non-decreasing elements starting at 0 and ending at the number of values.
+    pub fn new(
+        shape: (usize, usize),
+        row_offsets: Vec<i64>,
+        cols: Vec<i64>,
+        values: Vec<T>,
+    ) -> Result<Self> {
+        if cols.len() != values.len() {
+            Err(Error::InvalidSparseMatrix(format!(
+                "got {} column indices and {} values",
+                cols.len(),
+                values.len()
+            )))?
+        }
+        let valid_offsets = row_offsets.len() == shape.0 + 1
+            && row_offsets.first() == Some(&0)
+            && row_offsets.last() == Some(&(values.len() as i64))
+            && row_offsets.windows(2).all(|w| w[0] <= w[1]);
+        if !valid_offsets {
+            Err(Error::InvalidSparseMatrix(format!(
+                "invalid row offsets {row_offsets:?} for {} rows and {} values",
+                shape.0,
+                values.len()
+            )))?
+        }
+        check_indices(&cols, shape.1, "column")?;
+        Ok(Self { shape, row_offsets, cols, values })
+    }
+
+    /// The number of rows and columns of the matrix.
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// The number of stored elements.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The `shape.0 + 1` offsets delimiting the elements of each row in `cols` and `values`.
+    pub fn row_offsets(&self) -> &[i64] {
+        &self.row_offsets
+    }
+
+    /// The column index of each stored element, sorted by row.
+    pub fn cols(&self) -> &[i64] {
+        &self.cols
+    }
+
+    /// The value of each stored element, sorted by row.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// The same matrix in the coordinate format, which is the format used on device.
+    pub fn to_coo(&self) -> CooMatrix<T> {
+        let rows = self
+            .row_offsets
+            .windows(2)
+            .enumerate()
+            .flat_map(|(row, w)| std::iter::repeat(row as i64).take((w[1] - w[0]) as usize))
+            .collect();
+        CooMatrix { shape: self.shape, rows, cols: self.cols.clone(), values: self.values.clone() }
+    }
+
+    /// Transfer the matrix to a device in the coordinate format, see [`CooMatrix::to_buffers`].
+    pub fn to_buffers(
+        &self,
+        client: &PjRtClient,
+        device: Option<&PjRtDevice>,
+    ) -> Result<Vec<PjRtBuffer>> {
+        self.to_coo().to_buffers(client, device)
+    }
+
+    /// Embed the matrix as constants in a computation, in the coordinate format.
+    pub fn constant(&self, builder: &XlaBuilder) -> Result<SparseOp> {
+        self.to_coo().constant(builder)
+    }
+}
+
+/// A sparse matrix in a computation, made of three rank 1 nodes with one element per non-zero
+/// value: the `S64` row indices, the `S64` column indices, and the values.
+#[derive(Debug, Clone)]
+pub struct SparseOp {
+    /// The number of rows and columns of the matrix.
+    pub shape: (usize, usize),
+    /// The row index of each stored element.
+    pub rows: XlaOp,
+    /// The column index of each stored element.
+    pub cols: XlaOp,
+    /// The value of each stored element, elements with the same position are summed.
+    pub values: XlaOp,
+}
+
+impl SparseOp {
+    /// Create the parameters holding a matrix with `nnz` stored elements of type `ty`, with the
+    /// parameter numbers `param_index` to `param_index + 2`. The arguments are expected in the
+    /// order used by [`CooMatrix::to_buffers`].
+    pub fn parameters(
+        builder: &XlaBuilder,
+        param_index: i64,
+        ty: ElementType,
+        shape: (usize, usize),
+        nnz: usize,
+    ) -> Result<Self> {
+        let nnz = [nnz as i64];
+        let rows = builder.parameter(param_index, ElementType::S64, &nnz, "sparse_rows")?;
+        let cols = builder.parameter(param_index + 1, ElementType::S64, &nnz, "sparse_cols")?;
+        let values = builder.parameter(param_index + 2, ty, &nnz, "sparse_values")?;
+        Ok(Self { shape, rows, cols, values })
+    }
+}
+
+/// The product of the sparse matrix `lhs` with the dense matrix `rhs`, the result is a dense
+/// matrix with `lhs.shape.0` rows. The rows of `rhs` are gathered for each stored element of
+/// `lhs`, scaled by the element value, and summed per row of `lhs`.
+pub fn sparse_dense_matmul(lhs: &SparseOp, rhs: &XlaOp) -> Result<XlaOp> {
+    let dims: Vec<i64> = rhs.dims()?.iter().map(|d| *d as i64).collect();
+    if dims.len() != 2 || dims[0] != lhs.shape.1 as i64 {
+        Err(Error::InvalidDims {
+            dims,
+            msg: "the dense operand must be a matrix with one row per sparse column",
+        })?
+    }
+    let nnz = lhs.values.dims()?[0] as i64;
+    let gathered = rhs.take(&lhs.cols, 0)?;
+    let values = lhs.values.broadcast_in_dim(&[nnz, dims[1]], &[0])?;
+    (gathered * values)?.segment_sum(&lhs.rows, lhs.shape.0)
+}
@@ -0,0 +1,715 @@
+use super::literal::{check_supported, Literal};
+use super::{NativeType, XlaComputation};
+use crate::{ArrayShape, ElementType, Error, PrimitiveType, Result, Shape};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum UnaryOp {
+    Neg,
+    Abs,
+    Sign,
+    Not,
+    Exp,
+    Expm1,
+    Log,
+    Log1p,
+    Sqrt,
+    Rsqrt,
+    Tanh,
+    Sin,
+    Cos,
+    Floor,
+    Ceil,
+    Round,
+    Logistic,
+}
+
+impl UnaryOp {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Neg => "neg",
+            Self::Abs => "abs",
+            Self::Sign => "sign",
+            Self::Not => "not",
+            Self::Exp => "exp",
+            Self::Expm1 => "expm1",
+            Self::Log => "log",
+            Self::Log1p => "log1p",
+            Self::Sqrt => "sqrt",
+            Self::Rsqrt => "rsqrt",
+            Self::Tanh => "tanh",
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+            Self::Floor => "floor",
+            Self::Ceil => "ceil",
+            Self::Round => "round",
+            Self::Logistic => "logistic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Max,
+    Min,
+    Pow,
+    Atan2,
+    And,
+    Or,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+impl BinaryOp {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Mul => "mul",
+            Self::Div => "div",
+            Self::Rem => "rem",
+            Self::Max => "max",
+            Self::Min => "min",
+            Self::Pow => "pow",
+            Self::Atan2 => "atan2",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Eq => "eq",
+            Self::Ne => "ne",
+            Self::Ge => "ge",
+            Self::Gt => "gt",
+            Self::Le => "le",
+            Self::Lt => "lt",
+        }
+    }
+
+    fn is_comparison(&self) -> bool {
+        matches!(self, Self::Eq | Self::Ne | Self::Ge | Self::Gt | Self::Le | Self::Lt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ReduceOp {
+    Sum,
+    Max,
+    Min,
+}
+
+// The operands are referred to by their position in the builder, the output shape of each
+// instruction is stored alongside it.
+#[derive(Debug, Clone)]
+pub(super) enum Node {
+    Parameter(i64),
+    Constant(Literal),
+    Unary(UnaryOp, usize),
+    Binary(BinaryOp, usize, usize),
+    Select(usize, usize, usize),
+    Convert(usize),
+    Reshape(usize),
+    BroadcastInDim(usize, Vec<i64>),
+    Transpose(usize, Vec<i64>),
+    Reduce(ReduceOp, usize, Vec<i64>),
+    MatMul(usize, usize),
+    Tuple(Vec<usize>),
+    GetTupleElement(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Instruction {
+    pub(super) node: Node,
+    pub(super) shape: Shape,
+}
+
+struct XlaBuilderInternal {
+    name: String,
+    instructions: RefCell<Vec<Instruction>>,
+}
+
+/// A builder is used to keep track of a computation graph while it's being built.
+#[derive(Clone)]
+pub struct XlaBuilder(Rc<XlaBuilderInternal>);
+
+/// A node in the graph of a builder.
+#[derive(Clone)]
+pub struct XlaOp {
+    id: usize,
+    builder: XlaBuilder,
+}
+
+fn is_float(ty: ElementType) -> bool {
+    matches!(ty, ElementType::F32 | ElementType::F64)
+}
+
+// Check that `dims` is a permutation or a subset of the dimensions of a rank `rank` array.
+fn check_dim_indexes(dims: &[i64], rank: usize, permutation: bool) -> Result<()> {
+    let mut seen = vec![false; rank];
+    for &d in dims.iter() {
+        if d < 0 || d as usize >= rank {
+            Err(Error::IndexOutOfBounds { index: d, rank })?
+        }
+        if std::mem::replace(&mut seen[d as usize], true) {
+            Err(Error::InvalidDims { dims: dims.to_vec(), msg: "duplicate dimension" })?
+        }
+    }
+    if permutation && dims.len() != rank {
+        Err(Error::InvalidDims { dims: dims.to_vec(), msg: "not a permutation" })?
+    }
+    Ok(())
+}
+
+fn count_parameters(instructions: &[Instruction]) -> usize {
+    instructions.iter().filter(|i| matches!(i.node, Node::Parameter(_))).count()
+}
+
+impl XlaBuilder {
+    /// Create a new builder with the associated name, the name is only used for debugging
+    /// purposes.
+    pub fn new(name: &str) -> XlaBuilder {
+        let internal =
+            XlaBuilderInternal { name: name.to_string(), instructions: RefCell::new(vec![]) };
+        XlaBuilder(Rc::new(internal))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Whether `self` and `other` are the same builder, ops can only be combined with ops
+    /// created by the same builder.
+    pub fn is_same(&self, other: &XlaBuilder) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn check_op(&self, op: &XlaOp) -> Result<()> {
+        if !self.is_same(&op.builder) {
+            Err(Error::BuilderMismatch)?
+        }
+        Ok(())
+    }
+
+    fn push(&self, node: Node, shape: Shape) -> XlaOp {
+        let mut instructions = self.0.instructions.borrow_mut();
+        instructions.push(Instruction { node, shape });
+        XlaOp { id: instructions.len() - 1, builder: self.clone() }
+    }
+
+    fn shape_of(&self, id: usize) -> Shape {
+        self.0.instructions.borrow()[id].shape.clone()
+    }
+
+    /// Build a computation from the specified root node.
+    pub fn build(&self, op: &XlaOp) -> Result<XlaComputation> {
+        self.check_op(op)?;
+        let instructions = self.0.instructions.borrow().clone();
+        let mut parameters: Vec<Option<ArrayShape>> = vec![];
+        for instruction in instructions.iter() {
+            if let Node::Parameter(number) = instruction.node {
+                let number = number as usize;
+                if parameters.len() <= number {
+                    parameters.resize(number + 1, None)
+                }
+                parameters[number] = Some(ArrayShape::try_from(&instruction.shape)?);
+            }
+        }
+        let expected = parameters.len();
+        let parameters = match parameters.into_iter().collect::<Option<Vec<_>>>() {
+            Some(parameters) => parameters,
+            None => {
+                Err(Error::WrongArgumentCount { expected, got: count_parameters(&instructions) })?
+            }
+        };
+        Ok(XlaComputation::new(&self.0.name, instructions, op.id, parameters))
+    }
+
+    /// Create an input node with the specified type and dimensions, parameters are numbered
+    /// from 0 and the numbers have to be contiguous.
+    pub fn parameter(
+        &self,
+        parameter_number: i64,
+        ty: ElementType,
+        dims: &[i64],
+        _name: &str,
+    ) -> Result<XlaOp> {
+        check_supported(ty, "parameter")?;
+        if parameter_number < 0 {
+            Err(Error::IndexOutOfBounds { index: parameter_number, rank: 0 })?
+        }
+        if dims.iter().any(|d| *d < 0) {
+            Err(Error::InvalidDims { dims: dims.to_vec(), msg: "negative dimension" })?
+        }
+        let duplicate = self
+            .0
+            .instructions
+            .borrow()
+            .iter()
+            .any(|i| matches!(i.node, Node::Parameter(n) if n == parameter_number));
+        if duplicate {
+            Err(Error::DuplicateParameterBinding { index: parameter_number as usize })?
+        }
+        Ok(self.push(Node::Parameter(parameter_number), Shape::array_with_type(ty, dims.to_vec())))
+    }
+
+    /// Create a node with a constant value defined by the specified literal.
+    pub fn constant_literal(&self, literal: &Literal) -> Result<XlaOp> {
+        let shape = literal.shape()?;
+        Ok(self.push(Node::Constant(literal.clone()), shape))
+    }
+
+    /// Create a node with a constant scalar value.
+    pub fn constant_r0<T: NativeType>(&self, f: T) -> Result<XlaOp> {
+        self.constant_literal(&Literal::scalar(f))
+    }
+
+    /// A shorter notation for `constant_r0`.
+    pub fn c0<T: NativeType>(&self, f: T) -> Result<XlaOp> {
+        self.constant_r0(f)
+    }
+
+    /// A one dimension constant node.
+    pub fn constant_r1<T: NativeType>(&self, f: &[T]) -> Result<XlaOp> {
+        self.constant_literal(&Literal::vec1(f))
+    }
+
+    /// A shorter notation for `constant_r1`.
+    pub fn c1<T: NativeType>(&self, f: &[T]) -> Result<XlaOp> {
+        self.constant_r1(f)
+    }
+
+    /// A scalar node with the zero value for the associated type.
+    pub fn zero(&self, ty: ElementType) -> Result<XlaOp> {
+        check_supported(ty, "zero")?;
+        self.constant_literal(&Literal::array(ty, vec![], vec![0.]))
+    }
+
+    /// A scalar node with the one value for the associated type.
+    pub fn one(&self, ty: ElementType) -> Result<XlaOp> {
+        check_supported(ty, "one")?;
+        self.constant_literal(&Literal::array(ty, vec![], vec![1.]))
+    }
+
+    /// Build a tuple from multiple operands.
+    pub fn tuple<B: std::borrow::Borrow<XlaOp>>(&self, args: &[B]) -> Result<XlaOp> {
+        let mut ids = Vec::with_capacity(args.len());
+        let mut shapes = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            let arg = arg.borrow();
+            self.check_op(arg)?;
+            ids.push(arg.id);
+            shapes.push(self.shape_of(arg.id));
+        }
+        Ok(self.push(Node::Tuple(ids), Shape::tuple(shapes)))
+    }
+}
+
+macro_rules! unary_op {
+    ($func_name:ident, $op:ident) => {
+        pub fn $func_name(&self) -> Result<Self> {
+            self.unary(UnaryOp::$op)
+        }
+    };
+}
+
+macro_rules! binary_op {
+    ($func_name:ident, $op:ident) => {
+        pub fn $func_name(&self, op: &XlaOp) -> Result<Self> {
+            self.binary(BinaryOp::$op, op)
+        }
+    };
+}
+
+impl XlaOp {
+    pub fn builder(&self) -> &XlaBuilder {
+        &self.builder
+    }
+
+    pub fn shape(&self) -> Result<Shape> {
+        Ok(self.builder.shape_of(self.id))
+    }
+
+    pub fn array_shape(&self) -> Result<ArrayShape> {
+        ArrayShape::try_from(&self.shape()?)
+    }
+
+    pub fn primitive_type(&self) -> Result<PrimitiveType> {
+        Ok(self.shape()?.primitive_type())
+    }
+
+    pub fn ty(&self) -> Result<PrimitiveType> {
+        self.primitive_type()
+    }
+
+    pub fn rank(&self) -> Result<usize> {
+        Ok(self.array_shape()?.dims().len())
+    }
+
+    pub fn dims(&self) -> Result<Vec<usize>> {
+        Ok(self.array_shape()?.dims().iter().map(|d| *d as usize).collect())
+    }
+
+    fn push(&self, node: Node, ty: ElementType, dims: Vec<i64>) -> Result<Self> {
+        Ok(self.builder.push(node, Shape::array_with_type(ty, dims)))
+    }
+
+    // Broadcast a scalar, or a node of the same rank with some dimensions of size 1, to `dims`.
+    fn broadcast_to(&self, dims: &[i64]) -> Result<Self> {
+        let shape = self.array_shape()?;
+        if shape.dims() == dims {
+            Ok(self.clone())
+        } else if shape.dims().is_empty() {
+            self.broadcast(dims)
+        } else {
+            let broadcast_dims: Vec<i64> = (0..dims.len() as i64).collect();
+            self.broadcast_in_dim(dims, &broadcast_dims)
+        }
+    }
+
+    fn unary(&self, op: UnaryOp) -> Result<Self> {
+        let shape = self.array_shape()?;
+        let supported = match op {
+            UnaryOp::Neg | UnaryOp::Abs | UnaryOp::Sign => shape.ty() != ElementType::Pred,
+            UnaryOp::Not => !is_float(shape.ty()),
+            _ => is_float(shape.ty()),
+        };
+        if !supported {
+            Err(Error::UnsupportedElementType { ty: shape.primitive_type(), op: op.name() })?
+        }
+        self.push(Node::Unary(op, self.id), shape.ty(), shape.dims().to_vec())
+    }
+
+    fn binary(&self, op: BinaryOp, rhs: &XlaOp) -> Result<Self> {
+        self.builder.check_op(rhs)?;
+        let (lhs_shape, rhs_shape) = (self.array_shape()?, rhs.array_shape()?);
+        let ty = lhs_shape.ty();
+        let supported = match op {
+            BinaryOp::And | BinaryOp::Or => !is_float(ty),
+            BinaryOp::Pow | BinaryOp::Atan2 => is_float(ty),
+            BinaryOp::Eq | BinaryOp::Ne => true,
+            _ => ty != ElementType::Pred,
+        };
+        if !supported {
+            Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: op.name() })?
+        }
+        if lhs_shape.ty() != rhs_shape.ty() {
+            Err(Error::ShapeMismatch {
+                expected: Shape::Array(lhs_shape.clone()),
+                got: Shape::Array(rhs_shape.clone()),
+            })?
+        }
+        // Scalars and dimensions of size 1 are implicitly broadcast, as done by XLA.
+        let (lhs_dims, rhs_dims) = (lhs_shape.dims(), rhs_shape.dims());
+        let dims = if lhs_dims == rhs_dims || rhs_dims.is_empty() {
+            lhs_dims.to_vec()
+        } else if lhs_dims.is_empty() {
+            rhs_dims.to_vec()
+        } else if lhs_dims.len() == rhs_dims.len()
+            && lhs_dims.iter().zip(rhs_dims.iter()).all(|(l, r)| l == r || *l == 1 || *r == 1)
+        {
+            lhs_dims.iter().zip(rhs_dims.iter()).map(|(l, r)| i64::max(*l, *r)).collect()
+        } else {
+            Err(Error::ShapeMismatch {
+                expected: Shape::Array(lhs_shape.clone()),
+                got: Shape::Array(rhs_shape.clone()),
+            })?
+        };
+        let (lhs, rhs) = (self.broadcast_to(&dims)?, rhs.broadcast_to(&dims)?);
+        let ty = if op.is_comparison() { ElementType::Pred } else { ty };
+        self.push(Node::Binary(op, lhs.id, rhs.id), ty, dims)
+    }
+
+    binary_op!(add_, Add);
+    binary_op!(sub_, Sub);
+    binary_op!(mul_, Mul);
+    binary_op!(div_, Div);
+    binary_op!(rem_, Rem);
+    binary_op!(max, Max);
+    binary_op!(min, Min);
+    binary_op!(pow, Pow);
+    binary_op!(atan2, Atan2);
+    binary_op!(and, And);
+    binary_op!(or, Or);
+    binary_op!(eq, Eq);
+    binary_op!(ne, Ne);
+    binary_op!(ge, Ge);
+    binary_op!(gt, Gt);
+    binary_op!(le, Le);
+    binary_op!(lt, Lt);
+
+    unary_op!(neg, Neg);
+    unary_op!(abs, Abs);
+    unary_op!(sign, Sign);
+    unary_op!(not, Not);
+    unary_op!(exp, Exp);
+    unary_op!(expm1, Expm1);
+    unary_op!(log, Log);
+    unary_op!(log1p, Log1p);
+    unary_op!(sqrt, Sqrt);
+    unary_op!(rsqrt, Rsqrt);
+    unary_op!(tanh, Tanh);
+    unary_op!(sin, Sin);
+    unary_op!(cos, Cos);
+    unary_op!(floor, Floor);
+    unary_op!(ceil, Ceil);
+    unary_op!(round, Round);
+    unary_op!(logistic, Logistic);
+
+    /// Sigmoid activation function.
+    pub fn sigmoid(&self) -> Result<Self> {
+        self.logistic()
+    }
+
+    /// SiLU activation function, x.sigmoid(x).
+    pub fn silu(&self) -> Result<Self> {
+        self.mul_(&self.logistic()?)
+    }
+
+    /// An element-wise `pred ? on_true : on_false`, `self` is the `PRED` node, it can be a
+    /// scalar.
+    pub fn select(&self, on_true: &Self, on_false: &Self) -> Result<Self> {
+        self.builder.check_op(on_true)?;
+        self.builder.check_op(on_false)?;
+        let pred = self.array_shape()?;
+        let (on_true_shape, on_false_shape) = (on_true.array_shape()?, on_false.array_shape()?);
+        if on_true_shape != on_false_shape {
+            Err(Error::ShapeMismatch {
+                expected: Shape::Array(on_true_shape.clone()),
+                got: Shape::Array(on_false_shape),
+            })?
+        }
+        if pred.ty() != ElementType::Pred {
+            Err(Error::ElementTypeMismatch { on_device: pred.ty(), on_host: ElementType::Pred })?
+        }
+        if !pred.dims().is_empty() && pred.dims() != on_true_shape.dims() {
+            Err(Error::ShapeMismatch {
+                expected: Shape::Array(on_true_shape.clone()),
+                got: Shape::Array(pred),
+            })?
+        }
+        let pred = self.broadcast_to(on_true_shape.dims())?;
+        let node = Node::Select(pred.id, on_true.id, on_false.id);
+        self.push(node, on_true_shape.ty(), on_true_shape.dims().to_vec())
+    }
+
+    /// Convert the values to another element type.
+    pub fn convert(&self, ty: PrimitiveType) -> Result<Self> {
+        let ty = ty.element_type()?;
+        check_supported(ty, "convert")?;
+        self.push(Node::Convert(self.id), ty, self.array_shape()?.dims().to_vec())
+    }
+
+    /// A node with the same values and the new dimensions `dims`.
+    pub fn reshape(&self, dims: &[i64]) -> Result<Self> {
+        let shape = self.array_shape()?;
+        if dims.iter().any(|d| *d < 0)
+            || dims.iter().product::<i64>() as usize != shape.element_count()
+        {
+            Err(Error::ShapeMismatch {
+                expected: Shape::Array(shape.clone()),
+                got: Shape::array_with_type(shape.ty(), dims.to_vec()),
+            })?
+        }
+        self.push(Node::Reshape(self.id), shape.ty(), dims.to_vec())
+    }
+
+    /// Add some leading dimensions of sizes `dims`.
+    pub fn broadcast(&self, dims: &[i64]) -> Result<Self> {
+        let shape = self.array_shape()?;
+        let out_dims: Vec<i64> = dims.iter().chain(shape.dims().iter()).copied().collect();
+        let broadcast_dims: Vec<i64> = (dims.len() as i64..out_dims.len() as i64).collect();
+        self.broadcast_in_dim(&out_dims, &broadcast_dims)
+    }
+
+    /// Broadcast to `out_dims`, the dimension `i` of this node maps to the output dimension
+    /// `broadcast_dims[i]` and has to be of the same size or of size 1.
+    pub fn broadcast_in_dim(&self, out_dims: &[i64], broadcast_dims: &[i64]) -> Result<Self> {
+        let shape = self.array_shape()?;
+        if broadcast_dims.len() != shape.dims().len() {
+            Err(Error::WrongArgumentCount {
+                expected: shape.dims().len(),
+                got: broadcast_dims.len(),
+            })?
+        }
+        check_dim_indexes(broadcast_dims, out_dims.len(), false)?;
+        for (&size, &out_dim) in shape.dims().iter().zip(broadcast_dims.iter()) {
+            if size != 1 && size != out_dims[out_dim as usize] {
+                Err(Error::ShapeMismatch {
+                    expected: Shape::array_with_type(shape.ty(), out_dims.to_vec()),
+                    got: Shape::Array(shape.clone()),
+                })?
+            }
+        }
+        let node = Node::BroadcastInDim(self.id, broadcast_dims.to_vec());
+        self.push(node, shape.ty(), out_dims.to_vec())
+    }
+
+    /// Permute the dimensions, the output dimension `i` is the dimension `index_perm[i]`.
+    pub fn transpose(&self, index_perm: &[i64]) -> Result<Self> {
+        let shape = self.array_shape()?;
+        check_dim_indexes(index_perm, shape.dims().len(), true)?;
+        let dims = index_perm.iter().map(|&i| shape.dims()[i as usize]).collect();
+        self.push(Node::Transpose(self.id, index_perm.to_vec()), shape.ty(), dims)
+    }
+
+    fn reduce(&self, op: ReduceOp, dims: &[i64], keep_dims: bool) -> Result<Self> {
+        let shape = self.array_shape()?;
+        if shape.ty() == ElementType::Pred {
+            Err(Error::UnsupportedElementType { ty: shape.primitive_type(), op: "reduce" })?
+        }
+        check_dim_indexes(dims, shape.dims().len(), false)?;
+        let out_dims = (0..shape.dims().len() as i64)
+            .filter(|d| !dims.contains(d))
+            .map(|d| shape.dims()[d as usize])
+            .collect();
+        let reduced = self.push(Node::Reduce(op, self.id, dims.to_vec()), shape.ty(), out_dims)?;
+        if keep_dims {
+            let dims: Vec<i64> = (0..shape.dims().len() as i64)
+                .map(|d| if dims.contains(&d) { 1 } else { shape.dims()[d as usize] })
+                .collect();
+            reduced.reshape(&dims)
+        } else {
+            Ok(reduced)
+        }
+    }
+
+    pub fn reduce_sum(&self, dims: &[i64], keep_dims: bool) -> Result<Self> {
+        self.reduce(ReduceOp::Sum, dims, keep_dims)
+    }
+
+    pub fn reduce_max(&self, dims: &[i64], keep_dims: bool) -> Result<Self> {
+        self.reduce(ReduceOp::Max, dims, keep_dims)
+    }
+
+    pub fn reduce_min(&self, dims: &[i64], keep_dims: bool) -> Result<Self> {
+        self.reduce(ReduceOp::Min, dims, keep_dims)
+    }
+
+    pub fn reduce_mean(&self, dims: &[i64], keep_dims: bool) -> Result<Self> {
+        let shape = self.array_shape()?;
+        let count: i64 = dims.iter().filter_map(|&d| shape.dims().get(d as usize)).product();
+        let sum = self.reduce_sum(dims, keep_dims)?;
+        let count = self.builder.c0(count as f64)?.convert(shape.primitive_type())?;
+        sum.div_(&count)
+    }
+
+    pub fn softmax(&self, dim: i64) -> Result<Self> {
+        let max = self.reduce_max(&[dim], true)?;
+        let unnormalized = self.sub_(&max)?.exp()?;
+        let sum = unnormalized.reduce_sum(&[dim], true)?;
+        unnormalized.div_(&sum)
+    }
+
+    /// Matrix multiplication, the last dimension of this node is contracted with the second to
+    /// last dimension of `rhs`, or its only dimension for vectors. The leading batch dimensions
+    /// of both nodes have to match, a `rhs` without batch dimensions is used for all batches.
+    pub fn matmul(&self, rhs: &Self) -> Result<Self> {
+        self.builder.check_op(rhs)?;
+        let (lhs_shape, rhs_shape) = (self.array_shape()?, rhs.array_shape()?);
+        let (lhs_dims, rhs_dims) = (lhs_shape.dims(), rhs_shape.dims());
+        let err = |msg| Error::MatMulIncorrectDims {
+            lhs_dims: lhs_dims.to_vec(),
+            rhs_dims: rhs_dims.to_vec(),
+            msg,
+        };
+        if lhs_dims.is_empty() || rhs_dims.is_empty() {
+            Err(err("empty dimension"))?
+        }
+        if lhs_shape.ty() != rhs_shape.ty() {
+            Err(err("different element types"))?
+        }
+        let k = lhs_dims[lhs_dims.len() - 1];
+        let rhs_k = if rhs_dims.len() == 1 { rhs_dims[0] } else { rhs_dims[rhs_dims.len() - 2] };
+        if k != rhs_k {
+            Err(err("incompatible contracting dimensions"))?
+        }
+        let lhs_batch = &lhs_dims[..lhs_dims.len().saturating_sub(2)];
+        let rhs_batch = &rhs_dims[..rhs_dims.len().saturating_sub(2)];
+        if !rhs_batch.is_empty() && lhs_batch != rhs_batch {
+            Err(err("incompatible batch dimensions"))?
+        }
+        let mut dims = lhs_dims[..lhs_dims.len() - 1].to_vec();
+        if rhs_dims.len() > 1 {
+            dims.push(rhs_dims[rhs_dims.len() - 1])
+        }
+        self.push(Node::MatMul(self.id, rhs.id), lhs_shape.ty(), dims)
+    }
+
+    /// A dot product for vectors and matrices, see [`XlaOp::matmul`].
+    pub fn dot(&self, rhs: &Self) -> Result<Self> {
+        if self.rank()? > 2 || rhs.rank()? > 2 {
+            Err(Error::MatMulIncorrectDims {
+                lhs_dims: self.array_shape()?.dims().to_vec(),
+                rhs_dims: rhs.array_shape()?.dims().to_vec(),
+                msg: "dot only supports vectors and matrices",
+            })?
+        }
+        self.matmul(rhs)
+    }
+
+    pub fn get_tuple_element(&self, index: i64) -> Result<Self> {
+        match self.shape()? {
+            Shape::Tuple(shapes) => match shapes.get(index as usize) {
+                Some(shape) if index >= 0 => {
+                    let node = Node::GetTupleElement(self.id, index as usize);
+                    Ok(self.builder.push(node, shape.clone()))
+                }
+                _ => Err(Error::IndexOutOfBounds { index, rank: shapes.len() }),
+            },
+            got => Err(Error::NotATuple { got }),
+        }
+    }
+
+    /// Generate a computation which root value is this node.
+    pub fn build(&self) -> Result<XlaComputation> {
+        self.builder.build(self)
+    }
+}
+
+macro_rules! bin_trait {
+    ($trait:ident, $fn1:ident, $fn2:ident) => {
+        impl<B: std::borrow::Borrow<XlaOp>> std::ops::$trait<B> for XlaOp {
+            type Output = Result<XlaOp>;
+
+            fn $fn1(self, rhs: B) -> Self::Output {
+                (&self).$fn1(rhs)
+            }
+        }
+
+        impl<B: std::borrow::Borrow<XlaOp>> std::ops::$trait<B> for &XlaOp {
+            type Output = Result<XlaOp>;
+
+            fn $fn1(self, rhs: B) -> Self::Output {
+                self.$fn2(rhs.borrow())
+            }
+        }
+
+        impl<B: std::borrow::Borrow<XlaOp>> std::ops::$trait<Result<B>> for XlaOp {
+            type Output = Result<XlaOp>;
+
+            fn $fn1(self, rhs: Result<B>) -> Self::Output {
+                (&self).$fn1(rhs)
+            }
+        }
+
+        impl<B: std::borrow::Borrow<XlaOp>> std::ops::$trait<Result<B>> for &XlaOp {
+            type Output = Result<XlaOp>;
+
+            fn $fn1(self, rhs: Result<B>) -> Self::Output {
+                self.$fn2(rhs?.borrow())
+            }
+        }
+    };
+}
+
+bin_trait!(Add, add, add_);
+bin_trait!(Sub, sub, sub_);
+bin_trait!(Mul, mul, mul_);
+bin_trait!(Div, div, div_);
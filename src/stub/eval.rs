@@ -0,0 +1,231 @@
+//! A reference evaluator for the graphs built with the stub builder, this favors simplicity
+//! over speed.
+use super::builder::{BinaryOp, Instruction, Node, ReduceOp, UnaryOp};
+use super::literal::{Literal, Value};
+use crate::{ElementType, Error, Result, Shape};
+
+// The row major strides of an array with dimensions `dims`, in number of elements.
+fn strides(dims: &[i64]) -> Vec<usize> {
+    let mut strides = vec![1; dims.len()];
+    for i in (0..dims.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * dims[i + 1] as usize
+    }
+    strides
+}
+
+// The multi-dimensional index of the element at position `offset` in a row major array.
+fn unravel(mut offset: usize, dims: &[i64], index: &mut [usize]) {
+    for (i, &d) in dims.iter().enumerate().rev() {
+        index[i] = offset % d as usize;
+        offset /= d as usize;
+    }
+}
+
+fn nan_aware(a: f64, b: f64, f: fn(f64, f64) -> f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else {
+        f(a, b)
+    }
+}
+
+fn unary(op: UnaryOp, ty: ElementType, v: f64) -> f64 {
+    match op {
+        UnaryOp::Neg => -v,
+        UnaryOp::Abs => v.abs(),
+        UnaryOp::Sign if v == 0. => 0.,
+        UnaryOp::Sign => v.signum(),
+        UnaryOp::Not if ty == ElementType::Pred => 1. - v,
+        UnaryOp::Not => !(v as i64) as f64,
+        UnaryOp::Exp => v.exp(),
+        UnaryOp::Expm1 => v.exp_m1(),
+        UnaryOp::Log => v.ln(),
+        UnaryOp::Log1p => v.ln_1p(),
+        UnaryOp::Sqrt => v.sqrt(),
+        UnaryOp::Rsqrt => 1. / v.sqrt(),
+        UnaryOp::Tanh => v.tanh(),
+        UnaryOp::Sin => v.sin(),
+        UnaryOp::Cos => v.cos(),
+        UnaryOp::Floor => v.floor(),
+        UnaryOp::Ceil => v.ceil(),
+        UnaryOp::Round => v.round(),
+        UnaryOp::Logistic => 1. / (1. + (-v).exp()),
+    }
+}
+
+fn binary(op: BinaryOp, ty: ElementType, a: f64, b: f64) -> f64 {
+    let is_int = !matches!(ty, ElementType::F32 | ElementType::F64);
+    match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        // Integer divisions round towards zero.
+        BinaryOp::Div if is_int => (a / b).trunc(),
+        BinaryOp::Div => a / b,
+        BinaryOp::Rem => a % b,
+        BinaryOp::Max => nan_aware(a, b, f64::max),
+        BinaryOp::Min => nan_aware(a, b, f64::min),
+        BinaryOp::Pow => a.powf(b),
+        BinaryOp::Atan2 => a.atan2(b),
+        BinaryOp::And if ty == ElementType::Pred => (a != 0. && b != 0.) as u8 as f64,
+        BinaryOp::And => (a as i64 & b as i64) as f64,
+        BinaryOp::Or if ty == ElementType::Pred => (a != 0. || b != 0.) as u8 as f64,
+        BinaryOp::Or => (a as i64 | b as i64) as f64,
+        BinaryOp::Eq => (a == b) as u8 as f64,
+        BinaryOp::Ne => (a != b) as u8 as f64,
+        BinaryOp::Ge => (a >= b) as u8 as f64,
+        BinaryOp::Gt => (a > b) as u8 as f64,
+        BinaryOp::Le => (a <= b) as u8 as f64,
+        BinaryOp::Lt => (a < b) as u8 as f64,
+    }
+}
+
+fn broadcast_in_dim(
+    data: &[f64],
+    dims: &[i64],
+    out_dims: &[i64],
+    broadcast_dims: &[i64],
+) -> Vec<f64> {
+    let in_strides = strides(dims);
+    let count = out_dims.iter().product::<i64>() as usize;
+    let mut index = vec![0; out_dims.len()];
+    (0..count)
+        .map(|offset| {
+            unravel(offset, out_dims, &mut index);
+            let src: usize = (0..dims.len())
+                .map(|k| if dims[k] == 1 { 0 } else { index[broadcast_dims[k] as usize] })
+                .zip(in_strides.iter())
+                .map(|(i, s)| i * s)
+                .sum();
+            data[src]
+        })
+        .collect()
+}
+
+fn transpose(data: &[f64], dims: &[i64], out_dims: &[i64], perm: &[i64]) -> Vec<f64> {
+    let in_strides = strides(dims);
+    let mut index = vec![0; out_dims.len()];
+    (0..data.len())
+        .map(|offset| {
+            unravel(offset, out_dims, &mut index);
+            let src: usize =
+                perm.iter().zip(index.iter()).map(|(p, i)| i * in_strides[*p as usize]).sum();
+            data[src]
+        })
+        .collect()
+}
+
+fn reduce(op: ReduceOp, data: &[f64], dims: &[i64], reduced: &[i64], out_len: usize) -> Vec<f64> {
+    let (init, f): (f64, fn(f64, f64) -> f64) = match op {
+        ReduceOp::Sum => (0., |a, b| a + b),
+        ReduceOp::Max => (f64::NEG_INFINITY, |a, b| nan_aware(a, b, f64::max)),
+        ReduceOp::Min => (f64::INFINITY, |a, b| nan_aware(a, b, f64::min)),
+    };
+    let kept: Vec<usize> = (0..dims.len()).filter(|d| !reduced.contains(&(*d as i64))).collect();
+    let kept_dims: Vec<i64> = kept.iter().map(|d| dims[*d]).collect();
+    let out_strides = strides(&kept_dims);
+    let mut out = vec![init; out_len];
+    let mut index = vec![0; dims.len()];
+    for (offset, v) in data.iter().enumerate() {
+        unravel(offset, dims, &mut index);
+        let dst: usize = kept.iter().zip(out_strides.iter()).map(|(d, s)| index[*d] * s).sum();
+        out[dst] = f(out[dst], *v)
+    }
+    out
+}
+
+fn matmul(lhs: &[f64], lhs_dims: &[i64], rhs: &[f64], rhs_dims: &[i64]) -> Vec<f64> {
+    // Both operands are seen as batches of matrices, vectors being matrices with a single
+    // row or column.
+    let k = lhs_dims[lhs_dims.len() - 1] as usize;
+    let m = if lhs_dims.len() > 1 { lhs_dims[lhs_dims.len() - 2] as usize } else { 1 };
+    let n = if rhs_dims.len() > 1 { rhs_dims[rhs_dims.len() - 1] as usize } else { 1 };
+    let batch = lhs.len() / (m * k).max(1);
+    let rhs_batched = rhs_dims.len() > 2;
+    let mut out = vec![0.; batch * m * n];
+    for b in 0..batch {
+        let lhs = &lhs[b * m * k..];
+        let rhs = if rhs_batched { &rhs[b * k * n..] } else { rhs };
+        for i in 0..m {
+            for j in 0..n {
+                out[(b * m + i) * n + j] = (0..k).map(|l| lhs[i * k + l] * rhs[l * n + j]).sum()
+            }
+        }
+    }
+    out
+}
+
+fn eval_array(node: &Node, out_dims: &[i64], values: &[Literal]) -> Result<Vec<f64>> {
+    let data = match node {
+        Node::Unary(op, id) => {
+            let (ty, _, data) = values[*id].parts()?;
+            data.iter().map(|v| unary(*op, ty, *v)).collect()
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let (ty, _, lhs) = values[*lhs].parts()?;
+            let (_, _, rhs) = values[*rhs].parts()?;
+            lhs.iter().zip(rhs.iter()).map(|(a, b)| binary(*op, ty, *a, *b)).collect()
+        }
+        Node::Select(pred, on_true, on_false) => {
+            let (_, _, pred) = values[*pred].parts()?;
+            let (_, _, on_true) = values[*on_true].parts()?;
+            let (_, _, on_false) = values[*on_false].parts()?;
+            (0..pred.len()).map(|i| if pred[i] != 0. { on_true[i] } else { on_false[i] }).collect()
+        }
+        Node::Convert(id) | Node::Reshape(id) => values[*id].parts()?.2.to_vec(),
+        Node::BroadcastInDim(id, broadcast_dims) => {
+            let (_, dims, data) = values[*id].parts()?;
+            broadcast_in_dim(data, dims, out_dims, broadcast_dims)
+        }
+        Node::Transpose(id, perm) => {
+            let (_, dims, data) = values[*id].parts()?;
+            transpose(data, dims, out_dims, perm)
+        }
+        Node::Reduce(op, id, reduced) => {
+            let (_, dims, data) = values[*id].parts()?;
+            reduce(*op, data, dims, reduced, out_dims.iter().product::<i64>() as usize)
+        }
+        Node::MatMul(lhs, rhs) => {
+            let (_, lhs_dims, lhs) = values[*lhs].parts()?;
+            let (_, rhs_dims, rhs) = values[*rhs].parts()?;
+            matmul(lhs, lhs_dims, rhs, rhs_dims)
+        }
+        Node::Parameter(_) | Node::Constant(_) | Node::Tuple(_) | Node::GetTupleElement(_, _) => {
+            unreachable!("not an array node {node:?}")
+        }
+    };
+    Ok(data)
+}
+
+/// Evaluate the instructions up to `root`, the arguments have already been checked against
+/// the parameter shapes.
+pub(super) fn evaluate(
+    instructions: &[Instruction],
+    root: usize,
+    args: &[&Literal],
+) -> Result<Literal> {
+    let mut values: Vec<Literal> = Vec::with_capacity(root + 1);
+    for instruction in instructions[..=root].iter() {
+        let value = match &instruction.node {
+            Node::Parameter(number) => args[*number as usize].clone(),
+            Node::Constant(literal) => literal.clone(),
+            Node::Tuple(ids) => Literal::tuple(ids.iter().map(|id| values[*id].clone()).collect()),
+            Node::GetTupleElement(id, index) => match &values[*id].0 {
+                Value::Tuple(elems) => elems[*index].clone(),
+                Value::Array { .. } => Err(Error::NotATuple { got: values[*id].shape()? })?,
+            },
+            node => match &instruction.shape {
+                Shape::Array(shape) => {
+                    let data = eval_array(node, shape.dims(), &values)?;
+                    Literal::array(shape.ty(), shape.dims().to_vec(), data)
+                }
+                shape => Err(Error::NotAnArray { expected: None, got: shape.clone() })?,
+            },
+        };
+        values.push(value)
+    }
+    match values.pop() {
+        Some(value) => Ok(value),
+        None => Err(Error::EmptyLiteral),
+    }
+}
@@ -0,0 +1,179 @@
+use super::NativeType;
+use crate::{ArrayShape, ElementType, Error, PrimitiveType, Result, Shape};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Value {
+    // The values are stored as `f64`, already rounded to the element type.
+    Array { ty: ElementType, dims: Vec<i64>, data: Vec<f64> },
+    Tuple(Vec<Literal>),
+}
+
+/// A value stored on the host, either an array or a tuple of literals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Literal(pub(super) Value);
+
+// Round a value to what can be represented by the element type.
+pub(super) fn cast(ty: ElementType, v: f64) -> f64 {
+    match ty {
+        ElementType::Pred => (v != 0.) as u8 as f64,
+        ElementType::S8 => v as i8 as f64,
+        ElementType::S16 => v as i16 as f64,
+        ElementType::S32 => v as i32 as f64,
+        ElementType::S64 => v as i64 as f64,
+        ElementType::U8 => v as u8 as f64,
+        ElementType::U16 => v as u16 as f64,
+        ElementType::U32 => v as u32 as f64,
+        ElementType::U64 => v as u64 as f64,
+        ElementType::F32 => v as f32 as f64,
+        ElementType::F16
+        | ElementType::Bf16
+        | ElementType::F64
+        | ElementType::C64
+        | ElementType::C128 => v,
+    }
+}
+
+// The half precision and complex types are not supported by the stub backend.
+pub(super) fn check_supported(ty: ElementType, op: &'static str) -> Result<()> {
+    match ty {
+        ElementType::F16 | ElementType::Bf16 | ElementType::C64 | ElementType::C128 => {
+            Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op })
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Literal {
+    pub(super) fn array(ty: ElementType, dims: Vec<i64>, data: Vec<f64>) -> Self {
+        let data = data.into_iter().map(|v| cast(ty, v)).collect();
+        Self(Value::Array { ty, dims, data })
+    }
+
+    pub(super) fn parts(&self) -> Result<(ElementType, &[i64], &[f64])> {
+        match &self.0 {
+            Value::Array { ty, dims, data } => Ok((*ty, dims, data)),
+            Value::Tuple(_) => Err(Error::NotAnArray { expected: None, got: self.shape()? }),
+        }
+    }
+
+    /// A scalar literal.
+    pub fn scalar<T: NativeType>(t: T) -> Self {
+        Self::array(T::TY, vec![], vec![t.to_f64().unwrap_or(0.)])
+    }
+
+    /// A one dimension literal holding the values of `f`.
+    pub fn vec1<T: NativeType>(f: &[T]) -> Self {
+        let data = f.iter().map(|v| v.to_f64().unwrap_or(0.)).collect();
+        Self::array(T::TY, vec![f.len() as i64], data)
+    }
+
+    /// A tuple literal made of the given elements.
+    pub fn tuple(elems: Vec<Self>) -> Self {
+        Self(Value::Tuple(elems))
+    }
+
+    pub fn shape(&self) -> Result<Shape> {
+        match &self.0 {
+            Value::Array { ty, dims, .. } => Ok(Shape::array_with_type(*ty, dims.clone())),
+            Value::Tuple(elems) => {
+                Ok(Shape::tuple(elems.iter().map(|e| e.shape()).collect::<Result<Vec<_>>>()?))
+            }
+        }
+    }
+
+    pub fn array_shape(&self) -> Result<ArrayShape> {
+        ArrayShape::try_from(&self.shape()?)
+    }
+
+    /// The element type of an array literal.
+    pub fn ty(&self) -> Result<ElementType> {
+        Ok(self.parts()?.0)
+    }
+
+    pub fn primitive_type(&self) -> Result<PrimitiveType> {
+        Ok(self.ty()?.primitive_type())
+    }
+
+    /// The number of elements in an array literal, tuples have no elements.
+    pub fn element_count(&self) -> usize {
+        match &self.0 {
+            Value::Array { data, .. } => data.len(),
+            Value::Tuple(_) => 0,
+        }
+    }
+
+    /// A literal with the same values and the new dimensions `dims`.
+    pub fn reshape(&self, dims: &[i64]) -> Result<Literal> {
+        let (ty, old_dims, data) = self.parts()?;
+        if dims.iter().any(|d| *d < 0) || dims.iter().product::<i64>() as usize != data.len() {
+            Err(Error::ShapeMismatch {
+                expected: Shape::array_with_type(ty, old_dims.to_vec()),
+                got: Shape::array_with_type(ty, dims.to_vec()),
+            })?
+        }
+        Ok(Self::array(ty, dims.to_vec(), data.to_vec()))
+    }
+
+    /// Convert the values to another element type.
+    pub fn convert(&self, ty: PrimitiveType) -> Result<Literal> {
+        let ty = ty.element_type()?;
+        check_supported(ty, "convert")?;
+        let (_, dims, data) = self.parts()?;
+        Ok(Self::array(ty, dims.to_vec(), data.to_vec()))
+    }
+
+    /// The values of an array literal, `T` has to match the element type of the literal.
+    pub fn to_vec<T: NativeType>(&self) -> Result<Vec<T>> {
+        let (ty, _, data) = self.parts()?;
+        if ty != T::TY {
+            Err(Error::ElementTypeMismatch { on_device: ty, on_host: T::TY })?
+        }
+        // The values are already rounded to the element type so the cast cannot fail.
+        Ok(data.iter().map(|v| T::from(*v).unwrap_or(T::ZERO)).collect())
+    }
+
+    /// The first value of an array literal.
+    pub fn get_first_element<T: NativeType>(&self) -> Result<T> {
+        match self.to_vec::<T>()?.first() {
+            Some(v) => Ok(*v),
+            None => Err(Error::EmptyLiteral),
+        }
+    }
+
+    /// The elements of a tuple literal.
+    pub fn to_tuple(self) -> Result<Vec<Literal>> {
+        match self.0 {
+            Value::Tuple(elems) => Ok(elems),
+            Value::Array { ty, dims, .. } => {
+                Err(Error::NotATuple { got: Shape::array_with_type(ty, dims) })
+            }
+        }
+    }
+
+    pub fn to_tuple1(self) -> Result<Self> {
+        match <[Self; 1]>::try_from(self.to_tuple()?) {
+            Ok([a]) => Ok(a),
+            Err(elems) => {
+                Err(Error::UnexpectedNumberOfElemsInTuple { expected: 1, got: elems.len() })
+            }
+        }
+    }
+
+    pub fn to_tuple2(self) -> Result<(Self, Self)> {
+        match <[Self; 2]>::try_from(self.to_tuple()?) {
+            Ok([a, b]) => Ok((a, b)),
+            Err(elems) => {
+                Err(Error::UnexpectedNumberOfElemsInTuple { expected: 2, got: elems.len() })
+            }
+        }
+    }
+
+    pub fn to_tuple3(self) -> Result<(Self, Self, Self)> {
+        match <[Self; 3]>::try_from(self.to_tuple()?) {
+            Ok([a, b, c]) => Ok((a, b, c)),
+            Err(elems) => {
+                Err(Error::UnexpectedNumberOfElemsInTuple { expected: 3, got: elems.len() })
+            }
+        }
+    }
+}
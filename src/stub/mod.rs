@@ -0,0 +1,224 @@
+//! A pure Rust backend that evaluates computations on the host.
+//!
+//! This backend does not use the XLA C++ library so it can be compiled for targets where the
+//! library is not available, e.g. `wasm32-unknown-unknown` for demos and UI previews. It mirrors
+//! the subset of the API needed to build and run simple computations: [`XlaBuilder`],
+//! [`XlaOp`], [`Literal`], [`PjRtClient`], and [`PjRtLoadedExecutable`]. When the `ffi` feature
+//! is disabled, these types are re-exported at the root of the crate so that code written
+//! against this subset compiles with both backends:
+//!
+//! ```toml
+//! [target.'cfg(target_arch = "wasm32")'.dependencies]
+//! xla = { version = "0.2", default-features = false, features = ["stub"] }
+//! [target.'cfg(not(target_arch = "wasm32"))'.dependencies]
+//! xla = { version = "0.2" }
+//! ```
+//!
+//! Only a limited set of ops is supported: element-wise ops, comparisons, select, conversions,
+//! reshapes, broadcasts, transpositions, reductions, and matrix multiplications, on the
+//! `PRED`, integer, `F32`, and `F64` element types. Values are stored as `f64`, so integers
+//! with a magnitude above 2^53 are not represented exactly.
+mod builder;
+mod eval;
+mod literal;
+
+use crate::{ArrayElement, ArrayShape, Error, Result, Shape};
+use std::borrow::Borrow;
+use std::rc::Rc;
+
+pub use builder::{XlaBuilder, XlaOp};
+pub use literal::Literal;
+
+/// The element types that can be used to create literals and constants.
+pub trait NativeType: ArrayElement + num_traits::NumCast {}
+
+impl NativeType for u8 {}
+impl NativeType for u16 {}
+impl NativeType for u32 {}
+impl NativeType for u64 {}
+impl NativeType for i8 {}
+impl NativeType for i16 {}
+impl NativeType for i32 {}
+impl NativeType for i64 {}
+impl NativeType for f32 {}
+impl NativeType for f64 {}
+
+struct XlaComputationInternal {
+    name: String,
+    instructions: Vec<builder::Instruction>,
+    root: usize,
+    parameters: Vec<ArrayShape>,
+}
+
+/// A computation built from a root node.
+#[derive(Clone)]
+pub struct XlaComputation(Rc<XlaComputationInternal>);
+
+impl XlaComputation {
+    fn new(
+        name: &str,
+        instructions: Vec<builder::Instruction>,
+        root: usize,
+        parameters: Vec<ArrayShape>,
+    ) -> Self {
+        let internal =
+            XlaComputationInternal { name: name.to_string(), instructions, root, parameters };
+        Self(Rc::new(internal))
+    }
+
+    /// The computation name.
+    pub fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    /// The shapes of the parameters, ordered by parameter number.
+    pub fn parameter_shapes(&self) -> &[ArrayShape] {
+        &self.0.parameters
+    }
+
+    /// The shape of the result.
+    pub fn result_shape(&self) -> &Shape {
+        &self.0.instructions[self.0.root].shape
+    }
+
+    /// Compile this computation for the specified client.
+    pub fn compile(&self, client: &PjRtClient) -> Result<PjRtLoadedExecutable> {
+        client.compile(self)
+    }
+}
+
+/// The single host device of the stub backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PjRtDevice;
+
+impl PjRtDevice {
+    pub fn id(&self) -> usize {
+        0
+    }
+
+    pub fn kind(&self) -> String {
+        "stub".to_string()
+    }
+}
+
+/// A client for the stub backend, computations are evaluated on the calling thread.
+#[derive(Debug, Clone, Default)]
+pub struct PjRtClient;
+
+impl PjRtClient {
+    /// The stub client, this is named after the CPU client of the XLA backend so that the same
+    /// code can create clients for both backends.
+    pub fn cpu() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn platform_name(&self) -> String {
+        "stub".to_string()
+    }
+
+    pub fn device_count(&self) -> usize {
+        1
+    }
+
+    pub fn addressable_device_count(&self) -> usize {
+        1
+    }
+
+    pub fn devices(&self) -> Vec<PjRtDevice> {
+        vec![PjRtDevice]
+    }
+
+    pub fn addressable_devices(&self) -> Vec<PjRtDevice> {
+        vec![PjRtDevice]
+    }
+
+    /// Compile a computation, there is nothing to compile for the stub backend so this only
+    /// keeps a handle on the computation.
+    pub fn compile(&self, c: &XlaComputation) -> Result<PjRtLoadedExecutable> {
+        Ok(PjRtLoadedExecutable { computation: c.clone(), client: self.clone() })
+    }
+
+    /// A buffer holding a copy of `literal`.
+    pub fn buffer_from_host_literal(
+        &self,
+        _device: Option<&PjRtDevice>,
+        literal: &Literal,
+    ) -> Result<PjRtBuffer> {
+        Ok(PjRtBuffer(literal.clone()))
+    }
+
+    /// A buffer holding the values of `data` with the dimensions `dims`.
+    pub fn buffer_from_host_buffer<T: NativeType>(
+        &self,
+        data: &[T],
+        dims: &[usize],
+        _device: Option<&PjRtDevice>,
+    ) -> Result<PjRtBuffer> {
+        let element_count: usize = dims.iter().product();
+        if element_count != data.len() {
+            Err(Error::WrongElementCount { dims: dims.to_vec(), element_count: data.len() })?
+        }
+        let dims: Vec<i64> = dims.iter().map(|d| *d as i64).collect();
+        Ok(PjRtBuffer(Literal::vec1(data).reshape(&dims)?))
+    }
+}
+
+/// A value stored on the host device.
+#[derive(Debug, Clone)]
+pub struct PjRtBuffer(Literal);
+
+impl PjRtBuffer {
+    pub fn on_device_shape(&self) -> Result<Shape> {
+        self.0.shape()
+    }
+
+    /// Copy the buffer to a literal.
+    pub fn to_literal_sync(&self) -> Result<Literal> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A computation ready to be run by the stub backend.
+#[derive(Clone)]
+pub struct PjRtLoadedExecutable {
+    computation: XlaComputation,
+    client: PjRtClient,
+}
+
+impl PjRtLoadedExecutable {
+    pub fn client(&self) -> &PjRtClient {
+        &self.client
+    }
+
+    fn run(&self, args: &[&Literal]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let computation = &self.computation.0;
+        if args.len() != computation.parameters.len() {
+            Err(Error::WrongArgumentCount {
+                expected: computation.parameters.len(),
+                got: args.len(),
+            })?
+        }
+        for (arg, shape) in args.iter().zip(computation.parameters.iter()) {
+            let arg_shape = arg.shape()?;
+            let shape = Shape::Array(shape.clone());
+            if arg_shape != shape {
+                Err(Error::ShapeMismatch { expected: shape, got: arg_shape })?
+            }
+        }
+        let result = eval::evaluate(&computation.instructions, computation.root, args)?;
+        Ok(vec![vec![PjRtBuffer(result)]])
+    }
+
+    /// Run the computation on some literals, there is a single replica with a single output
+    /// which is a tuple when the root of the computation is a tuple.
+    pub fn execute<L: Borrow<Literal>>(&self, args: &[L]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let args: Vec<&Literal> = args.iter().map(|a| a.borrow()).collect();
+        self.run(&args)
+    }
+
+    /// Run the computation on some buffers, see [`PjRtLoadedExecutable::execute`].
+    pub fn execute_b<L: Borrow<PjRtBuffer>>(&self, args: &[L]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let args: Vec<&Literal> = args.iter().map(|a| &a.borrow().0).collect();
+        self.run(&args)
+    }
+}
@@ -0,0 +1,221 @@
+//! Conversions between tch (LibTorch) tensors and literals or device buffers.
+//!
+//! This module is only available with the `tch` feature. When a tensor already lives in the
+//! memory of the target device, i.e. a CPU tensor used with a CPU client or a CUDA tensor used
+//! with a GPU client on the same device, [`buffer_from_tensor`] hands the tensor memory over
+//! through [DLPack](crate::dlpack) rather than copying it, the tensor storage is kept alive
+//! until the buffer is dropped. CPU tensors which storage is not suitably aligned for XLA are
+//! copied. In the other direction [`tensor_view_from_buffer`] creates a tensor using the memory
+//! of a buffer. Other conversions go through host memory.
+//!
+//! Before a CUDA tensor is handed over, the current stream of its device is synchronized so that
+//! the kernels writing to the tensor have completed before XLA reads it.
+use crate::dlpack::{DLDataType, DLDevice, DLManagedTensor, DLTensor, DL_CPU, DL_CUDA};
+use crate::{ElementType, Error, Literal, PjRtBuffer, PjRtClient, PjRtDevice, Result};
+use ::tch::{Cuda, Device, Kind, Tensor};
+
+fn element_type(kind: Kind) -> Result<ElementType> {
+    let ty = match kind {
+        Kind::Bool => ElementType::Pred,
+        Kind::Int8 => ElementType::S8,
+        Kind::Int16 => ElementType::S16,
+        Kind::Int => ElementType::S32,
+        Kind::Int64 => ElementType::S64,
+        Kind::Uint8 => ElementType::U8,
+        Kind::Half => ElementType::F16,
+        Kind::Float => ElementType::F32,
+        Kind::BFloat16 => ElementType::Bf16,
+        Kind::Double => ElementType::F64,
+        Kind::ComplexFloat => ElementType::C64,
+        Kind::ComplexDouble => ElementType::C128,
+        kind => Err(Error::Tch(::tch::TchError::Kind(format!("unsupported kind {kind:?}"))))?,
+    };
+    Ok(ty)
+}
+
+fn kind(ty: ElementType) -> Result<Kind> {
+    let kind = match ty {
+        ElementType::Pred => Kind::Bool,
+        ElementType::S8 => Kind::Int8,
+        ElementType::S16 => Kind::Int16,
+        ElementType::S32 => Kind::Int,
+        ElementType::S64 => Kind::Int64,
+        ElementType::U8 => Kind::Uint8,
+        ElementType::F16 => Kind::Half,
+        ElementType::F32 => Kind::Float,
+        ElementType::Bf16 => Kind::BFloat16,
+        ElementType::F64 => Kind::Double,
+        ElementType::C64 => Kind::ComplexFloat,
+        ElementType::C128 => Kind::ComplexDouble,
+        ty @ (ElementType::U16 | ElementType::U32 | ElementType::U64) => {
+            Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "tch" })?
+        }
+    };
+    Ok(kind)
+}
+
+fn dims(tensor: &Tensor) -> Vec<usize> {
+    tensor.size().iter().map(|d| *d as usize).collect()
+}
+
+// The raw bytes of a tensor in row major order.
+fn tensor_bytes(tensor: &Tensor) -> Result<Vec<u8>> {
+    let tensor = tensor.f_to_device(Device::Cpu)?.f_contiguous()?;
+    let numel = tensor.numel();
+    let mut data = vec![0u8; numel * tensor.kind().elt_size_in_bytes()];
+    tensor.f_copy_data_u8(&mut data, numel)?;
+    Ok(data)
+}
+
+/// Copy a tch tensor into a literal with the same element type and dimensions.
+pub fn literal_from_tensor(tensor: &Tensor) -> Result<Literal> {
+    let ty = element_type(tensor.kind())?;
+    Literal::create_from_shape_and_untyped_data(ty, &dims(tensor), &tensor_bytes(tensor)?)
+}
+
+/// Create a device buffer from a tch tensor, see the [module documentation](self) for when the
+/// tensor memory is used without copying it. If the device is not specified, the default device
+/// of the client is used.
+pub fn buffer_from_tensor(
+    client: &PjRtClient,
+    tensor: &Tensor,
+    device: Option<&PjRtDevice>,
+) -> Result<PjRtBuffer> {
+    let ty = element_type(tensor.kind())?;
+    let dims = dims(tensor);
+    let hardware_id = match device {
+        Some(device) => Some(device.local_hardware_id()),
+        None => client.devices().first().map(|d| d.local_hardware_id()),
+    };
+    let same_memory = match (tensor.device(), client.platform_name().as_str()) {
+        (Device::Cpu, "cpu") => true,
+        (Device::Cuda(index), "cuda" | "gpu") => hardware_id == Some(index),
+        _ => false,
+    };
+    if !same_memory {
+        return client.buffer_from_host_raw_bytes(ty, &tensor_bytes(tensor)?, &dims, device);
+    }
+    if let Device::Cuda(index) = tensor.device() {
+        Cuda::synchronize(index as i64)
+    }
+    let dlpack = to_dlpack(tensor.f_contiguous()?, ty)?;
+    match unsafe { client.buffer_from_dlpack(dlpack, device) } {
+        Ok(buffer) => Ok(buffer),
+        // XLA rejects CPU views which are not aligned enough, e.g. tensors at an offset in
+        // their storage, copy these.
+        Err(_) if tensor.device() == Device::Cpu => {
+            client.buffer_from_host_raw_bytes(ty, &tensor_bytes(tensor)?, &dims, device)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// The state of a tensor exported as a DLPack tensor, the shallow clone keeps the storage alive.
+struct ExportedTensor {
+    _tensor: Tensor,
+    shape: Vec<i64>,
+}
+
+unsafe extern "C" fn exported_tensor_deleter(tensor: *mut DLManagedTensor) {
+    let tensor = Box::from_raw(tensor);
+    drop(Box::from_raw(tensor.manager_ctx as *mut ExportedTensor))
+}
+
+// Export a contiguous tensor as a DLPack tensor.
+fn to_dlpack(tensor: Tensor, ty: ElementType) -> Result<*mut DLManagedTensor> {
+    let device = match tensor.device() {
+        Device::Cpu => DLDevice { device_type: DL_CPU, device_id: 0 },
+        Device::Cuda(index) => DLDevice { device_type: DL_CUDA, device_id: index as i32 },
+        device => Err(Error::DlPack(format!("unsupported tch device {device:?}")))?,
+    };
+    let data = tensor.data_ptr();
+    let mut ctx = Box::new(ExportedTensor { shape: tensor.size(), _tensor: tensor });
+    let dl_tensor = DLTensor {
+        data,
+        device,
+        ndim: ctx.shape.len() as i32,
+        dtype: DLDataType::from_element_type(ty),
+        shape: ctx.shape.as_mut_ptr(),
+        strides: std::ptr::null_mut(),
+        byte_offset: 0,
+    };
+    let tensor = DLManagedTensor {
+        dl_tensor,
+        manager_ctx: Box::into_raw(ctx) as *mut libc::c_void,
+        deleter: Some(exported_tensor_deleter),
+    };
+    Ok(Box::into_raw(Box::new(tensor)))
+}
+
+/// A tch tensor using the memory of a device buffer, the buffer is kept alive as long as the
+/// view. The tensor must not be used, e.g. through a shallow clone, after the view is dropped.
+pub struct TensorView {
+    // Dropped explicitly so that the tensor goes away before the buffer is released.
+    tensor: std::mem::ManuallyDrop<Tensor>,
+    dlpack: *mut DLManagedTensor,
+}
+
+impl TensorView {
+    pub fn tensor(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl Drop for TensorView {
+    fn drop(&mut self) {
+        unsafe { std::mem::ManuallyDrop::drop(&mut self.tensor) }
+        if let Some(deleter) = unsafe { (*self.dlpack).deleter } {
+            unsafe { deleter(self.dlpack) }
+        }
+    }
+}
+
+/// Create a tch tensor using the memory of an array buffer without copying it, this waits for
+/// the buffer to be ready. Only buffers on a CPU or CUDA client are supported.
+pub fn tensor_view_from_buffer(buffer: PjRtBuffer) -> Result<TensorView> {
+    let dlpack = buffer.into_dlpack()?;
+    let view = unsafe {
+        let dl_tensor = &(*dlpack).dl_tensor;
+        let device = match dl_tensor.device.device_type {
+            DL_CPU => Some(Device::Cpu),
+            DL_CUDA => Some(Device::Cuda(dl_tensor.device.device_id as usize)),
+            _ => None,
+        };
+        let kind = dl_tensor.dtype.element_type().and_then(kind);
+        let shape = std::slice::from_raw_parts(dl_tensor.shape, dl_tensor.ndim as usize);
+        let mut strides = vec![1i64; shape.len()];
+        for i in (1..shape.len()).rev() {
+            strides[i - 1] = strides[i] * shape[i]
+        }
+        let data = dl_tensor.data as *const u8;
+        match (device, kind) {
+            (Some(device), Ok(kind)) => {
+                Tensor::f_from_blob(data, shape, &strides, kind, device).map_err(Error::from)
+            }
+            (None, _) => Err(Error::DlPack(format!("unsupported device {:?}", dl_tensor.device))),
+            (_, Err(err)) => Err(err),
+        }
+    };
+    match view {
+        Ok(tensor) => Ok(TensorView { tensor: std::mem::ManuallyDrop::new(tensor), dlpack }),
+        Err(err) => {
+            if let Some(deleter) = unsafe { (*dlpack).deleter } {
+                unsafe { deleter(dlpack) }
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Create a tch tensor on `device` from an array literal.
+pub fn tensor_from_literal(literal: &Literal, device: Device) -> Result<Tensor> {
+    let shape = literal.array_shape()?;
+    let kind = kind(shape.ty())?;
+    let tensor = Tensor::f_from_data_size(&literal.untyped_data(), shape.dims(), kind)?;
+    Ok(tensor.f_to_device(device)?)
+}
+
+/// Copy a device buffer back to the host and create a tch tensor on `device` from it.
+pub fn tensor_from_buffer(buffer: &PjRtBuffer, device: Device) -> Result<Tensor> {
+    tensor_from_literal(&buffer.to_literal_sync()?, device)
+}
@@ -0,0 +1,185 @@
+//! Randomized testing of computations against reference implementations.
+//!
+//! A [`Generator`] produces random shapes and literals from a seed so that failures can be
+//! reproduced. With the `ndarray` feature, a [`Checker`] runs a computation built from some
+//! random inputs and compares its output with a reference closure over `ndarray` arrays:
+//!
+//! ```ignore
+//! let client = xla::PjRtClient::cpu()?;
+//! let mut checker = xla::testing::Checker::new(&client, 42);
+//! checker.unary::<f32, _, _>(|x| x.exp(), |x| x.mapv(f32::exp))?;
+//! checker.binary::<f32, _, _>(|x, y| x.add_(y), |x, y| x + y)?;
+//! ```
+use crate::{ElementType, Error, Literal, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// A seeded generator of random shapes and literals.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rng: StdRng,
+    /// The maximum number of dimensions of the generated shapes.
+    pub max_rank: usize,
+    /// The maximum size of each dimension, dimensions have a size of at least one.
+    pub max_dim: usize,
+    /// The generated values are sampled uniformly from `[lo, hi)`, they are rounded down for
+    /// integer types and negative values are skipped for unsigned types.
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), max_rank: 3, max_dim: 5, lo: -10., hi: 10. }
+    }
+
+    /// Random dimensions with up to `max_rank` dimensions.
+    pub fn dims(&mut self) -> Vec<usize> {
+        let rank = self.rng.gen_range(0..=self.max_rank);
+        (0..rank).map(|_| self.rng.gen_range(1..=self.max_dim.max(1))).collect()
+    }
+
+    /// One of the `candidates` element types, picked at random.
+    pub fn element_type(&mut self, candidates: &[ElementType]) -> Result<ElementType> {
+        if candidates.is_empty() {
+            Err(Error::EmptyOpList { op: "element_type" })?
+        }
+        Ok(candidates[self.rng.gen_range(0..candidates.len())])
+    }
+
+    /// A literal with random values.
+    pub fn literal(&mut self, ty: ElementType, dims: &[usize]) -> Result<Literal> {
+        let element_count = dims.iter().product::<usize>();
+        let lo = match ty {
+            ElementType::U8 | ElementType::U16 | ElementType::U32 | ElementType::U64 => {
+                self.lo.max(0.)
+            }
+            _ => self.lo,
+        };
+        let hi = self.hi.max(lo);
+        let values: Vec<f64> = (0..element_count)
+            .map(|_| {
+                let v = lo + (hi - lo) * self.rng.gen::<f64>();
+                match ty {
+                    ElementType::Pred => self.rng.gen_range(0..2) as f64,
+                    ElementType::F16
+                    | ElementType::Bf16
+                    | ElementType::F32
+                    | ElementType::F64
+                    | ElementType::C64
+                    | ElementType::C128 => v,
+                    _ => v.floor(),
+                }
+            })
+            .collect();
+        let dims: Vec<i64> = dims.iter().map(|d| *d as i64).collect();
+        let literal = Literal::vec1(&values).reshape(&dims)?;
+        if ty == ElementType::F64 {
+            Ok(literal)
+        } else {
+            literal.convert(ty.primitive_type())
+        }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+pub use reference::{array_to_literal, literal_to_array, Checker};
+
+#[cfg(feature = "ndarray")]
+mod reference {
+    use super::Generator;
+    use crate::{ArrayElement, Error, Literal, PjRtClient, Result, XlaBuilder, XlaOp};
+    use ndarray::{ArrayD, IxDyn};
+
+    /// Copy a literal to an `ndarray` array, the element type of the literal must be `T`.
+    pub fn literal_to_array<T: ArrayElement>(literal: &Literal) -> Result<ArrayD<T>> {
+        let shape = literal.array_shape()?;
+        let dims: Vec<usize> = shape.dims().iter().map(|d| *d as usize).collect();
+        let array = ArrayD::from_shape_vec(IxDyn(&dims), literal.to_vec::<T>()?)?;
+        Ok(array)
+    }
+
+    /// Copy an `ndarray` array to a literal.
+    pub fn array_to_literal<T: ArrayElement>(array: &ArrayD<T>) -> Result<Literal> {
+        let mut literal = Literal::create_from_shape(T::TY.primitive_type(), array.shape())?;
+        // Iterating over the array yields the elements in row major order whatever its layout.
+        let data: Vec<T> = array.iter().copied().collect();
+        literal.copy_raw_from(&data)?;
+        Ok(literal)
+    }
+
+    /// Compares computations run on a client with reference implementations on random inputs.
+    /// The inputs of each case share the same random dimensions.
+    pub struct Checker<'a> {
+        client: &'a PjRtClient,
+        pub generator: Generator,
+        /// The number of random cases that are checked.
+        pub cases: usize,
+        /// The tolerances used to compare the outputs, see [`Literal::approx_eq`].
+        pub rtol: f64,
+        pub atol: f64,
+    }
+
+    impl<'a> Checker<'a> {
+        pub fn new(client: &'a PjRtClient, seed: u64) -> Self {
+            Self { client, generator: Generator::new(seed), cases: 32, rtol: 1e-5, atol: 1e-6 }
+        }
+
+        /// Check `op` against `reference` on `arity` random inputs of type `T`. The first failing
+        /// case is reported as an [`Error::ReferenceMismatch`] with its inputs.
+        pub fn check<T, F, R>(&mut self, arity: usize, op: F, reference: R) -> Result<()>
+        where
+            T: ArrayElement + std::fmt::Debug,
+            F: Fn(&[XlaOp]) -> Result<XlaOp>,
+            R: Fn(&[ArrayD<T>]) -> ArrayD<T>,
+        {
+            for case in 0..self.cases {
+                let dims = self.generator.dims();
+                let inputs = (0..arity)
+                    .map(|_| self.generator.literal(T::TY, &dims))
+                    .collect::<Result<Vec<_>>>()?;
+                let builder = XlaBuilder::new("check");
+                let dims_i64: Vec<i64> = dims.iter().map(|d| *d as i64).collect();
+                let params = (0..arity)
+                    .map(|i| builder.parameter(i as i64, T::TY, &dims_i64, &format!("x{i}")))
+                    .collect::<Result<Vec<_>>>()?;
+                let computation = builder.build(&op(&params)?)?;
+                let result = self.client.compile(&computation)?.execute::<Literal>(&inputs)?;
+                let result = result[0][0].to_literal_sync()?;
+                let arrays =
+                    inputs.iter().map(literal_to_array::<T>).collect::<Result<Vec<_>>>()?;
+                let expected = reference(&arrays);
+                if !result.approx_eq(&array_to_literal(&expected)?, self.rtol, self.atol)? {
+                    let msg = format!(
+                        "inputs: {:?}, expected: {:?}, got: {:?} with shape {:?}",
+                        arrays,
+                        expected,
+                        literal_to_array::<T>(&result).ok(),
+                        result.shape().ok(),
+                    );
+                    Err(Error::ReferenceMismatch { case, msg })?
+                }
+            }
+            Ok(())
+        }
+
+        /// Check an element-wise unary op, see [`Checker::check`].
+        pub fn unary<T, F, R>(&mut self, op: F, reference: R) -> Result<()>
+        where
+            T: ArrayElement + std::fmt::Debug,
+            F: Fn(&XlaOp) -> Result<XlaOp>,
+            R: Fn(&ArrayD<T>) -> ArrayD<T>,
+        {
+            self.check(1, |xs| op(&xs[0]), |xs: &[ArrayD<T>]| reference(&xs[0]))
+        }
+
+        /// Check an element-wise binary op, see [`Checker::check`].
+        pub fn binary<T, F, R>(&mut self, op: F, reference: R) -> Result<()>
+        where
+            T: ArrayElement + std::fmt::Debug,
+            F: Fn(&XlaOp, &XlaOp) -> Result<XlaOp>,
+            R: Fn(&ArrayD<T>, &ArrayD<T>) -> ArrayD<T>,
+        {
+            self.check(2, |xs| op(&xs[0], &xs[1]), |xs: &[ArrayD<T>]| reference(&xs[0], &xs[1]))
+        }
+    }
+}
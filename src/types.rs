@@ -0,0 +1,319 @@
+//! The element types and shapes used by XLA.
+//!
+//! These types do not depend on the XLA C++ library so this module is also available when the
+//! `ffi` feature is disabled, e.g. to share shapes and element types in schema definitions
+//! without linking XLA. The crate still depends on `std` in this configuration, the error type
+//! implements `std::error::Error` and wraps some `std` errors, so it cannot be used in `no_std`
+//! crates.
+use crate::{Error, Result};
+use num_derive::FromPrimitive;
+
+/// The primitive types supported by XLA. `S8` is a signed 1 byte integer,
+/// `U32` is an unsigned 4 bytes integer, etc.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, FromPrimitive)]
+pub enum PrimitiveType {
+    Invalid = 0,
+    Pred = 1,
+    S8 = 2,
+    S16 = 3,
+    S32 = 4,
+    S64 = 5,
+    U8 = 6,
+    U16 = 7,
+    U32 = 8,
+    U64 = 9,
+    F16 = 10,
+    F32 = 11,
+    Bf16 = 16,
+    F64 = 12,
+    C64 = 15,
+    C128 = 18,
+    Tuple = 13,
+    OpaqueType = 14,
+    Token = 17,
+}
+
+impl PrimitiveType {
+    #[cfg_attr(not(any(feature = "ffi", feature = "stub")), allow(dead_code))]
+    pub(crate) fn element_type(self) -> Result<ElementType> {
+        match self {
+            Self::Pred => Ok(ElementType::Pred),
+            Self::S8 => Ok(ElementType::S8),
+            Self::S16 => Ok(ElementType::S16),
+            Self::S32 => Ok(ElementType::S32),
+            Self::S64 => Ok(ElementType::S64),
+            Self::U8 => Ok(ElementType::U8),
+            Self::U16 => Ok(ElementType::U16),
+            Self::U32 => Ok(ElementType::U32),
+            Self::U64 => Ok(ElementType::U64),
+            Self::F16 => Ok(ElementType::F16),
+            Self::F32 => Ok(ElementType::F32),
+            Self::Bf16 => Ok(ElementType::Bf16),
+            Self::F64 => Ok(ElementType::F64),
+            Self::C64 => Ok(ElementType::C64),
+            Self::C128 => Ok(ElementType::C128),
+            Self::Invalid | Self::Tuple | Self::OpaqueType | Self::Token => {
+                Err(Error::NotAnElementType { got: self })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ElementType {
+    Pred,
+    S8,
+    S16,
+    S32,
+    S64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F16,
+    F32,
+    Bf16,
+    F64,
+    C64,
+    C128,
+}
+
+impl ElementType {
+    /// The size for this element type in bytes.
+    pub fn element_size_in_bytes(&self) -> usize {
+        match self {
+            Self::Pred => 1,
+            Self::S8 => 1,
+            Self::S16 => 2,
+            Self::S32 => 4,
+            Self::S64 => 8,
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 => 4,
+            Self::U64 => 8,
+            Self::F16 => 2,
+            Self::F32 => 4,
+            Self::Bf16 => 2,
+            Self::F64 => 8,
+            Self::C64 => 8,
+            Self::C128 => 16,
+        }
+    }
+
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            Self::Pred => PrimitiveType::Pred,
+            Self::S8 => PrimitiveType::S8,
+            Self::S16 => PrimitiveType::S16,
+            Self::S32 => PrimitiveType::S32,
+            Self::S64 => PrimitiveType::S64,
+            Self::U8 => PrimitiveType::U8,
+            Self::U16 => PrimitiveType::U16,
+            Self::U32 => PrimitiveType::U32,
+            Self::U64 => PrimitiveType::U64,
+            Self::F16 => PrimitiveType::F16,
+            Self::F32 => PrimitiveType::F32,
+            Self::Bf16 => PrimitiveType::Bf16,
+            Self::F64 => PrimitiveType::F64,
+            Self::C64 => PrimitiveType::C64,
+            Self::C128 => PrimitiveType::C128,
+        }
+    }
+}
+
+pub trait ArrayElement: Copy {
+    const TY: ElementType;
+    const ELEMENT_SIZE_IN_BYTES: usize;
+    const ZERO: Self;
+}
+
+macro_rules! element_type {
+    ($ty:ty, $v:ident, $sz:tt) => {
+        impl ArrayElement for $ty {
+            const TY: ElementType = ElementType::$v;
+            const ELEMENT_SIZE_IN_BYTES: usize = $sz;
+            const ZERO: Self = 0 as Self;
+        }
+    };
+}
+
+// Dummy F16 type.
+#[derive(Copy, Clone, Debug)]
+pub struct F16;
+
+impl ArrayElement for F16 {
+    const TY: ElementType = ElementType::F16;
+    const ELEMENT_SIZE_IN_BYTES: usize = 2;
+    const ZERO: Self = Self;
+}
+
+// Dummy BF16 type.
+#[derive(Copy, Clone, Debug)]
+pub struct Bf16;
+
+impl ArrayElement for Bf16 {
+    const TY: ElementType = ElementType::Bf16;
+    const ELEMENT_SIZE_IN_BYTES: usize = 2;
+    const ZERO: Self = Self;
+}
+
+element_type!(u8, U8, 1);
+element_type!(u16, U16, 2);
+element_type!(u32, U32, 4);
+element_type!(u64, U64, 8);
+element_type!(i8, S8, 1);
+element_type!(i16, S16, 2);
+element_type!(i32, S32, 4);
+element_type!(i64, S64, 8);
+element_type!(f32, F32, 4);
+element_type!(f64, F64, 8);
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ArrayShape {
+    pub(crate) ty: ElementType,
+    pub(crate) dims: Vec<i64>,
+}
+
+impl ArrayShape {
+    /// Create a new array shape.
+    pub fn new<E: ArrayElement>(dims: Vec<i64>) -> Self {
+        Self { ty: E::TY, dims }
+    }
+
+    /// Create a new array shape.
+    pub fn new_with_type(ty: ElementType, dims: Vec<i64>) -> Self {
+        Self { ty, dims }
+    }
+
+    pub fn element_type(&self) -> ElementType {
+        self.ty
+    }
+
+    pub fn ty(&self) -> ElementType {
+        self.ty
+    }
+
+    /// The stored primitive type.
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.ty.primitive_type()
+    }
+
+    /// The number of elements stored in arrays that use this shape, this is the product of sizes
+    /// across each dimension.
+    pub fn element_count(&self) -> usize {
+        self.dims.iter().map(|d| *d as usize).product::<usize>()
+    }
+
+    pub fn dims(&self) -> &[i64] {
+        &self.dims
+    }
+
+    pub fn first_dim(&self) -> Option<i64> {
+        self.dims.first().copied()
+    }
+
+    pub fn last_dim(&self) -> Option<i64> {
+        self.dims.last().copied()
+    }
+}
+
+/// A shape specifies a primitive type as well as some array dimensions.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Shape {
+    Tuple(Vec<Shape>),
+    Array(ArrayShape),
+    Unsupported(PrimitiveType),
+}
+
+impl Shape {
+    /// Create a new array shape.
+    pub fn array<E: ArrayElement>(dims: Vec<i64>) -> Self {
+        Self::Array(ArrayShape { ty: E::TY, dims })
+    }
+
+    /// Create a new array shape.
+    pub fn array_with_type(ty: ElementType, dims: Vec<i64>) -> Self {
+        Self::Array(ArrayShape { ty, dims })
+    }
+
+    /// Create a new tuple shape.
+    pub fn tuple(shapes: Vec<Self>) -> Self {
+        Self::Tuple(shapes)
+    }
+
+    /// The stored primitive type.
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            Self::Tuple(_) => PrimitiveType::Tuple,
+            Self::Array(a) => a.ty.primitive_type(),
+            Self::Unsupported(ty) => *ty,
+        }
+    }
+
+    pub fn is_tuple(&self) -> bool {
+        match self {
+            Self::Tuple(_) => true,
+            Self::Array { .. } | Self::Unsupported(_) => false,
+        }
+    }
+
+    pub fn tuple_size(&self) -> Option<usize> {
+        match self {
+            Self::Tuple(shapes) => Some(shapes.len()),
+            Self::Array { .. } | Self::Unsupported(_) => None,
+        }
+    }
+}
+
+impl TryFrom<&Shape> for ArrayShape {
+    type Error = Error;
+
+    fn try_from(value: &Shape) -> Result<Self> {
+        match value {
+            Shape::Tuple(_) | Shape::Unsupported(_) => {
+                Err(Error::NotAnArray { expected: None, got: value.clone() })
+            }
+            Shape::Array(a) => Ok(a.clone()),
+        }
+    }
+}
+
+macro_rules! extract_dims {
+    ($cnt:tt, $dims:expr, $out_type:ty) => {
+        #[allow(clippy::redundant_closure_call)]
+        impl TryFrom<&ArrayShape> for $out_type {
+            type Error = Error;
+
+            fn try_from(value: &ArrayShape) -> Result<Self> {
+                if value.dims.len() != $cnt {
+                    Err(Error::UnexpectedNumberOfDims {
+                        expected: $cnt,
+                        got: value.dims.len(),
+                        dims: value.dims.clone(),
+                    })
+                } else {
+                    Ok($dims(&value.dims))
+                }
+            }
+        }
+
+        impl TryFrom<&Shape> for $out_type {
+            type Error = Error;
+
+            fn try_from(value: &Shape) -> Result<Self> {
+                match value {
+                    Shape::Tuple(_) | Shape::Unsupported(_) => {
+                        Err(Error::NotAnArray { expected: Some($cnt), got: value.clone() })
+                    }
+                    Shape::Array(a) => Self::try_from(a),
+                }
+            }
+        }
+    };
+}
+
+extract_dims!(1, |d: &Vec<i64>| d[0], i64);
+extract_dims!(2, |d: &Vec<i64>| (d[0], d[1]), (i64, i64));
+extract_dims!(3, |d: &Vec<i64>| (d[0], d[1], d[2]), (i64, i64, i64));
+extract_dims!(4, |d: &Vec<i64>| (d[0], d[1], d[2], d[3]), (i64, i64, i64, i64));
+extract_dims!(5, |d: &Vec<i64>| (d[0], d[1], d[2], d[3], d[4]), (i64, i64, i64, i64, i64));
@@ -0,0 +1,150 @@
+//! Host to device transfers split in multiple chunks.
+//!
+//! Arrays are split along their leading dimension so that each chunk holds at most a given
+//! number of bytes, or a single row if the rows are larger than that. The chunks are assembled
+//! on the device using small helper computations that are compiled for each transfer, this
+//! bounds the memory used for staging on the host and lets the caller track progress. On upload,
+//! the destination buffer is donated to each update so that it is updated in place rather than
+//! copied for every chunk.
+use super::{
+    AliasKind, ArrayShape, ElementType, InputOutputAlias, Literal, PjRtBuffer, PjRtClient,
+    XlaBuilder, XlaComputation, XlaOp,
+};
+use crate::Result;
+use std::collections::HashMap;
+
+struct Chunks {
+    ty: ElementType,
+    dims: Vec<usize>,
+    row_bytes: usize,
+    rows_per_chunk: usize,
+}
+
+impl Chunks {
+    fn new(shape: &ArrayShape, chunk_bytes: usize) -> Self {
+        let dims: Vec<usize> = shape.dims().iter().map(|d| *d as usize).collect();
+        let row_bytes = dims.iter().skip(1).product::<usize>() * shape.ty().element_size_in_bytes();
+        let rows_per_chunk = usize::max(1, chunk_bytes / usize::max(1, row_bytes));
+        Self { ty: shape.ty(), dims, row_bytes, rows_per_chunk }
+    }
+
+    // The start row and the number of rows for each chunk.
+    fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.dims[0])
+            .step_by(self.rows_per_chunk)
+            .map(|start| (start, usize::min(self.rows_per_chunk, self.dims[0] - start)))
+    }
+
+    fn dims_i64(&self, rows: usize) -> Vec<i64> {
+        let mut dims: Vec<i64> = self.dims.iter().map(|d| *d as i64).collect();
+        dims[0] = rows as i64;
+        dims
+    }
+
+    fn start_indices(&self, builder: &XlaBuilder, index: XlaOp) -> Result<Vec<XlaOp>> {
+        let mut start_indices = vec![index];
+        for _ in 1..self.dims.len() {
+            start_indices.push(builder.zero(ElementType::S64)?)
+        }
+        Ok(start_indices)
+    }
+}
+
+impl PjRtClient {
+    /// Transfer a literal to the default device in chunks of at most `chunk_bytes` bytes.
+    /// `progress` is called after each chunk with the number of bytes transferred so far and the
+    /// total number of bytes.
+    pub fn buffer_from_host_literal_chunked<F: FnMut(usize, usize)>(
+        &self,
+        literal: &Literal,
+        chunk_bytes: usize,
+        mut progress: F,
+    ) -> Result<PjRtBuffer> {
+        let total_bytes = literal.size_bytes();
+        let shape = literal.array_shape()?;
+        if shape.dims().is_empty() || total_bytes <= chunk_bytes {
+            let buffer = self.buffer_from_host_literal(None, literal)?;
+            progress(total_bytes, total_bytes);
+            return Ok(buffer);
+        }
+        let chunks = Chunks::new(&shape, chunk_bytes);
+        let mut buffer = {
+            let builder = XlaBuilder::new("chunked_upload_init");
+            let zeros = builder.zero(chunks.ty)?.broadcast(&chunks.dims_i64(chunks.dims[0]))?;
+            let exe = self.compile(&zeros.build()?)?;
+            exe.execute::<Literal>(&[])?.remove(0).remove(0)
+        };
+        let mut update_exes = HashMap::new();
+        let data = literal.raw_bytes();
+        for (start, rows) in chunks.iter() {
+            if !update_exes.contains_key(&rows) {
+                let builder = XlaBuilder::new("chunked_upload_update");
+                let dst =
+                    builder.parameter(0, chunks.ty, &chunks.dims_i64(chunks.dims[0]), "dst")?;
+                let update = builder.parameter(1, chunks.ty, &chunks.dims_i64(rows), "update")?;
+                let index = builder.parameter(2, ElementType::S64, &[], "index")?;
+                let start_indices = chunks.start_indices(&builder, index)?;
+                let op = dst.dynamic_update_slice(&update, &start_indices)?;
+                // Alias the output with the destination so that the destination is donated.
+                let mut proto = op.build()?.proto();
+                proto.set_input_output_aliases(&[InputOutputAlias {
+                    output_index: vec![],
+                    parameter_number: 0,
+                    parameter_index: vec![],
+                    kind: AliasKind::Must,
+                }]);
+                let computation = XlaComputation::from_proto(&proto);
+                update_exes.insert(rows, self.compile(&computation)?);
+            }
+            let mut chunk_dims = chunks.dims.clone();
+            chunk_dims[0] = rows;
+            let bytes = &data[start * chunks.row_bytes..(start + rows) * chunks.row_bytes];
+            let update = self.buffer_from_host_raw_bytes(chunks.ty, bytes, &chunk_dims, None)?;
+            let index = self.buffer_from_host_buffer(&[start as i64], &[], None)?;
+            buffer = update_exes[&rows].execute_b(&[&buffer, &update, &index])?.remove(0).remove(0);
+            progress((start + rows) * chunks.row_bytes, total_bytes);
+        }
+        Ok(buffer)
+    }
+}
+
+impl PjRtBuffer {
+    /// Copy the buffer back to the host as a literal, in chunks of at most `chunk_bytes` bytes.
+    /// `progress` is called after each chunk with the number of bytes transferred so far and the
+    /// total number of bytes.
+    pub fn to_literal_sync_chunked<F: FnMut(usize, usize)>(
+        &self,
+        chunk_bytes: usize,
+        mut progress: F,
+    ) -> Result<Literal> {
+        let shape = ArrayShape::try_from(&self.on_device_shape()?)?;
+        let total_bytes = shape.element_count() * shape.ty().element_size_in_bytes();
+        if shape.dims().is_empty() || total_bytes <= chunk_bytes {
+            let literal = self.to_literal_sync()?;
+            progress(total_bytes, total_bytes);
+            return Ok(literal);
+        }
+        let chunks = Chunks::new(&shape, chunk_bytes);
+        let mut literal = Literal::create_from_shape(chunks.ty.primitive_type(), &chunks.dims)?;
+        let mut slice_exes = HashMap::new();
+        for (start, rows) in chunks.iter() {
+            if !slice_exes.contains_key(&rows) {
+                let builder = XlaBuilder::new("chunked_download_slice");
+                let src =
+                    builder.parameter(0, chunks.ty, &chunks.dims_i64(chunks.dims[0]), "src")?;
+                let index = builder.parameter(1, ElementType::S64, &[], "index")?;
+                let start_indices = chunks.start_indices(&builder, index)?;
+                let op = src.dynamic_slice(&start_indices, &chunks.dims_i64(rows))?;
+                slice_exes.insert(rows, self.client.compile(&op.build()?)?);
+            }
+            let index = self.client.buffer_from_host_buffer(&[start as i64], &[], None)?;
+            let chunk = slice_exes[&rows].execute_b(&[self, &index])?.remove(0).remove(0);
+            let chunk = chunk.to_literal_sync()?;
+            let offset = start * chunks.row_bytes;
+            literal.raw_bytes_mut()[offset..offset + rows * chunks.row_bytes]
+                .copy_from_slice(chunk.raw_bytes());
+            progress(offset + rows * chunks.row_bytes, total_bytes);
+        }
+        Ok(literal)
+    }
+}
@@ -0,0 +1,131 @@
+//! Configuration types shared by the collective ops, e.g. [`super::XlaOp::all_reduce`].
+use crate::{Error, Result};
+
+/// A group of replicas taking part together in a collective op. When a channel handle is used,
+/// the ids are interpreted as partition ids instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaGroup(Vec<i64>);
+
+impl ReplicaGroup {
+    /// A group holding the replicas with the specified ids, the ids must be non-negative and
+    /// distinct.
+    pub fn new(ids: Vec<i64>) -> Result<Self> {
+        if ids.is_empty() {
+            Err(Error::InvalidCollective { msg: "empty replica group".to_string() })?
+        }
+        for (i, id) in ids.iter().enumerate() {
+            if *id < 0 {
+                Err(Error::InvalidCollective { msg: format!("negative replica id {id}") })?
+            }
+            if ids[..i].contains(id) {
+                Err(Error::InvalidCollective { msg: format!("duplicate replica id {id}") })?
+            }
+        }
+        Ok(Self(ids))
+    }
+
+    pub fn ids(&self) -> &[i64] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The channel used by a cross-partition collective op. Each collective op in a computation
+/// should use a distinct handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelHandle(i64);
+
+impl ChannelHandle {
+    /// A channel handle, `handle` has to be strictly positive.
+    pub fn new(handle: i64) -> Result<Self> {
+        if handle <= 0 {
+            Err(Error::InvalidCollective { msg: format!("invalid channel handle {handle}") })?
+        }
+        Ok(Self(handle))
+    }
+
+    pub fn handle(&self) -> i64 {
+        self.0
+    }
+}
+
+/// The replica groups used by a collective op. The groups are disjoint and all have the same
+/// size. An empty list means that all the replicas form a single group.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CollectiveDeviceList(Vec<ReplicaGroup>);
+
+impl CollectiveDeviceList {
+    /// A single group with all the replicas.
+    pub fn all() -> Self {
+        Self(vec![])
+    }
+
+    /// A list made of some groups, the groups have to be disjoint and of the same size.
+    pub fn new(groups: Vec<ReplicaGroup>) -> Result<Self> {
+        if let Some(first) = groups.first() {
+            if let Some(group) = groups.iter().find(|g| g.len() != first.len()) {
+                Err(Error::InvalidCollective {
+                    msg: format!(
+                        "replica groups of different sizes {} and {}",
+                        first.len(),
+                        group.len()
+                    ),
+                })?
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        for id in groups.iter().flat_map(|g| g.ids()) {
+            if !seen.insert(*id) {
+                Err(Error::InvalidCollective {
+                    msg: format!("replica {id} is in multiple groups"),
+                })?
+            }
+        }
+        Ok(Self(groups))
+    }
+
+    /// `num_groups` groups of `group_size` consecutive replicas, e.g. `[[0, 1], [2, 3]]` for two
+    /// groups of size two.
+    pub fn iota(num_groups: usize, group_size: usize) -> Result<Self> {
+        let groups = (0..num_groups)
+            .map(|g| {
+                let start = (g * group_size) as i64;
+                ReplicaGroup::new((start..start + group_size as i64).collect())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(groups)
+    }
+
+    pub fn groups(&self) -> &[ReplicaGroup] {
+        &self.0
+    }
+
+    /// The number of replicas in each group, `None` when all the replicas form a single group.
+    pub fn group_size(&self) -> Option<usize> {
+        self.0.first().map(|g| g.len())
+    }
+
+    // Check that the groups, if specified, have `count` replicas each.
+    pub(super) fn check_group_size(&self, count: usize) -> Result<()> {
+        match self.group_size() {
+            Some(size) if size != count => Err(Error::InvalidCollective {
+                msg: format!("expected groups of {count} replicas, got {size}"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // The flattened replica ids and the size of each group, as used by the C api.
+    pub(super) fn to_c(&self) -> (Vec<i64>, Vec<usize>) {
+        let ids = self.0.iter().flat_map(|g| g.ids().iter().copied()).collect();
+        let sizes = self.0.iter().map(|g| g.len()).collect();
+        (ids, sizes)
+    }
+}
@@ -0,0 +1,309 @@
+//! Loops and conditionals with Rust closure bodies.
+//!
+//! These helpers build the computations used by [`XlaOp::while_`] and by the conditional ops in
+//! their own sub-builders. The loop state and the branch operands are lists of nodes, they get
+//! packed in a tuple when crossing the computation boundaries and unpacked before being passed
+//! to the closures.
+use super::{ArrayShape, ElementType, Shape, XlaBuilder, XlaComputation, XlaOp};
+use crate::{Error, Result};
+
+// Create the state parameter of a loop computation and split it in its elements.
+fn state_parameter(builder: &XlaBuilder, shapes: &[Shape]) -> Result<Vec<XlaOp>> {
+    let state = builder.parameter_s(0, &Shape::tuple(shapes.to_vec()), "state")?;
+    (0..shapes.len()).map(|i| state.get_tuple_element(i as i64)).collect()
+}
+
+// The state produced by a loop body must have the same shapes as the initial state.
+fn check_state(builder: &XlaBuilder, shapes: &[Shape], state: &[XlaOp]) -> Result<()> {
+    if state.len() != shapes.len() {
+        Err(Error::UnexpectedNumberOfElemsInTuple { expected: shapes.len(), got: state.len() })?
+    }
+    for (expected, op) in shapes.iter().zip(state.iter()) {
+        let got = builder.get_shape(op)?;
+        if &got != expected {
+            Err(Error::ShapeMismatch { expected: expected.clone(), got })?
+        }
+    }
+    Ok(())
+}
+
+/// A closure used as a conditional branch, see [`XlaBuilder::switch`].
+pub type Branch<'a> = &'a dyn Fn(&XlaBuilder, &[XlaOp]) -> Result<Vec<XlaOp>>;
+
+// Build the branches of a conditional, all the branches must return values with the same shapes.
+// This also returns the number of values returned by each branch.
+fn branch_computations(
+    shapes: &[Shape],
+    branches: &[Branch],
+) -> Result<(Vec<XlaComputation>, usize)> {
+    let mut result_shapes: Option<Vec<Shape>> = None;
+    let mut computations = Vec::with_capacity(branches.len());
+    for (i, branch) in branches.iter().enumerate() {
+        let builder = XlaBuilder::new(&format!("branch_{i}"));
+        let operands = state_parameter(&builder, shapes)?;
+        let results = branch(&builder, &operands)?;
+        match &result_shapes {
+            None => {
+                let shapes =
+                    results.iter().map(|op| builder.get_shape(op)).collect::<Result<_>>()?;
+                result_shapes = Some(shapes)
+            }
+            Some(shapes) => check_state(&builder, shapes, &results)?,
+        }
+        computations.push(builder.build(&builder.tuple(results.as_slice())?)?);
+    }
+    Ok((computations, result_shapes.map_or(0, |shapes| shapes.len())))
+}
+
+// Check that `op` is a scalar of the given type.
+fn check_scalar(op: &XlaOp, ty: ElementType) -> Result<()> {
+    let expected = Shape::array_with_type(ty, vec![]);
+    let got = op.builder().get_shape(op)?;
+    if got != expected {
+        Err(Error::ShapeMismatch { expected, got })?
+    }
+    Ok(())
+}
+
+impl XlaBuilder {
+    /// Run `body` as long as `cond` returns true, starting from the `init` state and returning
+    /// the final state.
+    ///
+    /// Both closures are called once with the builder of their computation and the state
+    /// elements. `cond` has to return a scalar `PRED` node and `body` the next state, which must
+    /// have the same number of elements and the same shapes as `init`. The closures can only use
+    /// nodes created from the builder that they are given.
+    pub fn while_loop<C, B>(&self, cond: C, body: B, init: &[XlaOp]) -> Result<Vec<XlaOp>>
+    where
+        C: FnOnce(&XlaBuilder, &[XlaOp]) -> Result<XlaOp>,
+        B: FnOnce(&XlaBuilder, &[XlaOp]) -> Result<Vec<XlaOp>>,
+    {
+        let shapes = init.iter().map(|op| self.get_shape(op)).collect::<Result<Vec<_>>>()?;
+        let cond = {
+            let builder = XlaBuilder::new("while_cond");
+            let state = state_parameter(&builder, &shapes)?;
+            let pred = cond(&builder, &state)?;
+            builder.check_op(&pred)?;
+            check_scalar(&pred, ElementType::Pred)?;
+            builder.build(&pred)?
+        };
+        let body = {
+            let builder = XlaBuilder::new("while_body");
+            let state = state_parameter(&builder, &shapes)?;
+            let state = body(&builder, &state)?;
+            check_state(&builder, &shapes, &state)?;
+            builder.build(&builder.tuple(state.as_slice())?)?
+        };
+        let state = XlaOp::while_(cond, body, self.tuple(init)?)?;
+        (0..shapes.len()).map(|i| state.get_tuple_element(i as i64)).collect()
+    }
+
+    /// Run `body` for each index `i` from `start` (included) to `end` (excluded), starting from
+    /// the `init` state and returning the final state.
+    ///
+    /// `start` and `end` are scalar integer nodes of the same type. `body` is called once with
+    /// the builder of the loop body, the index node and the state elements, it has to return the
+    /// next state with the same number of elements and the same shapes as `init`.
+    pub fn fori_loop<B>(
+        &self,
+        start: &XlaOp,
+        end: &XlaOp,
+        init: &[XlaOp],
+        body: B,
+    ) -> Result<Vec<XlaOp>>
+    where
+        B: FnOnce(&XlaBuilder, &XlaOp, &[XlaOp]) -> Result<Vec<XlaOp>>,
+    {
+        let index_shape = self.get_shape(start)?;
+        let array_shape = ArrayShape::try_from(&index_shape)?;
+        if !array_shape.dims().is_empty() {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 0,
+                got: array_shape.dims().len(),
+                dims: array_shape.dims().to_vec(),
+            })?
+        }
+        let ty = array_shape.ty();
+        match ty {
+            ElementType::S8
+            | ElementType::S16
+            | ElementType::S32
+            | ElementType::S64
+            | ElementType::U8
+            | ElementType::U16
+            | ElementType::U32
+            | ElementType::U64 => {}
+            _ => Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "fori_loop" })?,
+        }
+        let end_shape = self.get_shape(end)?;
+        if end_shape != index_shape {
+            Err(Error::ShapeMismatch { expected: index_shape, got: end_shape })?
+        }
+        let shapes = init.iter().map(|op| self.get_shape(op)).collect::<Result<Vec<_>>>()?;
+
+        // The index and the end bound are carried in front of the user state.
+        let mut state = vec![start.clone(), end.clone()];
+        state.extend(init.iter().cloned());
+        let state = self.while_loop(
+            |_, state| state[0].lt(&state[1]),
+            |builder, state| {
+                let user_state = body(builder, &state[0], &state[2..])?;
+                check_state(builder, &shapes, &user_state)?;
+                let mut next = vec![state[0].add_(&builder.one(ty)?)?, state[1].clone()];
+                next.extend(user_state);
+                Ok(next)
+            },
+            &state,
+        )?;
+        Ok(state[2..].to_vec())
+    }
+
+    /// Loop over the leading dimension of the `xs` nodes, similar to `jax.lax.scan`.
+    ///
+    /// For each index `i` along the leading dimension, `body` is given the carry and the `i`-th
+    /// slices of the `xs` nodes, with the leading dimension removed. It returns the next carry,
+    /// which must have the same shapes as `init`, and some outputs `ys`. This returns the final
+    /// carry and the outputs of all the iterations stacked along a new leading dimension.
+    ///
+    /// `body` is called twice: once on a separate builder to get the shapes of the outputs and
+    /// once to build the loop body.
+    pub fn scan<B>(&self, init: &[XlaOp], xs: &[XlaOp], body: B) -> Result<(Vec<XlaOp>, Vec<XlaOp>)>
+    where
+        B: Fn(&XlaBuilder, &[XlaOp], &[XlaOp]) -> Result<(Vec<XlaOp>, Vec<XlaOp>)>,
+    {
+        let carry_shapes = init.iter().map(|op| self.get_shape(op)).collect::<Result<Vec<_>>>()?;
+        let x_shapes = xs.iter().map(|x| x.array_shape()).collect::<Result<Vec<_>>>()?;
+        let len = match x_shapes.first() {
+            None => Err(Error::EmptyOpList { op: "scan" })?,
+            Some(shape) => match shape.dims().first() {
+                None => Err(Error::InvalidDims {
+                    dims: vec![],
+                    msg: "scan inputs must have a leading dimension",
+                })?,
+                Some(len) => *len,
+            },
+        };
+        if let Some(shape) = x_shapes.iter().find(|s| s.dims().first() != Some(&len)) {
+            Err(Error::InvalidDims {
+                dims: shape.dims().to_vec(),
+                msg: "scan inputs must have the same leading dimension",
+            })?
+        }
+        let slice_shapes: Vec<Shape> = x_shapes
+            .iter()
+            .map(|s| Shape::array_with_type(s.ty(), s.dims()[1..].to_vec()))
+            .collect();
+
+        // Build the body once on its own to get the shapes of the outputs.
+        let y_shapes = {
+            let builder = XlaBuilder::new("scan_shapes");
+            let params = carry_shapes
+                .iter()
+                .chain(slice_shapes.iter())
+                .enumerate()
+                .map(|(i, shape)| builder.parameter_s(i as i64, shape, "state"))
+                .collect::<Result<Vec<_>>>()?;
+            let (carry, ys) = body(&builder, &params[..init.len()], &params[init.len()..])?;
+            check_state(&builder, &carry_shapes, &carry)?;
+            ys.iter()
+                .map(|y| ArrayShape::try_from(&builder.get_shape(y)?))
+                .collect::<Result<Vec<_>>>()?
+        };
+        let mut state = init.to_vec();
+        state.extend(xs.iter().cloned());
+        for shape in y_shapes.iter() {
+            let mut dims = vec![len];
+            dims.extend_from_slice(shape.dims());
+            state.push(self.zero(shape.ty())?.broadcast(&dims)?);
+        }
+
+        let (n_carry, n_xs) = (init.len(), xs.len());
+        let state =
+            self.fori_loop(&self.c0(0i64)?, &self.c0(len)?, &state, |builder, i, state| {
+                let (carry, rest) = state.split_at(n_carry);
+                let (xs, ys) = rest.split_at(n_xs);
+                let zero = builder.c0(0i64)?;
+                // Start indices for slicing along the leading dimension of a node of rank `rank`.
+                let start = |rank: usize| {
+                    let mut start = vec![i.clone()];
+                    start.resize(rank, zero.clone());
+                    start
+                };
+                let x = xs
+                    .iter()
+                    .zip(x_shapes.iter())
+                    .map(|(x, shape)| {
+                        let mut sizes = shape.dims().to_vec();
+                        sizes[0] = 1;
+                        x.dynamic_slice(&start(sizes.len()), &sizes)?.reshape(&shape.dims()[1..])
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let (mut next, y) = body(builder, carry, &x)?;
+                if y.len() != ys.len() {
+                    Err(Error::UnexpectedNumberOfElemsInTuple { expected: ys.len(), got: y.len() })?
+                }
+                next.extend(xs.iter().cloned());
+                for ((acc, y), shape) in ys.iter().zip(y.iter()).zip(y_shapes.iter()) {
+                    let mut dims = vec![1];
+                    dims.extend_from_slice(shape.dims());
+                    let y = y.reshape(&dims)?;
+                    next.push(acc.dynamic_update_slice(&y, &start(dims.len()))?);
+                }
+                Ok(next)
+            })?;
+        let ys = state[n_carry + n_xs..].to_vec();
+        let mut carry = state;
+        carry.truncate(n_carry);
+        Ok((carry, ys))
+    }
+
+    /// Run `on_true` if `pred` is true and `on_false` otherwise, returning the results of the
+    /// branch that has been run. `pred` has to be a scalar `PRED` node.
+    ///
+    /// Both closures are called once with the builder of their computation and the `operands`
+    /// nodes. They must return the same number of nodes with the same shapes.
+    pub fn cond<T, F>(
+        &self,
+        pred: &XlaOp,
+        on_true: T,
+        on_false: F,
+        operands: &[XlaOp],
+    ) -> Result<Vec<XlaOp>>
+    where
+        T: Fn(&XlaBuilder, &[XlaOp]) -> Result<Vec<XlaOp>>,
+        F: Fn(&XlaBuilder, &[XlaOp]) -> Result<Vec<XlaOp>>,
+    {
+        self.check_op(pred)?;
+        check_scalar(pred, ElementType::Pred)?;
+        let shapes = operands.iter().map(|op| self.get_shape(op)).collect::<Result<Vec<_>>>()?;
+        let (mut computations, count) = branch_computations(&shapes, &[&on_true, &on_false])?;
+        let false_comp = computations.remove(1);
+        let true_comp = computations.remove(0);
+        let operands = self.tuple(operands)?;
+        let results = pred.conditional(operands.clone(), true_comp, operands, false_comp)?;
+        (0..count).map(|i| results.get_tuple_element(i as i64)).collect()
+    }
+
+    /// Run `branches[index]` and return its results, the last branch is run when `index` is out
+    /// of bounds. `index` has to be a scalar `S32` node.
+    ///
+    /// Each branch is called once with the builder of its computation and the `operands` nodes.
+    /// All the branches must return the same number of nodes with the same shapes.
+    pub fn switch(
+        &self,
+        index: &XlaOp,
+        branches: &[Branch],
+        operands: &[XlaOp],
+    ) -> Result<Vec<XlaOp>> {
+        if branches.is_empty() {
+            Err(Error::EmptyOpList { op: "switch" })?
+        }
+        self.check_op(index)?;
+        check_scalar(index, ElementType::S32)?;
+        let shapes = operands.iter().map(|op| self.get_shape(op)).collect::<Result<Vec<_>>>()?;
+        let (computations, count) = branch_computations(&shapes, branches)?;
+        let operands = vec![self.tuple(operands)?; computations.len()];
+        let results = index.indexed_conditional(&computations, &operands)?;
+        (0..count).map(|i| results.get_tuple_element(i as i64)).collect()
+    }
+}
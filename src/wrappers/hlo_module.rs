@@ -0,0 +1,197 @@
+//! Module level metadata of a [`HloModuleProto`], these can be adjusted before compiling the
+//! module, e.g. by export pipelines.
+use super::{shape::CShape, HloModuleProto, Shape};
+use crate::{c_lib, Error, Result};
+use std::collections::HashMap;
+
+/// The shapes of the parameters and of the result of a computation as seen by the host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramShape {
+    pub parameters: Vec<Shape>,
+    /// The parameter names, empty names are used for unnamed parameters.
+    pub parameter_names: Vec<String>,
+    pub result: Shape,
+}
+
+/// Whether an output is guaranteed to reuse the buffer of the aliased parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AliasKind {
+    /// The buffer is only reused when the parameter is donated at runtime.
+    May = 1,
+    /// The buffer is always reused, the parameter has to be donated.
+    Must = 2,
+}
+
+/// A part of the output that reuses the buffer of a part of a parameter, the indexes are tuple
+/// indexes with the empty index referring to the whole value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputOutputAlias {
+    pub output_index: Vec<i64>,
+    pub parameter_number: i64,
+    pub parameter_index: Vec<i64>,
+    pub kind: AliasKind,
+}
+
+// Take ownership of an array allocated with malloc by the C api.
+pub(super) unsafe fn c_array_to_vec<T: Copy>(ptr: *mut T, len: usize) -> Vec<T> {
+    // Allocating zero bytes may return a null pointer.
+    let vec = if len == 0 { vec![] } else { std::slice::from_raw_parts(ptr, len).to_vec() };
+    libc::free(ptr as *mut libc::c_void);
+    vec
+}
+
+impl HloModuleProto {
+    /// The name of the computation that is run when executing the module.
+    pub fn entry_computation_name(&self) -> String {
+        unsafe { super::c_ptr_to_string(c_lib::hlo_module_proto_entry_computation_name(self.0)) }
+    }
+
+    /// The id of the computation that is run when executing the module.
+    pub fn entry_computation_id(&self) -> i64 {
+        unsafe { c_lib::hlo_module_proto_entry_computation_id(self.0) }
+    }
+
+    /// Rename the entry computation.
+    pub fn set_entry_computation_name(&mut self, name: &str) -> Result<()> {
+        let name = std::ffi::CString::new(name)?;
+        unsafe { c_lib::hlo_module_proto_set_entry_computation_name(self.0, name.as_ptr()) };
+        Ok(())
+    }
+
+    /// The program shape of the entry computation as seen by the host, if set.
+    pub fn host_program_shape(&self) -> Result<Option<ProgramShape>> {
+        let mut parameters: c_lib::shape = std::ptr::null_mut();
+        let mut result: c_lib::shape = std::ptr::null_mut();
+        let mut names: *mut *mut std::ffi::c_char = std::ptr::null_mut();
+        let has_shape = unsafe {
+            c_lib::hlo_module_proto_host_program_shape(
+                self.0,
+                &mut parameters,
+                &mut result,
+                &mut names,
+            )
+        };
+        if !has_shape {
+            return Ok(None);
+        }
+        let (parameters, result) = (CShape::from_ptr(parameters), CShape::from_ptr(result));
+        let parameters = match parameters.shape()? {
+            Shape::Tuple(parameters) => parameters,
+            shape => Err(Error::NotATuple { got: shape })?,
+        };
+        let result = result.shape()?;
+        // There is one name per parameter.
+        let parameter_names = unsafe {
+            let parameter_names =
+                (0..parameters.len()).map(|i| super::c_ptr_to_string(*names.add(i))).collect();
+            libc::free(names as *mut libc::c_void);
+            parameter_names
+        };
+        Ok(Some(ProgramShape { parameters, parameter_names, result }))
+    }
+
+    /// Replace the program shape of the entry computation as seen by the host.
+    pub fn set_host_program_shape(&mut self, program_shape: &ProgramShape) -> Result<()> {
+        let ProgramShape { parameters, parameter_names, result } = program_shape;
+        if parameters.len() != parameter_names.len() {
+            Err(Error::WrongArgumentCount {
+                expected: parameters.len(),
+                got: parameter_names.len(),
+            })?
+        }
+        let parameters = parameters.iter().map(|s| s.c_shape()).collect::<Result<Vec<_>>>()?;
+        let parameter_ptrs: Vec<_> = parameters.iter().map(|s| s.as_ptr()).collect();
+        let names = parameter_names
+            .iter()
+            .map(|name| std::ffi::CString::new(name.as_str()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let name_ptrs: Vec<_> = names.iter().map(|name| name.as_ptr()).collect();
+        let result = result.c_shape()?;
+        unsafe {
+            c_lib::hlo_module_proto_set_host_program_shape(
+                self.0,
+                parameter_ptrs.as_ptr(),
+                name_ptrs.as_ptr() as *mut *const std::ffi::c_char,
+                parameter_ptrs.len(),
+                result.as_ptr(),
+            )
+        };
+        Ok(())
+    }
+
+    /// The outputs of the entry computation that reuse the buffers of some parameters.
+    pub fn input_output_aliases(&self) -> Vec<InputOutputAlias> {
+        let count = unsafe { c_lib::hlo_module_proto_alias_count(self.0) };
+        (0..count)
+            .map(|i| {
+                let mut output_index = std::ptr::null_mut();
+                let mut noutput_index = 0;
+                let mut parameter_number = 0;
+                let mut parameter_index = std::ptr::null_mut();
+                let mut nparameter_index = 0;
+                let mut kind = 0;
+                unsafe {
+                    c_lib::hlo_module_proto_alias(
+                        self.0,
+                        i,
+                        &mut output_index,
+                        &mut noutput_index,
+                        &mut parameter_number,
+                        &mut parameter_index,
+                        &mut nparameter_index,
+                        &mut kind,
+                    );
+                    InputOutputAlias {
+                        output_index: c_array_to_vec(output_index, noutput_index),
+                        parameter_number,
+                        parameter_index: c_array_to_vec(parameter_index, nparameter_index),
+                        // XLA treats the entries without a kind as must-alias ones.
+                        kind: if kind == AliasKind::May as i32 {
+                            AliasKind::May
+                        } else {
+                            AliasKind::Must
+                        },
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the input/output aliases of the entry computation.
+    pub fn set_input_output_aliases(&mut self, aliases: &[InputOutputAlias]) {
+        unsafe { c_lib::hlo_module_proto_clear_aliases(self.0) };
+        for alias in aliases.iter() {
+            unsafe {
+                c_lib::hlo_module_proto_add_alias(
+                    self.0,
+                    alias.output_index.as_ptr(),
+                    alias.output_index.len(),
+                    alias.parameter_number,
+                    alias.parameter_index.as_ptr(),
+                    alias.parameter_index.len(),
+                    alias.kind as i32,
+                )
+            }
+        }
+    }
+
+    /// The frontend attributes attached to the module, these are forwarded to the compiler.
+    pub fn frontend_attributes(&self) -> HashMap<String, String> {
+        let mut keys = std::ptr::null_mut();
+        let mut values = std::ptr::null_mut();
+        unsafe {
+            let n = c_lib::hlo_module_proto_frontend_attributes(self.0, &mut keys, &mut values);
+            super::c_attributes_to_map(n, keys, values)
+        }
+    }
+
+    /// Set a frontend attribute of the module, replacing any previous value for `key`.
+    pub fn set_frontend_attribute(&mut self, key: &str, value: &str) -> Result<()> {
+        let key = std::ffi::CString::new(key)?;
+        let value = std::ffi::CString::new(value)?;
+        unsafe {
+            c_lib::hlo_module_proto_set_frontend_attribute(self.0, key.as_ptr(), value.as_ptr())
+        };
+        Ok(())
+    }
+}
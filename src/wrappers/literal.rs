@@ -1,11 +1,61 @@
+use super::pinned_host_buffer::{host_buffer_allocator, HOST_BUFFER_ALIGNMENT};
+use super::HostBufferAllocator;
 use super::{
     ArrayElement, ArrayShape, ElementType, FromPrimitive, NativeType, PrimitiveType, Shape,
 };
+use crate::mesh::{Mesh, PartitionSpec};
 use crate::{c_lib, Error, Result};
 
+// Literals larger than this are copied and converted using multiple threads when the `rayon`
+// feature is enabled.
+#[cfg(feature = "rayon")]
+const PARALLEL_THRESHOLD_BYTES: usize = 1 << 24;
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK_BYTES: usize = 1 << 20;
+
+#[cfg(feature = "rayon")]
+fn par_copy(dst: &mut [u8], src: &[u8]) {
+    use rayon::prelude::*;
+    dst.par_chunks_mut(PARALLEL_CHUNK_BYTES)
+        .zip(src.par_chunks(PARALLEL_CHUNK_BYTES))
+        .for_each(|(dst, src)| dst.copy_from_slice(src))
+}
+
+#[cfg(feature = "rayon")]
+fn as_bytes<T: ArrayElement>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+// The allocator and the allocation size of the storage of a literal created by
+// `Literal::create_from_shape_in`.
+struct LiteralStorage {
+    allocator: &'static dyn HostBufferAllocator,
+    size: usize,
+}
+
+unsafe extern "C" fn free_literal_storage(ctx: *mut libc::c_void, data: *mut libc::c_void) {
+    let storage = Box::from_raw(ctx as *mut LiteralStorage);
+    storage.allocator.deallocate(data as *mut u8, storage.size, HOST_BUFFER_ALIGNMENT)
+}
+
 /// A literal represent a value, typically a multi-dimensional array, stored on the host device.
 pub struct Literal(pub(super) c_lib::literal);
 
+// The size of an array literal, dimensions which product overflows are rejected.
+fn checked_size_in_bytes(ty: ElementType, dims: &[usize]) -> Result<usize> {
+    let size_in_bytes = dims
+        .iter()
+        .try_fold(ty.element_size_in_bytes(), |acc, d| acc.checked_mul(*d))
+        .filter(|size| *size <= isize::MAX as usize);
+    match size_in_bytes {
+        Some(size) => Ok(size),
+        None => Err(Error::InvalidDims {
+            dims: dims.iter().map(|d| *d as i64).collect(),
+            msg: "the literal size does not fit in memory",
+        }),
+    }
+}
+
 impl Clone for Literal {
     fn clone(&self) -> Self {
         let v = unsafe { c_lib::literal_clone(self.0) };
@@ -14,11 +64,46 @@ impl Clone for Literal {
 }
 
 impl Literal {
-    /// Create an unitialized literal based on some primitive type and some dimensions.
-    pub fn create_from_shape(ty: PrimitiveType, dims: &[usize]) -> Self {
+    /// Create a zero-initialized array literal based on some primitive type and some dimensions.
+    /// An error is returned if the type is not an array element type or if the literal would not
+    /// fit in memory.
+    pub fn create_from_shape(ty: PrimitiveType, dims: &[usize]) -> Result<Self> {
+        checked_size_in_bytes(ty.element_type()?, dims)?;
         let dims: Vec<_> = dims.iter().map(|x| *x as i64).collect();
-        let v = unsafe { c_lib::literal_create_from_shape(ty as i32, dims.as_ptr(), dims.len()) };
-        Self(v)
+        let mut v = std::ptr::null_mut();
+        let status = unsafe {
+            c_lib::literal_create_from_shape(ty as i32, dims.as_ptr(), dims.len(), &mut v)
+        };
+        super::handle_status(status)?;
+        Ok(Self(v))
+    }
+
+    /// Create a zero-initialized array literal which storage comes from `allocator`, the storage
+    /// is released through the same allocator when the literal is dropped.
+    pub fn create_from_shape_in(
+        ty: ElementType,
+        dims: &[usize],
+        allocator: &'static dyn HostBufferAllocator,
+    ) -> Result<Self> {
+        let size = checked_size_in_bytes(ty, dims)?.max(1);
+        let data = allocator.allocate(size, HOST_BUFFER_ALIGNMENT);
+        if data.is_null() {
+            Err(Error::HostAllocationFailed { len: size })?
+        }
+        unsafe { std::ptr::write_bytes(data, 0, size) };
+        let storage = Box::into_raw(Box::new(LiteralStorage { allocator, size }));
+        let dims: Vec<_> = dims.iter().map(|x| *x as i64).collect();
+        let v = unsafe {
+            c_lib::literal_create_from_shape_in(
+                ty.primitive_type() as i32,
+                dims.as_ptr(),
+                dims.len(),
+                data as *mut libc::c_void,
+                Some(free_literal_storage),
+                storage as *mut libc::c_void,
+            )
+        };
+        Ok(Self(v))
     }
 
     /// Create an unitialized literal based on some primitive type, some dimensions, and some data.
@@ -29,7 +114,22 @@ impl Literal {
         dims: &[usize],
         untyped_data: &[u8],
     ) -> Result<Self> {
+        // Check the size upfront so that no overflowing dimensions get passed to the C++ library.
+        let size_in_bytes =
+            dims.iter().try_fold(ty.element_size_in_bytes(), |acc, d| acc.checked_mul(*d));
         let dims64: Vec<_> = dims.iter().map(|x| *x as i64).collect();
+        if size_in_bytes != Some(untyped_data.len()) {
+            Err(Error::CannotCreateLiteralWithData {
+                data_len_in_bytes: untyped_data.len(),
+                ty: ty.primitive_type(),
+                dims: dims.to_vec(),
+            })?
+        }
+        if let Some(allocator) = host_buffer_allocator() {
+            let mut literal = Self::create_from_shape_in(ty, dims, allocator)?;
+            literal.raw_bytes_mut().copy_from_slice(untyped_data);
+            return Ok(literal);
+        }
         let ty = ty.primitive_type();
         let v = unsafe {
             c_lib::literal_create_from_shape_and_data(
@@ -64,6 +164,19 @@ impl Literal {
         Ok(v)
     }
 
+    /// The value of a scalar literal. Contrary to [`Literal::get_first_element`], this returns an
+    /// error if the literal has some dimensions, even when it only holds a single element.
+    pub fn to_scalar<T: ArrayElement>(&self) -> Result<T> {
+        let shape = self.array_shape()?;
+        if !shape.dims().is_empty() {
+            let dims = shape.dims().to_vec();
+            Err(Error::UnexpectedNumberOfDims { expected: 0, got: dims.len(), dims })?
+        }
+        let mut value = [T::ZERO];
+        self.copy_raw_to(&mut value)?;
+        Ok(value[0])
+    }
+
     /// The number of elements stored in the literal.
     pub fn element_count(&self) -> usize {
         unsafe { c_lib::literal_element_count(self.0) as usize }
@@ -118,6 +231,13 @@ impl Literal {
         if dst.len() > element_count {
             Err(Error::BinaryBufferIsTooLarge { element_count, buffer_len: dst.len() })?
         }
+        #[cfg(feature = "rayon")]
+        if std::mem::size_of_val(dst) >= PARALLEL_THRESHOLD_BYTES {
+            let len = std::mem::size_of_val(dst);
+            let dst = unsafe { std::slice::from_raw_parts_mut(dst.as_mut_ptr() as *mut u8, len) };
+            par_copy(dst, &self.raw_bytes()[..len]);
+            return Ok(());
+        }
         unsafe {
             c_lib::literal_copy_to(
                 self.0,
@@ -128,6 +248,109 @@ impl Literal {
         Ok(())
     }
 
+    /// A view on the literal data as raw bytes.
+    pub(super) fn raw_bytes(&self) -> &[u8] {
+        unsafe {
+            let ptr = c_lib::literal_untyped_data(self.0) as *const u8;
+            std::slice::from_raw_parts(ptr, self.size_bytes())
+        }
+    }
+
+    /// A mutable view on the literal data as raw bytes.
+    pub(crate) fn raw_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            let ptr = c_lib::literal_untyped_data(self.0) as *mut u8;
+            std::slice::from_raw_parts_mut(ptr, self.size_bytes())
+        }
+    }
+
+    /// Copy the literal data to a newly created vector of bytes, whatever the primitive type used
+    /// by the literal.
+    pub fn untyped_data(&self) -> Vec<u8> {
+        let size_bytes = self.size_bytes();
+        let mut data = vec![0u8; size_bytes];
+        unsafe {
+            c_lib::literal_copy_to(self.0, data.as_mut_ptr() as *mut libc::c_void, size_bytes)
+        };
+        data
+    }
+
+    /// An array literal holding the raw bytes `bytes` reinterpreted as values of type `ty`, in
+    /// row major order and with the native endianness. The length of `bytes` has to match the
+    /// element type and dimensions.
+    pub fn from_bytes(ty: ElementType, dims: &[usize], bytes: &[u8]) -> Result<Self> {
+        Self::create_from_shape_and_untyped_data(ty, dims, bytes)
+    }
+
+    /// The raw bytes of an array literal without any copy, see [`Literal::from_bytes`]. This
+    /// returns an error for tuple literals.
+    pub fn as_bytes(&self) -> Result<&[u8]> {
+        self.array_shape()?;
+        Ok(self.array_bytes())
+    }
+
+    // The raw bytes of an array literal, without any copy.
+    fn array_bytes(&self) -> &[u8] {
+        let size_bytes = self.size_bytes();
+        if size_bytes == 0 {
+            return &[];
+        }
+        unsafe {
+            let data = c_lib::literal_untyped_data(self.0) as *const u8;
+            std::slice::from_raw_parts(data, size_bytes)
+        }
+    }
+
+    // The values of an array literal, without any copy.
+    pub(super) fn typed_data<T: ArrayElement>(&self) -> Result<&[T]> {
+        let ty = self.array_shape()?.ty();
+        if ty != T::TY {
+            Err(Error::ElementTypeMismatch { on_device: ty, on_host: T::TY })?
+        }
+        let bytes = self.array_bytes();
+        if bytes.is_empty() {
+            return Ok(&[]);
+        }
+        // XLA allocates the literal data with an alignment that suits all the element types, this
+        // is still checked as a misaligned slice would be undefined behavior.
+        let data = bytes.as_ptr() as *const T;
+        let align = std::mem::align_of::<T>();
+        if data.align_offset(align) != 0 {
+            Err(Error::MisalignedData { ty, align })?
+        }
+        Ok(unsafe { std::slice::from_raw_parts(data, bytes.len() / T::ELEMENT_SIZE_IN_BYTES) })
+    }
+
+    /// Whether both literals have the same shape and element-wise close values, i.e. for each
+    /// pair of elements `|a - b| <= atol + rtol * |b|`. The values are compared as `F64`, NaN
+    /// values are never close to any value. Complex literals are not supported.
+    pub fn approx_eq(&self, other: &Literal, rtol: f64, atol: f64) -> Result<bool> {
+        let shape = self.shape()?;
+        if shape != other.shape()? {
+            return Ok(false);
+        }
+        match shape {
+            Shape::Tuple(shapes) => {
+                for i in 0..shapes.len() {
+                    if !self.tuple_element(i)?.approx_eq(&other.tuple_element(i)?, rtol, atol)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Shape::Array(shape) if !matches!(shape.ty(), ElementType::C64 | ElementType::C128) => {
+                let lhs = self.convert(PrimitiveType::F64)?.to_vec::<f64>()?;
+                let rhs = other.convert(PrimitiveType::F64)?.to_vec::<f64>()?;
+                let close = |(a, b): (&f64, &f64)| a == b || (a - b).abs() <= atol + rtol * b.abs();
+                Ok(lhs.iter().zip(rhs.iter()).all(close))
+            }
+            Shape::Array(shape) => {
+                Err(Error::UnsupportedElementType { ty: shape.primitive_type(), op: "approx_eq" })
+            }
+            Shape::Unsupported(ty) => Err(Error::UnsupportedElementType { ty, op: "approx_eq" }),
+        }
+    }
+
     /// Copy data from a slice to the literal. This returns an error if the primitive type used
     /// by the literal is not `T` or if number of elements in the slice and the literal are
     /// different.
@@ -140,6 +363,12 @@ impl Literal {
         if src.len() > element_count {
             Err(Error::BinaryBufferIsTooLarge { element_count, buffer_len: src.len() })?
         }
+        #[cfg(feature = "rayon")]
+        if std::mem::size_of_val(src) >= PARALLEL_THRESHOLD_BYTES {
+            let src = as_bytes(src);
+            par_copy(&mut self.raw_bytes_mut()[..src.len()], src);
+            return Ok(());
+        }
         unsafe {
             c_lib::literal_copy_from(
                 self.0,
@@ -170,6 +399,14 @@ impl Literal {
     /// Create a literal from a slice of data, the resulting literal has one dimension which size
     /// is the same as the slice passed as argument.
     pub fn vec1<T: NativeType>(f: &[T]) -> Self {
+        #[cfg(feature = "rayon")]
+        if std::mem::size_of_val(f) >= PARALLEL_THRESHOLD_BYTES {
+            // The slice already fits in memory so creating the literal cannot fail.
+            if let Ok(mut literal) = Self::create_from_shape(T::TY.primitive_type(), &[f.len()]) {
+                par_copy(literal.raw_bytes_mut(), as_bytes(f));
+                return literal;
+            }
+        }
         let ptr = unsafe { T::create_r1(f.as_ptr(), f.len()) };
         Literal(ptr)
     }
@@ -178,6 +415,7 @@ impl Literal {
     /// error if the number of elements in the literal is different from the product of the target
     /// dimension sizes.
     pub fn reshape(&self, dims: &[i64]) -> Result<Literal> {
+        super::check_static_dims(dims)?;
         let mut result: c_lib::literal = std::ptr::null_mut();
         let status =
             unsafe { c_lib::literal_reshape(self.0, dims.as_ptr(), dims.len(), &mut result) };
@@ -185,19 +423,160 @@ impl Literal {
         Ok(Literal(result))
     }
 
+    /// Split an array literal in the shards held by the devices of `mesh` when partitioned with
+    /// `spec`, see [`Mesh::shards`]. Each shard is returned with the id of the device holding it,
+    /// in the order of [`Mesh::device_ids`].
+    pub fn shard(&self, mesh: &Mesh, spec: &PartitionSpec) -> Result<Vec<(usize, Literal)>> {
+        let shape = self.array_shape()?;
+        let dims: Vec<usize> = shape.dims().iter().map(|d| *d as usize).collect();
+        let size = shape.ty().element_size_in_bytes();
+        let src = self.raw_bytes();
+        mesh.shards(spec, &dims)?
+            .into_iter()
+            .map(|shard| {
+                let mut literal = Literal::create_from_shape(shape.primitive_type(), &shard.dims)?;
+                let dst = literal.raw_bytes_mut();
+                for (offset, shard_offset, len) in shard.runs(&dims) {
+                    dst[shard_offset * size..(shard_offset + len) * size]
+                        .copy_from_slice(&src[offset * size..(offset + len) * size])
+                }
+                Ok((shard.device_id, literal))
+            })
+            .collect()
+    }
+
+    /// Assemble the shards produced by [`Literal::shard`] back into a single literal. There must
+    /// be one shard per device of `mesh`, in the order of [`Mesh::device_ids`], and all the shards
+    /// must have the same element type and dimensions. The shards that are replicas of each other
+    /// must hold the same values.
+    pub fn unshard(shards: &[(usize, Literal)], mesh: &Mesh, spec: &PartitionSpec) -> Result<Self> {
+        let ids: Vec<usize> = shards.iter().map(|(id, _)| *id).collect();
+        if ids != mesh.device_ids() {
+            Err(Error::InvalidMesh {
+                msg: format!("expected shards for devices {:?}, got {ids:?}", mesh.device_ids()),
+            })?
+        }
+        let shard_shape = shards[0].1.array_shape()?;
+        for (_, shard) in shards.iter().skip(1) {
+            let shape = shard.array_shape()?;
+            if shape != shard_shape {
+                Err(Error::ShapeMismatch {
+                    expected: Shape::Array(shard_shape.clone()),
+                    got: Shape::Array(shape),
+                })?
+            }
+        }
+        let tile_dims = mesh.tile_dims(spec)?;
+        if tile_dims.len() != shard_shape.dims().len() {
+            Err(Error::InvalidMesh {
+                msg: format!(
+                    "partition spec of rank {} for shards of dims {:?}",
+                    spec.rank(),
+                    shard_shape.dims()
+                ),
+            })?
+        }
+        let dims: Vec<usize> =
+            shard_shape.dims().iter().zip(tile_dims.iter()).map(|(d, t)| *d as usize * t).collect();
+        let size = shard_shape.ty().element_size_in_bytes();
+        let mut result = Literal::create_from_shape(shard_shape.primitive_type(), &dims)?;
+        let dst = result.raw_bytes_mut();
+        let mut written = std::collections::HashSet::new();
+        for (shard, (_, literal)) in mesh.shards(spec, &dims)?.iter().zip(shards.iter()) {
+            let src = literal.raw_bytes();
+            let replica = !written.insert(shard.start.clone());
+            for (offset, shard_offset, len) in shard.runs(&dims) {
+                let dst = &mut dst[offset * size..(offset + len) * size];
+                let src = &src[shard_offset * size..(shard_offset + len) * size];
+                if !replica {
+                    dst.copy_from_slice(src)
+                } else if dst != src {
+                    Err(Error::ShardMismatch { start: shard.start.clone() })?
+                }
+            }
+        }
+        Ok(result)
+    }
+
     /// Create a new literal containing the data from the original literal casted to a new
     /// primitive type. The dimensions of the resulting literal are the same as the dimensions of
     /// the original literal.
     pub fn convert(&self, ty: PrimitiveType) -> Result<Literal> {
+        #[cfg(feature = "rayon")]
+        if self.size_bytes() >= PARALLEL_THRESHOLD_BYTES {
+            if let Ok(shape) = self.array_shape() {
+                return self.par_convert(&shape, ty);
+            }
+        }
         let mut result: c_lib::literal = std::ptr::null_mut();
         let status = unsafe { c_lib::literal_convert(self.0, ty as i32, &mut result) };
         super::handle_status(status)?;
         Ok(Literal(result))
     }
 
-    /// When the input is a tuple, return a vector of its elements. This replaces the original
-    /// value by an empty tuple, no copy is performed.
-    pub fn decompose_tuple(&mut self) -> Result<Vec<Literal>> {
+    // Convert the literal by splitting it in chunks that are converted on multiple threads.
+    #[cfg(feature = "rayon")]
+    fn par_convert(&self, shape: &ArrayShape, ty: PrimitiveType) -> Result<Literal> {
+        use rayon::prelude::*;
+        let src_ty = shape.ty();
+        let src_size = src_ty.element_size_in_bytes();
+        let dst_size = ty.element_type()?.element_size_in_bytes();
+        let chunk_elements = PARALLEL_CHUNK_BYTES / src_size;
+        let dims: Vec<_> = shape.dims().iter().map(|d| *d as usize).collect();
+        let mut result = Literal::create_from_shape(ty, &dims)?;
+        self.raw_bytes()
+            .par_chunks(chunk_elements * src_size)
+            .zip(result.raw_bytes_mut().par_chunks_mut(chunk_elements * dst_size))
+            .try_for_each(|(src, dst)| {
+                let chunk = Literal::create_from_shape_and_untyped_data(
+                    src_ty,
+                    &[src.len() / src_size],
+                    src,
+                )?;
+                dst.copy_from_slice(chunk.convert(ty)?.raw_bytes());
+                Ok::<(), Error>(())
+            })?;
+        Ok(result)
+    }
+
+    fn convert_float(&self, ty: ElementType, op: &'static str) -> Result<Literal> {
+        match self.ty()? {
+            ElementType::F16 | ElementType::Bf16 | ElementType::F32 | ElementType::F64 => {
+                self.convert(ty.primitive_type())
+            }
+            ty => Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op }),
+        }
+    }
+
+    /// Convert a floating point literal to `Bf16`, rounding to the nearest value. The conversion
+    /// runs on the host, no device is involved.
+    pub fn to_bf16(&self) -> Result<Literal> {
+        self.convert_float(ElementType::Bf16, "to_bf16")
+    }
+
+    /// Convert a floating point literal to `F16`, rounding to the nearest value. Values that are
+    /// out of the `F16` range become infinite. The conversion runs on the host, no device is
+    /// involved.
+    pub fn to_f16(&self) -> Result<Literal> {
+        self.convert_float(ElementType::F16, "to_f16")
+    }
+
+    /// Convert a `F16`, `Bf16`, or `F32` literal to `F32`, this conversion is exact. The
+    /// conversion runs on the host, no device is involved.
+    pub fn upcast_to_f32(&self) -> Result<Literal> {
+        match self.ty()? {
+            ElementType::F16 | ElementType::Bf16 | ElementType::F32 => {
+                self.convert(PrimitiveType::F32)
+            }
+            ty => {
+                Err(Error::UnsupportedElementType { ty: ty.primitive_type(), op: "upcast_to_f32" })
+            }
+        }
+    }
+
+    // When the input is a tuple, return a vector of its elements. This replaces the original
+    // value by an empty tuple so this is only used on literals that are consumed.
+    fn decompose_tuple(&mut self) -> Result<Vec<Literal>> {
         match self.shape()? {
             Shape::Array(_) | Shape::Unsupported(_) => Ok(vec![]),
             Shape::Tuple(shapes) => {
@@ -209,10 +588,31 @@ impl Literal {
         }
     }
 
-    pub fn to_tuple(mut self) -> Result<Vec<Literal>> {
+    /// When the input is a tuple, return a vector of its elements, no copy is performed. An empty
+    /// vector is returned for arrays.
+    pub fn into_tuple(mut self) -> Result<Vec<Literal>> {
         self.decompose_tuple()
     }
 
+    pub fn to_tuple(self) -> Result<Vec<Literal>> {
+        self.into_tuple()
+    }
+
+    /// A copy of the element at position `index` of a tuple literal, the original literal is left
+    /// unchanged.
+    pub fn tuple_element(&self, index: usize) -> Result<Literal> {
+        match self.shape()? {
+            Shape::Tuple(shapes) if index < shapes.len() => {
+                let v = unsafe { c_lib::literal_tuple_element(self.0, index as i64) };
+                Ok(Literal(v))
+            }
+            Shape::Tuple(shapes) => {
+                Err(Error::IndexOutOfBounds { index: index as i64, rank: shapes.len() })
+            }
+            shape => Err(Error::NotATuple { got: shape }),
+        }
+    }
+
     pub fn to_tuple1(mut self) -> Result<Self> {
         let mut tuple = self.decompose_tuple()?;
         if tuple.len() != 1 {
@@ -265,6 +665,43 @@ impl Literal {
     }
 }
 
+/// Literals are equal when they have the same shape and the same bytes, so NaN values are equal
+/// to themselves but `0.0` and `-0.0` are different. Use [`Literal::approx_eq`] to compare
+/// numerical values.
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        let shape = match (self.shape(), other.shape()) {
+            (Ok(shape), Ok(other_shape)) if shape == other_shape => shape,
+            _ => return false,
+        };
+        match shape {
+            Shape::Tuple(shapes) => {
+                (0..shapes.len()).all(|i| match (self.tuple_element(i), other.tuple_element(i)) {
+                    (Ok(lhs), Ok(rhs)) => lhs == rhs,
+                    _ => false,
+                })
+            }
+            Shape::Array(_) | Shape::Unsupported(_) => self.array_bytes() == other.array_bytes(),
+        }
+    }
+}
+
+impl Eq for Literal {}
+
+/// The hash covers the shape and the bytes of the literal, consistently with [`PartialEq`].
+impl std::hash::Hash for Literal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let shape = self.shape().ok();
+        shape.hash(state);
+        match shape {
+            Some(Shape::Tuple(shapes)) => {
+                (0..shapes.len()).for_each(|i| self.tuple_element(i).ok().hash(state))
+            }
+            _ => self.array_bytes().hash(state),
+        }
+    }
+}
+
 impl<T: NativeType> From<T> for Literal {
     fn from(f: T) -> Self {
         Literal::scalar(f)
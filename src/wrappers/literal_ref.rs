@@ -0,0 +1,92 @@
+//! Literals borrowing some memory owned by the caller.
+use super::{
+    check_static_dims, ArrayElement, ElementType, Literal, PjRtBuffer, PjRtClient, PjRtDevice,
+    XlaBuilder, XlaOp,
+};
+use crate::{c_lib, Error, Result};
+
+/// An array value whose data is borrowed from the caller rather than copied in a [`Literal`].
+/// This is cheap to create, e.g. from a stack array in a hot loop, and can be uploaded to a
+/// device or embedded as a constant without an intermediate copy of the data. The borrow checker
+/// guarantees that the data outlives the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiteralRef<'a> {
+    ty: ElementType,
+    dims: &'a [usize],
+    data: &'a [u8],
+}
+
+impl<'a> LiteralRef<'a> {
+    /// A literal with dimensions `dims` borrowing the values from `data`, in row major order.
+    pub fn new<T: ArrayElement>(data: &'a [T], dims: &'a [usize]) -> Result<Self> {
+        let element_count: usize = dims.iter().product();
+        if element_count != data.len() {
+            Err(Error::WrongElementCount { dims: dims.to_vec(), element_count })?
+        }
+        let data = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+        };
+        Ok(Self { ty: T::TY, dims, data })
+    }
+
+    /// A literal borrowing the raw bytes `data` reinterpreted as values of type `ty`, in row
+    /// major order and with the native endianness.
+    pub fn from_bytes(ty: ElementType, dims: &'a [usize], data: &'a [u8]) -> Result<Self> {
+        let element_count: usize = dims.iter().product();
+        if element_count * ty.element_size_in_bytes() != data.len() {
+            Err(Error::WrongElementCount { dims: dims.to_vec(), element_count })?
+        }
+        Ok(Self { ty, dims, data })
+    }
+
+    /// The element type of the literal.
+    pub fn ty(&self) -> ElementType {
+        self.ty
+    }
+
+    /// The dimensions of the literal.
+    pub fn dims(&self) -> &'a [usize] {
+        self.dims
+    }
+
+    /// The borrowed data as raw bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Copy the borrowed data to an owned literal.
+    pub fn to_literal(&self) -> Result<Literal> {
+        Literal::from_bytes(self.ty, self.dims, self.data)
+    }
+}
+
+impl PjRtClient {
+    /// Transfer a borrowed literal to a device, the default device is used if `device` is `None`.
+    /// The data is read directly from the borrowed memory.
+    pub fn buffer_from_literal_ref(
+        &self,
+        device: Option<&PjRtDevice>,
+        literal: &LiteralRef<'_>,
+    ) -> Result<PjRtBuffer> {
+        self.buffer_from_host_raw_bytes(literal.ty, literal.data, literal.dims, device)
+    }
+}
+
+impl XlaBuilder {
+    /// Create a node with a constant value defined by a borrowed literal, the data is copied
+    /// once in the computation without creating an intermediate [`Literal`].
+    pub fn constant_literal_ref(&self, literal: &LiteralRef<'_>) -> Result<XlaOp> {
+        let dims: Vec<i64> = literal.dims.iter().map(|d| *d as i64).collect();
+        check_static_dims(&dims)?;
+        let op = unsafe {
+            c_lib::constant_literal_borrowed(
+                self.ptr(),
+                literal.ty.primitive_type() as i32,
+                dims.as_ptr(),
+                dims.len(),
+                literal.data.as_ptr() as *const libc::c_void,
+            )
+        };
+        self.wrap(op)
+    }
+}
@@ -1,27 +1,50 @@
 #![allow(unsafe_op_in_unsafe_fn)]
 
+mod chunked_transfer;
+mod collective;
+mod control_flow;
+mod hlo_module;
 mod literal;
+mod literal_ref;
+mod pinned_host_buffer;
 mod pjrt_buffer;
 mod pjrt_client;
 mod pjrt_device;
 mod pjrt_loaded_executable;
 mod shape;
+mod symbolic;
 mod xla_builder;
 mod xla_op;
+mod xla_state;
 
 use crate::c_lib;
 use crate::error::{Error, Result};
-use num_derive::FromPrimitive;
+use crate::types::{ArrayElement, ArrayShape, ElementType, PrimitiveType, Shape};
 use num_traits::FromPrimitive;
 
+pub use collective::{ChannelHandle, CollectiveDeviceList, ReplicaGroup};
+pub use control_flow::Branch;
+pub use hlo_module::{AliasKind, InputOutputAlias, ProgramShape};
 pub use literal::Literal;
-pub use pjrt_buffer::PjRtBuffer;
-pub use pjrt_client::PjRtClient;
+pub use literal_ref::LiteralRef;
+pub use pinned_host_buffer::{
+    clear_host_buffer_allocator, set_host_buffer_allocator, HostBufferAllocator, PinnedHostBuffer,
+};
+pub use pjrt_buffer::{CachedHostView, PjRtBuffer};
+pub(crate) use pjrt_client::live_client_count;
+pub use pjrt_client::{
+    Backend, CpuClientOptions, GpuClientOptions, NcclConfig, PjRtClient, PlatformCapabilities,
+    RuntimeInfo, DEFAULT_CUDA_PLUGIN, DEFAULT_METAL_PLUGIN, DEFAULT_ROCM_PLUGIN,
+};
 pub use pjrt_device::PjRtDevice;
-pub use pjrt_loaded_executable::PjRtLoadedExecutable;
-pub use shape::{ArrayShape, Shape};
+pub use pjrt_loaded_executable::{
+    BackendIrKind, BoundExecutable, ExecuteInput, ExecuteOptions, ExecutePriority, FusedKernel,
+    FusionReport, IntoExecuteArg, PjRtLoadedExecutable,
+};
+pub use symbolic::{Dim, SymbolSize};
 pub use xla_builder::XlaBuilder;
-pub use xla_op::XlaOp;
+pub use xla_op::{ScatterDimensionNumbers, XlaOp};
+pub use xla_state::XlaState;
 
 unsafe fn c_ptr_to_string(ptr: *const std::ffi::c_char) -> String {
     let str = std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned();
@@ -29,128 +52,84 @@ unsafe fn c_ptr_to_string(ptr: *const std::ffi::c_char) -> String {
     str
 }
 
-/// The primitive types supported by XLA. `S8` is a signed 1 byte integer,
-/// `U32` is an unsigned 4 bytes integer, etc.
-#[derive(Clone, Copy, PartialEq, Eq, Debug, FromPrimitive)]
-pub enum PrimitiveType {
-    Invalid = 0,
-    Pred = 1,
-    S8 = 2,
-    S16 = 3,
-    S32 = 4,
-    S64 = 5,
-    U8 = 6,
-    U16 = 7,
-    U32 = 8,
-    U64 = 9,
-    F16 = 10,
-    F32 = 11,
-    Bf16 = 16,
-    F64 = 12,
-    C64 = 15,
-    C128 = 18,
-    Tuple = 13,
-    OpaqueType = 14,
-    Token = 17,
+// A C string for some text only used for display purposes, e.g. names and error messages.
+// Interior nul bytes are dropped rather than reported as an error.
+fn display_c_string(s: &str) -> std::ffi::CString {
+    let bytes: Vec<u8> = s.bytes().filter(|b| *b != 0).collect();
+    std::ffi::CString::new(bytes).unwrap_or_default()
 }
 
-impl PrimitiveType {
-    fn element_type(self) -> Result<ElementType> {
-        match self {
-            Self::Pred => Ok(ElementType::Pred),
-            Self::S8 => Ok(ElementType::S8),
-            Self::S16 => Ok(ElementType::S16),
-            Self::S32 => Ok(ElementType::S32),
-            Self::S64 => Ok(ElementType::S64),
-            Self::U8 => Ok(ElementType::U8),
-            Self::U16 => Ok(ElementType::U16),
-            Self::U32 => Ok(ElementType::U32),
-            Self::U64 => Ok(ElementType::U64),
-            Self::F16 => Ok(ElementType::F16),
-            Self::F32 => Ok(ElementType::F32),
-            Self::Bf16 => Ok(ElementType::Bf16),
-            Self::F64 => Ok(ElementType::F64),
-            Self::C64 => Ok(ElementType::C64),
-            Self::C128 => Ok(ElementType::C128),
-            Self::Invalid | Self::Tuple | Self::OpaqueType | Self::Token => {
-                Err(Error::NotAnElementType { got: self })
-            }
-        }
+// Check that some dimensions can be used to build a static shape, the C++ library aborts on
+// negative dimensions rather than returning an error.
+fn check_static_dims(dims: &[i64]) -> Result<()> {
+    if dims.iter().any(|d| *d < 0) {
+        Err(Error::InvalidDims { dims: dims.to_vec(), msg: "negative dimension" })?
     }
+    Ok(())
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum ElementType {
-    Pred,
-    S8,
-    S16,
-    S32,
-    S64,
-    U8,
-    U16,
-    U32,
-    U64,
-    F16,
-    F32,
-    Bf16,
-    F64,
-    C64,
-    C128,
+// Convert the key/value string arrays returned by the C api to a map, the arrays and their
+// elements are freed.
+unsafe fn c_attributes_to_map(
+    n: usize,
+    keys: *mut *mut std::ffi::c_char,
+    values: *mut *mut std::ffi::c_char,
+) -> std::collections::HashMap<String, String> {
+    let mut map = std::collections::HashMap::with_capacity(n);
+    for i in 0..n {
+        map.insert(c_ptr_to_string(*keys.add(i)), c_ptr_to_string(*values.add(i)));
+    }
+    libc::free(keys as *mut libc::c_void);
+    libc::free(values as *mut libc::c_void);
+    map
 }
 
-impl ElementType {
-    /// The size for this element type in bytes.
-    pub fn element_size_in_bytes(&self) -> usize {
-        match self {
-            Self::Pred => 1,
-            Self::S8 => 1,
-            Self::S16 => 2,
-            Self::S32 => 4,
-            Self::S64 => 8,
-            Self::U8 => 1,
-            Self::U16 => 2,
-            Self::U32 => 4,
-            Self::U64 => 8,
-            Self::F16 => 2,
-            Self::F32 => 4,
-            Self::Bf16 => 2,
-            Self::F64 => 8,
-            Self::C64 => 8,
-            Self::C128 => 16,
-        }
-    }
+/// The algorithms that can be used by [`XlaOp::rng_bit_generator`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum RandomAlgorithm {
+    /// The backend specific default algorithm.
+    Default = 0,
+    ThreeFry = 1,
+    Philox = 2,
+}
 
-    pub fn primitive_type(&self) -> PrimitiveType {
-        match self {
-            Self::Pred => PrimitiveType::Pred,
-            Self::S8 => PrimitiveType::S8,
-            Self::S16 => PrimitiveType::S16,
-            Self::S32 => PrimitiveType::S32,
-            Self::S64 => PrimitiveType::S64,
-            Self::U8 => PrimitiveType::U8,
-            Self::U16 => PrimitiveType::U16,
-            Self::U32 => PrimitiveType::U32,
-            Self::U64 => PrimitiveType::U64,
-            Self::F16 => PrimitiveType::F16,
-            Self::F32 => PrimitiveType::F32,
-            Self::Bf16 => PrimitiveType::Bf16,
-            Self::F64 => PrimitiveType::F64,
-            Self::C64 => PrimitiveType::C64,
-            Self::C128 => PrimitiveType::C128,
-        }
-    }
+/// How [`XlaOp::max_nan`] and [`XlaOp::min_nan`] handle NaN operands.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum NanMode {
+    /// The result is NaN when any of the operands is NaN.
+    Propagate,
+    /// NaN operands are ignored, the result is only NaN when both operands are NaN.
+    Ignore,
 }
 
-pub trait ArrayElement: Copy {
-    const TY: ElementType;
-    const ELEMENT_SIZE_IN_BYTES: usize;
-    const ZERO: Self;
+/// The element-wise binary ops that can be checked with [`XlaOp::infer_binary_shape`] before
+/// being added to a graph.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum BinaryOp {
+    Add = 0,
+    Sub = 1,
+    Mul = 2,
+    Div = 3,
+    Rem = 4,
+    Max = 5,
+    Min = 6,
+    And = 7,
+    Or = 8,
+    Xor = 9,
+    Atan2 = 10,
+    Pow = 11,
+    Eq = 12,
+    Ne = 13,
+    Ge = 14,
+    Gt = 15,
+    Le = 16,
+    Lt = 17,
 }
 
 #[allow(clippy::missing_safety_doc)]
 /// A type implementing the `NativeType` trait can be directly converted to constant ops or
 /// literals.
-pub trait NativeType: Copy {
+pub trait NativeType: ArrayElement {
     unsafe fn constant_r0(b: c_lib::xla_builder, v: Self) -> c_lib::xla_op;
     unsafe fn constant_r1(b: c_lib::xla_builder, v: *const Self, l: usize) -> c_lib::xla_op;
     unsafe fn constant_r1c(b: c_lib::xla_builder, v: Self, l: usize) -> c_lib::xla_op;
@@ -278,47 +257,6 @@ native_type!(
     literal_get_first_element_double
 );
 
-macro_rules! element_type {
-    ($ty:ty, $v:ident, $sz:tt) => {
-        impl ArrayElement for $ty {
-            const TY: ElementType = ElementType::$v;
-            const ELEMENT_SIZE_IN_BYTES: usize = $sz;
-            const ZERO: Self = 0 as Self;
-        }
-    };
-}
-
-// Dummy F16 type.
-#[derive(Copy, Clone, Debug)]
-pub struct F16;
-
-impl ArrayElement for F16 {
-    const TY: ElementType = ElementType::F16;
-    const ELEMENT_SIZE_IN_BYTES: usize = 2;
-    const ZERO: Self = Self;
-}
-
-// Dummy BF16 type.
-#[derive(Copy, Clone, Debug)]
-pub struct Bf16;
-
-impl ArrayElement for Bf16 {
-    const TY: ElementType = ElementType::Bf16;
-    const ELEMENT_SIZE_IN_BYTES: usize = 2;
-    const ZERO: Self = Self;
-}
-
-element_type!(u8, U8, 1);
-element_type!(u16, U16, 2);
-element_type!(u32, U32, 4);
-element_type!(u64, U64, 8);
-element_type!(i8, S8, 1);
-element_type!(i16, S16, 2);
-element_type!(i32, S32, 4);
-element_type!(i64, S64, 8);
-element_type!(f32, F32, 4);
-element_type!(f64, F64, 8);
-
 /// A computation is built from a root [`XlaOp`]. Computations are device independent and can be
 /// specialized to a given device through a compilation step.
 pub struct XlaComputation(c_lib::xla_computation);
@@ -333,8 +271,7 @@ fn handle_status(status: c_lib::status) -> Result<()> {
             c_lib::status_free(status);
             error_message
         };
-        let backtrace = std::backtrace::Backtrace::capture().to_string();
-        Err(Error::XlaError { msg, backtrace })
+        Err(Error::XlaError { msg, backtrace: crate::error::XlaBacktrace::capture() })
     }
 }
 
@@ -344,6 +281,20 @@ impl XlaComputation {
         Self(ptr)
     }
 
+    /// A computation combining two scalars of type `ty` with `f`, e.g. the update computation of
+    /// [`XlaOp::scatter`] or the reduction of [`XlaOp::reduce`]. `f` is called once with the two
+    /// parameters of the computation and must return a node from the same builder.
+    pub fn from_scalar_fn<F>(name: &str, ty: ElementType, f: F) -> Result<Self>
+    where
+        F: FnOnce(&XlaOp, &XlaOp) -> Result<XlaOp>,
+    {
+        let builder = XlaBuilder::new(name);
+        let x = builder.parameter(0, ty, &[], "x")?;
+        let y = builder.parameter(1, ty, &[], "y")?;
+        let result = f(&x, &y)?;
+        builder.build(&result)
+    }
+
     /// The computation name.
     pub fn name(&self) -> String {
         unsafe {
@@ -362,6 +313,37 @@ impl XlaComputation {
         let ptr = unsafe { c_lib::xla_computation_proto(self.0) };
         HloModuleProto(ptr)
     }
+
+    /// A fingerprint of the computation, computed on its canonical HLO text so that it does not
+    /// depend on the instruction names. Two computations with the same fingerprint compile to
+    /// the same executable, this can be used as a cache key or to detect graph changes.
+    pub fn fingerprint(&self) -> Result<String> {
+        let mut ptr = std::ptr::null_mut();
+        let status = unsafe { c_lib::xla_computation_fingerprint(self.0, &mut ptr) };
+        handle_status(status)?;
+        Ok(unsafe { c_ptr_to_string(ptr) })
+    }
+
+    /// A simplified version of this computation, see [`HloModuleProto::simplify`].
+    pub fn simplify(&self) -> Result<Self> {
+        Ok(Self::from_proto(&self.proto().simplify()?))
+    }
+
+    /// The computation as a StableHLO module in the MLIR text format, this can be consumed by
+    /// other compilers and runtimes such as IREE.
+    pub fn to_stablehlo(&self) -> Result<String> {
+        let mut ptr = std::ptr::null_mut();
+        let status = unsafe { c_lib::xla_computation_to_stablehlo(self.0, &mut ptr) };
+        handle_status(status)?;
+        Ok(unsafe { c_ptr_to_string(ptr) })
+    }
+}
+
+impl Clone for XlaComputation {
+    /// A deep copy of the computation, obtained by round-tripping through its proto.
+    fn clone(&self) -> Self {
+        Self::from_proto(&self.proto())
+    }
 }
 
 impl Drop for XlaComputation {
@@ -370,6 +352,18 @@ impl Drop for XlaComputation {
     }
 }
 
+/// The options used by [`HloModuleProto::auto_shard`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AutoShardingOptions {
+    /// The shape of the logical device mesh, e.g. `[2, 4]` for 8 devices.
+    pub mesh_shape: Vec<i64>,
+    /// The ids of the devices in the mesh in row major order, the devices are numbered
+    /// consecutively when empty.
+    pub mesh_ids: Vec<i64>,
+    /// The memory budget per device in GB, the pass default is used when not specified.
+    pub memory_budget_gb: Option<i64>,
+}
+
 #[derive(Debug)]
 pub struct HloModuleProto(c_lib::hlo_module_proto);
 
@@ -419,6 +413,46 @@ impl HloModuleProto {
         Ok(Self(ptr))
     }
 
+    /// A smaller module computing the same values, obtained by running dead code elimination
+    /// and algebraic simplifications such as `x * 1 => x` until nothing changes anymore. The
+    /// parameters of the entry computation are kept even when they are not used.
+    pub fn simplify(&self) -> Result<Self> {
+        let mut ptr: c_lib::hlo_module_proto = std::ptr::null_mut();
+        let status = unsafe { c_lib::hlo_module_proto_simplify(self.ptr(), &mut ptr) };
+        handle_status(status)?;
+        Ok(Self(ptr))
+    }
+
+    /// Run the XLA auto-sharding pass on this module, the sharding of each instruction is
+    /// chosen for the device mesh described in `options` and attached to the instruction, see
+    /// [`HloInstructionProto::sharding`]. The module is not partitioned, this happens when
+    /// compiling it with SPMD partitioning enabled.
+    pub fn auto_shard(&self, options: &AutoShardingOptions) -> Result<Self> {
+        if options.mesh_shape.is_empty() || options.mesh_shape.iter().any(|d| *d <= 0) {
+            Err(Error::InvalidMesh { msg: format!("invalid mesh shape {:?}", options.mesh_shape) })?
+        }
+        let device_count: i64 = options.mesh_shape.iter().product();
+        if !options.mesh_ids.is_empty() && options.mesh_ids.len() as i64 != device_count {
+            Err(Error::InvalidMesh {
+                msg: format!("expected {device_count} mesh ids, got {}", options.mesh_ids.len()),
+            })?
+        }
+        let mut ptr: c_lib::hlo_module_proto = std::ptr::null_mut();
+        let status = unsafe {
+            c_lib::hlo_module_proto_auto_shard(
+                self.ptr(),
+                options.mesh_shape.as_ptr(),
+                options.mesh_shape.len(),
+                options.mesh_ids.as_ptr(),
+                options.mesh_ids.len(),
+                options.memory_budget_gb.unwrap_or(0),
+                &mut ptr,
+            )
+        };
+        handle_status(status)?;
+        Ok(Self(ptr))
+    }
+
     pub fn get_computations_size(&self) -> Result<usize> {
         let mut comps_size = 0i32;
         let status = unsafe { c_lib::hlo_computation_protos_size(self.ptr(), &mut comps_size) };
@@ -479,6 +513,23 @@ impl HloComputationProto {
         Ok(instrs_out)
     }
 
+    /// The id of the instruction whose value is returned by this computation.
+    pub fn root_id(&self) -> i64 {
+        unsafe { c_lib::hlo_computation_proto_root_id(self.ptr()) }
+    }
+
+    /// The instruction with the given id, ids are unique within a module. Together with
+    /// [`HloComputationProto::root_id`] and [`HloInstructionProto::operand_ids`], this can be
+    /// used to walk the dependency graph starting from the root.
+    pub fn instruction_by_id(&self, id: i64) -> Option<HloInstructionProto> {
+        let ptr = unsafe { c_lib::hlo_computation_proto_instruction_by_id(self.ptr(), id) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(HloInstructionProto(ptr))
+        }
+    }
+
     fn ptr(&self) -> c_lib::hlo_computation_proto {
         self.0
     }
@@ -500,6 +551,91 @@ impl HloInstructionProto {
             c_ptr_to_string(ptr)
         })
     }
+
+    /// The id of this instruction, this is unique within a module.
+    pub fn id(&self) -> i64 {
+        unsafe { c_lib::hlo_instruction_proto_id(self.0) }
+    }
+
+    /// The backend specific json config of this instruction, empty if not set.
+    pub fn backend_config(&self) -> String {
+        unsafe { c_ptr_to_string(c_lib::hlo_instruction_proto_backend_config(self.0)) }
+    }
+
+    /// The frontend attributes attached to this instruction.
+    pub fn frontend_attributes(&self) -> std::collections::HashMap<String, String> {
+        let mut keys = std::ptr::null_mut();
+        let mut values = std::ptr::null_mut();
+        unsafe {
+            let n =
+                c_lib::hlo_instruction_proto_frontend_attributes(self.0, &mut keys, &mut values);
+            c_attributes_to_map(n, keys, values)
+        }
+    }
+
+    /// The sharding attached to this instruction, if any. The devices are always listed
+    /// explicitly, including for the shardings stored in the compact iota format.
+    pub fn sharding(&self) -> Result<Option<crate::mesh::OpSharding>> {
+        use crate::mesh::{OpSharding, OpShardingType};
+        let mut has_sharding = false;
+        let mut ty = 0;
+        let mut dims = std::ptr::null_mut();
+        let mut ndims = 0;
+        let mut devices = std::ptr::null_mut();
+        let mut ndevices = 0;
+        let mut replicate_on_last_tile_dim = false;
+        let status = unsafe {
+            c_lib::hlo_instruction_proto_sharding(
+                self.0,
+                &mut has_sharding,
+                &mut ty,
+                &mut dims,
+                &mut ndims,
+                &mut devices,
+                &mut ndevices,
+                &mut replicate_on_last_tile_dim,
+            )
+        };
+        handle_status(status)?;
+        if !has_sharding {
+            return Ok(None);
+        }
+        let tile_assignment_dimensions = unsafe { hlo_module::c_array_to_vec(dims, ndims) };
+        let tile_assignment_devices = unsafe { hlo_module::c_array_to_vec(devices, ndevices) };
+        let ty = match ty {
+            0 => OpShardingType::Replicated,
+            1 => OpShardingType::Maximal,
+            3 => OpShardingType::Other,
+            ty => Err(Error::UnsupportedShardingType(ty))?,
+        };
+        Ok(Some(OpSharding {
+            ty,
+            tile_assignment_dimensions,
+            tile_assignment_devices,
+            replicate_on_last_tile_dim,
+        }))
+    }
+
+    /// The ids of the instructions used as operands by this instruction, in order.
+    pub fn operand_ids(&self) -> Vec<i64> {
+        let count = unsafe { c_lib::hlo_instruction_proto_operand_count(self.0) } as usize;
+        let mut ids = vec![0i64; count];
+        unsafe { c_lib::hlo_instruction_proto_operand_ids(self.0, ids.as_mut_ptr()) };
+        ids
+    }
+
+    /// The value of a constant instruction, e.g. some weights embedded in the module. This
+    /// returns `None` for the other instructions.
+    pub fn literal(&self) -> Result<Option<Literal>> {
+        let mut ptr: c_lib::literal = std::ptr::null_mut();
+        let status = unsafe { c_lib::hlo_instruction_proto_literal(self.0, &mut ptr) };
+        handle_status(status)?;
+        if ptr.is_null() {
+            Ok(None)
+        } else {
+            Ok(Some(Literal(ptr)))
+        }
+    }
 }
 impl Drop for HloInstructionProto {
     fn drop(&mut self) {
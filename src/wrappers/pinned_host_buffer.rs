@@ -0,0 +1,199 @@
+//! Page-locked host memory, used for faster transfers between the host and the devices.
+use super::{ArrayElement, ElementType, PjRtBuffer, PjRtClient, PjRtDevice};
+use crate::metrics::{self, TransferDirection};
+use crate::{c_lib, Error, Result};
+use std::sync::RwLock;
+
+// The alignment of the host buffers, this matches the alignment used by the C++ side.
+pub(super) const HOST_BUFFER_ALIGNMENT: usize = 64;
+
+/// An allocator for host memory, e.g. to use a jemalloc arena, huge pages, or memory pinned to
+/// a NUMA node. Once registered with [`set_host_buffer_allocator`] or via
+/// [`crate::InitOptions`], this allocator provides the storage of:
+/// - the host buffers returned by [`PjRtClient::allocate_host_buffer`] on clients that do not
+///   allocate page-locked memory, page-locked memory is always allocated by the client,
+/// - the array literals created by [`super::Literal::create_from_shape_and_untyped_data`] and
+///   [`super::Literal::from_bytes`], or copied from the devices by
+///   [`super::PjRtBuffer::to_literal_sync`].
+///
+/// [`super::Literal::create_from_shape_in`] uses a given allocator regardless of the registered
+/// one. The other literals, e.g. the results of conversions or the tuples, and the staging copies
+/// made by the clients are allocated by XLA itself.
+///
+/// # Safety
+///
+/// `allocate` must return either a null pointer or a pointer to `size` writable bytes aligned on
+/// `align` bytes, this memory has to stay valid until it is passed to `deallocate`.
+pub unsafe trait HostBufferAllocator: Send + Sync {
+    fn allocate(&self, size: usize, align: usize) -> *mut u8;
+
+    /// # Safety
+    ///
+    /// `ptr` has been returned by `allocate` on the same allocator with the same `size` and
+    /// `align`.
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize);
+}
+
+impl std::fmt::Debug for dyn HostBufferAllocator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HostBufferAllocator")
+    }
+}
+
+static HOST_BUFFER_ALLOCATOR: RwLock<Option<&'static dyn HostBufferAllocator>> = RwLock::new(None);
+
+/// Use `allocator` for the host buffers and literals allocated after this call, see
+/// [`HostBufferAllocator`] for the allocations that are covered. This replaces any previously registered allocator,
+/// buffers are always released by the allocator that allocated them.
+pub fn set_host_buffer_allocator(allocator: &'static dyn HostBufferAllocator) {
+    let mut current = HOST_BUFFER_ALLOCATOR.write().unwrap_or_else(|e| e.into_inner());
+    *current = Some(allocator)
+}
+
+/// Remove the registered allocator, host buffers and literals are allocated by the C++ library
+/// again.
+pub fn clear_host_buffer_allocator() {
+    let mut current = HOST_BUFFER_ALLOCATOR.write().unwrap_or_else(|e| e.into_inner());
+    *current = None
+}
+
+pub(super) fn host_buffer_allocator() -> Option<&'static dyn HostBufferAllocator> {
+    *HOST_BUFFER_ALLOCATOR.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A host memory buffer allocated through a client. On GPU clients the memory is page-locked so
+/// that the device can access it directly, on other clients this is regular host memory. The
+/// memory is released when the buffer is dropped.
+pub struct PinnedHostBuffer {
+    ptr: *mut u8,
+    len: usize,
+    pinned: bool,
+    allocator: Option<&'static dyn HostBufferAllocator>,
+    client: PjRtClient,
+}
+
+impl PjRtClient {
+    /// Allocate `len` bytes of host memory, page-locked if supported by the client. Otherwise
+    /// the memory comes from the allocator registered with [`set_host_buffer_allocator`] if any.
+    /// The memory is zero initialized.
+    pub fn allocate_host_buffer(&self, len: usize) -> Result<PinnedHostBuffer> {
+        let allocator = if unsafe { c_lib::pjrt_client_has_pinned_host_allocator(self.ptr()) } {
+            None
+        } else {
+            host_buffer_allocator()
+        };
+        let (ptr, pinned) = match allocator {
+            Some(allocator) => {
+                let ptr = allocator.allocate(len.max(1), HOST_BUFFER_ALIGNMENT);
+                if ptr.is_null() {
+                    Err(Error::HostAllocationFailed { len })?
+                }
+                (ptr, false)
+            }
+            None => {
+                let mut ptr: *mut libc::c_void = std::ptr::null_mut();
+                let mut pinned = false;
+                let status = unsafe {
+                    c_lib::pjrt_client_allocate_host_buffer(self.ptr(), len, &mut ptr, &mut pinned)
+                };
+                super::handle_status(status)?;
+                (ptr as *mut u8, pinned)
+            }
+        };
+        unsafe { std::ptr::write_bytes(ptr, 0, len) };
+        Ok(PinnedHostBuffer { ptr, len, pinned, allocator, client: self.clone() })
+    }
+
+    /// Transfer the content of a host buffer to a `PjRtBuffer` stored on the target device, the
+    /// default device is used if `device` is `None`. The device reads directly from the host
+    /// buffer, which is faster than [`PjRtClient::buffer_from_host_raw_bytes`] when the memory
+    /// is page-locked. This blocks until the transfer has completed.
+    pub fn buffer_from_pinned_host_buffer(
+        &self,
+        data: &PinnedHostBuffer,
+        ty: ElementType,
+        dims: &[usize],
+        device: Option<&PjRtDevice>,
+    ) -> Result<PjRtBuffer> {
+        self.check_same(&data.client, "pinned host buffer")?;
+        self.check_device(device)?;
+        let element_count: usize = dims.iter().product();
+        if element_count * ty.element_size_in_bytes() != data.len() {
+            Err(Error::WrongElementCount { dims: dims.to_vec(), element_count })?
+        }
+        let mut buffer: c_lib::pjrt_buffer = std::ptr::null_mut();
+        let device = device.map_or(std::ptr::null_mut(), |d| d.device);
+        let dims: Vec<_> = dims.iter().map(|d| *d as i64).collect();
+        let status = unsafe {
+            c_lib::pjrt_buffer_from_pinned_host_buffer(
+                self.ptr(),
+                device,
+                data.ptr as *const libc::c_void,
+                ty.primitive_type() as i32,
+                dims.len() as i32,
+                dims.as_ptr(),
+                &mut buffer,
+            )
+        };
+        super::handle_status(status)?;
+        metrics::transfer(data.len(), TransferDirection::HostToDevice);
+        Ok(PjRtBuffer { buffer, client: self.clone() })
+    }
+}
+
+impl PinnedHostBuffer {
+    /// Whether the memory is page-locked.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// The client that allocated this buffer.
+    pub fn client(&self) -> &PjRtClient {
+        &self.client
+    }
+
+    /// A typed view on the buffer, this returns an error if the buffer size is not a multiple
+    /// of the element size.
+    pub fn as_mut_slice_of<T: ArrayElement>(&mut self) -> Result<&mut [T]> {
+        if self.len % T::ELEMENT_SIZE_IN_BYTES != 0 {
+            Err(Error::WrongElementCount {
+                dims: vec![self.len],
+                element_count: self.len / T::ELEMENT_SIZE_IN_BYTES,
+            })?
+        }
+        let len = self.len / T::ELEMENT_SIZE_IN_BYTES;
+        // The allocation is aligned on 64 bytes so this is properly aligned for all element types.
+        Ok(unsafe { std::slice::from_raw_parts_mut(self.ptr as *mut T, len) })
+    }
+}
+
+impl std::ops::Deref for PinnedHostBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl std::ops::DerefMut for PinnedHostBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for PinnedHostBuffer {
+    fn drop(&mut self) {
+        match self.allocator {
+            Some(allocator) => unsafe {
+                allocator.deallocate(self.ptr, self.len.max(1), HOST_BUFFER_ALIGNMENT)
+            },
+            None => unsafe {
+                c_lib::pjrt_client_free_host_buffer(
+                    self.client.ptr(),
+                    self.ptr as *mut libc::c_void,
+                    self.pinned,
+                )
+            },
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! A view on a memory slice hosted on a device.
 use super::{ArrayElement, ArrayShape, Literal, PjRtDevice, Shape};
+use crate::metrics::{self, TransferDirection};
 use crate::{c_lib, Error, Result};
 
 /// A buffer represents a view on a memory slice hosted on a device.
@@ -14,21 +15,134 @@ impl PjRtBuffer {
         &self.client
     }
 
-    /// Copy the buffer to a different device.
+    /// The address of the buffer data in the memory of its device. The pointer is only valid
+    /// while the buffer is alive and is not tracked by PJRT, accesses through it are not
+    /// synchronized with the computations using the buffer.
+    pub fn unsafe_device_pointer(&self) -> Result<*mut libc::c_void> {
+        let mut ptr = std::ptr::null_mut();
+        let status = unsafe { c_lib::pjrt_buffer_unsafe_pointer(self.buffer, &mut ptr) };
+        super::handle_status(status)?;
+        Ok(ptr)
+    }
+
+    /// The device where this buffer is stored.
+    pub fn device(&self) -> PjRtDevice<'_> {
+        let device = unsafe { c_lib::pjrt_buffer_device(self.buffer) };
+        PjRtDevice { device, marker: std::marker::PhantomData }
+    }
+
+    /// Release the device memory used by this buffer without waiting for it to be dropped. Any
+    /// later use of the buffer, e.g. copying it back to the host or passing it to an executable,
+    /// returns an error.
+    pub fn delete(&self) {
+        unsafe { c_lib::pjrt_buffer_delete(self.buffer) }
+    }
+
+    /// Whether the device memory of this buffer has been released, see [`PjRtBuffer::delete`].
+    pub fn is_deleted(&self) -> bool {
+        unsafe { c_lib::pjrt_buffer_is_deleted(self.buffer) }
+    }
+
+    /// Copy the buffer to a different device, the device has to belong to the same client as the
+    /// buffer.
     pub fn copy_to_device(&self, device: PjRtDevice) -> Result<PjRtBuffer> {
+        self.client.check_device(Some(&device))?;
         let mut buffer: c_lib::pjrt_buffer = std::ptr::null_mut();
         let status =
             unsafe { c_lib::pjrt_buffer_copy_to_device(self.buffer, device.device, &mut buffer) };
         super::handle_status(status)?;
+        if metrics::is_enabled() {
+            if let Ok(shape) = ArrayShape::try_from(&self.on_device_shape()?) {
+                let bytes = shape.element_count() * shape.ty().element_size_in_bytes();
+                metrics::transfer(bytes, TransferDirection::DeviceToDevice)
+            }
+        }
+        Ok(Self { buffer, client: self.client.clone() })
+    }
+
+    /// Copy the buffer to the default device of a different client, e.g. to move the output of
+    /// some preprocessing done on a CPU client to a GPU client. The data goes through host
+    /// memory, when `client` is the client of this buffer the copy stays on the device.
+    pub fn copy_to_client(&self, client: &super::PjRtClient) -> Result<PjRtBuffer> {
+        if self.client.is_same(client) {
+            return self.duplicate();
+        }
+        let literal = self.to_literal_sync()?;
+        client.buffer_from_host_literal(None, &literal)
+    }
+
+    /// A copy of this buffer on the same device, the data does not go through the host.
+    pub fn duplicate(&self) -> Result<PjRtBuffer> {
+        let mut buffer: c_lib::pjrt_buffer = std::ptr::null_mut();
+        let status = unsafe { c_lib::pjrt_buffer_duplicate(self.buffer, &mut buffer) };
+        super::handle_status(status)?;
         Ok(Self { buffer, client: self.client.clone() })
     }
 
-    /// Copy the buffer back to the host as a literal.
+    /// Copy the buffer back to the host as a literal. The storage of array literals comes from
+    /// the allocator registered with [`super::set_host_buffer_allocator`] if any.
     pub fn to_literal_sync(&self) -> Result<Literal> {
+        if let Some(allocator) = super::pinned_host_buffer::host_buffer_allocator() {
+            if let Shape::Array(shape) = self.on_device_shape()? {
+                let dims: Vec<usize> = shape.dims().iter().map(|d| *d as usize).collect();
+                let mut literal = Literal::create_from_shape_in(shape.ty(), &dims, allocator)?;
+                self.to_literal_into(&mut literal)?;
+                return Ok(literal);
+            }
+        }
         let mut result: c_lib::literal = std::ptr::null_mut();
         let status = unsafe { c_lib::pjrt_buffer_to_literal_sync(self.buffer, &mut result) };
         super::handle_status(status)?;
-        Ok(Literal(result))
+        let literal = Literal(result);
+        metrics::transfer(literal.size_bytes(), TransferDirection::DeviceToHost);
+        Ok(literal)
+    }
+
+    /// Copy the value of a scalar buffer back to the host. This returns an error if the buffer
+    /// has some dimensions, even when it only holds a single element.
+    pub fn to_scalar<T: ArrayElement>(&self) -> Result<T> {
+        let shape = ArrayShape::try_from(&self.on_device_shape()?)?;
+        if !shape.dims().is_empty() {
+            let dims = shape.dims().to_vec();
+            Err(Error::UnexpectedNumberOfDims { expected: 0, got: dims.len(), dims })?
+        }
+        let mut value = [T::ZERO];
+        self.copy_raw_to_host_sync(&mut value, 0)?;
+        Ok(value[0])
+    }
+
+    /// Copy the buffer back to the host in an existing literal, this avoids allocating a new
+    /// literal for each copy. The literal must have the same shape as the buffer.
+    pub fn to_literal_into(&self, literal: &mut Literal) -> Result<()> {
+        let expected = self.on_device_shape()?;
+        let got = literal.shape()?;
+        if expected != got {
+            Err(Error::ShapeMismatch { expected, got })?
+        }
+        let status = unsafe { c_lib::pjrt_buffer_to_literal_into(self.buffer, literal.0) };
+        super::handle_status(status)?;
+        metrics::transfer(literal.size_bytes(), TransferDirection::DeviceToHost);
+        Ok(())
+    }
+
+    /// Wait for the computation producing this buffer, or the transfer filling it, to have
+    /// completed.
+    pub fn block_until_ready(&self) -> Result<()> {
+        let status = unsafe { c_lib::pjrt_buffer_block_until_ready(self.buffer) };
+        super::handle_status(status)
+    }
+
+    /// Call `f` once the computation producing this buffer, or the transfer filling it, has
+    /// completed, whether it succeeded or not. `f` may be called right away on the current
+    /// thread or later on a runtime thread.
+    pub fn on_ready<F: FnOnce() + Send + 'static>(&self, f: F) {
+        unsafe extern "C" fn trampoline(user_data: *mut libc::c_void) {
+            let f = Box::from_raw(user_data as *mut Box<dyn FnOnce() + Send>);
+            f()
+        }
+        let f: Box<Box<dyn FnOnce() + Send>> = Box::new(Box::new(f));
+        let user_data = Box::into_raw(f) as *mut libc::c_void;
+        unsafe { c_lib::pjrt_buffer_on_ready(self.buffer, Some(trampoline), user_data) }
     }
 
     /// Retrieve the shape used by this buffer.
@@ -62,6 +176,7 @@ impl PjRtBuffer {
             )
         };
         super::handle_status(status)?;
+        metrics::transfer(dst.len() * T::ELEMENT_SIZE_IN_BYTES, TransferDirection::DeviceToHost);
         Ok(())
     }
 }
@@ -71,3 +186,60 @@ impl Drop for PjRtBuffer {
         unsafe { c_lib::pjrt_buffer_free(self.buffer) }
     }
 }
+
+/// A buffer together with a host copy of its data that is only transferred on first access, so
+/// that inspecting the same buffer several times, e.g. when debugging or extracting metrics,
+/// only performs a single device to host transfer.
+///
+/// The host copy is no longer served once the buffer has been deleted, either explicitly with
+/// [`PjRtBuffer::delete`] or by donating it to an execution: accessing the view then returns
+/// [`Error::BufferDeleted`].
+pub struct CachedHostView {
+    buffer: PjRtBuffer,
+    host: std::cell::OnceCell<Literal>,
+}
+
+impl CachedHostView {
+    pub fn new(buffer: PjRtBuffer) -> Self {
+        Self { buffer, host: std::cell::OnceCell::new() }
+    }
+
+    pub fn buffer(&self) -> &PjRtBuffer {
+        &self.buffer
+    }
+
+    /// The underlying buffer, the host copy is dropped.
+    pub fn into_buffer(self) -> PjRtBuffer {
+        self.buffer
+    }
+
+    /// Whether the host copy has already been transferred and can still be used.
+    pub fn is_cached(&self) -> bool {
+        self.host.get().is_some() && !self.buffer.is_deleted()
+    }
+
+    /// Drop the host copy, the next access transfers the buffer again.
+    pub fn invalidate(&mut self) {
+        self.host.take();
+    }
+
+    /// The host copy of the buffer, this performs the transfer on the first call.
+    pub fn literal(&self) -> Result<&Literal> {
+        if self.buffer.is_deleted() {
+            Err(Error::BufferDeleted)?
+        }
+        if let Some(literal) = self.host.get() {
+            return Ok(literal);
+        }
+        let literal = self.buffer.to_literal_sync()?;
+        Ok(self.host.get_or_init(|| literal))
+    }
+
+    /// The values of an array buffer, `T` has to match the element type of the buffer. This
+    /// performs the transfer on the first call. An error is returned if the host copy is not
+    /// suitably aligned for `T`, [`CachedHostView::literal`] can be used to copy the values
+    /// instead.
+    pub fn as_slice<T: ArrayElement>(&self) -> Result<&[T]> {
+        self.literal()?.typed_data()
+    }
+}
@@ -1,13 +1,266 @@
 //! A device (CPUs, GPUs, TPUs) where computations can be run.
 use super::{ArrayElement, Literal, PjRtBuffer, PjRtDevice, PjRtLoadedExecutable, XlaComputation};
+use crate::mesh::{Mesh, PartitionSpec};
+use crate::metrics::{self, TransferDirection};
+use crate::numa::{NodeBinding, NumaMemoryPolicy};
 use crate::{c_lib, Error, Result};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// The library loaded by [`PjRtClient::cuda`] when `XLA_CUDA_PLUGIN` is not set, it is looked up
+/// in the dynamic library search path.
+pub const DEFAULT_CUDA_PLUGIN: &str = "pjrt_c_api_gpu_plugin.so";
+
+/// The library loaded by [`PjRtClient::rocm`] when `XLA_ROCM_PLUGIN` is not set, it is looked up
+/// in the dynamic library search path.
+pub const DEFAULT_ROCM_PLUGIN: &str = "xla_rocm_plugin.so";
+
+/// The library loaded by [`PjRtClient::metal`] when `XLA_METAL_PLUGIN` is not set, it is looked
+/// up in the dynamic library search path. This library is distributed by Apple as part of the
+/// `jax-metal` python package.
+pub const DEFAULT_METAL_PLUGIN: &str = "libpjrt_plugin_metal_14.dylib";
+
+// A plugin library that has been loaded, its initialization may have failed in which case it is
+// attempted again on the next client creation.
+struct LoadedPlugin {
+    path: PathBuf,
+    initialized: bool,
+}
+
+// The plugins that have been loaded, indexed by device type. A plugin can only be loaded once per
+// process so the library path is kept to detect conflicting loads.
+fn loaded_plugins() -> &'static Mutex<HashMap<String, LoadedPlugin>> {
+    static LOADED_PLUGINS: OnceLock<Mutex<HashMap<String, LoadedPlugin>>> = OnceLock::new();
+    LOADED_PLUGINS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Guess the device type from a plugin library name, e.g. `pjrt_c_api_cuda_plugin.so` gives
+// `cuda` and `libtpu.so` gives `tpu`.
+fn plugin_device_type(path: &Path) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let stem = stem.strip_prefix("lib").unwrap_or(&stem);
+    let stem =
+        stem.strip_prefix("pjrt_c_api_").or_else(|| stem.strip_prefix("pjrt_")).unwrap_or(stem);
+    stem.strip_suffix("_plugin").unwrap_or(stem).to_string()
+}
 
 pub(super) struct PjRtClientInternal(pub(self) c_lib::pjrt_client);
 
+/// The backends that can be selected at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    Cuda,
+    Rocm,
+    Metal,
+    Cpu,
+}
+
+impl Backend {
+    /// The order in which backends are tried by [`PjRtClient::best_available`].
+    pub const PREFERENCE_ORDER: [Backend; 4] = [Self::Cuda, Self::Rocm, Self::Metal, Self::Cpu];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cuda => "cuda",
+            Self::Rocm => "rocm",
+            Self::Metal => "metal",
+            Self::Cpu => "cpu",
+        }
+    }
+
+    /// Create a client for this backend.
+    pub fn client(&self) -> Result<PjRtClient> {
+        match self {
+            Self::Cuda => PjRtClient::cuda(),
+            Self::Rocm => PjRtClient::rocm(),
+            Self::Metal => PjRtClient::metal(),
+            Self::Cpu => PjRtClient::cpu(),
+        }
+    }
+}
+
+/// What a client supports, so that applications can adapt to the backend they end up running
+/// on, see [`PjRtClient::platform_capabilities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformCapabilities {
+    pub platform_name: String,
+    pub platform_version: String,
+    pub device_count: usize,
+    pub addressable_device_count: usize,
+    /// Whether the computations run on an accelerator rather than on the host CPU.
+    pub is_accelerator: bool,
+    /// Whether `F64` and `C128` values can be used in computations.
+    pub supports_f64: bool,
+    /// Whether `Bf16` values can be used in computations.
+    pub supports_bf16: bool,
+    /// Whether executions can be given a stream priority, see [`super::ExecuteOptions`]. The
+    /// PJRT runtime of the xla extension does not expose per execution stream priorities so
+    /// this is currently false on all platforms.
+    pub supports_execute_priority: bool,
+}
+
+/// Information about the runtime serving a client, see [`PjRtClient::runtime_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeInfo {
+    pub platform_name: String,
+    pub platform_version: String,
+    /// The version of the device driver, if reported by the runtime.
+    pub driver_version: Option<String>,
+    /// The version of the toolkit the runtime was built against, e.g. CUDA or ROCm, if reported
+    /// by the runtime.
+    pub toolkit_version: Option<String>,
+    /// The client attributes, see [`PjRtClient::attributes`].
+    pub attributes: HashMap<String, String>,
+    /// The kind of each device attached to the client, e.g. `NVIDIA A100-SXM4-80GB`.
+    pub device_kinds: Vec<String>,
+}
+
+/// The options used by [`PjRtClient::cpu_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CpuClientOptions {
+    /// The NUMA node on which the threads of the client run, all the cpus can be used if `None`.
+    pub numa_node: Option<usize>,
+    /// Where the threads of the client allocate their memory, this is only used together with
+    /// `numa_node`.
+    pub memory_policy: NumaMemoryPolicy,
+    /// The number of devices exposed by the client, this defaults to the number of cpus. Using
+    /// multiple devices is useful to test multi-device code on a single machine.
+    pub device_count: Option<usize>,
+}
+
+/// The NCCL settings used by the collectives of GPU clients, see
+/// [`GpuClientOptions::nccl`]. NCCL and XLA only read these settings once per process, so they
+/// apply to all the clients of the process.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NcclConfig {
+    /// The `host:port` address used to bootstrap the communicators, this is `NCCL_COMM_ID`.
+    pub comm_id: Option<String>,
+    /// How long collectives can be stuck waiting for other participants before the process is
+    /// terminated, this is the `--xla_gpu_nccl_termination_timeout_seconds` XLA flag. XLA uses
+    /// a whole number of seconds so timeouts below one second are rejected.
+    pub timeout: Option<std::time::Duration>,
+    /// Whether NCCL can use its peer-to-peer transport between local GPUs, this is
+    /// `NCCL_P2P_DISABLE` and the NCCL default is kept if `None`. This only affects the NCCL
+    /// collectives, XLA still enables CUDA peer access between the devices that support it.
+    pub p2p_transport: Option<bool>,
+}
+
+// The NCCL config applied by the first GPU client that had one.
+static APPLIED_NCCL_CONFIG: Mutex<Option<NcclConfig>> = Mutex::new(None);
+
+impl NcclConfig {
+    /// The environment variables through which the settings are passed to NCCL and XLA,
+    /// `XLA_FLAGS` only lists the flags set by this config and is merged with the existing
+    /// value.
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![];
+        if let Some(p2p_transport) = self.p2p_transport {
+            vars.push(("NCCL_P2P_DISABLE", if p2p_transport { "0" } else { "1" }.to_string()))
+        }
+        if let Some(comm_id) = &self.comm_id {
+            vars.push(("NCCL_COMM_ID", comm_id.to_string()))
+        }
+        if let Some(timeout) = self.timeout {
+            let flag = format!("--xla_gpu_nccl_termination_timeout_seconds={}", timeout.as_secs());
+            vars.push(("XLA_FLAGS", flag))
+        }
+        vars
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(timeout) = self.timeout {
+            if timeout.as_secs() == 0 {
+                Err(Error::InvalidNcclConfig {
+                    msg: format!("the timeout {timeout:?} is below one second"),
+                })?
+            }
+        }
+        Ok(())
+    }
+
+    // Apply the settings for the process, this is done once. Later clients can use the same
+    // config or no config at all.
+    fn apply(&self) -> Result<()> {
+        self.validate()?;
+        let mut applied = APPLIED_NCCL_CONFIG.lock().unwrap_or_else(|e| e.into_inner());
+        match applied.as_ref() {
+            Some(applied) if applied == self => return Ok(()),
+            Some(applied) => Err(Error::InvalidNcclConfig {
+                msg: format!("{applied:?} has already been applied to the process"),
+            })?,
+            None => {}
+        }
+        for (name, value) in self.env_vars() {
+            let value = if name == "XLA_FLAGS" {
+                merge_xla_flags(&std::env::var("XLA_FLAGS").unwrap_or_default(), &value)
+            } else {
+                value
+            };
+            unsafe { std::env::set_var(name, value) }
+        }
+        if let Some(timeout) = self.timeout {
+            let in_effect = unsafe { c_lib::xla_gpu_nccl_termination_timeout_seconds() };
+            if in_effect != timeout.as_secs() as i64 {
+                Err(Error::InvalidNcclConfig {
+                    msg: format!("XLA_FLAGS has already been parsed, the timeout is {in_effect}s"),
+                })?
+            }
+        }
+        *applied = Some(self.clone());
+        Ok(())
+    }
+}
+
+/// The options used by [`PjRtClient::gpu_with_options`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuClientOptions {
+    /// The fraction of the device memory that the client can use.
+    pub memory_fraction: f64,
+    /// Whether this memory is allocated upfront rather than on demand.
+    pub preallocate: bool,
+    /// The ordinals of the local GPUs used by the client, all of them are used if `None`.
+    pub allowed_devices: Option<Vec<usize>>,
+    /// The NCCL settings, these are process wide so they have to be the same for all the clients
+    /// that set them.
+    pub nccl: NcclConfig,
+}
+
+impl Default for GpuClientOptions {
+    fn default() -> Self {
+        // The XLA defaults.
+        Self {
+            memory_fraction: 0.75,
+            preallocate: true,
+            allowed_devices: None,
+            nccl: NcclConfig::default(),
+        }
+    }
+}
+
+// Add some flags to an `XLA_FLAGS` value, replacing the previous values of these flags.
+fn merge_xla_flags(existing: &str, flags: &str) -> String {
+    let names: Vec<&str> =
+        flags.split_whitespace().map(|f| f.split('=').next().unwrap_or(f)).collect();
+    existing
+        .split_whitespace()
+        .filter(|f| !names.contains(&f.split('=').next().unwrap_or(f)))
+        .chain(flags.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// A client represents a device that can be used to run some computations. A computation graph is
 /// compiled in a way that is specific to a device before it can be run.
+///
+/// Multiple clients can be used in the same process, e.g. a CPU client and a GPU client. Buffers
+/// and executables keep track of the client that created them, and using a buffer or a device
+/// with an executable or a client it does not belong to returns an
+/// [`Error::ClientMismatch`] error.
 #[derive(Clone)]
 pub struct PjRtClient(Rc<PjRtClientInternal>);
 
@@ -17,7 +270,30 @@ impl PjRtClient {
         let mut ptr: c_lib::pjrt_client = std::ptr::null_mut();
         let status = unsafe { c_lib::pjrt_cpu_client_create(&mut ptr) };
         super::handle_status(status)?;
-        Ok(Self(Rc::new(PjRtClientInternal(ptr))))
+        Ok(Self::from_ptr(ptr))
+    }
+
+    /// A CPU client created with some specific options. When a NUMA node is specified, the
+    /// threads of the client are started while the current thread is bound to this node so they
+    /// only run on its cpus and allocate memory according to `memory_policy`. The binding of the
+    /// current thread is restored afterwards, literals that it creates are not placed on the node
+    /// unless it is bound too, see [`crate::numa::NodeBinding`].
+    pub fn cpu_with_options(options: &CpuClientOptions) -> Result<Self> {
+        let _binding = match options.numa_node {
+            None => None,
+            Some(node) => Some(NodeBinding::bind_current_thread(node, options.memory_policy)?),
+        };
+        match options.device_count {
+            None => Self::cpu(),
+            Some(device_count) => {
+                let mut ptr: c_lib::pjrt_client = std::ptr::null_mut();
+                let status = unsafe {
+                    c_lib::pjrt_cpu_client_create_with_device_count(&mut ptr, device_count as i32)
+                };
+                super::handle_status(status)?;
+                Ok(Self::from_ptr(ptr))
+            }
+        }
     }
 
     /// A GPU client, the memory requirements are limited by the specified `memory_fraction` and
@@ -28,7 +304,132 @@ impl PjRtClient {
         let status =
             unsafe { c_lib::pjrt_gpu_client_create(&mut ptr, memory_fraction, preallocate) };
         super::handle_status(status)?;
-        Ok(Self(Rc::new(PjRtClientInternal(ptr))))
+        Ok(Self::from_ptr(ptr))
+    }
+
+    /// A GPU client created with some specific options. The NCCL settings are applied to the
+    /// process before the client is created, they are passed to NCCL through its environment
+    /// variables and to XLA through `XLA_FLAGS` so this should happen before other threads
+    /// read the environment. An error is returned if they conflict with the settings of a
+    /// previous client, or if XLA has already parsed its flags when a timeout is set, e.g.
+    /// because another client or [`crate::init`] came first.
+    pub fn gpu_with_options(options: &GpuClientOptions) -> Result<Self> {
+        if options.nccl != NcclConfig::default() {
+            options.nccl.apply()?
+        }
+        let allowed_devices: Option<Vec<i64>> =
+            options.allowed_devices.as_ref().map(|d| d.iter().map(|d| *d as i64).collect());
+        let (allowed_ptr, num_allowed) = match &allowed_devices {
+            None => (std::ptr::null(), 0),
+            Some(devices) => (devices.as_ptr(), devices.len()),
+        };
+        let mut ptr: c_lib::pjrt_client = std::ptr::null_mut();
+        let status = unsafe {
+            c_lib::pjrt_gpu_client_create_with_options(
+                &mut ptr,
+                options.memory_fraction,
+                options.preallocate,
+                allowed_ptr,
+                num_allowed,
+            )
+        };
+        super::handle_status(status)?;
+        Ok(Self::from_ptr(ptr))
+    }
+
+    /// A client using a PJRT plugin, the plugin shared library at `path` is loaded at runtime so
+    /// the same binary can be used on machines with different accelerators. The device type is
+    /// derived from the library name, e.g. `pjrt_c_api_cuda_plugin.so` is registered as `cuda`,
+    /// use [`PjRtClient::plugin_with_device_type`] to set it explicitly.
+    pub fn plugin<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let device_type = plugin_device_type(path.as_ref());
+        Self::plugin_with_device_type(&device_type, path)
+    }
+
+    /// A client using the PJRT plugin at `path`, registered for `device_type`. Plugins are only
+    /// loaded once per process, later calls with the same device type and path reuse the loaded
+    /// plugin.
+    pub fn plugin_with_device_type<P: AsRef<Path>>(device_type: &str, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let device_type_c = std::ffi::CString::new(device_type)?;
+        {
+            let mut loaded_plugins = loaded_plugins().lock().unwrap_or_else(|e| e.into_inner());
+            let loaded = match loaded_plugins.entry(device_type.to_string()) {
+                Entry::Occupied(entry) if entry.get().path == path => entry.into_mut(),
+                Entry::Occupied(entry) => Err(Error::PluginAlreadyLoaded {
+                    device_type: device_type.to_string(),
+                    loaded: entry.get().path.clone(),
+                    path: path.to_path_buf(),
+                })?,
+                Entry::Vacant(entry) => {
+                    let path_c = std::ffi::CString::new(path.to_string_lossy().as_bytes())?;
+                    let status =
+                        unsafe { c_lib::pjrt_plugin_load(device_type_c.as_ptr(), path_c.as_ptr()) };
+                    super::handle_status(status)?;
+                    // Recorded before initializing, the library cannot be loaded a second time
+                    // so a failed initialization is retried on its own.
+                    entry.insert(LoadedPlugin { path: path.to_path_buf(), initialized: false })
+                }
+            };
+            if !loaded.initialized {
+                let status = unsafe { c_lib::pjrt_plugin_initialize(device_type_c.as_ptr()) };
+                super::handle_status(status)?;
+                loaded.initialized = true
+            }
+        }
+        let mut ptr: c_lib::pjrt_client = std::ptr::null_mut();
+        let status = unsafe { c_lib::pjrt_plugin_client_create(&mut ptr, device_type_c.as_ptr()) };
+        super::handle_status(status)?;
+        Ok(Self::from_ptr(ptr))
+    }
+
+    /// A CUDA client using the PJRT plugin, the library path is read from the `XLA_CUDA_PLUGIN`
+    /// environment variable and defaults to [`DEFAULT_CUDA_PLUGIN`]. Contrary to
+    /// [`PjRtClient::gpu`], this does not require building against the CUDA flavor of the xla
+    /// extension.
+    pub fn cuda() -> Result<Self> {
+        let path = std::env::var_os("XLA_CUDA_PLUGIN")
+            .map_or_else(|| PathBuf::from(DEFAULT_CUDA_PLUGIN), PathBuf::from);
+        Self::plugin_with_device_type("cuda", path)
+    }
+
+    /// A ROCm client for AMD GPUs using the PJRT plugin, the library path is read from the
+    /// `XLA_ROCM_PLUGIN` environment variable and defaults to [`DEFAULT_ROCM_PLUGIN`].
+    pub fn rocm() -> Result<Self> {
+        let path = std::env::var_os("XLA_ROCM_PLUGIN")
+            .map_or_else(|| PathBuf::from(DEFAULT_ROCM_PLUGIN), PathBuf::from);
+        Self::plugin_with_device_type("rocm", path)
+    }
+
+    /// A client for Apple GPUs using the Metal PJRT plugin, this is only available on macOS. The
+    /// library path is read from the `XLA_METAL_PLUGIN` environment variable and defaults to
+    /// [`DEFAULT_METAL_PLUGIN`].
+    pub fn metal() -> Result<Self> {
+        let path = std::env::var_os("XLA_METAL_PLUGIN")
+            .map_or_else(|| PathBuf::from(DEFAULT_METAL_PLUGIN), PathBuf::from);
+        Self::plugin_with_device_type("metal", path)
+    }
+
+    /// The first client that can be created out of the cuda, rocm, metal, and cpu backends, in
+    /// this order. The reason why each backend could not be used is logged at the debug level.
+    pub fn best_available() -> Result<Self> {
+        Self::first_available(&Backend::PREFERENCE_ORDER)
+    }
+
+    /// The first client that can be created out of `backends`. If none of them can be created,
+    /// the returned error lists the failure for each backend.
+    pub fn first_available(backends: &[Backend]) -> Result<Self> {
+        let mut failures = vec![];
+        for backend in backends.iter() {
+            match backend.client() {
+                Ok(client) => return Ok(client),
+                Err(err) => {
+                    log::debug!("cannot use the {} backend: {err}", backend.name());
+                    failures.push(format!("{}: {err}", backend.name()))
+                }
+            }
+        }
+        Err(Error::NoBackendAvailable { failures })
     }
 
     /*
@@ -38,19 +439,87 @@ impl PjRtClient {
         let status =
             unsafe { c_lib::pjrt_tpu_client_create(&mut ptr, max_inflight_computations as i32) };
         super::handle_status(status)?;
-        Ok(Self(Rc::new(PjRtClientInternal(ptr))))
+        Ok(Self::from_ptr(ptr))
     }
     */
 
-    fn ptr(&self) -> c_lib::pjrt_client {
+    fn from_ptr(ptr: c_lib::pjrt_client) -> Self {
+        LIVE_CLIENTS.fetch_add(1, Ordering::SeqCst);
+        Self(Rc::new(PjRtClientInternal(ptr)))
+    }
+
+    pub(super) fn ptr(&self) -> c_lib::pjrt_client {
         self.0 .0
     }
 
+    /// An identifier for this client, clones of a client share the same identifier and distinct
+    /// clients that are alive at the same time have different identifiers.
+    pub fn id(&self) -> usize {
+        self.ptr() as usize
+    }
+
+    /// Whether `self` and `other` are the same client, i.e. whether buffers and executables from
+    /// one can be used with the other.
+    pub fn is_same(&self, other: &PjRtClient) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub(super) fn describe(&self) -> String {
+        format!("client {}:{:#x}", self.platform_name(), self.id())
+    }
+
+    pub(super) fn check_same(&self, other: &PjRtClient, what: &str) -> Result<()> {
+        if !self.is_same(other) {
+            Err(Error::ClientMismatch {
+                what: what.to_string(),
+                expected: self.describe(),
+                got: other.describe(),
+            })?
+        }
+        Ok(())
+    }
+
+    /// Check that `device` is one of the devices of this client.
+    pub(super) fn check_device(&self, device: Option<&PjRtDevice>) -> Result<()> {
+        if let Some(device) = device {
+            if !self.devices().iter().any(|d| d.device == device.device) {
+                Err(Error::ClientMismatch {
+                    what: format!("device {}", device.to_string()),
+                    expected: self.describe(),
+                    got: "a different client".to_string(),
+                })?
+            }
+        }
+        Ok(())
+    }
+
     /// Compile a computation for this device, and return the executable.
     pub fn compile(&self, c: &XlaComputation) -> Result<PjRtLoadedExecutable> {
+        let start = std::time::Instant::now();
         let mut exe: c_lib::pjrt_loaded_executable = std::ptr::null_mut();
         let status = unsafe { c_lib::compile(self.ptr(), c.0, &mut exe) };
         super::handle_status(status)?;
+        if metrics::is_enabled() {
+            metrics::compile_finished(start.elapsed(), &c.name())
+        }
+        Ok(PjRtLoadedExecutable { exe, client: self.clone() })
+    }
+
+    /// Compile a computation that runs as `num_replicas` replicas, one per addressable device.
+    /// The replicas can communicate through collectives such as [`super::XlaOp::all_reduce`].
+    pub fn compile_replicated(
+        &self,
+        c: &XlaComputation,
+        num_replicas: usize,
+    ) -> Result<PjRtLoadedExecutable> {
+        let start = std::time::Instant::now();
+        let mut exe: c_lib::pjrt_loaded_executable = std::ptr::null_mut();
+        let status =
+            unsafe { c_lib::compile_replicated(self.ptr(), c.0, num_replicas as i32, &mut exe) };
+        super::handle_status(status)?;
+        if metrics::is_enabled() {
+            metrics::compile_finished(start.elapsed(), &c.name())
+        }
         Ok(PjRtLoadedExecutable { exe, client: self.clone() })
     }
 
@@ -80,6 +549,67 @@ impl PjRtClient {
         }
     }
 
+    /// Runtime specific attributes of the client. For plugin based clients, this includes the PJRT
+    /// C api version as `pjrt_c_api_version` as well as the attributes reported by the plugin,
+    /// e.g. `xla_version`. The values are converted to strings.
+    pub fn attributes(&self) -> HashMap<String, String> {
+        let mut keys = std::ptr::null_mut();
+        let mut values = std::ptr::null_mut();
+        unsafe {
+            let n = c_lib::pjrt_client_attributes(self.ptr(), &mut keys, &mut values);
+            super::c_attributes_to_map(n, keys, values)
+        }
+    }
+
+    /// Information about the runtime serving this client, to be logged by operational tooling.
+    pub fn runtime_info(&self) -> RuntimeInfo {
+        let platform_version = self.platform_version();
+        let attributes = self.attributes();
+        let devices = self.addressable_devices();
+        let device_attributes = devices.first().map(|d| d.attributes()).unwrap_or_default();
+        let lookup = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| attributes.get(*k).or_else(|| device_attributes.get(*k)))
+                .cloned()
+        };
+        let driver_version = lookup(&["driver_version", "cuda_driver_version"]);
+        // GPU clients report the toolkit version as their platform version, e.g. `cuda 12030`.
+        let toolkit_version =
+            lookup(&["toolkit_version", "cuda_version", "rocm_version"]).or_else(|| {
+                ["cuda ", "rocm "]
+                    .iter()
+                    .find_map(|p| platform_version.strip_prefix(p).map(|v| v.trim().to_string()))
+            });
+        RuntimeInfo {
+            platform_name: self.platform_name(),
+            platform_version,
+            driver_version,
+            toolkit_version,
+            attributes,
+            device_kinds: devices.iter().map(|d| d.kind()).collect(),
+        }
+    }
+
+    /// The capabilities of the platform used by this client.
+    pub fn platform_capabilities(&self) -> PlatformCapabilities {
+        let platform_name = self.platform_name();
+        let lower_name = platform_name.to_lowercase();
+        let is_accelerator = lower_name != "cpu" && lower_name != "host";
+        // Neither TPUs nor Apple GPUs support double precision.
+        let supports_f64 = !matches!(lower_name.as_str(), "tpu" | "metal");
+        let supports_bf16 = lower_name != "metal";
+        PlatformCapabilities {
+            platform_name,
+            platform_version: self.platform_version(),
+            device_count: self.device_count(),
+            addressable_device_count: self.addressable_device_count(),
+            is_accelerator,
+            supports_f64,
+            supports_bf16,
+            supports_execute_priority: false,
+        }
+    }
+
     /// A list of devices attached to this client.
     pub fn devices(&self) -> Vec<PjRtDevice> {
         let device_count = self.device_count();
@@ -112,6 +642,7 @@ impl PjRtClient {
         if element_count != data.len() {
             Err(Error::WrongElementCount { dims: dims.to_vec(), element_count })?
         }
+        self.check_device(device)?;
         let device = device.map_or(std::ptr::null_mut(), |d| d.device);
         let dims: Vec<_> = dims.iter().map(|d| *d as i64).collect();
         let status = unsafe {
@@ -126,6 +657,7 @@ impl PjRtClient {
             )
         };
         super::handle_status(status)?;
+        metrics::transfer(data.len() * T::ELEMENT_SIZE_IN_BYTES, TransferDirection::HostToDevice);
         Ok(PjRtBuffer { buffer, client: self.clone() })
     }
 
@@ -147,6 +679,7 @@ impl PjRtClient {
         if element_count * element_size_in_bytes != data.len() {
             Err(Error::WrongElementCount { dims: dims.to_vec(), element_count })?
         }
+        self.check_device(device)?;
         let device = device.map_or(std::ptr::null_mut(), |d| d.device);
         let dims: Vec<_> = dims.iter().map(|d| *d as i64).collect();
         let status = unsafe {
@@ -154,13 +687,14 @@ impl PjRtClient {
                 self.ptr(),
                 device,
                 data.as_ptr() as *const libc::c_void,
-                ty as i32,
+                ty.primitive_type() as i32,
                 dims.len() as i32,
                 dims.as_ptr(),
                 &mut buffer,
             )
         };
         super::handle_status(status)?;
+        metrics::transfer(data.len(), TransferDirection::HostToDevice);
         Ok(PjRtBuffer { buffer, client: self.clone() })
     }
 
@@ -173,17 +707,95 @@ impl PjRtClient {
         literal: &Literal,
     ) -> Result<PjRtBuffer> {
         let mut buffer: c_lib::pjrt_buffer = std::ptr::null_mut();
+        self.check_device(device)?;
         let device = device.map_or(std::ptr::null_mut(), |d| d.device);
         let status = unsafe {
             c_lib::pjrt_buffer_from_host_literal(self.ptr(), device, literal.0, &mut buffer)
         };
         super::handle_status(status)?;
+        metrics::transfer(literal.size_bytes(), TransferDirection::HostToDevice);
+        Ok(PjRtBuffer { buffer, client: self.clone() })
+    }
+
+    /// Split `literal` over the devices of `mesh` following `spec` and transfer each shard to the
+    /// device holding it, see [`Literal::shard`]. The buffers are returned in the order of
+    /// [`Mesh::device_ids`], all the mesh devices have to be addressable by this client.
+    pub fn upload_sharded(
+        &self,
+        literal: &Literal,
+        mesh: &Mesh,
+        spec: &PartitionSpec,
+    ) -> Result<Vec<PjRtBuffer>> {
+        let devices = self.addressable_devices();
+        literal
+            .shard(mesh, spec)?
+            .iter()
+            .map(|(device_id, shard)| {
+                let device = devices.iter().find(|d| d.id() == *device_id).ok_or_else(|| {
+                    Error::InvalidMesh { msg: format!("device {device_id} is not addressable") }
+                })?;
+                self.buffer_from_host_literal(Some(device), shard)
+            })
+            .collect()
+    }
+
+    /// Create a buffer that is a view on some memory already allocated on the device, no data
+    /// is copied. The data is laid out in row major order. `on_delete` is called once the
+    /// buffer is not used anymore, which can be used to keep the owner of the memory alive.
+    /// PJRT may call `on_delete` from one of its own threads, hence the `Send` bound. If the
+    /// device is not specified, the default device is used.
+    ///
+    /// # Safety
+    /// `ptr` has to point to some memory of `device` large enough for the element type and
+    /// dimensions, and this memory must remain valid until `on_delete` is called.
+    pub unsafe fn buffer_from_device_pointer<F: FnOnce() + Send + 'static>(
+        &self,
+        ptr: *mut libc::c_void,
+        ty: super::ElementType,
+        dims: &[usize],
+        device: Option<&PjRtDevice>,
+        on_delete: F,
+    ) -> Result<PjRtBuffer> {
+        unsafe extern "C" fn on_delete_trampoline(user_data: *mut libc::c_void) {
+            let on_delete = Box::from_raw(user_data as *mut Box<dyn FnOnce() + Send>);
+            on_delete()
+        }
+
+        self.check_device(device)?;
+        let device = device.map_or(std::ptr::null_mut(), |d| d.device);
+        let dims: Vec<_> = dims.iter().map(|d| *d as i64).collect();
+        let on_delete: Box<Box<dyn FnOnce() + Send>> = Box::new(Box::new(on_delete));
+        let user_data = Box::into_raw(on_delete) as *mut libc::c_void;
+        let mut buffer: c_lib::pjrt_buffer = std::ptr::null_mut();
+        let status = c_lib::pjrt_buffer_from_device_pointer(
+            self.ptr(),
+            device,
+            ptr,
+            ty.primitive_type() as i32,
+            dims.len() as i32,
+            dims.as_ptr(),
+            Some(on_delete_trampoline),
+            user_data,
+            &mut buffer,
+        );
+        if let Err(err) = super::handle_status(status) {
+            drop(Box::from_raw(user_data as *mut Box<dyn FnOnce() + Send>));
+            return Err(err);
+        }
         Ok(PjRtBuffer { buffer, client: self.clone() })
     }
 }
 
 impl Drop for PjRtClientInternal {
     fn drop(&mut self) {
-        unsafe { c_lib::pjrt_client_free(self.0) }
+        unsafe { c_lib::pjrt_client_free(self.0) };
+        LIVE_CLIENTS.fetch_sub(1, Ordering::SeqCst);
     }
 }
+
+// The number of clients that have not been dropped yet, clones of a client count once.
+static LIVE_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn live_client_count() -> usize {
+    LIVE_CLIENTS.load(Ordering::SeqCst)
+}
@@ -29,6 +29,17 @@ impl PjRtDevice<'_> {
         }
     }
 
+    /// Backend specific attributes of the device, e.g. the compute capability for CUDA devices.
+    /// The values are converted to strings, with lists of integers being comma separated.
+    pub fn attributes(&self) -> std::collections::HashMap<String, String> {
+        let mut keys = std::ptr::null_mut();
+        let mut values = std::ptr::null_mut();
+        unsafe {
+            let n = c_lib::pjrt_device_attributes(self.device, &mut keys, &mut values);
+            super::c_attributes_to_map(n, keys, values)
+        }
+    }
+
     pub fn kind(&self) -> String {
         unsafe {
             let ptr = c_lib::pjrt_device_kind(self.device);
@@ -1,5 +1,198 @@
-use super::{Literal, PjRtBuffer};
-use crate::{c_lib, Result};
+use super::{ElementType, Literal, NativeType, PjRtBuffer, PjRtDevice};
+use crate::metrics::{self, TransferDirection};
+use crate::{c_lib, Error, Result};
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A value that can be used as an argument of [`PjRtLoadedExecutable::execute`]. This is
+/// implemented for literals, for scalars and for slices of scalars, the latter being wrapped in
+/// a literal automatically. This trait is sealed and cannot be implemented outside of this crate.
+pub trait IntoExecuteArg: sealed::Sealed {
+    #[doc(hidden)]
+    fn to_literal(&self) -> Result<Cow<'_, Literal>>;
+}
+
+impl sealed::Sealed for Literal {}
+impl IntoExecuteArg for Literal {
+    fn to_literal(&self) -> Result<Cow<'_, Literal>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl<T: IntoExecuteArg + ?Sized> sealed::Sealed for &T {}
+impl<T: IntoExecuteArg + ?Sized> IntoExecuteArg for &T {
+    fn to_literal(&self) -> Result<Cow<'_, Literal>> {
+        (**self).to_literal()
+    }
+}
+
+impl sealed::Sealed for bool {}
+impl IntoExecuteArg for bool {
+    fn to_literal(&self) -> Result<Cow<'_, Literal>> {
+        let literal =
+            Literal::create_from_shape_and_untyped_data(ElementType::Pred, &[], &[*self as u8])?;
+        Ok(Cow::Owned(literal))
+    }
+}
+
+macro_rules! native_execute_arg {
+    ($ty:ty) => {
+        impl sealed::Sealed for $ty {}
+        impl IntoExecuteArg for $ty {
+            fn to_literal(&self) -> Result<Cow<'_, Literal>> {
+                Ok(Cow::Owned(Literal::scalar(*self)))
+            }
+        }
+    };
+}
+
+native_execute_arg!(i32);
+native_execute_arg!(i64);
+native_execute_arg!(u32);
+native_execute_arg!(u64);
+native_execute_arg!(f32);
+native_execute_arg!(f64);
+
+impl<T: NativeType> sealed::Sealed for [T] {}
+impl<T: NativeType> IntoExecuteArg for [T] {
+    fn to_literal(&self) -> Result<Cow<'_, Literal>> {
+        Ok(Cow::Owned(Literal::vec1(self)))
+    }
+}
+
+impl<T: NativeType> sealed::Sealed for Vec<T> {}
+impl<T: NativeType> IntoExecuteArg for Vec<T> {
+    fn to_literal(&self) -> Result<Cow<'_, Literal>> {
+        Ok(Cow::Owned(Literal::vec1(self)))
+    }
+}
+
+/// An argument of [`PjRtLoadedExecutable::execute_inputs`], literals are transferred to the
+/// device before the execution whereas buffers are used directly. This makes it possible to mix
+/// device resident weights with freshly uploaded activations in a single call.
+pub enum ExecuteInput<'a> {
+    Literal(&'a Literal),
+    Buffer(PjRtBuffer),
+    BufferRef(&'a PjRtBuffer),
+}
+
+impl ExecuteInput<'_> {
+    fn as_buffer(&self) -> Option<&PjRtBuffer> {
+        match self {
+            Self::Literal(_) => None,
+            Self::Buffer(b) => Some(b),
+            Self::BufferRef(b) => Some(b),
+        }
+    }
+
+    fn reborrow(&self) -> ExecuteInput<'_> {
+        match self {
+            Self::Literal(l) => ExecuteInput::Literal(l),
+            Self::Buffer(b) => ExecuteInput::BufferRef(b),
+            Self::BufferRef(b) => ExecuteInput::BufferRef(b),
+        }
+    }
+}
+
+impl<'a> From<&'a Literal> for ExecuteInput<'a> {
+    fn from(l: &'a Literal) -> Self {
+        Self::Literal(l)
+    }
+}
+
+impl From<PjRtBuffer> for ExecuteInput<'_> {
+    fn from(b: PjRtBuffer) -> Self {
+        Self::Buffer(b)
+    }
+}
+
+impl<'a> From<&'a PjRtBuffer> for ExecuteInput<'a> {
+    fn from(b: &'a PjRtBuffer) -> Self {
+        Self::BufferRef(b)
+    }
+}
+
+/// A kernel produced by fusing several HLO instructions together, see
+/// [`PjRtLoadedExecutable::fusion_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FusedKernel {
+    /// The name of the fusion instruction, GPU backends use it as the name of the emitted kernel.
+    pub name: String,
+    /// The fusion kind, e.g. `kLoop`, `kInput`, `kOutput` or `kCustom`.
+    pub kind: String,
+    /// The names of the instructions that were fused into this kernel.
+    pub instructions: Vec<String>,
+    /// The raw backend config of the fusion instruction. This is usually some json, on GPU it
+    /// includes the kernel launch configuration when the backend records it, e.g. for triton
+    /// fusions.
+    pub backend_config: String,
+}
+
+/// The fusion decisions made when compiling an executable.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FusionReport {
+    pub kernels: Vec<FusedKernel>,
+}
+
+impl FusionReport {
+    /// The kernel that the instruction named `instruction` was fused into, `None` if this
+    /// instruction was not fused.
+    pub fn kernel_for(&self, instruction: &str) -> Option<&FusedKernel> {
+        self.kernels.iter().find(|k| k.instructions.iter().any(|i| i == instruction))
+    }
+}
+
+/// The kind of low level code that can be retrieved from a compiled executable, see
+/// [`PjRtLoadedExecutable::backend_ir`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendIrKind {
+    /// The LLVM IR module, only available on CPU and only when the IR is embedded in the
+    /// executable, i.e. when running with `XLA_FLAGS=--xla_embed_ir_in_executable`.
+    LlvmIr = 0,
+    /// The PTX code, only available on CUDA GPUs. CPU executables are JIT-compiled and do not
+    /// keep their object code around so there is no CPU equivalent.
+    Ptx = 1,
+}
+
+/// The priority of an execution relative to the other executions on the same device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExecutePriority {
+    /// Latency critical executions, e.g. interactive requests.
+    High,
+    #[default]
+    Normal,
+    /// Bulk executions that can be delayed, e.g. offline batch jobs.
+    Low,
+}
+
+/// The options used by [`PjRtLoadedExecutable::execute_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecuteOptions {
+    priority: ExecutePriority,
+}
+
+impl ExecuteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run on an execution stream with the given priority. Executing with a priority other than
+    /// [`ExecutePriority::Normal`] returns an error on the platforms where
+    /// [`super::PlatformCapabilities::supports_execute_priority`] is false.
+    pub fn priority(mut self, priority: ExecutePriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn get_priority(&self) -> ExecutePriority {
+        self.priority
+    }
+}
 
 pub struct PjRtLoadedExecutable {
     pub(super) exe: c_lib::pjrt_loaded_executable,
@@ -12,6 +205,108 @@ impl PjRtLoadedExecutable {
         &self.client
     }
 
+    /// The fusions performed by the HLO optimization passes when compiling this executable. The
+    /// instruction names are the ones of the optimized module so they may differ from the names
+    /// in the module that was compiled.
+    pub fn fusion_report(&self) -> Result<FusionReport> {
+        let mut n = 0;
+        let mut names = std::ptr::null_mut();
+        let mut kinds = std::ptr::null_mut();
+        let mut fused = std::ptr::null_mut();
+        let mut backend_configs = std::ptr::null_mut();
+        let status = unsafe {
+            c_lib::pjrt_loaded_executable_fusions(
+                self.exe,
+                &mut n,
+                &mut names,
+                &mut kinds,
+                &mut fused,
+                &mut backend_configs,
+            )
+        };
+        super::handle_status(status)?;
+        let mut kernels = Vec::with_capacity(n);
+        unsafe {
+            for i in 0..n {
+                let instructions = super::c_ptr_to_string(*fused.add(i));
+                kernels.push(FusedKernel {
+                    name: super::c_ptr_to_string(*names.add(i)),
+                    kind: super::c_ptr_to_string(*kinds.add(i)),
+                    instructions: instructions
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_string())
+                        .collect(),
+                    backend_config: super::c_ptr_to_string(*backend_configs.add(i)),
+                })
+            }
+            for ptr in [names, kinds, fused, backend_configs] {
+                libc::free(ptr as *mut libc::c_void);
+            }
+        }
+        Ok(FusionReport { kernels })
+    }
+
+    /// The code generated by the backend for this executable. An error is returned if this kind
+    /// of code is not available for the backend of the executable.
+    pub fn backend_ir(&self, kind: BackendIrKind) -> Result<String> {
+        let mut ptr = std::ptr::null_mut();
+        let status =
+            unsafe { c_lib::pjrt_loaded_executable_backend_ir(self.exe, kind as i32, &mut ptr) };
+        super::handle_status(status)?;
+        Ok(unsafe { super::c_ptr_to_string(ptr) })
+    }
+
+    /// The cost estimates computed by the compiler for this executable, e.g. `flops` or
+    /// `bytes accessed`. Entries that are not numeric are skipped.
+    pub fn cost_analysis(&self) -> Result<std::collections::HashMap<String, f64>> {
+        let mut n = 0;
+        let mut keys = std::ptr::null_mut();
+        let mut values = std::ptr::null_mut();
+        let status = unsafe {
+            c_lib::pjrt_loaded_executable_cost_analysis(self.exe, &mut n, &mut keys, &mut values)
+        };
+        super::handle_status(status)?;
+        let attributes = unsafe { super::c_attributes_to_map(n, keys, values) };
+        Ok(attributes.into_iter().filter_map(|(k, v)| Some((k, v.parse().ok()?))).collect())
+    }
+
+    // Take ownership of a null terminated array of buffers returned by the C api, the array is
+    // freed.
+    unsafe fn collect_outputs(&self, outputs: *mut c_lib::pjrt_buffer) -> Vec<PjRtBuffer> {
+        let mut vec = vec![];
+        loop {
+            let buffer = *outputs.add(vec.len());
+            if buffer.is_null() {
+                break;
+            }
+            vec.push(PjRtBuffer { buffer, client: self.client.clone() });
+        }
+        libc::free(outputs as *mut libc::c_void);
+        vec
+    }
+
+    // Report the execution to the metrics hooks once all of its outputs are ready.
+    fn report_execute_finished(start: Instant, outputs: &[Vec<PjRtBuffer>]) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        if !metrics::is_enabled() {
+            return;
+        }
+        let count = outputs.iter().map(|o| o.len()).sum();
+        if count == 0 {
+            return metrics::execute_finished(start.elapsed());
+        }
+        let remaining = std::sync::Arc::new(AtomicUsize::new(count));
+        for output in outputs.iter().flatten() {
+            let remaining = remaining.clone();
+            output.on_ready(move || {
+                if remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    metrics::execute_finished(start.elapsed())
+                }
+            })
+        }
+    }
+
     fn process_execute_outputs(
         &self,
         outputs: *mut *mut c_lib::pjrt_buffer,
@@ -19,48 +314,282 @@ impl PjRtLoadedExecutable {
         unsafe {
             let mut vec = vec![];
             loop {
-                let outputs = *outputs.add(vec.len());
-                if outputs.is_null() {
+                let replica_outputs = *outputs.add(vec.len());
+                if replica_outputs.is_null() {
                     break;
                 }
-                let mut replica_vec = vec![];
-                loop {
-                    let buffer = *outputs.add(replica_vec.len());
-                    if buffer.is_null() {
-                        break;
-                    }
-                    replica_vec.push(PjRtBuffer { buffer, client: self.client.clone() });
-                }
-                libc::free(outputs as *mut libc::c_void);
-                vec.push(replica_vec);
+                vec.push(self.collect_outputs(replica_outputs));
             }
             libc::free(outputs as *mut libc::c_void);
             vec
         }
     }
 
-    pub fn execute<L: std::borrow::Borrow<Literal>>(
-        &self,
-        args: &[L],
-    ) -> Result<Vec<Vec<PjRtBuffer>>> {
+    /// Run the executable on some arguments, the arguments can be literals or plain scalars and
+    /// slices that are converted to literals, see [`IntoExecuteArg`].
+    pub fn execute<L: IntoExecuteArg>(&self, args: &[L]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let start = std::time::Instant::now();
         let mut outputs = std::ptr::null_mut();
-        let args: Vec<_> = args.iter().map(|x| x.borrow().0).collect();
+        let args = args.iter().map(|x| x.to_literal()).collect::<Result<Vec<_>>>()?;
+        let bytes: usize = args.iter().map(|x| x.size_bytes()).sum();
+        let args: Vec<_> = args.iter().map(|x| x.0).collect();
         let status =
             unsafe { c_lib::execute(self.exe, args.as_ptr(), args.len() as i32, &mut outputs) };
         super::handle_status(status)?;
-        Ok(self.process_execute_outputs(outputs))
+        metrics::transfer(bytes, TransferDirection::HostToDevice);
+        let outputs = self.process_execute_outputs(outputs);
+        Self::report_execute_finished(start, &outputs);
+        Ok(outputs)
+    }
+
+    /// Run several independent executions of this executable, `args` holds the arguments for
+    /// each execution. All the arguments are transferred to the device before enqueuing the
+    /// executions, which avoids a synchronization per execution compared to calling
+    /// [`PjRtLoadedExecutable::execute`] in a loop. The results are returned in the same order
+    /// as `args`.
+    pub fn execute_many<L: IntoExecuteArg>(
+        &self,
+        args: &[Vec<L>],
+    ) -> Result<Vec<Vec<Vec<PjRtBuffer>>>> {
+        let ninputs = args.first().map_or(0, |a| a.len());
+        if let Some(a) = args.iter().find(|a| a.len() != ninputs) {
+            Err(Error::WrongArgumentCount { expected: ninputs, got: a.len() })?
+        }
+        if args.is_empty() {
+            return Ok(vec![]);
+        }
+        let start = std::time::Instant::now();
+        let mut outputs = std::ptr::null_mut();
+        let literals = args.iter().flatten().map(|x| x.to_literal()).collect::<Result<Vec<_>>>()?;
+        let bytes: usize = literals.iter().map(|x| x.size_bytes()).sum();
+        let inputs: Vec<_> = literals.iter().map(|x| x.0).collect();
+        let status = unsafe {
+            c_lib::execute_many(
+                self.exe,
+                inputs.as_ptr(),
+                ninputs as i32,
+                args.len() as i32,
+                &mut outputs,
+            )
+        };
+        super::handle_status(status)?;
+        metrics::transfer(bytes, TransferDirection::HostToDevice);
+        let mut results = Vec::with_capacity(args.len());
+        unsafe {
+            for i in 0..args.len() {
+                results.push(self.process_execute_outputs(*outputs.add(i)))
+            }
+            libc::free(outputs as *mut libc::c_void);
+        }
+        for result in results.iter() {
+            Self::report_execute_finished(start, result)
+        }
+        Ok(results)
     }
 
     pub fn execute_b<L: std::borrow::Borrow<PjRtBuffer>>(
         &self,
         args: &[L],
     ) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let start = std::time::Instant::now();
         let mut outputs = std::ptr::null_mut();
+        for (index, arg) in args.iter().enumerate() {
+            self.client.check_same(arg.borrow().client(), &format!("argument {index}"))?
+        }
         let args: Vec<_> = args.iter().map(|x| x.borrow().buffer).collect();
         let status =
             unsafe { c_lib::execute_b(self.exe, args.as_ptr(), args.len() as i32, &mut outputs) };
         super::handle_status(status)?;
-        Ok(self.process_execute_outputs(outputs))
+        let outputs = self.process_execute_outputs(outputs);
+        Self::report_execute_finished(start, &outputs);
+        Ok(outputs)
+    }
+
+    // Reject the options that cannot be honored by the client of this executable.
+    fn check_options(&self, options: &ExecuteOptions) -> Result<()> {
+        if options.priority != ExecutePriority::Normal
+            && !self.client.platform_capabilities().supports_execute_priority
+        {
+            Err(Error::UnsupportedExecutePriority {
+                priority: format!("{:?}", options.priority),
+                platform: self.client.platform_name(),
+            })?
+        }
+        Ok(())
+    }
+
+    /// Same as [`PjRtLoadedExecutable::execute`] with some execution options.
+    pub fn execute_with_options<L: IntoExecuteArg>(
+        &self,
+        args: &[L],
+        options: &ExecuteOptions,
+    ) -> Result<Vec<Vec<PjRtBuffer>>> {
+        self.check_options(options)?;
+        self.execute(args)
+    }
+
+    /// Same as [`PjRtLoadedExecutable::execute_b`] with some execution options.
+    pub fn execute_b_with_options<L: std::borrow::Borrow<PjRtBuffer>>(
+        &self,
+        args: &[L],
+        options: &ExecuteOptions,
+    ) -> Result<Vec<Vec<PjRtBuffer>>> {
+        self.check_options(options)?;
+        self.execute_b(args)
+    }
+
+    /// The devices on which this executable runs, there is one device per replica.
+    pub fn addressable_devices(&self) -> Vec<PjRtDevice<'_>> {
+        let device_count =
+            unsafe { c_lib::pjrt_loaded_executable_addressable_device_count(self.exe) } as usize;
+        let mut device_ptrs = vec![std::ptr::null_mut(); device_count];
+        unsafe {
+            c_lib::pjrt_loaded_executable_addressable_devices(self.exe, device_ptrs.as_mut_ptr())
+        };
+        device_ptrs.into_iter().map(|device| PjRtDevice { device, marker: PhantomData }).collect()
+    }
+
+    /// Run the executable once on `example_args` and discard the results. The first execution
+    /// of an executable triggers some lazy initialization such as loading the kernels on the
+    /// device or autotuning, warming up at load time avoids paying this cost on the first actual
+    /// request. This waits for the execution to complete and returns its duration.
+    pub fn warmup<L: IntoExecuteArg>(&self, example_args: &[L]) -> Result<Duration> {
+        let start = Instant::now();
+        let outputs = self.execute(example_args)?;
+        for output in outputs.iter().flatten() {
+            output.block_until_ready()?
+        }
+        Ok(start.elapsed())
+    }
+
+    /// Same as [`PjRtLoadedExecutable::warmup`] but with one execution on each of the
+    /// [`PjRtLoadedExecutable::addressable_devices`]. The example arguments are uploaded to each
+    /// device and all the replicas are launched together, as required by the computations using
+    /// collectives. This returns for each device the time until its outputs were ready, in the
+    /// order of the devices.
+    pub fn warmup_devices<L: IntoExecuteArg>(&self, example_args: &[L]) -> Result<Vec<Duration>> {
+        let start = Instant::now();
+        let literals = example_args.iter().map(|x| x.to_literal()).collect::<Result<Vec<_>>>()?;
+        let devices = self.addressable_devices();
+        let buffers = devices
+            .iter()
+            .flat_map(|device| {
+                literals.iter().map(|l| self.client.buffer_from_host_literal(Some(device), l))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let inputs: Vec<_> = buffers.iter().map(|x| x.buffer).collect();
+        let mut outputs = std::ptr::null_mut();
+        let status = unsafe {
+            c_lib::execute_b_replicated(
+                self.exe,
+                inputs.as_ptr(),
+                literals.len() as i32,
+                devices.len() as i32,
+                &mut outputs,
+            )
+        };
+        super::handle_status(status)?;
+        let outputs = self.process_execute_outputs(outputs);
+        let mut durations = vec![];
+        for device_outputs in outputs.iter() {
+            for output in device_outputs.iter() {
+                output.block_until_ready()?
+            }
+            durations.push(start.elapsed())
+        }
+        metrics::execute_finished(start.elapsed());
+        Ok(durations)
+    }
+
+    /// Run the executable on a mix of literals and device buffers. The literals are transferred
+    /// to the default device first, the buffers must belong to the client of this executable.
+    pub fn execute_inputs(&self, args: &[ExecuteInput<'_>]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let uploaded = args
+            .iter()
+            .map(|arg| match arg {
+                ExecuteInput::Literal(l) => self.client.buffer_from_host_literal(None, l).map(Some),
+                ExecuteInput::Buffer(_) | ExecuteInput::BufferRef(_) => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Each argument is either a buffer or has been uploaded above.
+        let buffers: Vec<&PjRtBuffer> = args
+            .iter()
+            .zip(uploaded.iter())
+            .filter_map(|(arg, uploaded)| arg.as_buffer().or(uploaded.as_ref()))
+            .collect();
+        self.execute_b(&buffers)
+    }
+
+    /// Permanently associate some buffers, typically the model weights, with some parameter
+    /// slots. The resulting executable only takes the remaining arguments when run.
+    pub fn bind(self, mut bindings: Vec<(usize, PjRtBuffer)>) -> Result<BoundExecutable> {
+        bindings.sort_by_key(|(index, _)| *index);
+        for (i, (index, buffer)) in bindings.iter().enumerate() {
+            if i > 0 && bindings[i - 1].0 == *index {
+                Err(Error::DuplicateParameterBinding { index: *index })?
+            }
+            self.client.check_same(buffer.client(), &format!("bound parameter {index}"))?
+        }
+        Ok(BoundExecutable { exe: self, bindings })
+    }
+}
+
+/// An executable with some of its parameters bound to device buffers, see
+/// [`PjRtLoadedExecutable::bind`].
+pub struct BoundExecutable {
+    exe: PjRtLoadedExecutable,
+    // Sorted by parameter index.
+    bindings: Vec<(usize, PjRtBuffer)>,
+}
+
+impl BoundExecutable {
+    /// The underlying executable.
+    pub fn executable(&self) -> &PjRtLoadedExecutable {
+        &self.exe
+    }
+
+    /// The indexes of the bound parameters in increasing order.
+    pub fn bound_parameters(&self) -> Vec<usize> {
+        self.bindings.iter().map(|(index, _)| *index).collect()
+    }
+
+    // Interleave the bound buffers with `args` so that each bound buffer ends up at its
+    // parameter index.
+    fn merge_args<'a>(&'a self, args: &'a [ExecuteInput<'_>]) -> Result<Vec<ExecuteInput<'a>>> {
+        let nargs = self.bindings.len() + args.len();
+        if let Some((index, _)) = self.bindings.last() {
+            if *index >= nargs {
+                Err(Error::WrongArgumentCount {
+                    expected: index + 1 - self.bindings.len(),
+                    got: args.len(),
+                })?
+            }
+        }
+        let mut bindings = self.bindings.iter().peekable();
+        let mut args = args.iter();
+        let mut merged = Vec::with_capacity(nargs);
+        for i in 0..nargs {
+            match bindings.next_if(|(index, _)| *index == i) {
+                Some((_, buffer)) => merged.push(ExecuteInput::BufferRef(buffer)),
+                None => merged.extend(args.next().map(|arg| arg.reborrow())),
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Run the executable, `args` holds the values for the parameters that are not bound in
+    /// increasing parameter index order.
+    pub fn execute_inputs(&self, args: &[ExecuteInput<'_>]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        self.exe.execute_inputs(&self.merge_args(args)?)
+    }
+
+    /// Run the executable on some device buffers for the parameters that are not bound.
+    pub fn execute_b<L: std::borrow::Borrow<PjRtBuffer>>(
+        &self,
+        args: &[L],
+    ) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let args: Vec<_> = args.iter().map(|arg| ExecuteInput::BufferRef(arg.borrow())).collect();
+        self.exe.execute_inputs(&self.merge_args(&args)?)
     }
 }
 
@@ -0,0 +1,136 @@
+//! Symbolic dimensions, these let a single graph definition be used with multiple sizes along
+//! some dimensions, e.g. the batch size.
+//!
+//! Each symbol is declared on the builder before being used in [`XlaBuilder::parameter_dyn`].
+//! A symbol can either be refined to a known size, the resulting dimensions are then static, or
+//! be given an upper bound in which case the dimensions are dynamic and the actual size is only
+//! known at runtime, see [`XlaOp::dimension_size`].
+//!
+//! ```ignore
+//! let builder = xla::XlaBuilder::new("model");
+//! builder.set_symbol_bound("batch", 64)?;
+//! let x = builder.parameter_dyn(0, f32::TY, &["batch".into(), 128.into()], "x")?;
+//! let batch_size = x.dimension_size(0)?;
+//! ```
+use super::{XlaBuilder, XlaOp};
+use crate::{c_lib, Error, Result};
+
+/// A dimension that is either known or refers to a symbol declared on the builder.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dim {
+    Static(i64),
+    Sym(String),
+}
+
+impl From<i64> for Dim {
+    fn from(size: i64) -> Self {
+        Dim::Static(size)
+    }
+}
+
+impl From<&str> for Dim {
+    fn from(name: &str) -> Self {
+        Dim::Sym(name.to_string())
+    }
+}
+
+impl From<String> for Dim {
+    fn from(name: String) -> Self {
+        Dim::Sym(name)
+    }
+}
+
+/// How the dimensions using a symbol are lowered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolSize {
+    /// The symbol has been refined to this size, the dimensions are static.
+    Static(i64),
+    /// The dimensions are dynamic with a size of at most this bound.
+    Bounded(i64),
+}
+
+impl XlaBuilder {
+    fn declare_symbol(&self, name: &str, size: SymbolSize) -> Result<()> {
+        let mut symbols = self.0 .1.borrow_mut();
+        match symbols.get(name) {
+            // Parameters may already use the previous declaration so it cannot be changed.
+            Some(previous) if *previous != size => Err(Error::InvalidSymbol {
+                name: name.to_string(),
+                msg: "the symbol has already been declared with a different size",
+            })?,
+            _ => {}
+        }
+        symbols.insert(name.to_string(), size);
+        Ok(())
+    }
+
+    /// Refine a symbol to a known size, the dimensions using it are static.
+    pub fn set_symbol_size(&self, name: &str, size: i64) -> Result<()> {
+        if size < 0 {
+            Err(Error::InvalidSymbol { name: name.to_string(), msg: "negative size" })?
+        }
+        self.declare_symbol(name, SymbolSize::Static(size))
+    }
+
+    /// Declare a symbol with an upper bound, the dimensions using it are dynamic.
+    pub fn set_symbol_bound(&self, name: &str, bound: i64) -> Result<()> {
+        if bound <= 0 {
+            Err(Error::InvalidSymbol { name: name.to_string(), msg: "non-positive bound" })?
+        }
+        self.declare_symbol(name, SymbolSize::Bounded(bound))
+    }
+
+    /// How a symbol has been declared on this builder, if at all.
+    pub fn symbol_size(&self, name: &str) -> Option<SymbolSize> {
+        self.0 .1.borrow().get(name).copied()
+    }
+
+    /// Create an input node like [`XlaBuilder::parameter`], some of the dimensions can refer to
+    /// symbols that must have been declared on the builder beforehand.
+    pub fn parameter_dyn(
+        &self,
+        parameter_number: i64,
+        ty: super::ElementType,
+        dims: &[Dim],
+        name: &str,
+    ) -> Result<XlaOp> {
+        let symbols = self.0 .1.borrow();
+        // The C api uses negative dimensions for bounded dynamic dimensions.
+        let dims = dims
+            .iter()
+            .map(|dim| match dim {
+                Dim::Static(size) if *size < 0 => {
+                    Err(Error::InvalidDims { dims: vec![*size], msg: "negative dimension" })
+                }
+                Dim::Static(size) => Ok(*size),
+                Dim::Sym(name) => match symbols.get(name) {
+                    Some(SymbolSize::Static(size)) => Ok(*size),
+                    Some(SymbolSize::Bounded(bound)) => Ok(-bound),
+                    None => Err(Error::InvalidSymbol {
+                        name: name.to_string(),
+                        msg: "the symbol has not been declared",
+                    }),
+                },
+            })
+            .collect::<Result<Vec<_>>>()?;
+        drop(symbols);
+        self.parameter(parameter_number, ty, &dims, name)
+    }
+}
+
+impl XlaOp {
+    /// The runtime size of dimension `dim` as a scalar `S32` node. For static dimensions this is
+    /// a constant.
+    pub fn dimension_size(&self, dim: i64) -> Result<Self> {
+        let op = unsafe { c_lib::op_get_dimension_size(self.op, dim) };
+        self.wrap(op)
+    }
+
+    /// Set the runtime size of dimension `dim` to the scalar `S32` node `size`, the dimension
+    /// becomes dynamic and its current size acts as the bound.
+    pub fn set_dimension_size(&self, size: &XlaOp, dim: i64) -> Result<Self> {
+        self.builder.check_op(size)?;
+        let op = unsafe { c_lib::op_set_dimension_size(self.op, size.op, dim) };
+        self.wrap(op)
+    }
+}
@@ -2,10 +2,16 @@ use super::{
     handle_status, FromPrimitive, Literal, NativeType, PrimitiveType, Shape, XlaComputation, XlaOp,
 };
 use crate::{c_lib, Error, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
-/// A builder is used to keep track of a computation graph while it's being built.
-pub(super) struct XlaBuilderInternal(c_lib::xla_builder);
+/// A builder is used to keep track of a computation graph while it's being built. The symbolic
+/// dimensions declared on the builder are tracked alongside the C++ builder.
+pub(super) struct XlaBuilderInternal(
+    c_lib::xla_builder,
+    pub(super) RefCell<HashMap<String, super::SymbolSize>>,
+);
 
 #[derive(Clone)]
 pub struct XlaBuilder(Rc<XlaBuilderInternal>);
@@ -14,17 +20,33 @@ impl XlaBuilder {
     /// Create a new builder with the associated name, the name is only used for debugging
     /// purposes.
     pub fn new(name: &str) -> XlaBuilder {
-        let name = std::ffi::CString::new(name).unwrap();
+        let name = super::display_c_string(name);
         let xla_builder = unsafe { c_lib::xla_builder_create(name.as_ptr()) };
-        XlaBuilder(Rc::new(XlaBuilderInternal(xla_builder)))
+        XlaBuilder(Rc::new(XlaBuilderInternal(xla_builder, RefCell::default())))
     }
 
-    fn ptr(&self) -> c_lib::xla_builder {
+    pub(super) fn ptr(&self) -> c_lib::xla_builder {
         self.0 .0
     }
 
+    /// Whether `self` and `other` are the same builder, ops can only be combined with ops
+    /// created by the same builder.
+    pub fn is_same(&self, other: &XlaBuilder) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    // Mixing ops from different builders is undefined behavior on the C++ side so this is checked
+    // before passing any op created by another node or builder.
+    pub(super) fn check_op(&self, op: &XlaOp) -> Result<()> {
+        if !self.is_same(&op.builder) {
+            Err(Error::BuilderMismatch)?
+        }
+        Ok(())
+    }
+
     /// Build a computation from the specified root node. This can only be called once.
     pub fn build(&self, op: &XlaOp) -> Result<XlaComputation> {
+        self.check_op(op)?;
         let mut result: c_lib::xla_computation = std::ptr::null_mut();
         let status = unsafe { c_lib::build(self.ptr(), op.op, &mut result) };
         handle_status(status)?;
@@ -81,7 +103,7 @@ impl XlaBuilder {
         dims: &[i64],
         name: &str,
     ) -> Result<XlaOp> {
-        let name = std::ffi::CString::new(name).unwrap();
+        let name = std::ffi::CString::new(name)?;
         let op = unsafe {
             c_lib::parameter(
                 self.ptr(),
@@ -97,7 +119,7 @@ impl XlaBuilder {
 
     /// Read a single value from the implicit streaming interface of the device.
     pub fn infeed(&self, ty: PrimitiveType, dims: &[i64], config: &str) -> Result<XlaOp> {
-        let config = std::ffi::CString::new(config).unwrap();
+        let config = std::ffi::CString::new(config)?;
         let op = unsafe {
             c_lib::infeed(self.ptr(), ty as i32, dims.len() as i32, dims.as_ptr(), config.as_ptr())
         };
@@ -106,7 +128,7 @@ impl XlaBuilder {
 
     pub fn parameter_s(&self, parameter_number: i64, shape: &Shape, name: &str) -> Result<XlaOp> {
         let c_shape = shape.c_shape()?;
-        let name = std::ffi::CString::new(name).unwrap();
+        let name = std::ffi::CString::new(name)?;
         let op = unsafe {
             c_lib::parameter_s(self.ptr(), parameter_number, c_shape.as_ptr(), name.as_ptr())
         };
@@ -137,8 +159,11 @@ impl XlaBuilder {
         let cols = match row_lens.as_slice() {
             [head, tail @ ..] => tail.iter().all(|x| x == head).then_some(*head),
             [] => Some(0),
-        }
-        .expect("all rows must have the same number of columns!");
+        };
+        let cols = match cols {
+            Some(cols) => cols,
+            None => Err(Error::RaggedRows { row_lens })?,
+        };
         let f = Vec::from(f).into_iter().flatten().cloned().collect::<Vec<T>>();
         let f = f.as_slice();
         let op = unsafe { T::constant_r2(self.ptr(), f.as_ptr(), rows, cols) };
@@ -174,9 +199,17 @@ impl XlaBuilder {
         self.wrap(op)
     }
 
+    /// A scalar node with the minimum finite value for the associated type, for floating point
+    /// types this is the lowest value that is not minus infinity.
+    pub fn min_finite_value(&self, ty: super::ElementType) -> Result<XlaOp> {
+        let op = unsafe { c_lib::op_min_finite_value(self.ptr(), ty.primitive_type() as i32) };
+        self.wrap(op)
+    }
+
     /// A constant node with the specified shape that holds increasing values starting from 0 along
     /// the iota dimension.
     pub fn iota(&self, ty: super::ElementType, dims: &[i64], iota_dimension: i64) -> Result<XlaOp> {
+        super::check_static_dims(dims)?;
         let op = unsafe {
             c_lib::op_iota(
                 self.ptr(),
@@ -197,21 +230,21 @@ impl XlaBuilder {
 
     /// An error node, using the 'internal error' error type.
     pub fn internal_error(&self, msg: &str) -> XlaOp {
-        let msg = std::ffi::CString::new(msg).unwrap();
+        let msg = super::display_c_string(msg);
         let op = unsafe { c_lib::op_internal_error(self.ptr(), msg.as_ptr()) };
         XlaOp { op, builder: self.clone() }
     }
 
     /// An error node, using the 'unknown error' error type.
     pub fn unknown_error(&self, msg: &str) -> XlaOp {
-        let msg = std::ffi::CString::new(msg).unwrap();
+        let msg = super::display_c_string(msg);
         let op = unsafe { c_lib::op_unknown_error(self.ptr(), msg.as_ptr()) };
         XlaOp { op, builder: self.clone() }
     }
 
     /// An error node, using the 'invalid argument error' error type.
     pub fn invalid_argument_error(&self, msg: &str) -> XlaOp {
-        let msg = std::ffi::CString::new(msg).unwrap();
+        let msg = super::display_c_string(msg);
         let op = unsafe { c_lib::op_invalid_argument_error(self.ptr(), msg.as_ptr()) };
         XlaOp { op, builder: self.clone() }
     }
@@ -227,6 +260,7 @@ impl XlaBuilder {
 
     /// The shape associated with this op.
     pub fn get_shape(&self, op: &XlaOp) -> Result<Shape> {
+        self.check_op(op)?;
         let mut out: c_lib::shape = std::ptr::null_mut();
         let status = unsafe { c_lib::get_shape(self.ptr(), op.op, &mut out) };
         handle_status(status)?;
@@ -234,6 +268,20 @@ impl XlaBuilder {
         c_shape.shape()
     }
 
+    /// The shape associated with this op, or `None` if the op is invalid. Contrary to
+    /// [`XlaBuilder::get_shape`], no error value is created when the shape is not available which
+    /// makes this suitable for probing shapes in a loop.
+    pub fn infer_shape(&self, op: &XlaOp) -> Option<Shape> {
+        if !self.is_same(&op.builder) {
+            return None;
+        }
+        let mut out: c_lib::shape = std::ptr::null_mut();
+        if !unsafe { c_lib::try_get_shape(self.ptr(), op.op, &mut out) } {
+            return None;
+        }
+        super::shape::CShape::from_ptr(out).shape().ok()
+    }
+
     /// The dimension sizes associated with this op.
     pub fn get_dims(&self, op: &XlaOp) -> Result<Vec<usize>> {
         let rank = self.get_dimensions_size(op)?;
@@ -245,6 +293,7 @@ impl XlaBuilder {
 
     /// The element type associated with this op.
     pub fn get_primitive_type(&self, op: &XlaOp) -> Result<super::PrimitiveType> {
+        self.check_op(op)?;
         let mut ty = 0i32;
         let status = unsafe { c_lib::get_element_type(self.ptr(), op.op, &mut ty) };
         handle_status(status)?;
@@ -253,6 +302,7 @@ impl XlaBuilder {
 
     /// The number of dimensions (a.k.a the rank) associated with this op.
     pub fn get_dimensions_size(&self, op: &XlaOp) -> Result<usize> {
+        self.check_op(op)?;
         let mut dsize = 0i32;
         let status = unsafe { c_lib::get_dimensions_size(self.ptr(), op.op, &mut dsize) };
         handle_status(status)?;
@@ -261,10 +311,45 @@ impl XlaBuilder {
 
     /// Build a tuple from multiple operands.
     pub fn tuple<B: std::borrow::Borrow<XlaOp>>(&self, args: &[B]) -> Result<XlaOp> {
+        for arg in args.iter() {
+            self.check_op(arg.borrow())?
+        }
         let args: Vec<_> = args.iter().map(|a| a.borrow().op).collect();
         let op = unsafe { c_lib::op_tuple(self.ptr(), args.as_ptr(), args.len()) };
         self.wrap(op)
     }
+
+    /// Sort `operands` along the dimension `dim`, the operands must have the same dimensions and
+    /// are permuted together, e.g. to sort some values by key. The result is a tuple node when
+    /// there are multiple operands.
+    ///
+    /// `comparator` has two scalar parameters per operand: parameters `2 * i` and `2 * i + 1`
+    /// hold the two elements of operand `i` being compared. It returns a `PRED` node which is
+    /// true when the first elements have to be placed before the second ones, e.g. sorting a
+    /// single operand in decreasing order can use
+    /// `XlaComputation::from_scalar_fn("gt", ty, XlaOp::gt)`. When `is_stable` is set, elements
+    /// that compare equal keep their original order.
+    pub fn sort<B: std::borrow::Borrow<XlaOp>>(
+        &self,
+        operands: &[B],
+        comparator: &XlaComputation,
+        dim: i64,
+        is_stable: bool,
+    ) -> Result<XlaOp> {
+        let (first, others) = match operands.split_first() {
+            Some((first, others)) => (first.borrow(), others),
+            None => Err(Error::WrongArgumentCount { expected: 1, got: 0 })?,
+        };
+        for operand in operands.iter() {
+            self.check_op(operand.borrow())?
+        }
+        let dim = first.normalize_index(dim)?;
+        let others: Vec<_> = others.iter().map(|a| a.borrow().op).collect();
+        let op = unsafe {
+            c_lib::op_sort(first.op, others.as_ptr(), others.len(), comparator.0, dim, is_stable)
+        };
+        self.wrap(op)
+    }
 }
 
 impl Drop for XlaBuilderInternal {
@@ -8,15 +8,32 @@
 use std::collections::HashMap;
 
 use super::{
-    ArrayShape, HloModuleProto, Literal, PrimitiveType, Shape, XlaBuilder, XlaComputation,
+    ArrayShape, BinaryOp, ChannelHandle, CollectiveDeviceList, HloModuleProto, Literal, NanMode,
+    PrimitiveType, RandomAlgorithm, Shape, XlaBuilder, XlaComputation,
 };
 use crate::{c_lib, Error, Result};
 
+/// A node in a computation graph. Each op keeps a reference to the builder that created it so the
+/// builder stays alive as long as some of its ops are used. Ops can only be combined with ops
+/// from the same builder, [`Error::BuilderMismatch`] is returned otherwise.
 pub struct XlaOp {
     pub(super) op: c_lib::xla_op,
     pub(super) builder: XlaBuilder,
 }
 
+/// The dimension numbers of [`XlaOp::scatter`], see the XLA scatter semantics for details.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ScatterDimensionNumbers {
+    /// The dimensions of the updates that hold the update windows.
+    pub update_window_dims: Vec<i64>,
+    /// The dimensions of size 1 of the operand that are not part of the update windows.
+    pub inserted_window_dims: Vec<i64>,
+    /// Maps each coordinate of the scatter indices to a dimension of the operand.
+    pub scatter_dims_to_operand_dims: Vec<i64>,
+    /// The dimension of the scatter indices that contains the coordinates.
+    pub index_vector_dim: i64,
+}
+
 macro_rules! extract_dims {
     ($fn_name:ident, $cnt:tt, $dims:expr, $out_type:ty) => {
         #[allow(clippy::redundant_closure_call)]
@@ -36,6 +53,7 @@ macro_rules! extract_dims {
 macro_rules! binary_op {
     ($func_name:ident, $expression:expr) => {
         pub fn $func_name(&self, op: &XlaOp) -> Result<Self> {
+            self.builder.check_op(op)?;
             let op = unsafe { $expression(self.op, op.op) };
             self.wrap(op)
         }
@@ -68,6 +86,77 @@ impl XlaOp {
         &self.builder
     }
 
+    /// Attach a frontend attribute to the instruction of this node, replacing any previous value
+    /// for `key`. These attributes are forwarded to the compiler, e.g. to mark pipeline stages.
+    pub fn set_frontend_attribute(&self, key: &str, value: &str) -> Result<()> {
+        let key = std::ffi::CString::new(key)?;
+        let value = std::ffi::CString::new(value)?;
+        let status =
+            unsafe { c_lib::op_set_frontend_attribute(self.op, key.as_ptr(), value.as_ptr()) };
+        super::handle_status(status)?;
+        Ok(())
+    }
+
+    /// Attach a sharding to the instruction of this node, replacing any previous one. The SPMD
+    /// partitioner uses it to split the value over the devices, see [`crate::mesh::Mesh`].
+    pub fn with_sharding(self, sharding: &crate::mesh::OpSharding) -> Result<Self> {
+        let dims = &sharding.tile_assignment_dimensions;
+        let devices = &sharding.tile_assignment_devices;
+        let status = unsafe {
+            c_lib::op_set_sharding(
+                self.op,
+                sharding.ty as i32,
+                dims.as_ptr(),
+                dims.len(),
+                devices.as_ptr(),
+                devices.len(),
+                sharding.replicate_on_last_tile_dim,
+            )
+        };
+        super::handle_status(status)?;
+        Ok(self)
+    }
+
+    /// Set the backend config of the instruction of this node, this is a backend specific json
+    /// string, e.g. a gpu backend config selecting a fused attention kernel. The config is only
+    /// checked by the backend when compiling.
+    pub fn set_backend_config(&self, json: &str) -> Result<()> {
+        let json = std::ffi::CString::new(json)?;
+        let status = unsafe { c_lib::op_set_backend_config(self.op, json.as_ptr()) };
+        super::handle_status(status)?;
+        Ok(())
+    }
+
+    /// Whether the value of this node can be computed without any parameter, e.g. because it
+    /// only depends on constants and on shapes.
+    pub fn is_constant(&self) -> Result<bool> {
+        let mut is_constant = false;
+        let status = unsafe { c_lib::op_is_constant(self.op, &mut is_constant) };
+        super::handle_status(status)?;
+        Ok(is_constant)
+    }
+
+    /// Compute the value of this node on the host using the HLO evaluator, this returns an error
+    /// if the node depends on some parameters. The evaluator is slow so this is meant for small
+    /// subgraphs, e.g. shape arithmetic or lookup tables.
+    pub fn evaluate_constant(&self) -> Result<Literal> {
+        let mut result: c_lib::literal = std::ptr::null_mut();
+        let status = unsafe { c_lib::op_evaluate_constant(self.op, &mut result) };
+        super::handle_status(status)?;
+        Ok(Literal(result))
+    }
+
+    /// Replace this node with a constant holding its value when it does not depend on any
+    /// parameter, otherwise this returns the node unchanged. The replaced subgraph is left
+    /// unused and gets removed when compiling.
+    pub fn const_eval(&self) -> Result<Self> {
+        if !self.is_constant()? {
+            return Ok(self.clone());
+        }
+        let literal = self.evaluate_constant()?;
+        self.builder.constant_literal(&literal)
+    }
+
     binary_op!(add_, c_lib::op_add);
     binary_op!(sub_, c_lib::op_sub);
     binary_op!(mul_, c_lib::op_mul);
@@ -80,6 +169,9 @@ impl XlaOp {
     binary_op!(xor, c_lib::op_xor);
     binary_op!(atan2, c_lib::op_atan2);
     binary_op!(pow, c_lib::op_pow);
+    binary_op!(shift_left, c_lib::op_shift_left);
+    binary_op!(shift_right_arithmetic, c_lib::op_shift_right_arithmetic);
+    binary_op!(shift_right_logical, c_lib::op_shift_right_logical);
     binary_op!(dot, c_lib::op_dot);
     binary_op!(eq, c_lib::op_eq);
     binary_op!(ne, c_lib::op_ne);
@@ -109,12 +201,144 @@ impl XlaOp {
     unary_op!(rsqrt, c_lib::op_rsqrt);
     unary_op!(cbrt, c_lib::op_cbrt);
     unary_op!(is_finite, c_lib::op_is_finite);
+    unary_op!(erf, c_lib::op_erf);
+    unary_op!(erf_inv, c_lib::op_erf_inv);
     unary_op!(neg, c_lib::op_neg);
     unary_op!(lower_triangle, c_lib::op_lower_triangle);
     unary_op!(upper_triangle, c_lib::op_upper_triangle);
     unary_op!(copy, c_lib::op_copy);
     unary_op!(zeros_like, c_lib::op_zeros_like);
 
+    // Check that this node and `others` have some element type accepted by `accept`.
+    fn check_element_types(
+        &self,
+        others: &[&XlaOp],
+        op: &'static str,
+        accept: fn(PrimitiveType) -> bool,
+    ) -> Result<()> {
+        for node in std::iter::once(self).chain(others.iter().copied()) {
+            self.builder.check_op(node)?;
+            let ty = node.primitive_type()?;
+            if !accept(ty) {
+                Err(Error::UnsupportedElementType { ty, op })?
+            }
+        }
+        Ok(())
+    }
+
+    fn is_pred(ty: PrimitiveType) -> bool {
+        ty == PrimitiveType::Pred
+    }
+
+    fn is_integer(ty: PrimitiveType) -> bool {
+        use PrimitiveType::*;
+        matches!(ty, S8 | S16 | S32 | S64 | U8 | U16 | U32 | U64)
+    }
+
+    /// Element-wise logical and, both nodes must have the `Pred` element type.
+    pub fn logical_and(&self, rhs: &XlaOp) -> Result<Self> {
+        self.check_element_types(&[rhs], "logical_and", Self::is_pred)?;
+        self.and(rhs)
+    }
+
+    /// Element-wise logical or, both nodes must have the `Pred` element type.
+    pub fn logical_or(&self, rhs: &XlaOp) -> Result<Self> {
+        self.check_element_types(&[rhs], "logical_or", Self::is_pred)?;
+        self.or(rhs)
+    }
+
+    /// Element-wise logical xor, both nodes must have the `Pred` element type.
+    pub fn logical_xor(&self, rhs: &XlaOp) -> Result<Self> {
+        self.check_element_types(&[rhs], "logical_xor", Self::is_pred)?;
+        self.xor(rhs)
+    }
+
+    /// Element-wise logical negation, the node must have the `Pred` element type.
+    pub fn logical_not(&self) -> Result<Self> {
+        self.check_element_types(&[], "logical_not", Self::is_pred)?;
+        self.not()
+    }
+
+    /// Element-wise bitwise and, both nodes must have an integer element type.
+    pub fn bitwise_and(&self, rhs: &XlaOp) -> Result<Self> {
+        self.check_element_types(&[rhs], "bitwise_and", Self::is_integer)?;
+        self.and(rhs)
+    }
+
+    /// Element-wise bitwise or, both nodes must have an integer element type.
+    pub fn bitwise_or(&self, rhs: &XlaOp) -> Result<Self> {
+        self.check_element_types(&[rhs], "bitwise_or", Self::is_integer)?;
+        self.or(rhs)
+    }
+
+    /// Element-wise bitwise xor, both nodes must have an integer element type.
+    pub fn bitwise_xor(&self, rhs: &XlaOp) -> Result<Self> {
+        self.check_element_types(&[rhs], "bitwise_xor", Self::is_integer)?;
+        self.xor(rhs)
+    }
+
+    /// Element-wise bitwise negation, the node must have an integer element type.
+    pub fn bitwise_not(&self) -> Result<Self> {
+        self.check_element_types(&[], "bitwise_not", Self::is_integer)?;
+        self.not()
+    }
+
+    fn is_float(ty: PrimitiveType) -> bool {
+        use PrimitiveType::*;
+        matches!(ty, F16 | Bf16 | F32 | F64)
+    }
+
+    /// Element-wise check for NaN values, the node must have a floating point element type.
+    pub fn is_nan(&self) -> Result<Self> {
+        self.check_element_types(&[], "is_nan", Self::is_float)?;
+        self.ne(self)
+    }
+
+    /// Element-wise check for positive or negative infinite values, the node must have a
+    /// floating point element type.
+    pub fn is_inf(&self) -> Result<Self> {
+        self.check_element_types(&[], "is_inf", Self::is_float)?;
+        let ty = self.primitive_type()?.element_type()?;
+        self.abs()?.eq(&self.builder.max_value(ty)?)
+    }
+
+    /// A scalar `PRED` node that is true when all the elements of this node are finite, e.g. to
+    /// skip an optimizer step when some gradients are infinite or NaN.
+    pub fn all_finite(&self) -> Result<Self> {
+        self.check_element_types(&[], "all_finite", Self::is_float)?;
+        let builder = XlaBuilder::new("And");
+        let x = builder.parameter(0, crate::ElementType::Pred, &[], "x")?;
+        let y = builder.parameter(1, crate::ElementType::Pred, &[], "y")?;
+        let and = x.and(&y)?.build()?;
+        let init_value = self.builder.one(crate::ElementType::Pred)?;
+        let dims: Vec<i64> = (0..self.rank()? as i64).collect();
+        self.is_finite()?.reduce(init_value, and, &dims, false)
+    }
+
+    /// Element-wise maximum with explicit NaN semantics, both nodes must have a floating point
+    /// element type and the same shape.
+    pub fn max_nan(&self, rhs: &XlaOp, mode: NanMode) -> Result<Self> {
+        self.check_element_types(&[rhs], "max_nan", Self::is_float)?;
+        self.select_nan(rhs, mode, self.max(rhs)?)
+    }
+
+    /// Element-wise minimum with explicit NaN semantics, both nodes must have a floating point
+    /// element type and the same shape.
+    pub fn min_nan(&self, rhs: &XlaOp, mode: NanMode) -> Result<Self> {
+        self.check_element_types(&[rhs], "min_nan", Self::is_float)?;
+        self.select_nan(rhs, mode, self.min(rhs)?)
+    }
+
+    // Replace the elements of `result` where `self` or `rhs` are NaN according to `mode`.
+    fn select_nan(&self, rhs: &XlaOp, mode: NanMode, result: XlaOp) -> Result<Self> {
+        let (lhs_nan, rhs_nan) = (self.is_nan()?, rhs.is_nan()?);
+        match mode {
+            // The sum of the operands is NaN as soon as one of them is NaN.
+            NanMode::Propagate => lhs_nan.or(&rhs_nan)?.select(&self.add_(rhs)?, &result),
+            NanMode::Ignore => lhs_nan.select(rhs, &rhs_nan.select(self, &result)?),
+        }
+    }
+
     /// Sigmoid activation function.
     ///
     /// This computes the element-wise sigmoid.
@@ -131,7 +355,7 @@ impl XlaOp {
 
     /// A node that applies the specified Einstein summation formula to this node.
     pub fn einsum1(&self, config: &str) -> Result<Self> {
-        let config = std::ffi::CString::new(config).unwrap();
+        let config = std::ffi::CString::new(config)?;
         let op = unsafe { c_lib::op_einsum1(self.op, config.as_ptr()) };
         self.wrap(op)
     }
@@ -139,7 +363,8 @@ impl XlaOp {
     /// A node that applies the specified Einstein summation formula to this node and the other
     /// argument node.
     pub fn einsum2(&self, rhs: &XlaOp, config: &str) -> Result<Self> {
-        let config = std::ffi::CString::new(config).unwrap();
+        self.builder.check_op(rhs)?;
+        let config = std::ffi::CString::new(config)?;
         let op = unsafe { c_lib::op_einsum2(self.op, rhs.op, config.as_ptr()) };
         self.wrap(op)
     }
@@ -296,11 +521,97 @@ impl XlaOp {
         dim: i64,
     ) -> Result<Self> {
         let dim = self.normalize_index(dim)?;
+        for arg in args.iter() {
+            self.builder.check_op(arg.borrow())?
+        }
         let args: Vec<_> = args.iter().map(|a| a.borrow().op).collect();
         let op = unsafe { c_lib::op_concat_in_dim(self.op, args.as_ptr(), args.len(), dim) };
         self.wrap(op)
     }
 
+    /// A slice of this node of size `slice_sizes`, the slice starts at the position given by
+    /// `start_indices` which are scalar integer nodes, one per dimension. The start indices are
+    /// clamped so that the slice fits in the original node.
+    pub fn dynamic_slice<B: std::borrow::Borrow<XlaOp>>(
+        &self,
+        start_indices: &[B],
+        slice_sizes: &[i64],
+    ) -> Result<Self> {
+        for index in start_indices.iter() {
+            self.builder.check_op(index.borrow())?
+        }
+        let start_indices: Vec<_> = start_indices.iter().map(|a| a.borrow().op).collect();
+        let op = unsafe {
+            c_lib::op_dynamic_slice(
+                self.op,
+                start_indices.as_ptr(),
+                start_indices.len(),
+                slice_sizes.as_ptr(),
+                slice_sizes.len(),
+            )
+        };
+        self.wrap(op)
+    }
+
+    /// Overwrite a slice of this node with `update`, the slice starts at the position given by
+    /// `start_indices` which are scalar integer nodes, one per dimension. The start indices are
+    /// clamped so that the slice fits in the original node.
+    pub fn dynamic_update_slice<B: std::borrow::Borrow<XlaOp>>(
+        &self,
+        update: &XlaOp,
+        start_indices: &[B],
+    ) -> Result<Self> {
+        self.builder.check_op(update)?;
+        for index in start_indices.iter() {
+            self.builder.check_op(index.borrow())?
+        }
+        let start_indices: Vec<_> = start_indices.iter().map(|a| a.borrow().op).collect();
+        let op = unsafe {
+            c_lib::op_dynamic_update_slice(
+                self.op,
+                update.op,
+                start_indices.as_ptr(),
+                start_indices.len(),
+            )
+        };
+        self.wrap(op)
+    }
+
+    /// Pad this node with `padding_value`, a scalar node of the same element type. `low` and
+    /// `high` have one element per dimension and give the amount of padding added before the
+    /// first element and after the last element, negative values remove elements instead.
+    /// `interior` gives the amount of padding added between consecutive elements.
+    pub fn pad(
+        &self,
+        padding_value: &XlaOp,
+        low: &[i64],
+        high: &[i64],
+        interior: &[i64],
+    ) -> Result<Self> {
+        self.builder.check_op(padding_value)?;
+        let rank = self.rank()?;
+        for config in [low, high, interior] {
+            if config.len() != rank {
+                Err(Error::UnexpectedNumberOfDims {
+                    expected: rank,
+                    got: config.len(),
+                    dims: config.to_vec(),
+                })?
+            }
+        }
+        let op = unsafe {
+            c_lib::op_pad(
+                self.op,
+                padding_value.op,
+                low.as_ptr(),
+                high.as_ptr(),
+                interior.as_ptr(),
+                rank,
+            )
+        };
+        self.wrap(op)
+    }
+
     /// Index into tuples.
     pub fn get_tuple_element(&self, index: i64) -> Result<Self> {
         let op = unsafe { c_lib::op_get_tuple_element(self.op, index) };
@@ -309,6 +620,8 @@ impl XlaOp {
 
     /// Clamp the values in the original node to be between `min` and `max`.
     pub fn clamp(&self, min: &Self, max: &Self) -> Result<Self> {
+        self.builder.check_op(min)?;
+        self.builder.check_op(max)?;
         let op = unsafe { c_lib::op_clamp(min.op, self.op, max.op) };
         self.wrap(op)
     }
@@ -316,13 +629,17 @@ impl XlaOp {
     /// Select values from the original tensor to be values from `on_true` if the associated
     /// value in `self` is true, and the values from `on_false` otherwise.
     pub fn select(&self, on_true: &Self, on_false: &Self) -> Result<Self> {
+        self.builder.check_op(on_true)?;
+        self.builder.check_op(on_false)?;
         let op = unsafe { c_lib::op_select(self.op, on_true.op, on_false.op) };
         self.wrap(op)
     }
 
     /// A node that when executed generates values using a random uniform distribution.
     pub fn rng_uniform(min: &Self, max: &Self, shape: &ArrayShape) -> Result<Self> {
+        min.builder.check_op(max)?;
         let dims = shape.dims();
+        super::check_static_dims(dims)?;
         let op = unsafe {
             c_lib::op_rng_uniform(
                 min.op,
@@ -337,7 +654,9 @@ impl XlaOp {
 
     /// A node that when executed generates values using a random normal distribution.
     pub fn rng_normal(mu: &Self, sigma: &Self, shape: &ArrayShape) -> Result<Self> {
+        mu.builder.check_op(sigma)?;
         let dims = shape.dims();
+        super::check_static_dims(dims)?;
         let op = unsafe {
             c_lib::op_rng_normal(
                 mu.op,
@@ -350,12 +669,41 @@ impl XlaOp {
         mu.wrap(op)
     }
 
+    /// A node that when executed generates random bits using the specified algorithm, `self`
+    /// is the initial state of the generator. The result is a tuple made of the updated state
+    /// and of an array with the specified shape holding the random bits.
+    pub fn rng_bit_generator(
+        &self,
+        algorithm: RandomAlgorithm,
+        shape: &ArrayShape,
+    ) -> Result<Self> {
+        let dims = shape.dims();
+        super::check_static_dims(dims)?;
+        let op = unsafe {
+            c_lib::op_rng_bit_generator(
+                algorithm as i32,
+                self.op,
+                shape.primitive_type() as i32,
+                dims.len() as i32,
+                dims.as_ptr(),
+            )
+        };
+        self.wrap(op)
+    }
+
     /// Create a new node by casting the elements of the original node to a new primitive type.
     pub fn convert(&self, ty: PrimitiveType) -> Result<Self> {
         let op = unsafe { c_lib::op_convert_element_type(self.op, ty as i32) };
         self.wrap(op)
     }
 
+    /// Create a new node by reinterpreting the bits of the elements of the original node as a
+    /// new primitive type. Both types must have the same size.
+    pub fn bitcast_convert(&self, ty: PrimitiveType) -> Result<Self> {
+        let op = unsafe { c_lib::op_bitcast_convert_element_type(self.op, ty as i32) };
+        self.wrap(op)
+    }
+
     fn normalize_indexes(&self, indexes: &[i64]) -> Result<Vec<i64>> {
         let rank = self.rank()?;
         indexes
@@ -374,7 +722,7 @@ impl XlaOp {
             .collect()
     }
 
-    fn normalize_index(&self, index: i64) -> Result<i64> {
+    pub(super) fn normalize_index(&self, index: i64) -> Result<i64> {
         let rank = self.rank()?;
         if index >= rank as i64 {
             Err(Error::IndexOutOfBounds { index, rank })
@@ -406,6 +754,7 @@ impl XlaOp {
         dims: &[i64],
         keep_dims: bool,
     ) -> Result<Self> {
+        self.builder.check_op(&init_value)?;
         let dims = self.normalize_indexes(dims)?;
         let op =
             unsafe { c_lib::op_reduce(self.op, init_value.op, comp.0, dims.as_ptr(), dims.len()) };
@@ -413,6 +762,115 @@ impl XlaOp {
         self.maybe_keep_dims(op, &dims, keep_dims)
     }
 
+    /// Apply a reduction computation on sliding windows over this node. `window_dims` and
+    /// `window_strides` have one element per dimension of this node, `padding` specifies the
+    /// amount of low and high padding using `init_value` for each dimension.
+    pub fn reduce_window(
+        &self,
+        init_value: Self,
+        comp: XlaComputation,
+        window_dims: &[i64],
+        window_strides: &[i64],
+        padding: &[(i64, i64)],
+    ) -> Result<Self> {
+        self.builder.check_op(&init_value)?;
+        let padding: Vec<i64> = padding.iter().flat_map(|(lo, hi)| [*lo, *hi]).collect();
+        let op = unsafe {
+            c_lib::op_reduce_window(
+                self.op,
+                init_value.op,
+                comp.0,
+                window_dims.as_ptr(),
+                window_dims.len(),
+                window_strides.as_ptr(),
+                window_strides.len(),
+                padding.as_ptr(),
+                padding.len() / 2,
+            )
+        };
+        self.wrap(op)
+    }
+
+    /// Reduce this node across the replicas of each group using the `comp` computation, each
+    /// replica gets the reduced value. When `channel` is set, the reduction is performed across
+    /// partitions rather than replicas.
+    pub fn all_reduce(
+        &self,
+        comp: &XlaComputation,
+        devices: &CollectiveDeviceList,
+        channel: Option<ChannelHandle>,
+    ) -> Result<Self> {
+        let (ids, sizes) = devices.to_c();
+        let op = unsafe {
+            c_lib::op_all_reduce(
+                self.op,
+                comp.0,
+                ids.as_ptr(),
+                sizes.as_ptr(),
+                sizes.len(),
+                channel.map_or(0, |c| c.handle()),
+            )
+        };
+        self.wrap(op)
+    }
+
+    /// Concatenate the values of this node from all the replicas of each group along dimension
+    /// `dim`. `shard_count` is the number of replicas in each group, it has to match the size of
+    /// the groups when these are specified.
+    pub fn all_gather(
+        &self,
+        dim: i64,
+        shard_count: usize,
+        devices: &CollectiveDeviceList,
+        channel: Option<ChannelHandle>,
+    ) -> Result<Self> {
+        let dim = self.normalize_index(dim)?;
+        devices.check_group_size(shard_count)?;
+        let (ids, sizes) = devices.to_c();
+        let op = unsafe {
+            c_lib::op_all_gather(
+                self.op,
+                dim,
+                shard_count as i64,
+                ids.as_ptr(),
+                sizes.as_ptr(),
+                sizes.len(),
+                channel.map_or(0, |c| c.handle()),
+            )
+        };
+        self.wrap(op)
+    }
+
+    /// Split this node in `split_count` blocks along `split_dim` and send the blocks to the
+    /// replicas of each group, the received blocks are concatenated along `concat_dim`.
+    /// `split_count` has to match the size of the groups when these are specified.
+    pub fn all_to_all(
+        &self,
+        split_dim: i64,
+        concat_dim: i64,
+        split_count: usize,
+        devices: &CollectiveDeviceList,
+        channel: Option<ChannelHandle>,
+    ) -> Result<Self> {
+        let split_dim = self.normalize_index(split_dim)?;
+        let concat_dim = self.normalize_index(concat_dim)?;
+        devices.check_group_size(split_count)?;
+        let (ids, sizes) = devices.to_c();
+        let op = unsafe {
+            c_lib::op_all_to_all(
+                self.op,
+                split_dim,
+                concat_dim,
+                split_count as i64,
+                ids.as_ptr(),
+                sizes.as_ptr(),
+                sizes.len(),
+                channel.map_or(0, |c| c.handle()),
+            )
+        };
+        self.wrap(op)
+    }
+
     /// Sequentially execute `body` until `cond` fails.
     ///
     /// - `init` argument has a type `T`.
@@ -420,6 +878,8 @@ impl XlaOp {
     ///   `PRED`.
     /// - `body` is a computation with a single argument of type `T` producing a value of type
     ///   `T`.
+    ///
+    /// See [`XlaBuilder::while_loop`] to build `cond` and `body` from closures instead.
     pub fn while_(cond: XlaComputation, body: XlaComputation, init: Self) -> Result<Self> {
         let op = unsafe { c_lib::op_while(cond.0, body.0, init.op) };
         init.wrap(op)
@@ -429,6 +889,8 @@ impl XlaOp {
     /// `self` has to be a scalar of type `PRED`.
     /// `true_op` is used as the single argument to `true_comp` and `false_op` as the single
     /// argument to `false_comp`.
+    ///
+    /// See [`XlaBuilder::cond`] to build the branches from closures instead.
     pub fn conditional(
         &self,
         true_op: Self,
@@ -436,17 +898,49 @@ impl XlaOp {
         false_op: Self,
         false_comp: XlaComputation,
     ) -> Result<Self> {
+        self.builder.check_op(&true_op)?;
+        self.builder.check_op(&false_op)?;
         let op = unsafe {
             c_lib::op_conditional(self.op, true_op.op, true_comp.0, false_op.op, false_comp.0)
         };
         self.wrap(op)
     }
 
-    pub fn outfeed(&self, ty: PrimitiveType, dims: &[i64], config: &str) {
-        let config = std::ffi::CString::new(config).unwrap();
+    /// Execute the branch `branches[self]` on the associated operand and return the result, the
+    /// last branch is executed when `self` is out of bounds. `self` has to be a scalar of type
+    /// `S32` and there must be one operand per branch.
+    ///
+    /// See [`XlaBuilder::switch`] to build the branches from closures instead.
+    pub fn indexed_conditional(
+        &self,
+        branches: &[XlaComputation],
+        operands: &[Self],
+    ) -> Result<Self> {
+        if branches.len() != operands.len() {
+            Err(Error::WrongArgumentCount { expected: branches.len(), got: operands.len() })?
+        }
+        for operand in operands.iter() {
+            self.builder.check_op(operand)?
+        }
+        let branches: Vec<_> = branches.iter().map(|c| c.0).collect();
+        let operands: Vec<_> = operands.iter().map(|o| o.op).collect();
+        let op = unsafe {
+            c_lib::op_indexed_conditional(
+                self.op,
+                branches.as_ptr(),
+                operands.as_ptr(),
+                branches.len(),
+            )
+        };
+        self.wrap(op)
+    }
+
+    pub fn outfeed(&self, ty: PrimitiveType, dims: &[i64], config: &str) -> Result<()> {
+        let config = std::ffi::CString::new(config)?;
         unsafe {
             c_lib::outfeed(self.op, ty as i32, dims.len() as i32, dims.as_ptr(), config.as_ptr())
         }
+        Ok(())
     }
 
     /// The kind of elements that are computed by this operand.
@@ -476,6 +970,63 @@ impl XlaOp {
         self.builder.get_dims(self)
     }
 
+    /// The shape of the element-wise binary op `op` applied to `self` and `rhs`, or `None` if this
+    /// op would be invalid, e.g. because the operand shapes are not compatible. The op is not
+    /// added to the graph and no error value is created.
+    pub fn infer_binary_shape(&self, op: BinaryOp, rhs: &XlaOp) -> Option<Shape> {
+        if !self.builder.is_same(&rhs.builder) {
+            return None;
+        }
+        let mut out: c_lib::shape = std::ptr::null_mut();
+        let builder = self.builder.ptr();
+        if !unsafe { c_lib::infer_binary_op_shape(builder, op as i32, self.op, rhs.op, &mut out) } {
+            return None;
+        }
+        super::shape::CShape::from_ptr(out).shape().ok()
+    }
+
+    /// Add the element-wise binary op `op` to the graph if it is valid, see
+    /// [`XlaOp::infer_binary_shape`]. An invalid op returns `None` and, contrary to the ops
+    /// returning a `Result`, leaves the builder usable which is useful when speculatively
+    /// building a graph.
+    pub fn try_binary(&self, op: BinaryOp, rhs: &XlaOp) -> Option<XlaOp> {
+        self.infer_binary_shape(op, rhs)?;
+        let result = match op {
+            BinaryOp::Add => self.add_(rhs),
+            BinaryOp::Sub => self.sub_(rhs),
+            BinaryOp::Mul => self.mul_(rhs),
+            BinaryOp::Div => self.div_(rhs),
+            BinaryOp::Rem => self.rem_(rhs),
+            BinaryOp::Max => self.max(rhs),
+            BinaryOp::Min => self.min(rhs),
+            BinaryOp::And => self.and(rhs),
+            BinaryOp::Or => self.or(rhs),
+            BinaryOp::Xor => self.xor(rhs),
+            BinaryOp::Atan2 => self.atan2(rhs),
+            BinaryOp::Pow => self.pow(rhs),
+            BinaryOp::Eq => self.eq(rhs),
+            BinaryOp::Ne => self.ne(rhs),
+            BinaryOp::Ge => self.ge(rhs),
+            BinaryOp::Gt => self.gt(rhs),
+            BinaryOp::Le => self.le(rhs),
+            BinaryOp::Lt => self.lt(rhs),
+        };
+        result.ok()
+    }
+
+    /// Reshape this node if `dims` has the same number of elements as the node, `None` is
+    /// returned otherwise and the builder is left usable.
+    pub fn try_reshape(&self, dims: &[i64]) -> Option<XlaOp> {
+        let shape = self.builder.infer_shape(self)?;
+        let shape = ArrayShape::try_from(&shape).ok()?;
+        if dims.iter().any(|d| *d < 0)
+            || dims.iter().map(|d| *d as usize).product::<usize>() != shape.element_count()
+        {
+            return None;
+        }
+        self.reshape(dims).ok()
+    }
+
     extract_dims!(dim1, 1, |d: Vec<usize>| d[0], usize);
     extract_dims!(dim2, 2, |d: Vec<usize>| (d[0], d[1]), (usize, usize));
     extract_dims!(dim3, 3, |d: Vec<usize>| (d[0], d[1], d[2]), (usize, usize, usize));
@@ -497,6 +1048,7 @@ impl XlaOp {
         lhs_batch_dims: &[i64],
         rhs_batch_dims: &[i64],
     ) -> Result<Self> {
+        self.builder.check_op(rhs)?;
         let op = unsafe {
             c_lib::op_dot_general(
                 self.op,
@@ -522,19 +1074,28 @@ impl XlaOp {
         unit_diagonal: bool,
         transpose_a: isize,
     ) -> Result<Self> {
+        self.builder.check_op(b)?;
+        let transpose_a: i32 = transpose_a.try_into()?;
         let op = unsafe {
-            c_lib::op_triangular_solve(
-                self.op,
-                b.op,
-                left_side,
-                lower,
-                unit_diagonal,
-                transpose_a.try_into().unwrap(),
-            )
+            c_lib::op_triangular_solve(self.op, b.op, left_side, lower, unit_diagonal, transpose_a)
         };
         self.wrap(op)
     }
 
+    /// Gather slices of this node at the positions given by `start_indices`, see the XLA
+    /// gather semantics for details.
+    ///
+    /// - `start_indices` holds the start positions of the slices, the dimension
+    ///   `set_index_vector_dim` contains the coordinates of each position, this is the
+    ///   dimension 0 when `None`.
+    /// - `start_index_map` maps each coordinate to a dimension of this node.
+    /// - `slice_sizes` gives the size of the slices for each dimension of this node.
+    /// - `collapsed_slice_dims` lists the dimensions of size 1 that are removed from the slices.
+    /// - `offset_dims` lists the dimensions of the output that hold the remaining slice
+    ///   dimensions, the other output dimensions are the batch dimensions of `start_indices`.
+    ///
+    /// E.g. an embedding lookup of the `ids` in a `(vocab, dim)` table is
+    /// `table.gather(&ids, &[1], &[0], &[0], Some(1), &[1, dim])` with `ids` of shape `(n, 1)`.
     pub fn gather(
         &self,
         start_indices: &XlaOp,
@@ -544,6 +1105,7 @@ impl XlaOp {
         set_index_vector_dim: Option<i64>,
         slice_sizes: &[i64],
     ) -> Result<Self> {
+        self.builder.check_op(start_indices)?;
         let set_index_vector_dim_ptr =
             set_index_vector_dim.as_ref().map(|p| p as *const _).unwrap_or(std::ptr::null());
         let op = unsafe {
@@ -564,6 +1126,47 @@ impl XlaOp {
         self.wrap(op)
     }
 
+    /// Update the slices of this node at the positions given by `indices` by combining them
+    /// with `updates`, the combination is done by `update_computation` which takes the current
+    /// value and the update as scalar arguments, e.g. an addition to accumulate the updates.
+    /// Updates outside of the bounds of this node are skipped.
+    ///
+    /// `indices_are_sorted` and `unique_indices` are hints that can only be set when the indices
+    /// are guaranteed to be sorted or unique, the result is undefined otherwise.
+    pub fn scatter(
+        &self,
+        indices: &XlaOp,
+        updates: &XlaOp,
+        update_computation: &XlaComputation,
+        dims: &ScatterDimensionNumbers,
+        indices_are_sorted: bool,
+        unique_indices: bool,
+    ) -> Result<Self> {
+        self.builder.check_op(indices)?;
+        self.builder.check_op(updates)?;
+        let op = unsafe {
+            c_lib::op_scatter(
+                self.op,
+                indices.op,
+                updates.op,
+                update_computation.0,
+                dims.update_window_dims.as_ptr(),
+                dims.update_window_dims.len(),
+                dims.inserted_window_dims.as_ptr(),
+                dims.inserted_window_dims.len(),
+                dims.scatter_dims_to_operand_dims.as_ptr(),
+                dims.scatter_dims_to_operand_dims.len(),
+                dims.index_vector_dim,
+                indices_are_sorted,
+                unique_indices,
+            )
+        };
+        self.wrap(op)
+    }
+
+    /// Select the elements at the positions `indices` along the dimension `axis`, similar to
+    /// `numpy.take`. The dimension `axis` of the result is replaced by the dimensions of
+    /// `indices`.
     pub fn take(&self, indices: &XlaOp, axis: i64) -> Result<Self> {
         let axis = self.normalize_index(axis)?;
         let shape = self.array_shape()?;
@@ -583,6 +1186,92 @@ impl XlaOp {
         self.gather(&indices, &offset_dims, &[axis], &[axis], index_vector_dim, &slice_sizes)
     }
 
+    /// Resample the target dimension to `out_size` using the value of the nearest element, the
+    /// half-pixel convention is used to map output indexes to input indexes.
+    fn resize_nearest_in_dim(&self, out_size: usize, dim: i64) -> Result<Self> {
+        let dim = self.normalize_index(dim)?;
+        let dims = self.dims()?;
+        let in_size = dims[dim as usize];
+        if in_size == 0 {
+            Err(Error::InvalidDims {
+                dims: dims.iter().map(|d| *d as i64).collect(),
+                msg: "cannot resize an empty dimension",
+            })?
+        }
+        let scale = in_size as f64 / out_size as f64;
+        let indices: Vec<i64> = (0..out_size)
+            .map(|i| i64::min(((i as f64 + 0.5) * scale).floor() as i64, in_size as i64 - 1))
+            .collect();
+        let indices = self.builder.c1(&indices)?;
+        self.take(&indices, dim)
+    }
+
+    /// Resample the target dimension to `out_size` using a linear interpolation between the two
+    /// nearest elements, the half-pixel convention is used to map output indexes to input
+    /// indexes.
+    fn resize_linear_in_dim(&self, out_size: usize, dim: i64) -> Result<Self> {
+        let dim = self.normalize_index(dim)?;
+        let mut dims: Vec<i64> = self.dims()?.iter().map(|d| *d as i64).collect();
+        let in_size = dims[dim as usize];
+        if in_size == 0 {
+            Err(Error::InvalidDims { dims, msg: "cannot resize an empty dimension" })?
+        }
+        let scale = in_size as f64 / out_size as f64;
+        let mut lo_indices = Vec::with_capacity(out_size);
+        let mut hi_indices = Vec::with_capacity(out_size);
+        let mut weights = Vec::with_capacity(out_size);
+        for i in 0..out_size {
+            let src = ((i as f64 + 0.5) * scale - 0.5).clamp(0., (in_size - 1) as f64);
+            let lo = src.floor() as i64;
+            lo_indices.push(lo);
+            hi_indices.push(i64::min(lo + 1, in_size - 1));
+            weights.push(src - lo as f64);
+        }
+        let ty = self.ty()?;
+        dims[dim as usize] = out_size as i64;
+        let hi_weights = self.builder.c1(&weights)?.convert(ty)?.broadcast_in_dim(&dims, &[dim])?;
+        let lo_weights = (self.builder.one(ty.element_type()?)? - &hi_weights)?;
+        let lo = self.take(&self.builder.c1(&lo_indices)?, dim)?;
+        let hi = self.take(&self.builder.c1(&hi_indices)?, dim)?;
+        (lo * lo_weights)? + (hi * hi_weights)?
+    }
+
+    /// Resize an image using the nearest neighbor method. The last two dimensions of this node
+    /// are the height and the width of the image, e.g. it can use a `(batch, channels, height,
+    /// width)` layout.
+    pub fn resize_nearest(&self, out_h: usize, out_w: usize) -> Result<Self> {
+        self.resize_nearest_in_dim(out_h, -2)?.resize_nearest_in_dim(out_w, -1)
+    }
+
+    /// Resize an image using bilinear interpolation. The last two dimensions of this node are the
+    /// height and the width of the image, the element type has to be a floating point type.
+    pub fn resize_bilinear(&self, out_h: usize, out_w: usize) -> Result<Self> {
+        self.resize_linear_in_dim(out_h, -2)?.resize_linear_in_dim(out_w, -1)
+    }
+
+    /// Extract the `height` x `width` region which top-left corner is at `(top, left)`, the last
+    /// two dimensions of this node are the height and the width of the image.
+    pub fn crop(&self, top: i64, left: i64, height: i64, width: i64) -> Result<Self> {
+        self.slice_in_dim1(top, top + height, -2)?.slice_in_dim1(left, left + width, -1)
+    }
+
+    /// Extract the `height` x `width` region at the center of the image, the last two dimensions
+    /// of this node are the height and the width of the image.
+    pub fn center_crop(&self, height: i64, width: i64) -> Result<Self> {
+        let dims = self.dims()?;
+        let rank = dims.len();
+        if rank < 2 {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 2,
+                got: rank,
+                dims: dims.iter().map(|d| *d as i64).collect(),
+            })?
+        }
+        let top = (dims[rank - 2] as i64 - height) / 2;
+        let left = (dims[rank - 1] as i64 - width) / 2;
+        self.crop(top, left, height, width)
+    }
+
     fn maybe_keep_dims(&self, res: XlaOp, dims_to_keep: &[i64], keep_dims: bool) -> Result<XlaOp> {
         if keep_dims && !dims_to_keep.is_empty() {
             let shape = self.array_shape()?;
@@ -643,6 +1332,176 @@ impl XlaOp {
         self.reduce(init_value, sum, dims, keep_dims)
     }
 
+    // Combine the rows of this node that have the same segment id using `combine`, the rows of
+    // segments without any element are set to `init_value`.
+    fn segment_reduce(
+        &self,
+        segment_ids: &XlaOp,
+        num_segments: usize,
+        init_value: XlaOp,
+        combine: fn(&XlaOp, &XlaOp) -> Result<XlaOp>,
+        op: &'static str,
+    ) -> Result<Self> {
+        self.builder.check_op(segment_ids)?;
+        segment_ids.check_element_types(&[], op, Self::is_integer)?;
+        let dims: Vec<i64> = self.dims()?.iter().map(|d| *d as i64).collect();
+        let ids_dims: Vec<i64> = segment_ids.dims()?.iter().map(|d| *d as i64).collect();
+        if dims.is_empty() || ids_dims != dims[..1] {
+            Err(Error::InvalidDims {
+                dims: ids_dims,
+                msg: "segment ids must have one element per row",
+            })?
+        }
+        let ty = self.primitive_type()?.element_type()?;
+        let combine = XlaComputation::from_scalar_fn(op, ty, combine)?;
+        let mut out_dims = dims.clone();
+        out_dims[0] = num_segments as i64;
+        let scatter_dims = ScatterDimensionNumbers {
+            update_window_dims: (1..dims.len() as i64).collect(),
+            inserted_window_dims: vec![0],
+            scatter_dims_to_operand_dims: vec![0],
+            index_vector_dim: 1,
+        };
+        let indices = segment_ids.reshape(&[dims[0], 1])?;
+        let init = init_value.broadcast(&out_dims)?;
+        init.scatter(&indices, self, &combine, &scatter_dims, false, false)
+    }
+
+    /// Sum the rows of this node that belong to the same segment. `segment_ids` is an integer
+    /// node with one segment id per row, i.e. per element of the first dimension. The result
+    /// has `num_segments` rows, empty segments are set to zero and ids outside of
+    /// `[0, num_segments)` are ignored.
+    pub fn segment_sum(&self, segment_ids: &XlaOp, num_segments: usize) -> Result<Self> {
+        let zero = self.builder.zero(self.primitive_type()?.element_type()?)?;
+        self.segment_reduce(segment_ids, num_segments, zero, XlaOp::add_, "segment_sum")
+    }
+
+    /// The average of the rows of this node that belong to the same segment, see
+    /// [`XlaOp::segment_sum`]. Empty segments are set to zero.
+    pub fn segment_mean(&self, segment_ids: &XlaOp, num_segments: usize) -> Result<Self> {
+        let ty = self.primitive_type()?.element_type()?;
+        let sum = self.segment_sum(segment_ids, num_segments)?;
+        let ones = self.builder.one(ty)?.broadcast(&[self.dims()?[0] as i64])?;
+        let count = ones.segment_sum(segment_ids, num_segments)?.max(&self.builder.one(ty)?)?;
+        let out_dims: Vec<i64> = sum.dims()?.iter().map(|d| *d as i64).collect();
+        sum / count.broadcast_in_dim(&out_dims, &[0])?
+    }
+
+    /// The maximum of the rows of this node that belong to the same segment, see
+    /// [`XlaOp::segment_sum`]. Empty segments are set to the minimum value of the element type.
+    pub fn segment_max(&self, segment_ids: &XlaOp, num_segments: usize) -> Result<Self> {
+        let init_value = self.builder.min_value(self.primitive_type()?.element_type()?)?;
+        self.segment_reduce(segment_ids, num_segments, init_value, XlaOp::max, "segment_max")
+    }
+
+    /// The minimum of the rows of this node that belong to the same segment, see
+    /// [`XlaOp::segment_sum`]. Empty segments are set to the maximum value of the element type.
+    pub fn segment_min(&self, segment_ids: &XlaOp, num_segments: usize) -> Result<Self> {
+        let init_value = self.builder.max_value(self.primitive_type()?.element_type()?)?;
+        self.segment_reduce(segment_ids, num_segments, init_value, XlaOp::min, "segment_min")
+    }
+
+    /// The distinct values of this rank 1 node in increasing order. As the output shapes have to
+    /// be static, this returns `size` values with their number of occurrences as `S32` and a
+    /// `PRED` mask which is true for the valid entries. The values and counts past the number of
+    /// distinct values are set to zero, and the distinct values past `size` are dropped.
+    pub fn unique(&self, size: usize) -> Result<(Self, Self, Self)> {
+        let dims: Vec<i64> = self.dims()?.iter().map(|d| *d as i64).collect();
+        if dims.len() != 1 {
+            Err(Error::InvalidDims { dims, msg: "unique expects a rank 1 node" })?
+        }
+        let n = dims[0];
+        let ty = self.primitive_type()?.element_type()?;
+        let sorted = self.sort(0)?;
+        // An element starts a new group of equal values when it differs from the previous one.
+        let is_new = if n <= 1 {
+            self.builder.one(crate::ElementType::Pred)?.broadcast(&[n])?
+        } else {
+            let first = self.builder.one(crate::ElementType::Pred)?.reshape(&[1])?;
+            let changed =
+                sorted.slice_in_dim1(1, n, 0)?.ne(&sorted.slice_in_dim1(0, n - 1, 0)?)?;
+            first.concat_in_dim(&[changed], 0)?
+        };
+        let is_new = is_new.convert(PrimitiveType::S32)?;
+        let positions = (is_new.cumsum(0)? - self.builder.one(crate::ElementType::S32)?)?;
+        let ones = self.builder.one(crate::ElementType::S32)?.broadcast(&[n])?;
+        let counts = ones.segment_sum(&positions, size)?;
+        let num_unique = is_new.reduce_sum(&[0], false)?.broadcast(&[size as i64])?;
+        let mask = self.builder.iota1(crate::ElementType::S32, size)?.lt(&num_unique)?;
+        // All the elements of a group are equal so the maximum is the group value.
+        let values = sorted.segment_max(&positions, size)?;
+        let zeros = self.builder.zero(ty)?.broadcast(&[size as i64])?;
+        Ok((mask.select(&values, &zeros)?, counts, mask))
+    }
+
+    /// The number of occurrences of each value in `0..num_bins` in this rank 1 integer node, or
+    /// the sum of the corresponding `weights` when set. Values outside of this range are ignored.
+    /// The counts use the `S32` type and the weighted counts use the type of `weights`.
+    pub fn bincount(&self, weights: Option<&XlaOp>, num_bins: usize) -> Result<Self> {
+        match weights {
+            Some(weights) => weights.segment_sum(self, num_bins),
+            None => {
+                let dims: Vec<i64> = self.dims()?.iter().map(|d| *d as i64).collect();
+                let ones = self.builder.one(crate::ElementType::S32)?.broadcast(&dims)?;
+                ones.segment_sum(self, num_bins)
+            }
+        }
+    }
+
+    /// A node that computes the cumulative sum along the target dimension.
+    pub fn cumsum(&self, dim: i64) -> Result<Self> {
+        let dim = self.normalize_index(dim)?;
+        let dims = self.dims()?;
+        let builder = XlaBuilder::new("Sum");
+        let ty = self.primitive_type()?.element_type()?;
+        let x = builder.parameter(0, ty, &[], "x")?;
+        let y = builder.parameter(1, ty, &[], "y")?;
+        let sum = x.add_(&y)?.build()?;
+        let init_value = self.builder.zero(ty)?;
+        let mut window_dims = vec![1; dims.len()];
+        window_dims[dim as usize] = dims[dim as usize] as i64;
+        let mut padding = vec![(0, 0); dims.len()];
+        padding[dim as usize] = (dims[dim as usize] as i64 - 1, 0);
+        self.reduce_window(init_value, sum, &window_dims, &vec![1; dims.len()], &padding)
+    }
+
+    /// A node with the index of the maximum value along the target dimension, using the
+    /// smallest index when there are multiple maximums. The indexes use the `S32` type.
+    pub fn argmax(&self, dim: i64, keep_dims: bool) -> Result<Self> {
+        let max = self.reduce_max(&[dim], true)?;
+        self.first_index_where(&self.eq(&max)?, dim, keep_dims)
+    }
+
+    /// A node with the index of the minimum value along the target dimension, using the
+    /// smallest index when there are multiple minimums. The indexes use the `S32` type.
+    pub fn argmin(&self, dim: i64, keep_dims: bool) -> Result<Self> {
+        let min = self.reduce_min(&[dim], true)?;
+        self.first_index_where(&self.eq(&min)?, dim, keep_dims)
+    }
+
+    fn first_index_where(&self, mask: &Self, dim: i64, keep_dims: bool) -> Result<Self> {
+        let dim = self.normalize_index(dim)?;
+        let dims: Vec<i64> = self.dims()?.iter().map(|d| *d as i64).collect();
+        let iota = self.builder.iota(crate::ElementType::S32, &dims, dim)?;
+        let max_index = self.builder.c0(dims[dim as usize] as i32)?.broadcast(&dims)?;
+        mask.select(&iota, &max_index)?.reduce_min(&[dim], keep_dims)
+    }
+
+    /// Sort this node in increasing order along the target dimension, the sort is stable. See
+    /// [`XlaBuilder::sort`] for sorting with another order or sorting multiple nodes together.
+    pub fn sort(&self, dim: i64) -> Result<Self> {
+        let ty = self.primitive_type()?.element_type()?;
+        let comparator = XlaComputation::from_scalar_fn("lt", ty, XlaOp::lt)?;
+        self.builder.sort(&[self], &comparator, dim, true)
+    }
+
+    /// The `k` largest values along the last dimension and their indexes, sorted in decreasing
+    /// order. This returns a tuple node, the indexes use the `S32` type.
+    pub fn top_k(&self, k: i64) -> Result<Self> {
+        let op = unsafe { c_lib::op_top_k(self.op, k) };
+        self.wrap(op)
+    }
+
     pub fn softmax(&self, dim: i64) -> Result<Self> {
         let max = self.reduce_max(&[dim], true)?;
         let unnormalized = (self - max)?.exp()?;
@@ -650,6 +1509,15 @@ impl XlaOp {
         unnormalized / sum
     }
 
+    /// The logarithm of the softmax along the target dimension, this is computed in a numerically
+    /// stable way.
+    pub fn log_softmax(&self, dim: i64) -> Result<Self> {
+        let max = self.reduce_max(&[dim], true)?;
+        let shifted = (self - max)?;
+        let log_sum = shifted.exp()?.reduce_sum(&[dim], true)?.log()?;
+        shifted - log_sum
+    }
+
     /// Layer normalization, this normalizes values on the target dimension to be of zero mean and
     /// standard deviation one, and then scales the result by `scale` and adds `bias`.
     pub fn layer_norm(&self, dim: i64, scale: &XlaOp, bias: &XlaOp) -> Result<Self> {
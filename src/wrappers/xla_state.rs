@@ -0,0 +1,105 @@
+//! Structured values made of multiple nodes, e.g. the state of a loop.
+//!
+//! The [`XlaState`] trait maps a value to a flat list of nodes, this is what the control flow
+//! helpers such as [`XlaBuilder::while_loop`] take and return, and to a tuple node. It can be
+//! derived for structs with `#[derive(XlaState)]`:
+//!
+//! ```ignore
+//! #[derive(xla::XlaState)]
+//! struct State {
+//!     step: xla::XlaOp,
+//!     acc: xla::XlaOp,
+//! }
+//!
+//! let [step, acc] = [builder.c0(0i32)?, builder.c1(&[0f32, 0.])?];
+//! let state = builder.while_state(
+//!     |b, s: &State| s.step.lt(&b.c0(10i32)?),
+//!     |b, s| Ok(State { step: (&s.step + b.c0(1i32)?)?, acc: (&s.acc + &s.acc)? }),
+//!     &State { step, acc },
+//! )?;
+//! ```
+use super::{XlaBuilder, XlaOp};
+use crate::{Error, Result};
+
+/// A value made of a fixed number of nodes.
+pub trait XlaState: Sized {
+    /// The number of nodes in a value of this type.
+    fn state_len() -> usize;
+
+    /// Append the nodes of this value to `ops`, `state_len` nodes are appended.
+    fn push_state_ops(&self, ops: &mut Vec<XlaOp>);
+
+    /// Rebuild a value from exactly `state_len` nodes, in the order used by `push_state_ops`.
+    fn from_state_ops(ops: &[XlaOp]) -> Result<Self>;
+
+    /// The nodes of this value.
+    fn state_ops(&self) -> Vec<XlaOp> {
+        let mut ops = Vec::with_capacity(Self::state_len());
+        self.push_state_ops(&mut ops);
+        ops
+    }
+
+    /// Pack the nodes of this value in a tuple node.
+    fn to_tuple(&self, builder: &XlaBuilder) -> Result<XlaOp> {
+        builder.tuple(self.state_ops().as_slice())
+    }
+
+    /// Unpack a tuple node created by `to_tuple`.
+    fn from_tuple(op: &XlaOp) -> Result<Self> {
+        let ops = (0..Self::state_len())
+            .map(|i| op.get_tuple_element(i as i64))
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_state_ops(&ops)
+    }
+}
+
+impl XlaState for XlaOp {
+    fn state_len() -> usize {
+        1
+    }
+
+    fn push_state_ops(&self, ops: &mut Vec<XlaOp>) {
+        ops.push(self.clone())
+    }
+
+    fn from_state_ops(ops: &[XlaOp]) -> Result<Self> {
+        match ops {
+            [op] => Ok(op.clone()),
+            _ => Err(Error::UnexpectedNumberOfElemsInTuple { expected: 1, got: ops.len() }),
+        }
+    }
+}
+
+impl<const N: usize> XlaState for [XlaOp; N] {
+    fn state_len() -> usize {
+        N
+    }
+
+    fn push_state_ops(&self, ops: &mut Vec<XlaOp>) {
+        ops.extend(self.iter().cloned())
+    }
+
+    fn from_state_ops(ops: &[XlaOp]) -> Result<Self> {
+        match <&[XlaOp; N]>::try_from(ops) {
+            Ok(ops) => Ok(ops.clone()),
+            Err(_) => Err(Error::UnexpectedNumberOfElemsInTuple { expected: N, got: ops.len() }),
+        }
+    }
+}
+
+impl XlaBuilder {
+    /// A version of [`XlaBuilder::while_loop`] where the state is a structured value.
+    pub fn while_state<S, C, B>(&self, cond: C, body: B, init: &S) -> Result<S>
+    where
+        S: XlaState,
+        C: FnOnce(&XlaBuilder, &S) -> Result<XlaOp>,
+        B: FnOnce(&XlaBuilder, &S) -> Result<S>,
+    {
+        let state = self.while_loop(
+            |builder, ops| cond(builder, &S::from_state_ops(ops)?),
+            |builder, ops| Ok(body(builder, &S::from_state_ops(ops)?)?.state_ops()),
+            &init.state_ops(),
+        )?;
+        S::from_state_ops(&state)
+    }
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "arrow")]
+use anyhow::Result;
+use arrow_array::{ArrayRef, Float32Array, Int64Array, RecordBatch};
+use std::sync::Arc;
+
+#[test]
+fn array_to_literal() -> Result<()> {
+    let array = Int64Array::from(vec![1, 2, 3, 4, 5]);
+    let literal = xla::arrow::literal_from_array(&array.slice(1, 3))?;
+    assert_eq!(literal.array_shape()?, xla::ArrayShape::new::<i64>(vec![3]));
+    assert_eq!(literal.to_vec::<i64>()?, [2, 3, 4]);
+
+    let with_nulls = Int64Array::from(vec![Some(1), None]);
+    assert!(xla::arrow::literal_from_array(&with_nulls).is_err());
+    Ok(())
+}
+
+#[test]
+fn record_batch_to_literal() -> Result<()> {
+    let a: ArrayRef = Arc::new(Float32Array::from(vec![1., 2., 3.]));
+    let b: ArrayRef = Arc::new(Float32Array::from(vec![4., 5., 6.]));
+    let c: ArrayRef = Arc::new(Int64Array::from(vec![7, 8, 9]));
+    let batch = RecordBatch::try_from_iter([("a", a), ("b", b), ("c", c)])?;
+    let literal = xla::arrow::literal_from_columns(&batch, &["b", "a"])?;
+    assert_eq!(literal.array_shape()?, xla::ArrayShape::new::<f32>(vec![3, 2]));
+    assert_eq!(literal.to_vec::<f32>()?, [4., 1., 5., 2., 6., 3.]);
+    assert!(xla::arrow::literal_from_columns(&batch, &["a", "c"]).is_err());
+    assert!(xla::arrow::literal_from_columns(&batch, &["d"]).is_err());
+    Ok(())
+}
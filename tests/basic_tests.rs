@@ -1,3 +1,4 @@
+#![cfg(feature = "ffi")]
 use xla::{ArrayElement, Result};
 
 #[test]
@@ -64,33 +65,435 @@ fn malformed_mat() -> Result<()> {
         [4.0f32, 5.0f32, 6.0f32].as_slice(),
         [7.0f32, 8.0f32].as_slice(),
     ];
-
-    let result = std::panic::catch_unwind(|| {
-        let builder = xla::XlaBuilder::new("test");
-        builder.constant_r2(&bad_mat)
-    });
-    assert!(result.is_err_and(|e| {
-        let msg = match e.downcast_ref::<&'static str>() {
-            Some(s) => *s,
-            None => match e.downcast_ref::<String>() {
-                Some(s) => &s[..],
-                None => "Box<dyn Any>",
-            },
-        };
-        msg == "all rows must have the same number of columns!"
-    }));
+    let builder = xla::XlaBuilder::new("test");
+    let result = builder.constant_r2(&bad_mat);
+    assert!(matches!(result, Err(xla::Error::RaggedRows { row_lens }) if row_lens == [3, 3, 2]));
 
     let good_mat = [
         [1.0f32, 2.0f32, 3.0f32].as_slice(),
         [4.0f32, 5.0f32, 6.0f32].as_slice(),
         [7.0f32, 8.0f32, 9.0f32].as_slice(),
     ];
-    let result = std::panic::catch_unwind(|| {
-        let builder = xla::XlaBuilder::new("test");
-        builder.constant_r2(&good_mat)
-    });
-    assert!(result.is_ok());
+    let op = builder.constant_r2(&good_mat)?;
+    assert_eq!(op.dims()?, [3, 3]);
+    Ok(())
+}
 
+#[test]
+fn literal_bytes() -> Result<()> {
+    let values = [1i32, -2, 3, 4, 5, 6];
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let literal = xla::Literal::from_bytes(i32::TY, &[2, 3], &bytes)?;
+    assert_eq!(literal.array_shape()?.dims(), [2, 3]);
+    assert_eq!(literal.to_vec::<i32>()?, values);
+    assert_eq!(literal.as_bytes()?, bytes);
+    // The same bytes can be reinterpreted with another element type of the same size.
+    let literal = xla::Literal::from_bytes(u32::TY, &[6], literal.as_bytes()?)?;
+    assert_eq!(literal.to_vec::<u32>()?[1], -2i32 as u32);
+    assert!(xla::Literal::from_bytes(f32::TY, &[2], &bytes).is_err());
+    let tuple = xla::Literal::tuple(vec![literal]);
+    assert!(tuple.as_bytes().is_err());
+    Ok(())
+}
+
+#[test]
+fn literal_serialization() -> Result<()> {
+    use xla::Endianness;
+    let literal = xla::Literal::vec1(&[1f32, -2.5, 3.25]).reshape(&[3, 1])?;
+    let little = literal.to_serialized_bytes(Endianness::Little)?;
+    let big = literal.to_serialized_bytes(Endianness::Big)?;
+    assert_eq!(&little[..8], b"XLALIT\x01\x00");
+    assert_eq!(&big[..8], b"XLALIT\x01\x01");
+    assert_eq!(&little[little.len() - 4..], 3.25f32.to_le_bytes());
+    assert_eq!(&big[big.len() - 4..], 3.25f32.to_be_bytes());
+    for bytes in [&little, &big] {
+        let read = xla::Literal::from_serialized_bytes(bytes)?;
+        assert_eq!(read.array_shape()?.dims(), [3, 1]);
+        assert_eq!(read.to_vec::<f32>()?, [1., -2.5, 3.25]);
+    }
+
+    let tuple = xla::Literal::tuple(vec![
+        xla::Literal::vec1(&[1u32, 258]),
+        xla::Literal::tuple(vec![xla::Literal::scalar(-7i64)]),
+    ]);
+    let bytes = tuple.to_serialized_bytes(Endianness::Big)?;
+    let mut tuple = xla::Literal::read_serialized(&mut bytes.as_slice())?.to_tuple()?;
+    assert_eq!(tuple.len(), 2);
+    let inner = tuple.pop().unwrap().to_tuple1()?;
+    assert_eq!(inner.to_vec::<i64>()?, [-7]);
+    assert_eq!(tuple[0].to_vec::<u32>()?, [1, 258]);
+
+    let zeros = xla::Literal::vec1(&[0f64; 512]);
+    let compressed = zeros.to_serialized_bytes_compressed(Endianness::Big, 3)?;
+    assert!(compressed.len() < zeros.size_bytes());
+    let read = xla::Literal::from_serialized_bytes(&compressed)?;
+    assert_eq!(read.to_vec::<f64>()?, [0f64; 512]);
+    let read = xla::Literal::read_serialized(&mut compressed.as_slice())?;
+    assert_eq!(read.element_count(), 512);
+
+    let mut bytes = little.clone();
+    bytes[6] = 2;
+    assert!(matches!(
+        xla::Literal::from_serialized_bytes(&bytes),
+        Err(xla::Error::SerializedLiteral(_))
+    ));
+    assert!(xla::Literal::from_serialized_bytes(&little[1..]).is_err());
+    assert!(xla::Literal::from_serialized_bytes(&little[..little.len() - 1]).is_err());
+    assert!(xla::Literal::from_serialized_bytes(&[little.as_slice(), &[0]].concat()).is_err());
+    // Deeply nested tuples are rejected rather than overflowing the stack.
+    let mut bytes = little[..8].to_vec();
+    for _ in 0..100_000 {
+        bytes.extend_from_slice(&[1, 1, 0, 0, 0])
+    }
+    assert!(matches!(
+        xla::Literal::from_serialized_bytes(&bytes),
+        Err(xla::Error::SerializedLiteral(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn literal_ref() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, f32::TY, &[2, 2], "x")?;
+    let mut sums = vec![];
+    for step in 0..3 {
+        // The per-step data lives on the stack and is only borrowed.
+        let data = [step as f32; 4];
+        let literal = xla::LiteralRef::new(&data, &[2, 2])?;
+        assert_eq!(literal.ty(), xla::ElementType::F32);
+        assert_eq!(literal.as_bytes().len(), 16);
+        let buffer = client.buffer_from_literal_ref(None, &literal)?;
+        assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, data);
+        sums.push(builder.constant_literal_ref(&literal)?);
+    }
+    let sum = sums.into_iter().try_fold(x, |acc, c| acc.add_(&c))?;
+    let exe = client.compile(&sum.build()?)?;
+    let x = xla::Literal::vec1(&[1f32, 2., 3., 4.]).reshape(&[2, 2])?;
+    let result = exe.execute::<xla::Literal>(&[x])?[0][0].to_literal_sync()?;
+    assert_eq!(result.to_vec::<f32>()?, [4., 5., 6., 7.]);
+
+    let bytes = [1u8, 0, 0, 0, 2, 0, 0, 0];
+    let literal = xla::LiteralRef::from_bytes(xla::ElementType::U8, &[8], &bytes)?;
+    assert_eq!(literal.to_literal()?.to_vec::<u8>()?, bytes);
+    assert!(xla::LiteralRef::new(&[1f32, 2.], &[3]).is_err());
+    assert!(xla::LiteralRef::from_bytes(xla::ElementType::F32, &[3], &bytes).is_err());
+    Ok(())
+}
+
+#[test]
+fn logical_ops() -> Result<()> {
+    let unsupported =
+        |r: Result<xla::XlaOp>| matches!(r, Err(xla::Error::UnsupportedElementType { .. }));
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.constant_r1(&[-1f32, 0., 1., 2.])?;
+    let a = x.gt(&builder.c0(0f32)?.broadcast(&[4])?)?;
+    let b = x.lt(&builder.c0(2f32)?.broadcast(&[4])?)?;
+    assert!(unsupported(x.logical_not()));
+    assert!(unsupported(a.logical_or(&x)));
+    assert!(unsupported(a.bitwise_xor(&b)));
+    let ops = [a.logical_and(&b)?, a.logical_or(&b)?, a.logical_xor(&b)?, a.logical_not()?];
+    let ops =
+        ops.iter().map(|op| op.convert(xla::PrimitiveType::S32)).collect::<Result<Vec<_>>>()?;
+    let exe = client.compile(&builder.tuple(&ops)?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].to_vec::<i32>()?, [0, 0, 1, 0]);
+    assert_eq!(result[1].to_vec::<i32>()?, [1, 1, 1, 1]);
+    assert_eq!(result[2].to_vec::<i32>()?, [1, 1, 0, 1]);
+    assert_eq!(result[3].to_vec::<i32>()?, [1, 1, 0, 0]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let i = builder.constant_r1(&[0b1100i32, 0b1010])?;
+    let j = builder.constant_r1(&[0b1010i32, 0b0110])?;
+    assert!(unsupported(i.logical_and(&j)));
+    assert!(unsupported(builder.constant_r1(&[1f32])?.bitwise_not()));
+    let ops = [i.bitwise_and(&j)?, i.bitwise_or(&j)?, i.bitwise_xor(&j)?, i.bitwise_not()?];
+    let exe = client.compile(&builder.tuple(&ops)?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].to_vec::<i32>()?, [0b1000, 0b0010]);
+    assert_eq!(result[1].to_vec::<i32>()?, [0b1110, 0b1110]);
+    assert_eq!(result[2].to_vec::<i32>()?, [0b0110, 0b1100]);
+    assert_eq!(result[3].to_vec::<i32>()?, [!0b1100, !0b1010]);
+    Ok(())
+}
+
+#[test]
+fn nan_and_inf_ops() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.constant_r1(&[1f32, f32::NAN, f32::INFINITY, f32::NEG_INFINITY, f32::NAN])?;
+    let y = builder.constant_r1(&[2f32, 3., 0., f32::NAN, f32::NAN])?;
+    let finite = builder.constant_r1(&[1f32, -2.])?;
+    assert!(matches!(
+        builder.constant_r1(&[1i32])?.is_nan(),
+        Err(xla::Error::UnsupportedElementType { .. })
+    ));
+    let pred_ops = [x.is_nan()?, x.is_inf()?, x.all_finite()?, finite.all_finite()?];
+    let mut ops = pred_ops
+        .iter()
+        .map(|op| op.convert(xla::PrimitiveType::S32))
+        .collect::<Result<Vec<_>>>()?;
+    ops.push(x.max_nan(&y, xla::NanMode::Propagate)?);
+    ops.push(x.max_nan(&y, xla::NanMode::Ignore)?);
+    ops.push(x.min_nan(&y, xla::NanMode::Ignore)?);
+    let exe = client.compile(&builder.tuple(&ops)?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].to_vec::<i32>()?, [0, 1, 0, 0, 1]);
+    assert_eq!(result[1].to_vec::<i32>()?, [0, 0, 1, 1, 0]);
+    assert_eq!(result[2].to_vec::<i32>()?, [0]);
+    assert_eq!(result[3].to_vec::<i32>()?, [1]);
+    let max = result[4].to_vec::<f32>()?;
+    assert_eq!(max[0], 2.);
+    assert!(max[1].is_nan() && max[3].is_nan() && max[4].is_nan());
+    assert_eq!(max[2], f32::INFINITY);
+    let max = result[5].to_vec::<f32>()?;
+    assert_eq!(max[..4], [2., 3., f32::INFINITY, f32::NEG_INFINITY]);
+    assert!(max[4].is_nan());
+    let min = result[6].to_vec::<f32>()?;
+    assert_eq!(min[..4], [1., 3., 0., f32::NEG_INFINITY]);
+    assert!(min[4].is_nan());
+    Ok(())
+}
+
+#[test]
+fn gather() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("gather");
+    let table = builder.constant_r1(&[0f32, 1., 2., 3., 4., 5., 6., 7.])?.reshape(&[4, 2])?;
+    // Embedding lookup, one row per id.
+    let ids = builder.constant_r1(&[3i32, 0, 3])?.reshape(&[3, 1])?;
+    let rows = table.gather(&ids, &[1], &[0], &[0], Some(1), &[1, 2])?;
+    // Gather single elements from (row, column) coordinates.
+    let coords = builder.constant_r1(&[1i32, 0, 2, 1])?.reshape(&[2, 2])?;
+    let points = table.gather(&coords, &[], &[0, 1], &[0, 1], Some(1), &[1, 1])?;
+    let columns = table.take(&builder.constant_r1(&[1i32, 1, 0])?, 1)?;
+    let exe = client.compile(&builder.tuple(&[rows, points, columns])?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].array_shape()?.dims(), [3, 2]);
+    assert_eq!(result[0].to_vec::<f32>()?, [6., 7., 0., 1., 6., 7.]);
+    assert_eq!(result[1].to_vec::<f32>()?, [2., 5.]);
+    assert_eq!(result[2].array_shape()?.dims(), [4, 3]);
+    assert_eq!(result[2].to_vec::<f32>()?, [1., 1., 0., 3., 3., 2., 5., 5., 4., 7., 7., 6.]);
+    Ok(())
+}
+
+#[test]
+fn scatter_and_segment_ops() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("segments");
+    let data = builder.constant_r1(&[1f32, 2., 3., 4., 5., 6., 7., 8.])?.reshape(&[4, 2])?;
+    // Rows 0 and 2 go to segment 1, row 1 to segment 0, segment 2 is empty and the out of
+    // range id of row 3 is ignored.
+    let ids = builder.constant_r1(&[1i32, 0, 1, 5])?;
+    let ops = [
+        data.segment_sum(&ids, 3)?,
+        data.segment_mean(&ids, 3)?,
+        data.segment_max(&ids, 3)?,
+        data.segment_min(&ids, 3)?,
+    ];
+    let add = xla::XlaComputation::from_scalar_fn("add", f32::TY, |x, y| x.add_(y))?;
+    let max = xla::XlaComputation::from_scalar_fn("max", f32::TY, |x, y| x.max(y))?;
+    let dims = xla::ScatterDimensionNumbers {
+        update_window_dims: vec![],
+        inserted_window_dims: vec![0],
+        scatter_dims_to_operand_dims: vec![0],
+        index_vector_dim: 1,
+    };
+    let indices = builder.constant_r1(&[0i32, 2, 0])?.reshape(&[3, 1])?;
+    let updates = builder.constant_r1(&[1f32, 2., 3.])?;
+    let scattered = builder.zero(xla::ElementType::F32)?.broadcast(&[3])?;
+    let scatter_add = scattered.scatter(&indices, &updates, &add, &dims, false, false)?;
+    let scatter_max = scattered.scatter(&indices, &updates, &max, &dims, false, false)?;
+    assert!(data.segment_sum(&builder.constant_r1(&[0i32, 1])?, 2).is_err());
+    assert!(data.segment_sum(&builder.constant_r1(&[0f32, 1., 2., 3.])?, 2).is_err());
+
+    let mut outputs = ops.to_vec();
+    outputs.extend([scatter_add, scatter_max]);
+    let exe = client.compile(&builder.tuple(&outputs)?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].array_shape()?.dims(), [3, 2]);
+    assert_eq!(result[0].to_vec::<f32>()?, [3., 4., 6., 8., 0., 0.]);
+    assert_eq!(result[1].to_vec::<f32>()?, [3., 4., 3., 4., 0., 0.]);
+    let max = result[2].to_vec::<f32>()?;
+    assert_eq!(max[..4], [3., 4., 5., 6.]);
+    assert_eq!(max[4..], [f32::NEG_INFINITY; 2]);
+    let min = result[3].to_vec::<f32>()?;
+    assert_eq!(min[..4], [3., 4., 1., 2.]);
+    assert_eq!(min[4..], [f32::INFINITY; 2]);
+    assert_eq!(result[4].to_vec::<f32>()?, [4., 0., 2.]);
+    assert_eq!(result[5].to_vec::<f32>()?, [3., 0., 2.]);
+    Ok(())
+}
+
+#[test]
+fn dynamic_slices() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("ring_buffer");
+    let buffer_op = builder.parameter(0, f32::TY, &[4, 2], "buffer")?;
+    let row = builder.parameter(1, f32::TY, &[1, 2], "row")?;
+    let offset = builder.parameter(2, i32::TY, &[], "offset")?;
+    let zero = builder.c0(0i32)?;
+    let updated = buffer_op.dynamic_update_slice(&row, &[&offset, &zero])?;
+    let window = updated.dynamic_slice(&[&offset, &zero], &[2, 2])?;
+    let exe = client.compile(&builder.tuple(&[updated, window])?.build()?)?;
+
+    let buffer = xla::Literal::vec1(&[0f32; 8]).reshape(&[4, 2])?;
+    let row = xla::Literal::vec1(&[1f32, 2.]).reshape(&[1, 2])?;
+    let run = |offset: i32| -> Result<Vec<xla::Literal>> {
+        let offset = xla::Literal::scalar(offset);
+        exe.execute::<&xla::Literal>(&[&buffer, &row, &offset])?[0][0].to_literal_sync()?.to_tuple()
+    };
+    let result = run(1)?;
+    assert_eq!(result[0].to_vec::<f32>()?, [0., 0., 1., 2., 0., 0., 0., 0.]);
+    assert_eq!(result[1].to_vec::<f32>()?, [1., 2., 0., 0.]);
+    // Start indices are clamped so that the slices fit in the buffer.
+    let result = run(7)?;
+    assert_eq!(result[0].to_vec::<f32>()?, [0., 0., 0., 0., 0., 0., 1., 2.]);
+    assert_eq!(result[1].to_vec::<f32>()?, [0., 0., 1., 2.]);
+
+    // The update and the start indices have to come from the same builder as the operand.
+    let other = xla::XlaBuilder::new("other");
+    let other_zero = other.c0(0i32)?;
+    let other_row = other.constant_r2(&[[1f32, 2.].as_slice()])?;
+    let err = buffer_op.dynamic_slice(&[&other_zero, &zero], &[2, 2]);
+    assert!(matches!(err, Err(xla::Error::BuilderMismatch)));
+    let err = buffer_op.dynamic_update_slice(&other_row, &[&zero, &zero]);
+    assert!(matches!(err, Err(xla::Error::BuilderMismatch)));
+    Ok(())
+}
+
+#[test]
+fn sparse_dense_matmul() -> Result<()> {
+    use xla::sparse::{CooMatrix, CsrMatrix, SparseOp};
+    let client = xla::PjRtClient::cpu()?;
+    // [[1, 0, 2], [0, 0, 0], [0, 3, 0]] with an empty row.
+    let csr = CsrMatrix::new((3, 3), vec![0, 2, 2, 3], vec![0, 2, 1], vec![1f32, 2., 3.])?;
+    let coo = csr.to_coo();
+    assert_eq!(coo.rows(), [0, 0, 2]);
+    let builder = xla::XlaBuilder::new("sparse");
+    let lhs = SparseOp::parameters(&builder, 0, f32::TY, coo.shape(), coo.nnz())?;
+    let rhs = builder.parameter(3, f32::TY, &[3, 2], "rhs")?;
+    assert!(xla::sparse::sparse_dense_matmul(&lhs, &lhs.values).is_err());
+    let exe = client.compile(&xla::sparse::sparse_dense_matmul(&lhs, &rhs)?.build()?)?;
+    let mut args = coo.to_buffers(&client, None)?;
+    args.push(client.buffer_from_host_buffer(&[1f32, 2., 3., 4., 5., 6.], &[3, 2], None)?);
+    let result = exe.execute_b(&args)?[0][0].to_literal_sync()?;
+    assert_eq!(result.to_vec::<f32>()?, [11., 14., 0., 0., 9., 12.]);
+    let args = csr.to_buffers(&client, None)?;
+    assert_eq!(args[0].to_literal_sync()?.to_vec::<i64>()?, [0, 0, 2]);
+
+    let builder = xla::XlaBuilder::new("sparse_constant");
+    let lhs = csr.constant(&builder)?;
+    let rhs = builder.c1(&[1f32, 2., 3., 4., 5., 6.])?.reshape(&[3, 2])?;
+    let exe = client.compile(&xla::sparse::sparse_dense_matmul(&lhs, &rhs)?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?;
+    assert_eq!(result.to_vec::<f32>()?, [11., 14., 0., 0., 9., 12.]);
+
+    assert!(CooMatrix::new((2, 2), vec![0, 2], vec![0, 1], vec![1f32, 2.]).is_err());
+    assert!(CooMatrix::new((2, 2), vec![0], vec![0, 1], vec![1f32, 2.]).is_err());
+    assert!(CsrMatrix::new((2, 2), vec![0, 2, 1], vec![0, 1], vec![1f32, 2.]).is_err());
+    Ok(())
+}
+
+#[test]
+fn pad_op() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("pad");
+    let x = builder.c1(&[1f32, 2., 3., 4.])?.reshape(&[2, 2])?;
+    let zero = builder.c0(0f32)?;
+    assert!(x.pad(&zero, &[1], &[0, 0], &[0, 0]).is_err());
+    let padded = x.pad(&zero, &[1, 0], &[0, -1], &[0, 1])?;
+    let exe = client.compile(&padded.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?;
+    assert_eq!(result.array_shape()?.dims(), [3, 2]);
+    assert_eq!(result.to_vec::<f32>()?, [0., 0., 1., 0., 3., 0.]);
+    Ok(())
+}
+
+#[test]
+fn sort_unique_and_bincount() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("unique");
+    let x = builder.c1(&[3i32, 1, 3, 7, 1, 3])?;
+    let (values, counts, mask) = x.unique(4)?;
+    let sorted = x.sort(0)?;
+    let bincount = x.bincount(None, 4)?;
+    let weighted = x.bincount(Some(&builder.c1(&[0.5f32, 1., 0.5, 2., 1., 1.])?), 4)?;
+    let mask = mask.convert(xla::PrimitiveType::S32)?;
+    let outputs = builder.tuple(&[values, counts, mask, sorted, bincount, weighted])?;
+    let exe = client.compile(&outputs.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].to_vec::<i32>()?, [1, 3, 7, 0]);
+    assert_eq!(result[1].to_vec::<i32>()?, [2, 3, 1, 0]);
+    assert_eq!(result[2].to_vec::<i32>()?, [1, 1, 1, 0]);
+    assert_eq!(result[3].to_vec::<i32>()?, [1, 1, 3, 3, 3, 7]);
+    // The value 7 is past the number of bins and is ignored.
+    assert_eq!(result[4].to_vec::<i32>()?, [0, 2, 0, 3]);
+    assert_eq!(result[5].to_vec::<f32>()?, [0., 2., 0., 2.]);
+    Ok(())
+}
+
+#[test]
+fn sort_with_comparator() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    // Sort key/value pairs by decreasing key, the values are only permuted.
+    let cmp = xla::XlaBuilder::new("cmp");
+    let lhs_key = cmp.parameter(0, f32::TY, &[], "lhs_key")?;
+    let rhs_key = cmp.parameter(1, f32::TY, &[], "rhs_key")?;
+    let _lhs_value = cmp.parameter(2, i32::TY, &[], "lhs_value")?;
+    let _rhs_value = cmp.parameter(3, i32::TY, &[], "rhs_value")?;
+    let comparator = lhs_key.gt(&rhs_key)?.build()?;
+
+    let builder = xla::XlaBuilder::new("sort");
+    let keys = builder.c1(&[0.5f32, 2., -1., 2.])?;
+    let values = builder.c1(&[0i32, 1, 2, 3])?;
+    assert!(builder.sort::<xla::XlaOp>(&[], &comparator, 0, true).is_err());
+    let sorted = builder.sort(&[keys, values], &comparator, -1, true)?;
+    let exe = client.compile(&sorted.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].to_vec::<f32>()?, [2., 2., 0.5, -1.]);
+    assert_eq!(result[1].to_vec::<i32>()?, [1, 3, 0, 2]);
+    Ok(())
+}
+
+#[test]
+fn buffer_from_host_raw_bytes() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    // The element type has to be converted to its primitive type, the two enums use different
+    // values for most types.
+    let data: Vec<u8> = [1.5f32, -2.].iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let buffer = client.buffer_from_host_raw_bytes(f32::TY, &data, &[2], None)?;
+    assert_eq!(buffer.on_device_shape()?, xla::Shape::array::<f32>(vec![2]));
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, [1.5, -2.]);
+    let data: Vec<u8> = [3i64].iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let buffer = client.buffer_from_host_raw_bytes(i64::TY, &data, &[1], None)?;
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<i64>()?, [3]);
+    assert!(client.buffer_from_host_raw_bytes(f32::TY, &data, &[1], None).is_err());
+    Ok(())
+}
+
+#[test]
+fn invalid_builder_inputs() -> Result<()> {
+    let builder = xla::XlaBuilder::new("nul\0name");
+    let result = builder.parameter(0, f32::TY, &[2], "x\0");
+    assert!(matches!(result, Err(xla::Error::Nul(_))));
+    let result = builder.iota(f32::TY, &[2, -3], 0);
+    assert!(matches!(result, Err(xla::Error::InvalidDims { .. })));
+    let x = builder.parameter(0, f32::TY, &[3, 3], "x")?;
+    let result = x.triangular_solve(&x, true, true, false, isize::MAX);
+    assert!(matches!(result, Err(xla::Error::TryFromInt(_))));
+    let result =
+        xla::Literal::create_from_shape_and_untyped_data(f32::TY, &[usize::MAX, 2], &[0u8; 8]);
+    assert!(matches!(result, Err(xla::Error::CannotCreateLiteralWithData { .. })));
+    let result = xla::Literal::create_from_shape(xla::PrimitiveType::U8, &[1 << 63]);
+    assert!(matches!(result, Err(xla::Error::InvalidDims { .. })));
+    let result = xla::Literal::create_from_shape(xla::PrimitiveType::Tuple, &[2]);
+    assert!(matches!(result, Err(xla::Error::NotAnElementType { .. })));
+    let result = xla::Literal::vec1(&[1f32, 2., 3., 4.]).reshape(&[-1, -4]);
+    assert!(matches!(result, Err(xla::Error::InvalidDims { .. })));
+    let _err = builder.internal_error("message with a \0 byte");
     Ok(())
 }
 
@@ -179,8 +582,7 @@ fn tuple_op() -> Result<()> {
     let result = tuple.execute::<xla::Literal>(&[x, y])?;
     let result = result[0][0].to_literal_sync()?;
     assert_eq!(result.shape()?.tuple_size(), Some(2));
-    let mut result = result;
-    let result = result.decompose_tuple()?;
+    let result = result.into_tuple()?;
     assert_eq!(result[1].to_vec::<f32>()?, [4.2, 1.337]);
     assert_eq!(result[0].to_vec::<f32>()?, [3.1]);
     Ok(())
@@ -192,8 +594,7 @@ fn tuple_literal() -> Result<()> {
     let y = xla::Literal::vec1(&[4.2f32, 1.337f32]);
     let result = xla::Literal::tuple(vec![x, y]);
     assert_eq!(result.shape()?.tuple_size(), Some(2));
-    let mut result = result;
-    let result = result.decompose_tuple()?;
+    let result = result.into_tuple()?;
     assert_eq!(result[1].to_vec::<f32>()?, [4.2, 1.337]);
     assert_eq!(result[0].to_vec::<f32>()?, [3.1]);
     Ok(())
@@ -230,3 +631,955 @@ fn get_hlo_computations() -> Result<()> {
     // assert_eq!(result.array_shape()?.dims(), []);
     Ok(())
 }
+
+#[test]
+fn resize_and_crop() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let img = builder.constant_r2(&[[0f32, 2f32].as_slice(), [4f32, 6f32].as_slice()])?;
+    let nearest = img.resize_nearest(4, 4)?;
+    let bilinear = img.resize_bilinear(1, 1)?;
+    let cropped = nearest.center_crop(2, 2)?;
+    let tuple = builder.tuple(&[nearest, bilinear, cropped])?.build()?.compile(&client)?;
+    let result = tuple.execute::<xla::Literal>(&[])?;
+    let (nearest, bilinear, cropped) = result[0][0].to_literal_sync()?.to_tuple3()?;
+    assert_eq!(nearest.array_shape()?.dims(), [4, 4]);
+    assert_eq!(
+        nearest.to_vec::<f32>()?,
+        [0., 0., 2., 2., 0., 0., 2., 2., 4., 4., 6., 6., 4., 4., 6., 6.]
+    );
+    assert_eq!(bilinear.to_vec::<f32>()?, [3.]);
+    assert_eq!(cropped.to_vec::<f32>()?, [0., 2., 4., 6.]);
+
+    let empty = builder.parameter(1, f32::TY, &[0, 3], "empty")?;
+    let result = empty.resize_nearest(2, 2);
+    assert!(matches!(result, Err(xla::Error::InvalidDims { .. })));
+    let result = empty.resize_bilinear(2, 2);
+    assert!(matches!(result, Err(xla::Error::InvalidDims { .. })));
+    Ok(())
+}
+
+#[test]
+fn sdpa_op() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let q = builder.constant_r2(&[[1f32, 0f32].as_slice(), [0f32, 1f32].as_slice()])?;
+    let k = q.clone();
+    let v = builder.constant_r2(&[[1f32, 2f32].as_slice(), [3f32, 4f32].as_slice()])?;
+    let mask = builder.constant_r2(&[[1f32, 0f32].as_slice(), [1f32, 1f32].as_slice()])?;
+    let mask = mask.gt(&builder.c0(0f32)?)?;
+    let out = xla::nn::sdpa(&q, &k, &v, Some(&mask), Some(1e4))?;
+    let exe = out.build()?.compile(&client)?;
+    let result = exe.execute::<xla::Literal>(&[])?;
+    let result = result[0][0].to_literal_sync()?;
+    assert_eq!(result.array_shape()?.dims(), [2, 2]);
+    assert_eq!(result.to_vec::<f32>()?, [1., 2., 3., 4.]);
+    Ok(())
+}
+
+#[test]
+fn rotary_embedding() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.constant_r2(&[[1f32, 0f32, 1f32, 0f32].as_slice()])?;
+    let rope = xla::nn::RotaryEmbedding::new(4, 8, 1.0, true)?;
+    let y = rope.apply(&x, 1)?;
+    let exe = y.build()?.compile(&client)?;
+    let result = exe.execute::<xla::Literal>(&[])?;
+    let result = result[0][0].to_literal_sync()?.to_vec::<f32>()?;
+    let (c, s) = (1f32.cos(), 1f32.sin());
+    let expected = [c, s, c, s];
+    for (r, e) in result.iter().zip(expected.iter()) {
+        assert!((r - e).abs() < 1e-6, "{result:?} {expected:?}");
+    }
+    assert!(xla::nn::RotaryEmbedding::new(3, 8, 1.0, true).is_err());
+    Ok(())
+}
+
+#[test]
+fn sampling_ops() -> Result<()> {
+    use xla::sampling::{argmax, sample, SamplingConfig};
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let logits = builder.constant_r2(&[[1f32, 3., 2.].as_slice(), [5f32, 0., 1.].as_slice()])?;
+    let key = xla::PrngKey::new(0);
+    let greedy = argmax(&logits)?;
+    let top_k = SamplingConfig { top_k: Some(1), ..Default::default() };
+    let top_k = sample(&logits, &key, &top_k)?;
+    let top_p = SamplingConfig { top_p: Some(1e-3), ..Default::default() };
+    let top_p = sample(&logits, &key, &top_p)?;
+    let cumsum = logits.cumsum(-1)?;
+    let tuple = builder.tuple(&[greedy, top_k, top_p, cumsum])?.build()?.compile(&client)?;
+    let result = tuple.execute::<xla::Literal>(&[])?;
+    let (greedy, top_k, top_p, cumsum) = result[0][0].to_literal_sync()?.to_tuple4()?;
+    assert_eq!(greedy.to_vec::<i32>()?, [1, 0]);
+    assert_eq!(top_k.to_vec::<i32>()?, [1, 0]);
+    assert_eq!(top_p.to_vec::<i32>()?, [1, 0]);
+    assert_eq!(cumsum.to_vec::<f32>()?, [1., 4., 6., 5., 5., 6.]);
+    Ok(())
+}
+
+#[test]
+fn losses() -> Result<()> {
+    use xla::nn::losses;
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let logits = builder.constant_r2(&[[0f32, 0.].as_slice(), [0f32, 0.].as_slice()])?;
+    let labels = builder.constant_r1(&[0i32, 1])?;
+    let ce = losses::cross_entropy(&logits, &labels, 0.1)?;
+    let pred = builder.constant_r1(&[0f32, 3.])?;
+    let target = builder.constant_r1(&[1f32, 0.])?;
+    let mse = losses::mse(&pred, &target)?;
+    let huber = losses::huber(&pred, &target, 1.0)?;
+    let tuple = builder.tuple(&[ce, mse, huber])?.build()?.compile(&client)?;
+    let result = tuple.execute::<xla::Literal>(&[])?;
+    let (ce, mse, huber) = result[0][0].to_literal_sync()?.to_tuple3()?;
+    assert!((ce.to_vec::<f32>()?[0] - std::f32::consts::LN_2).abs() < 1e-6);
+    assert_eq!(mse.to_vec::<f32>()?, [5.]);
+    assert_eq!(huber.to_vec::<f32>()?, [1.5]);
+    Ok(())
+}
+
+#[test]
+fn grad_scaler() -> Result<()> {
+    use xla::nn::amp::GradScaler;
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("train_step");
+    let scaler = GradScaler { init_scale: 4., growth_interval: 2, ..Default::default() };
+    let p = builder.parameter(0, f32::TY, &[2], "p")?;
+    let g = builder.parameter(1, f32::TY, &[2], "g")?;
+    let state = scaler.state_parameters(&builder, 2)?;
+    let loss = scaler.scale_loss(&builder.c0(0.5f32)?, &state)?;
+    let (params, state) = scaler.step(&state, &[p], &[g], |builder, params, grads| {
+        let lr = builder.c0(0.5f32)?;
+        Ok(vec![(&params[0] - (&grads[0] * lr)?)?])
+    })?;
+    let outputs = [&params[0], &state.scale, &state.growth_tracker, &loss];
+    let exe = client.compile(&builder.tuple(&outputs)?.build()?)?;
+
+    let step = |p: &xla::Literal, g: &[f32], scale: &xla::Literal, tracker: &xla::Literal| {
+        let g = xla::Literal::vec1(g);
+        let result =
+            exe.execute::<&xla::Literal>(&[p, &g, scale, tracker])?[0][0].to_literal_sync()?;
+        result.to_tuple()
+    };
+    let (scale, tracker) = scaler.initial_state();
+    let p = xla::Literal::vec1(&[1f32, 1.]);
+    // The gradients are unscaled before the update: p - 0.5 * g / 4.
+    let mut result = step(&p, &[4., 8.], &scale, &tracker)?;
+    assert_eq!(result[0].to_vec::<f32>()?, [0.5, 0.]);
+    assert_eq!(result[1].to_vec::<f32>()?, [4.]);
+    assert_eq!(result[2].to_vec::<i32>()?, [1]);
+    assert_eq!(result[3].to_vec::<f32>()?, [2.]);
+    let (p, scale, tracker) = (result.remove(0), result.remove(0), result.remove(0));
+
+    // The scale grows after two finite steps.
+    let result = step(&p, &[0., 4.], &scale, &tracker)?;
+    assert_eq!(result[0].to_vec::<f32>()?, [0.5, -0.5]);
+    assert_eq!(result[1].to_vec::<f32>()?, [8.]);
+    assert_eq!(result[2].to_vec::<i32>()?, [0]);
+
+    // Overflowing gradients skip the update and reduce the scale.
+    let result = step(&p, &[f32::INFINITY, 4.], &scale, &tracker)?;
+    assert_eq!(result[0].to_vec::<f32>()?, [0.5, 0.]);
+    assert_eq!(result[1].to_vec::<f32>()?, [2.]);
+    assert_eq!(result[2].to_vec::<i32>()?, [0]);
+
+    // Half precision gradients are unscaled in f32, 1 / 2^25 would flush to zero in f16.
+    let builder = xla::XlaBuilder::new("unscale");
+    let state = xla::nn::amp::LossScale {
+        scale: builder.c0((1u32 << 25) as f32)?,
+        growth_tracker: builder.c0(0i32)?,
+    };
+    let g = builder.c1(&[1024f32])?.convert(xla::PrimitiveType::F16)?;
+    let (unscaled, finite) = scaler.unscale(&[g], &state)?;
+    assert_eq!(unscaled[0].ty()?, xla::PrimitiveType::F16);
+    let unscaled = unscaled[0].convert(xla::PrimitiveType::F32)?;
+    let finite = finite.convert(xla::PrimitiveType::S32)?;
+    let exe = client.compile(&builder.tuple(&[unscaled, finite])?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?.to_tuple()?;
+    assert_eq!(result[0].to_vec::<f32>()?, [1. / 32768.]);
+    assert_eq!(result[1].to_vec::<i32>()?, [1]);
+    Ok(())
+}
+
+#[test]
+fn dropout() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("dropout");
+    let x = builder.constant_literal(&xla::Literal::vec1(&[1f32; 1000]))?;
+    let key = xla::PrngKey::new(42);
+    let (y1, next_key) = xla::nn::dropout(&x, 0.25, &key)?;
+    let (y2, _) = xla::nn::dropout(&x, 0.25, &key)?;
+    let (y3, _) = xla::nn::dropout(&x, 0.25, &next_key)?;
+    assert_ne!(key, next_key);
+    let state = builder.parameter(0, xla::ElementType::U64, &[2], "state")?;
+    let (y4, state) = xla::nn::dropout_with_state(&x, 0.5, &state, key.algorithm())?;
+    let exe = client.compile(&builder.tuple(&[y1, y2, y3, y4, state])?.build()?)?;
+    let run = |state: &xla::Literal| -> Result<Vec<xla::Literal>> {
+        exe.execute::<&xla::Literal>(&[state])?[0][0].to_literal_sync()?.to_tuple()
+    };
+    let mut result = run(&xla::Literal::vec1(&key.state()))?;
+    let y1 = result[0].to_vec::<f32>()?;
+    // The kept values are rescaled so that the expected value is unchanged.
+    assert!(y1.iter().all(|v| *v == 0. || (*v - 4. / 3.).abs() < 1e-6));
+    let dropped = y1.iter().filter(|v| **v == 0.).count();
+    assert!((150..350).contains(&dropped), "{dropped}");
+    // The mask only depends on the key.
+    assert_eq!(y1, result[1].to_vec::<f32>()?);
+    assert_ne!(y1, result[2].to_vec::<f32>()?);
+
+    // Threading the generator state gives a new mask on each execution.
+    let y4 = result[3].to_vec::<f32>()?;
+    let result = run(&result.remove(4))?;
+    assert_ne!(y4, result[3].to_vec::<f32>()?);
+
+    assert!(matches!(xla::nn::dropout(&x, 1., &key), Err(xla::Error::InvalidDropoutRate(_))));
+    assert!(xla::nn::dropout(&x, -0.1, &key).is_err());
+    Ok(())
+}
+
+#[test]
+fn init_ops() -> Result<()> {
+    use rand::SeedableRng;
+    use xla::nn::init::Init;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let literal = Init::KaimingUniform.literal(&mut rng, xla::ElementType::F32, &[100, 20])?;
+    assert_eq!(literal.array_shape()?.dims(), [100, 20]);
+    let bound = (6f32 / 100.).sqrt();
+    assert!(literal.to_vec::<f32>()?.iter().all(|v| v.abs() <= bound));
+
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let key = xla::PrngKey::new(42);
+    let init = Init::TruncatedNormal { mean: 1., stdev: 0.5 };
+    let values = init.op(&key, &builder, xla::ElementType::F32, &[1000])?;
+    let exe = values.build()?.compile(&client)?;
+    let result = exe.execute::<xla::Literal>(&[])?;
+    let result = result[0][0].to_literal_sync()?.to_vec::<f32>()?;
+    assert!(result.iter().all(|v| (0.0..=2.0).contains(v)));
+    Ok(())
+}
+
+#[test]
+fn clip_by_global_norm() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let g1 = builder.constant_r1(&[3f32])?;
+    let g2 = builder.constant_r1(&[4f64])?;
+    let (clipped, norm) = xla::nn::clip_by_global_norm(&[g1, g2], 1.0)?;
+    let mut outputs = clipped;
+    outputs.push(norm);
+    let tuple = builder.tuple(&outputs)?.build()?.compile(&client)?;
+    let result = tuple.execute::<xla::Literal>(&[])?;
+    let (g1, g2, norm) = result[0][0].to_literal_sync()?.to_tuple3()?;
+    assert_eq!(norm.to_vec::<f32>()?, [5.]);
+    assert!((g1.to_vec::<f32>()?[0] - 0.6).abs() < 1e-6);
+    assert!((g2.to_vec::<f64>()?[0] - 0.8).abs() < 1e-6);
+    Ok(())
+}
+
+#[test]
+fn missing_plugin() -> Result<()> {
+    let client = xla::PjRtClient::plugin("/nonexistent/pjrt_c_api_missing_plugin.so");
+    assert!(client.is_err());
+    Ok(())
+}
+
+#[test]
+fn best_available() -> Result<()> {
+    let client = xla::PjRtClient::first_available(&[xla::Backend::Metal, xla::Backend::Cpu])?;
+    let capabilities = client.platform_capabilities();
+    assert_eq!(capabilities.platform_name, "cpu");
+    assert!(!capabilities.is_accelerator);
+    assert!(capabilities.supports_f64);
+    assert!(capabilities.device_count > 0);
+    Ok(())
+}
+
+#[test]
+fn execute_priority() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    assert!(!client.platform_capabilities().supports_execute_priority);
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, f32::TY, &[2], "x")?;
+    let exe = client.compile(&(&x + &x)?.build()?)?;
+    let options = xla::ExecuteOptions::new();
+    assert_eq!(options.get_priority(), xla::ExecutePriority::Normal);
+    let arg = xla::Literal::vec1(&[1f32, 2.]);
+    let result = exe.execute_with_options(&[&arg], &options)?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [2., 4.]);
+    // The cpu client has no stream priorities.
+    let options = options.priority(xla::ExecutePriority::High);
+    assert_eq!(options.get_priority(), xla::ExecutePriority::High);
+    let err = exe.execute_with_options(&[&arg], &options);
+    assert!(matches!(err, Err(xla::Error::UnsupportedExecutePriority { .. })));
+    let arg = client.buffer_from_host_literal(None, &arg)?;
+    let options = options.priority(xla::ExecutePriority::Low);
+    let err = exe.execute_b_with_options(&[arg], &options);
+    assert!(matches!(err, Err(xla::Error::UnsupportedExecutePriority { .. })));
+    Ok(())
+}
+
+#[test]
+fn runtime_info() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let info = client.runtime_info();
+    assert_eq!(info.platform_name, "cpu");
+    assert_eq!(info.platform_version, client.platform_version());
+    assert_eq!(info.device_kinds.len(), client.addressable_device_count());
+    assert!(info.toolkit_version.is_none());
+    Ok(())
+}
+
+#[test]
+fn nccl_config() {
+    assert!(xla::NcclConfig::default().env_vars().is_empty());
+    let config = xla::NcclConfig {
+        comm_id: Some("10.0.0.1:4242".to_string()),
+        timeout: Some(std::time::Duration::from_secs(120)),
+        p2p_transport: Some(false),
+    };
+    assert_eq!(
+        config.env_vars(),
+        [
+            ("NCCL_P2P_DISABLE", "1".to_string()),
+            ("NCCL_COMM_ID", "10.0.0.1:4242".to_string()),
+            ("XLA_FLAGS", "--xla_gpu_nccl_termination_timeout_seconds=120".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn half_precision_literals() -> Result<()> {
+    let literal = xla::Literal::vec1(&[1f32, 0.1, -3.5, 1e5]);
+    let bf16 = literal.to_bf16()?;
+    assert_eq!(bf16.ty()?, xla::ElementType::Bf16);
+    assert_eq!(bf16.size_bytes(), 8);
+    assert_eq!(bf16.upcast_to_f32()?.to_vec::<f32>()?, [1., 0.10009765625, -3.5, 99840.]);
+    let f16 = literal.to_f16()?;
+    assert_eq!(f16.ty()?, xla::ElementType::F16);
+    let f16 = f16.upcast_to_f32()?.to_vec::<f32>()?;
+    assert_eq!(f16[..3], [1., 0.099975586, -3.5]);
+    assert!(f16[3].is_infinite());
+    assert!(xla::Literal::vec1(&[1i32]).to_bf16().is_err());
+    assert!(xla::Literal::vec1(&[1f64]).upcast_to_f32().is_err());
+    Ok(())
+}
+
+#[test]
+fn chunked_transfers() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let values: Vec<f32> = (0..60).map(|v| v as f32).collect();
+    let literal = xla::Literal::vec1(&values).reshape(&[5, 3, 4])?;
+    let mut upload_progress = vec![];
+    // Each row is 48 bytes so this results in chunks of two rows.
+    let buffer = client
+        .buffer_from_host_literal_chunked(&literal, 100, |d, t| upload_progress.push((d, t)))?;
+    assert_eq!(upload_progress, [(96, 240), (192, 240), (240, 240)]);
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, values);
+
+    let mut download_progress = vec![];
+    let literal = buffer.to_literal_sync_chunked(50, |d, t| download_progress.push((d, t)))?;
+    assert_eq!(download_progress.len(), 5);
+    assert_eq!(literal.array_shape()?.dims(), [5, 3, 4]);
+    assert_eq!(literal.to_vec::<f32>()?, values);
+    Ok(())
+}
+
+#[test]
+fn pinned_host_buffer() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let mut host_buffer = client.allocate_host_buffer(6 * 4)?;
+    assert_eq!(host_buffer.len(), 24);
+    assert!(!host_buffer.is_pinned());
+    host_buffer.as_mut_slice_of::<f32>()?.copy_from_slice(&[1., 2., 3., 4., 5., 6.]);
+    let buffer = client.buffer_from_pinned_host_buffer(
+        &host_buffer,
+        xla::ElementType::F32,
+        &[2, 3],
+        None,
+    )?;
+    let literal = buffer.to_literal_sync()?;
+    assert_eq!(literal.array_shape()?.dims(), [2, 3]);
+    assert_eq!(literal.to_vec::<f32>()?, [1., 2., 3., 4., 5., 6.]);
+    assert!(client
+        .buffer_from_pinned_host_buffer(&host_buffer, xla::ElementType::F32, &[2, 2], None)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn execute_many() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let y = builder.parameter(1, xla::ElementType::F32, &[2], "y")?;
+    let exe = client.compile(&(x * y)?.build()?)?;
+    let args: Vec<Vec<xla::Literal>> = (0..4)
+        .map(|i| vec![xla::Literal::vec1(&[i as f32, 1.]), xla::Literal::vec1(&[2f32, i as f32])])
+        .collect();
+    let results = exe.execute_many(&args)?;
+    assert_eq!(results.len(), 4);
+    for (i, result) in results.iter().enumerate() {
+        let result = result[0][0].to_literal_sync()?.to_vec::<f32>()?;
+        assert_eq!(result, [2. * i as f32, i as f32]);
+    }
+    let empty: Vec<Vec<xla::Literal>> = vec![];
+    assert!(exe.execute_many(&empty)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn mixing_builders() -> Result<()> {
+    let builder1 = xla::XlaBuilder::new("test1");
+    let builder2 = xla::XlaBuilder::new("test2");
+    let x1 = builder1.c0(1f32)?;
+    let x2 = builder2.c0(2f32)?;
+    assert!(x1.builder().is_same(&builder1));
+    assert!(!x1.builder().is_same(&builder2));
+    assert!(matches!(x1.add_(&x2), Err(xla::Error::BuilderMismatch)));
+    assert!(matches!(builder1.tuple(&[&x1, &x2]), Err(xla::Error::BuilderMismatch)));
+    assert!(matches!(builder2.build(&x1), Err(xla::Error::BuilderMismatch)));
+    // Ops keep their builder alive.
+    drop(builder1);
+    let sum = (&x1 + &x1)?;
+    let client = xla::PjRtClient::cpu()?;
+    let result = client.compile(&sum.build()?)?.execute::<xla::Literal>(&[])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [2.]);
+    Ok(())
+}
+
+#[test]
+fn clone_computation_and_buffer() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let computation = (&x + &x)?.build()?;
+    let cloned = computation.clone();
+    drop(computation);
+    assert_eq!(cloned.name(), "test");
+    let exe = client.compile(&cloned)?;
+
+    let buffer = client.buffer_from_host_buffer(&[1f32, 2.], &[2], None)?;
+    let copy = buffer.duplicate()?;
+    drop(buffer);
+    assert_eq!(copy.to_literal_sync()?.to_vec::<f32>()?, [1., 2.]);
+    let result = exe.execute_b(&[&copy])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [2., 4.]);
+
+    // The copy stays on the device of the original buffer.
+    let options = xla::CpuClientOptions { device_count: Some(2), ..Default::default() };
+    let client = xla::PjRtClient::cpu_with_options(&options)?;
+    let device = client.addressable_devices().remove(1);
+    let buffer = client.buffer_from_host_buffer(&[5f32, 6.], &[2], Some(&device))?;
+    let copy = buffer.duplicate()?;
+    assert_eq!(copy.device().id(), device.id());
+    assert_eq!(copy.to_literal_sync()?.to_vec::<f32>()?, [5., 6.]);
+
+    let literal = xla::Literal::vec1(&[3i32, 4]);
+    let literal_copy = literal.clone();
+    drop(literal);
+    assert_eq!(literal_copy.to_vec::<i32>()?, [3, 4]);
+    Ok(())
+}
+
+#[test]
+fn fusion_report() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[16], "x")?;
+    let y = (&x.exp()? + &x)?.tanh()?;
+    let exe = client.compile(&y.build()?)?;
+    let report = exe.fusion_report()?;
+    assert!(!report.kernels.is_empty());
+    for kernel in report.kernels.iter() {
+        assert!(!kernel.kind.is_empty());
+        for instruction in kernel.instructions.iter() {
+            assert_eq!(report.kernel_for(instruction).map(|k| &k.name), Some(&kernel.name));
+        }
+    }
+    assert!(report.kernel_for("not-an-instruction").is_none());
+    Ok(())
+}
+
+#[test]
+fn backend_ir() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[16], "x")?;
+    let exe = client.compile(&x.exp()?.build()?)?;
+    // The cpu backend does not produce any PTX.
+    assert!(exe.backend_ir(xla::BackendIrKind::Ptx).is_err());
+    Ok(())
+}
+
+#[test]
+fn execute_scalar_args() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[], "x")?;
+    let y = builder.parameter(1, xla::ElementType::F32, &[], "y")?;
+    let exe = client.compile(&(x + y)?.build()?)?;
+    let result = exe.execute(&[1.5f32, 2.])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [3.5]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::S64, &[3], "x")?;
+    let exe = client.compile(&(&x + &x)?.build()?)?;
+    let result = exe.execute(&[[1i64, 2, 3].as_slice()])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<i64>()?, [2, 4, 6]);
+    let result = exe.execute(&[vec![4i64, 5, 6]])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<i64>()?, [8, 10, 12]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::Pred, &[], "x")?;
+    let exe = client.compile(&x.not()?.build()?)?;
+    let result = exe.execute(&[true])?;
+    assert_eq!(result[0][0].to_literal_sync()?.untyped_data(), [0]);
+    Ok(())
+}
+
+#[test]
+fn execute_mixed_inputs() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let w = builder.parameter(0, xla::ElementType::F32, &[2], "w")?;
+    let x = builder.parameter(1, xla::ElementType::F32, &[2], "x")?;
+    let exe = client.compile(&(w * x)?.build()?)?;
+    let weights = client.buffer_from_host_buffer(&[2f32, 3.], &[2], None)?;
+    let x = xla::Literal::vec1(&[4f32, 5.]);
+    let inputs = [xla::ExecuteInput::from(&weights), xla::ExecuteInput::from(&x)];
+    let result = exe.execute_inputs(&inputs)?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [8., 15.]);
+
+    let w = xla::Literal::vec1(&[1f32, 2.]);
+    let result = exe.execute(&[&w, &x])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [4., 10.]);
+    let x = client.buffer_from_host_buffer(&[1f32, 1.], &[2], None)?;
+    let inputs = [xla::ExecuteInput::from(&w), xla::ExecuteInput::from(x)];
+    let result = exe.execute_inputs(&inputs)?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [1., 2.]);
+    Ok(())
+}
+
+#[test]
+fn bound_executable() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let w = builder.parameter(0, xla::ElementType::F32, &[2], "w")?;
+    let x = builder.parameter(1, xla::ElementType::F32, &[2], "x")?;
+    let b = builder.parameter(2, xla::ElementType::F32, &[2], "b")?;
+    let exe = client.compile(&((w * x)? + b)?.build()?)?;
+    let w = client.buffer_from_host_buffer(&[2f32, 3.], &[2], None)?;
+    let b = client.buffer_from_host_buffer(&[1f32, 1.], &[2], None)?;
+    let exe = exe.bind(vec![(2, b), (0, w)])?;
+    assert_eq!(exe.bound_parameters(), [0, 2]);
+    let x = xla::Literal::vec1(&[4f32, 5.]);
+    let result = exe.execute_inputs(&[xla::ExecuteInput::from(&x)])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [9., 16.]);
+    let x = client.buffer_from_host_buffer(&[1f32, 2.], &[2], None)?;
+    let result = exe.execute_b(&[&x])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [3., 7.]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let exe = client.compile(&(&x + &x)?.build()?)?;
+    let b1 = client.buffer_from_host_buffer(&[1f32, 1.], &[2], None)?;
+    let b2 = client.buffer_from_host_buffer(&[1f32, 1.], &[2], None)?;
+    assert!(matches!(
+        exe.bind(vec![(0, b1), (0, b2)]),
+        Err(xla::Error::DuplicateParameterBinding { index: 0 })
+    ));
+    Ok(())
+}
+
+#[test]
+fn delete_buffer() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let buffer = client.buffer_from_host_buffer(&[1f32, 2.], &[2], None)?;
+    assert!(!buffer.is_deleted());
+    buffer.delete();
+    assert!(buffer.is_deleted());
+    assert!(buffer.to_literal_sync().is_err());
+    Ok(())
+}
+
+#[test]
+fn cached_host_view() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let buffer = client.buffer_from_host_buffer(&[1f32, 2., 3.], &[3], None)?;
+    let mut view = xla::CachedHostView::new(buffer);
+    assert!(!view.is_cached());
+    assert_eq!(view.as_slice::<f32>()?, [1., 2., 3.]);
+    assert!(view.is_cached());
+    assert_eq!(view.literal()?.to_vec::<f32>()?, [1., 2., 3.]);
+    assert!(view.as_slice::<i32>().is_err());
+    view.invalidate();
+    assert!(!view.is_cached());
+    assert_eq!(view.as_slice::<f32>()?, [1., 2., 3.]);
+    view.buffer().delete();
+    assert!(!view.is_cached());
+    assert!(matches!(view.as_slice::<f32>(), Err(xla::Error::BufferDeleted)));
+    Ok(())
+}
+
+#[test]
+fn to_stablehlo() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let computation = (&x + &x)?.build()?;
+    let stablehlo = computation.to_stablehlo()?;
+    assert!(stablehlo.contains("stablehlo.add"), "{stablehlo}");
+    Ok(())
+}
+
+#[test]
+fn large_literal() -> Result<()> {
+    // Large enough to use the parallel code paths when the rayon feature is enabled.
+    let data: Vec<f32> = (0..(1 << 22) + 3).map(|i| (i % 256) as f32).collect();
+    let literal = xla::Literal::vec1(&data);
+    assert_eq!(literal.to_vec::<f32>()?, data);
+    let bf16 = literal.to_bf16()?;
+    assert_eq!(bf16.ty()?, xla::ElementType::Bf16);
+    assert_eq!(bf16.upcast_to_f32()?.to_vec::<f32>()?, data);
+    let mut copy = xla::Literal::create_from_shape(xla::PrimitiveType::F32, &[data.len()])?;
+    copy.copy_raw_from(&data)?;
+    assert_eq!(copy.to_vec::<f32>()?, data);
+    Ok(())
+}
+
+#[test]
+fn to_literal_into() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let mut literal = xla::Literal::create_from_shape(xla::PrimitiveType::F32, &[2, 2])?;
+    for i in 0..3 {
+        let data: Vec<f32> = (0..4).map(|j| (i * 4 + j) as f32).collect();
+        let buffer = client.buffer_from_host_buffer(&data, &[2, 2], None)?;
+        buffer.to_literal_into(&mut literal)?;
+        assert_eq!(literal.to_vec::<f32>()?, data);
+    }
+    let buffer = client.buffer_from_host_buffer(&[1f32, 2., 3.], &[3], None)?;
+    assert!(matches!(buffer.to_literal_into(&mut literal), Err(xla::Error::ShapeMismatch { .. })));
+    Ok(())
+}
+
+#[test]
+fn computation_fingerprint() -> Result<()> {
+    let build = |name: &str, param_name: &str| -> Result<xla::XlaComputation> {
+        let builder = xla::XlaBuilder::new(name);
+        let x = builder.parameter(0, xla::ElementType::F32, &[2], param_name)?;
+        (&x + &x)?.build()
+    };
+    let fingerprint = build("a", "x")?.fingerprint()?;
+    assert!(!fingerprint.is_empty());
+    assert_eq!(build("a", "x")?.fingerprint()?, fingerprint);
+    assert_eq!(build("a", "y")?.fingerprint()?, fingerprint);
+    assert_eq!(build("a", "x")?.clone().fingerprint()?, fingerprint);
+
+    let builder = xla::XlaBuilder::new("a");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let other = (&x * &x)?.build()?;
+    assert_ne!(other.fingerprint()?, fingerprint);
+    Ok(())
+}
+
+#[test]
+fn simplify_computation() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let _dead = x.exp()?;
+    let one = builder.one(xla::ElementType::F32)?.broadcast(&[2])?;
+    let zero = builder.zero(xla::ElementType::F32)?.broadcast(&[2])?;
+    let computation = ((&x * one)? + zero)?.build()?;
+    let instruction_count = |c: &xla::XlaComputation| -> Result<usize> {
+        let computations = c.proto().computations()?;
+        computations.iter().map(|c| c.get_instructions_size()).sum()
+    };
+    let simplified = computation.simplify()?;
+    assert!(instruction_count(&simplified)? < instruction_count(&computation)?);
+    let result = client.compile(&simplified)?.execute(&[[1f32, 2.].as_slice()])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [1., 2.]);
+    Ok(())
+}
+
+#[test]
+fn constant_literals() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[3], "x")?;
+    let weights = builder.constant_r1(&[1f32, 2., 3.])?;
+    let computation = (x * weights)?.build()?;
+    let mut literals = vec![];
+    let mut other_literals = 0;
+    for computation in computation.proto().computations()? {
+        for instruction in computation.instructions()? {
+            match (instruction.opcode()?.as_str(), instruction.literal()?) {
+                ("constant", Some(literal)) => literals.push(literal),
+                (_, Some(_)) => other_literals += 1,
+                (_, None) => {}
+            }
+        }
+    }
+    assert_eq!(other_literals, 0);
+    assert_eq!(literals.len(), 1);
+    assert_eq!(literals[0].to_vec::<f32>()?, [1., 2., 3.]);
+    Ok(())
+}
+
+#[test]
+fn to_scalar() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    assert_eq!(xla::Literal::scalar(42i32).to_scalar::<i32>()?, 42);
+    assert!(xla::Literal::scalar(42i32).to_scalar::<f32>().is_err());
+    let single = xla::Literal::vec1(&[42f32]);
+    assert_eq!(single.get_first_element::<f32>()?, 42.);
+    assert!(matches!(
+        single.to_scalar::<f32>(),
+        Err(xla::Error::UnexpectedNumberOfDims { expected: 0, got: 1, .. })
+    ));
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.c0(40f32)?;
+    let computation = builder.tuple(&[(&x + builder.c0(2f32)?)?, x.broadcast(&[1])?])?.build()?;
+    let result = client.compile(&computation)?.execute::<xla::Literal>(&[])?;
+    let (sum, single) = result[0][0].to_literal_sync()?.to_tuple2()?;
+    assert_eq!(sum.to_scalar::<f32>()?, 42.);
+    assert!(single.to_scalar::<f32>().is_err());
+    let sum = client.buffer_from_host_literal(None, &sum)?;
+    assert_eq!(sum.to_scalar::<f32>()?, 42.);
+    let single = client.buffer_from_host_literal(None, &single)?;
+    assert!(single.to_scalar::<f32>().is_err());
+    Ok(())
+}
+
+#[test]
+fn literal_eq_and_hash() -> Result<()> {
+    let lit = xla::Literal::vec1(&[1f32, f32::NAN, 3.]);
+    assert_eq!(lit, lit.clone());
+    assert_ne!(lit, xla::Literal::vec1(&[1f32, f32::NAN, 4.]));
+    // The comparison is bitwise and takes the shape into account.
+    assert_ne!(xla::Literal::scalar(0f32), xla::Literal::scalar(-0f32));
+    assert_ne!(lit, lit.reshape(&[3, 1])?);
+    assert_ne!(xla::Literal::vec1(&[1i32]), xla::Literal::vec1(&[1u32]));
+    let tuple = xla::Literal::tuple(vec![lit.clone(), xla::Literal::scalar(2i64)]);
+    assert_eq!(tuple, tuple.clone());
+    assert_ne!(tuple, xla::Literal::tuple(vec![lit.clone(), xla::Literal::scalar(3i64)]));
+
+    let mut cache = std::collections::HashMap::new();
+    cache.insert(lit.clone(), 1);
+    cache.insert(tuple.clone(), 2);
+    cache.insert(lit.clone(), 3);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache[&lit], 3);
+    assert_eq!(cache[&tuple], 2);
+
+    let lhs = xla::Literal::vec1(&[1f32, 2., 3.]);
+    assert!(lhs.approx_eq(&xla::Literal::vec1(&[1f32, 2.000001, 3.]), 1e-5, 0.)?);
+    assert!(!lhs.approx_eq(&xla::Literal::vec1(&[1f32, 2.1, 3.]), 1e-5, 0.)?);
+    assert!(!lhs.approx_eq(&xla::Literal::vec1(&[1f64, 2., 3.]), 1e-5, 0.)?);
+    assert!(!lit.approx_eq(&lit, 1e-5, 1e-5)?);
+    assert!(xla::Literal::vec1(&[1i32, 2]).approx_eq(&xla::Literal::vec1(&[1i32, 3]), 0., 1.)?);
+    Ok(())
+}
+
+#[test]
+fn const_eval() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[3], "x")?;
+    let table = builder.iota1(xla::ElementType::F32, 3)?.exp()?;
+    let scale = (builder.c0(2f32)? * builder.c0(3f32)?)?;
+    assert!(table.is_constant()?);
+    assert!(!x.is_constant()?);
+    assert_eq!(scale.evaluate_constant()?.to_vec::<f32>()?, [6.]);
+    assert!(x.exp()?.evaluate_constant().is_err());
+    let table = table.const_eval()?;
+    let scale = scale.const_eval()?;
+    assert!(!x.const_eval()?.is_constant()?);
+    let computation = ((x * table)? * scale)?.build()?.simplify()?;
+    let computations = computation.proto().computations()?;
+    let entry = computations.last().unwrap();
+    let opcodes =
+        entry.instructions()?.iter().map(|i| i.opcode()).collect::<xla::Result<Vec<_>>>()?;
+    assert!(!opcodes.iter().any(|o| o == "exponential" || o == "iota"), "{opcodes:?}");
+    let result = client.compile(&computation)?.execute(&[[1f32, 1., 1.].as_slice()])?;
+    let result = result[0][0].to_literal_sync()?.to_vec::<f32>()?;
+    let expected = [6f32, 6. * 1f32.exp(), 6. * 2f32.exp()];
+    assert!(result.iter().zip(expected.iter()).all(|(r, e)| (r - e).abs() < 1e-4), "{result:?}");
+    Ok(())
+}
+
+#[test]
+fn instruction_graph() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[3], "x")?;
+    let y = builder.parameter(1, xla::ElementType::F32, &[3], "y")?;
+    let computation = (x.exp()? + y)?.build()?;
+    let proto = computation.proto();
+    let computations = proto.computations()?;
+    let entry = computations.last().unwrap();
+    let root = entry.instruction_by_id(entry.root_id()).unwrap();
+    assert_eq!(root.opcode()?, "add");
+    let operands: Vec<_> =
+        root.operand_ids().into_iter().map(|id| entry.instruction_by_id(id).unwrap()).collect();
+    assert_eq!(operands.len(), 2);
+    assert_eq!(operands[0].opcode()?, "exponential");
+    assert_eq!(operands[1].opcode()?, "parameter");
+    let exp_operands = operands[0].operand_ids();
+    assert_eq!(exp_operands.len(), 1);
+    assert_eq!(entry.instruction_by_id(exp_operands[0]).unwrap().opcode()?, "parameter");
+    assert!(entry.instruction_by_id(-1).is_none());
+    Ok(())
+}
+
+#[test]
+fn op_attributes() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[3], "x")?;
+    let y = x.exp()?;
+    y.set_frontend_attribute("_pipeline_stage", "1")?;
+    y.set_backend_config("{}")?;
+    let computation = (y + x)?.build()?;
+    let computations = computation.proto().computations()?;
+    let entry = computations.last().unwrap();
+    let root = entry.instruction_by_id(entry.root_id()).unwrap();
+    assert!(root.frontend_attributes().is_empty());
+    assert_eq!(root.backend_config(), "");
+    let exp = entry.instruction_by_id(root.operand_ids()[0]).unwrap();
+    assert_eq!(exp.opcode()?, "exponential");
+    assert_eq!(exp.frontend_attributes().get("_pipeline_stage").map(|v| v.as_str()), Some("1"));
+    assert_eq!(exp.backend_config(), "{}");
+    Ok(())
+}
+
+#[test]
+fn module_metadata() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let mut proto = (&x + &x)?.build()?.proto();
+
+    proto.set_entry_computation_name("main")?;
+    assert_eq!(proto.entry_computation_name(), "main");
+    let computations = proto.computations()?;
+    let entry = computations.last().unwrap();
+    assert_eq!(entry.instruction_by_id(entry.root_id()).unwrap().opcode()?, "add");
+
+    let f32_2 = xla::Shape::array::<f32>(vec![2]);
+    let program_shape = proto.host_program_shape()?.unwrap();
+    assert_eq!(program_shape.parameters, [f32_2.clone()]);
+    assert_eq!(program_shape.parameter_names, ["x"]);
+    assert_eq!(program_shape.result, f32_2);
+    let renamed = xla::ProgramShape { parameter_names: vec!["y".to_string()], ..program_shape };
+    proto.set_host_program_shape(&renamed)?;
+    assert_eq!(proto.host_program_shape()?, Some(renamed));
+
+    assert!(proto.input_output_aliases().is_empty());
+    let alias = xla::InputOutputAlias {
+        output_index: vec![],
+        parameter_number: 0,
+        parameter_index: vec![],
+        kind: xla::AliasKind::May,
+    };
+    proto.set_input_output_aliases(&[alias.clone()]);
+    assert_eq!(proto.input_output_aliases(), [alias]);
+
+    proto.set_frontend_attribute("exported_by", "test")?;
+    assert_eq!(proto.frontend_attributes().get("exported_by").map(|v| v.as_str()), Some("test"));
+    let client = xla::PjRtClient::cpu()?;
+    let result = client
+        .compile(&xla::XlaComputation::from_proto(&proto))?
+        .execute(&[[1f32, 2.].as_slice()])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [2., 4.]);
+    Ok(())
+}
+
+#[test]
+fn speculative_ops() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2, 3], "x")?;
+    let y = builder.parameter(1, xla::ElementType::F32, &[3], "y")?;
+    let z = builder.parameter(2, xla::ElementType::F32, &[2, 3], "z")?;
+    assert_eq!(
+        builder.infer_shape(&x),
+        Some(xla::Shape::Array(xla::ArrayShape::new::<f32>(vec![2, 3])))
+    );
+    assert_eq!(x.infer_binary_shape(xla::BinaryOp::Add, &y), None);
+    assert!(x.try_binary(xla::BinaryOp::Add, &y).is_none());
+    assert!(x.try_reshape(&[4, 2]).is_none());
+    // The failed attempts leave the builder usable.
+    builder.first_error()?;
+    let shape = x.infer_binary_shape(xla::BinaryOp::Lt, &z);
+    let pred = xla::ArrayShape::new_with_type(xla::ElementType::Pred, vec![2, 3]);
+    assert_eq!(shape, Some(xla::Shape::Array(pred)));
+    let sum = x.try_binary(xla::BinaryOp::Add, &z).expect("valid add");
+    let sum = sum.try_reshape(&[3, 2]).expect("valid reshape");
+    assert_eq!(sum.dims()?, [3, 2]);
+    Ok(())
+}
+
+#[test]
+fn symbolic_dims() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let dims: [xla::Dim; 2] = ["batch".into(), 3.into()];
+
+    let builder = xla::XlaBuilder::new("test");
+    let err = builder.parameter_dyn(0, f32::TY, &dims, "x");
+    assert!(matches!(err, Err(xla::Error::InvalidSymbol { .. })));
+    builder.set_symbol_bound("batch", 8)?;
+    assert_eq!(builder.symbol_size("batch"), Some(xla::SymbolSize::Bounded(8)));
+    assert!(builder.set_symbol_size("batch", 4).is_err());
+    let x = builder.parameter_dyn(0, f32::TY, &dims, "x")?;
+    // Bounded dimensions report their bound as static size.
+    assert_eq!(x.dims()?, [8, 3]);
+    assert!(!x.dimension_size(0)?.is_constant()?);
+
+    // Refining the symbol on another builder gives static shapes.
+    for batch in [2, 4] {
+        let builder = xla::XlaBuilder::new("test");
+        builder.set_symbol_size("batch", batch)?;
+        let x = builder.parameter_dyn(0, f32::TY, &dims, "x")?;
+        assert_eq!(x.dims()?, [batch as usize, 3]);
+        let sum = x.reduce_sum(&[0, 1], false)?;
+        let size = x.dimension_size(0)?.convert(f32::TY.primitive_type())?;
+        let computation = builder.build(&builder.tuple(&[sum, size])?)?;
+        let input = xla::Literal::vec1(&vec![1f32; 3 * batch as usize]).reshape(&[batch, 3])?;
+        let result = client.compile(&computation)?.execute::<xla::Literal>(&[input])?;
+        let (sum, size) = result[0][0].to_literal_sync()?.to_tuple2()?;
+        assert_eq!(sum.to_vec::<f32>()?, [3. * batch as f32]);
+        assert_eq!(size.to_vec::<f32>()?, [batch as f32]);
+    }
+    Ok(())
+}
+
+#[test]
+fn warmup() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let exe = client.compile(&(&x + &x)?.build()?)?;
+    let devices = exe.addressable_devices();
+    assert_eq!(devices.len(), 1);
+    exe.warmup(&[[1f32, 2.].as_slice()])?;
+    let durations = exe.warmup_devices(&[[1f32, 2.].as_slice()])?;
+    assert_eq!(durations.len(), 1);
+    assert!(exe.warmup::<&[f32]>(&[]).is_err());
+
+    // The replicas of a computation using collectives are warmed up together.
+    let options = xla::CpuClientOptions { device_count: Some(2), ..Default::default() };
+    let client = xla::PjRtClient::cpu_with_options(&options)?;
+    let sum = {
+        let builder = xla::XlaBuilder::new("sum");
+        let x = builder.parameter(0, xla::ElementType::F32, &[], "x")?;
+        let y = builder.parameter(1, xla::ElementType::F32, &[], "y")?;
+        (x + y)?.build()?
+    };
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let reduced = x.all_reduce(&sum, &xla::CollectiveDeviceList::all(), None)?;
+    let exe = client.compile_replicated(&reduced.build()?, 2)?;
+    assert_eq!(exe.addressable_devices().len(), 2);
+    let durations = exe.warmup_devices(&[[1f32, 2.].as_slice()])?;
+    assert_eq!(durations.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,23 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+use xla::bench::{self, BenchOptions};
+
+#[test]
+fn bench_matmul() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[64, 64], "x")?;
+    let computation = x.matmul(&x)?.build()?;
+    let x = client.buffer_from_host_buffer(&[1f32; 64 * 64], &[64, 64], None)?;
+    let options = BenchOptions { warmup_iterations: 2, iterations: 10 };
+    let report = bench::run(&client, &computation, &[&x], &options)?;
+    assert_eq!(report.iterations, 10);
+    assert!(report.min <= report.p50 && report.p50 <= report.p99 && report.p99 <= report.max);
+    assert!(report.min <= report.mean && report.mean <= report.max);
+    if let Some(flops) = report.flops {
+        assert!(flops > 0.);
+        assert!(report.flops_per_second().is_some());
+    }
+    assert!(report.to_string().starts_with("10 iterations"));
+    Ok(())
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "ffi")]
+use xla::bucketing::{batch_padding_mask, pad_literal, BucketedExecutable, Buckets, TokenBatch};
+use xla::{ElementType, Result};
+
+#[test]
+fn buckets() -> Result<()> {
+    let buckets = Buckets::powers_of_two(3, 20)?;
+    assert_eq!(buckets.lengths(), [4, 8, 16, 20]);
+    assert_eq!(buckets.bucket_len(1)?, 4);
+    assert_eq!(buckets.bucket_len(8)?, 8);
+    assert_eq!(buckets.bucket_len(17)?, 20);
+    assert!(matches!(
+        buckets.bucket_len(21),
+        Err(xla::Error::SequenceTooLong { len: 21, max: 20 })
+    ));
+    assert_eq!(Buckets::new(&[8, 2, 8])?.lengths(), [2, 8]);
+    assert!(Buckets::new(&[]).is_err());
+    Ok(())
+}
+
+#[test]
+fn padding() -> Result<()> {
+    let literal = xla::Literal::vec1(&[1i32, 2, 3, 4, 5, 6]).reshape(&[2, 3])?;
+    let padded = pad_literal(&literal, 1, 4)?;
+    assert_eq!(padded.array_shape()?.dims(), [2, 4]);
+    assert_eq!(padded.to_vec::<i32>()?, [1, 2, 3, 0, 4, 5, 6, 0]);
+    let padded = pad_literal(&literal, 0, 3)?;
+    assert_eq!(padded.to_vec::<i32>()?, [1, 2, 3, 4, 5, 6, 0, 0, 0]);
+    assert!(pad_literal(&literal, 1, 2).is_err());
+
+    let mask = batch_padding_mask(&[1, 3], 3)?;
+    assert_eq!(mask.array_shape()?.dims(), [2, 3]);
+    assert_eq!(mask.untyped_data(), [1, 0, 0, 1, 1, 1]);
+    Ok(())
+}
+
+#[test]
+fn token_batch() -> Result<()> {
+    let sequences = vec![vec![5u32, 6, 7], vec![8]];
+    let batch = TokenBatch::new::<i32, _>(&sequences, 0, None)?;
+    assert_eq!(batch.tokens.array_shape()?.dims(), [2, 3]);
+    assert_eq!(batch.tokens.to_vec::<i32>()?, [5, 6, 7, 8, 0, 0]);
+    assert_eq!(batch.mask.untyped_data(), [1, 1, 1, 1, 0, 0]);
+    assert_eq!(batch.lens, [3, 1]);
+
+    let buckets = Buckets::new(&[2, 4])?;
+    let batch = TokenBatch::new::<i64, _>(&sequences, 1, Some(&buckets))?;
+    assert_eq!(batch.tokens.to_vec::<i64>()?, [5, 6, 7, 1, 8, 1, 1, 1]);
+    assert_eq!(batch.mask.array_shape()?.dims(), [2, 4]);
+    assert!(TokenBatch::new::<i32, _>(&[[u32::MAX]], 0, None).is_err());
+    assert!(TokenBatch::new::<i32, _>(&[[0u32; 5]], 0, Some(&buckets)).is_err());
+    Ok(())
+}
+
+#[test]
+fn bucketed_executable() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let buckets = Buckets::new(&[4, 8])?;
+    let exe = BucketedExecutable::new(&client, buckets, 0, |builder, len| {
+        let x = builder.parameter(0, ElementType::F32, &[len as i64], "x")?;
+        let mask = builder.parameter(1, ElementType::Pred, &[len as i64], "mask")?;
+        let ones = builder.c0(1f32)?.broadcast(&[len as i64])?;
+        let count =
+            mask.select(&ones, &builder.zero(ElementType::F32)?.broadcast(&[len as i64])?)?;
+        let sum = x.reduce_sum(&[0], false)?;
+        builder.build(&builder.tuple(&[sum, count.reduce_sum(&[0], false)?])?)
+    })?;
+    for (len, bucket_len) in [(3, 4), (6, 8)] {
+        assert_eq!(exe.executable(len)?.0, bucket_len);
+        let x = xla::Literal::vec1(&vec![2f32; len]);
+        let result = exe.execute_with_mask(&[x])?;
+        let (sum, count) = result[0][0].to_literal_sync()?.to_tuple2()?;
+        assert_eq!(sum.to_vec::<f32>()?, [2. * len as f32]);
+        assert_eq!(count.to_vec::<f32>()?, [len as f32]);
+    }
+    let x = xla::Literal::vec1(&[0f32; 9]);
+    assert!(exe.execute_with_mask(&[x]).is_err());
+    Ok(())
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "candle")]
+use anyhow::Result;
+use candle_core::{Device, Tensor};
+
+#[test]
+fn tensor_roundtrip() -> Result<()> {
+    let tensor = Tensor::arange(0f32, 6., &Device::Cpu)?.reshape((2, 3))?;
+    let literal = xla::candle::literal_from_tensor(&tensor)?;
+    assert_eq!(literal.array_shape()?, xla::ArrayShape::new::<f32>(vec![2, 3]));
+    assert_eq!(literal.to_vec::<f32>()?, [0., 1., 2., 3., 4., 5.]);
+
+    // Non contiguous tensors are made contiguous before the copy.
+    let client = xla::PjRtClient::cpu()?;
+    let buffer = xla::candle::buffer_from_tensor(&client, &tensor.t()?, None)?;
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, [0., 3., 1., 4., 2., 5.]);
+
+    let back = xla::candle::tensor_from_buffer(&buffer, &Device::Cpu)?;
+    assert_eq!(back.dims(), [3, 2]);
+    assert_eq!(back.to_vec2::<f32>()?, [[0., 3.], [1., 4.], [2., 5.]]);
+    Ok(())
+}
+
+// This requires a CUDA device.
+#[cfg(feature = "cuda")]
+#[test]
+fn cuda_tensor_view() -> Result<()> {
+    let device = Device::new_cuda(0)?;
+    let tensor = Tensor::arange(0f32, 6., &device)?.reshape((2, 3))?;
+    let client = xla::PjRtClient::gpu(0.5, false)?;
+    // The view starts at the offset of the narrowed tensor in the storage.
+    let buffer = xla::candle::buffer_from_tensor(&client, &tensor.narrow(0, 1, 1)?, None)?;
+    drop(tensor);
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, [3., 4., 5.]);
+    Ok(())
+}
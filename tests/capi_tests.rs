@@ -0,0 +1,134 @@
+#![cfg(feature = "capi")]
+use std::ffi::CStr;
+use xla::capi::*;
+
+// The error message of a status, the status is released.
+unsafe fn take_error(status: *mut Status) -> Option<String> {
+    if status.is_null() {
+        return None;
+    }
+    let msg = CStr::from_ptr(xla_rs_status_message(status)).to_string_lossy().into_owned();
+    xla_rs_status_free(status);
+    Some(msg)
+}
+
+fn exported_names(src: &str, prefix: &str) -> std::collections::BTreeSet<String> {
+    src.split(prefix)
+        .skip(1)
+        .filter_map(|s| s.split_once('(').map(|(name, _)| name))
+        .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .map(|name| format!("xla_rs_{name}"))
+        .collect()
+}
+
+#[test]
+fn header_matches_exports() {
+    let header = std::fs::read_to_string("capi/xla_rs.h").unwrap();
+    let src = std::fs::read_to_string("src/capi.rs").unwrap();
+    let declared = exported_names(&header, " *xla_rs_");
+    let declared: std::collections::BTreeSet<_> =
+        declared.union(&exported_names(&header, " xla_rs_")).cloned().collect();
+    let exported = exported_names(&src, "pub unsafe extern \"C\" fn xla_rs_");
+    assert!(!exported.is_empty());
+    assert_eq!(declared, exported);
+}
+
+#[test]
+fn compile_and_execute() {
+    let hlo = std::fs::read("examples/fn_hlo.txt").unwrap();
+    unsafe {
+        let mut client = std::ptr::null_mut();
+        assert_eq!(take_error(xla_rs_client_cpu(&mut client)), None);
+        assert!(xla_rs_client_device_count(client) > 0);
+        let mut exe = std::ptr::null_mut();
+        let status =
+            xla_rs_client_compile_hlo(client, hlo.as_ptr(), hlo.len(), XLA_RS_HLO_TEXT, &mut exe);
+        assert_eq!(take_error(status), None);
+        // The executable keeps the client alive.
+        xla_rs_client_free(client);
+
+        let dims = [2i64, 2];
+        let x = [1f32, 2., 3., 4.];
+        let y = [1f32, 1., 1., 1.];
+        let (mut x_lit, mut y_lit) = (std::ptr::null_mut(), std::ptr::null_mut());
+        let f32_ty = xla::PrimitiveType::F32 as i32;
+        let status =
+            xla_rs_literal_create(f32_ty, dims.as_ptr(), 2, x.as_ptr().cast(), 16, &mut x_lit);
+        assert_eq!(take_error(status), None);
+        let status =
+            xla_rs_literal_create(f32_ty, dims.as_ptr(), 2, y.as_ptr().cast(), 16, &mut y_lit);
+        assert_eq!(take_error(status), None);
+
+        let args = [x_lit as *const _, y_lit as *const _];
+        let (mut outputs, mut num_outputs) = (std::ptr::null_mut(), 0);
+        let status =
+            xla_rs_executable_execute(exe, args.as_ptr(), 2, &mut outputs, &mut num_outputs);
+        assert_eq!(take_error(status), None);
+        assert_eq!(num_outputs, 1);
+        let output = *outputs;
+        let (mut ty, mut rank) = (0, 0);
+        assert_eq!(take_error(xla_rs_literal_shape(output, &mut ty, &mut rank)), None);
+        assert_eq!((ty, rank), (f32_ty, 2));
+        let mut out_dims = [0i64; 2];
+        assert_eq!(take_error(xla_rs_literal_dims(output, out_dims.as_mut_ptr(), 2)), None);
+        assert_eq!(out_dims, [2, 2]);
+        assert_eq!(xla_rs_literal_size_bytes(output), 16);
+        let mut values = [0f32; 4];
+        let status = xla_rs_literal_copy_to(output, values.as_mut_ptr().cast(), 16);
+        assert_eq!(take_error(status), None);
+        assert_eq!(values, [1.5, 1.5, 3.5, 3.5]);
+
+        xla_rs_literals_free(outputs, num_outputs);
+        xla_rs_literal_free(x_lit);
+        xla_rs_literal_free(y_lit);
+        xla_rs_executable_free(exe);
+    }
+}
+
+#[test]
+fn errors() {
+    let invalid = b"not an hlo module";
+    unsafe {
+        let mut client = std::ptr::null_mut();
+        assert_eq!(take_error(xla_rs_client_cpu(&mut client)), None);
+        let mut exe = std::ptr::null_mut();
+        let status = xla_rs_client_compile_hlo(
+            client,
+            invalid.as_ptr(),
+            invalid.len(),
+            XLA_RS_HLO_TEXT,
+            &mut exe,
+        );
+        assert!(take_error(status).is_some());
+        assert!(exe.is_null());
+        let status =
+            xla_rs_client_compile_hlo(client, invalid.as_ptr(), invalid.len(), 42, &mut exe);
+        assert!(take_error(status).unwrap().contains("format"));
+        let status = xla_rs_client_compile_hlo(
+            client,
+            invalid.as_ptr(),
+            invalid.len(),
+            XLA_RS_HLO_TEXT,
+            std::ptr::null_mut(),
+        );
+        assert!(take_error(status).unwrap().contains("out"));
+        xla_rs_client_free(client);
+
+        let mut literal = std::ptr::null_mut();
+        let dims = [3i64];
+        let data = [0u8; 4];
+        let f32_ty = xla::PrimitiveType::F32 as i32;
+        let status =
+            xla_rs_literal_create(f32_ty, dims.as_ptr(), 1, data.as_ptr().cast(), 4, &mut literal);
+        assert!(take_error(status).is_some());
+        let status =
+            xla_rs_literal_create(-1, dims.as_ptr(), 1, data.as_ptr().cast(), 4, &mut literal);
+        assert!(take_error(status).is_some());
+        assert!(literal.is_null());
+
+        // Releasing null handles is a no-op.
+        xla_rs_status_free(std::ptr::null_mut());
+        xla_rs_literal_free(std::ptr::null_mut());
+        xla_rs_literals_free(std::ptr::null_mut(), 0);
+    }
+}
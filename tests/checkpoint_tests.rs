@@ -0,0 +1,82 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+use xla::checkpoint::{self, SaveOptions};
+
+#[test]
+fn save_and_load() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let dir = std::env::temp_dir().join(format!("xla-checkpoint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = checkpoint::shard_path(&dir, 0, 1);
+    let weights: Vec<f32> = (0..24).map(|v| v as f32 * 0.5).collect();
+    let weights = client.buffer_from_host_buffer(&weights, &[2, 3, 4], None)?;
+    let step = client.buffer_from_host_buffer(&[42i64], &[], None)?;
+    // Use small chunks so that the weights get split across multiple entries.
+    let options = SaveOptions { chunk_size_in_bytes: 10, compress: true, ..Default::default() };
+    checkpoint::save(&path, &[("weights", &weights), ("step", &step)], &options)?;
+
+    let info = checkpoint::read_info(&path)?;
+    assert_eq!(info["weights"].ty, xla::ElementType::F32);
+    assert_eq!(info["weights"].dims, [2, 3, 4]);
+    assert_eq!(info["step"].ty, xla::ElementType::S64);
+    assert!(info["step"].dims.is_empty());
+
+    let buffers = checkpoint::load(&path, &client, None)?;
+    assert_eq!(buffers.len(), 2);
+    let loaded = buffers["weights"].to_literal_sync()?;
+    assert_eq!(loaded.array_shape()?.dims(), [2, 3, 4]);
+    assert_eq!(loaded.to_vec::<f32>()?, weights.to_literal_sync()?.to_vec::<f32>()?);
+    assert_eq!(buffers["step"].to_literal_sync()?.to_vec::<i64>()?, [42]);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn zstd_compression() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let dir = std::env::temp_dir().join(format!("xla-checkpoint-zstd-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = checkpoint::shard_path(&dir, 0, 1);
+    let weights = client.buffer_from_host_buffer(&[0f32; 1024], &[32, 32], None)?;
+    let options = SaveOptions { zstd_level: Some(3), ..Default::default() };
+    checkpoint::save(&path, &[("weights", &weights)], &options)?;
+    assert!(std::fs::metadata(&path)?.len() < 4096);
+    let buffers = checkpoint::load(&path, &client, None)?;
+    assert_eq!(buffers["weights"].to_literal_sync()?.to_vec::<f32>()?, [0f32; 1024]);
+
+    // A checkpoint compressed as a whole, e.g. for a network transfer, loads the same way.
+    let compressed_path = dir.join("compressed.ckpt.zst");
+    let compressed = zstd::encode_all(std::fs::File::open(&path)?, 0)?;
+    std::fs::write(&compressed_path, compressed)?;
+    assert_eq!(checkpoint::read_info(&compressed_path)?["weights"].dims, [32, 32]);
+    let buffers = checkpoint::load(&compressed_path, &client, None)?;
+    assert_eq!(buffers["weights"].to_literal_sync()?.to_vec::<f32>()?, [0f32; 1024]);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn checkpoint_format() -> Result<()> {
+    use std::io::{Read, Write};
+    let client = xla::PjRtClient::cpu()?;
+    let dir = std::env::temp_dir().join(format!("xla-checkpoint-format-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = checkpoint::shard_path(&dir, 0, 1);
+    let step = client.buffer_from_host_buffer(&[42i64], &[], None)?;
+    checkpoint::save(&path, &[("step", &step)], &SaveOptions::default())?;
+    // The data is stored little-endian whatever the host byte order.
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+    let mut data = vec![];
+    zip.by_name("step/0")?.read_to_end(&mut data)?;
+    assert_eq!(data, 42i64.to_le_bytes());
+
+    // Metadata which dimensions overflow is rejected before reading any data.
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path)?);
+    zip.start_file("huge/metadata", zip::write::FileOptions::default())?;
+    zip.write_all(b"ty: F32\ndims: 4294967296,4294967296\nchunks: 1\n")?;
+    zip.finish()?;
+    let err = checkpoint::read_info(&path).unwrap_err();
+    assert!(matches!(err, xla::Error::Checkpoint(_)));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
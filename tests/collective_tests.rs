@@ -0,0 +1,70 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+use xla::{ChannelHandle, CollectiveDeviceList, ReplicaGroup};
+
+#[test]
+fn collective_config_validation() -> Result<()> {
+    assert!(ReplicaGroup::new(vec![]).is_err());
+    assert!(ReplicaGroup::new(vec![0, -1]).is_err());
+    assert!(ReplicaGroup::new(vec![0, 1, 0]).is_err());
+    assert!(ChannelHandle::new(0).is_err());
+    assert_eq!(ChannelHandle::new(3)?.handle(), 3);
+
+    let groups = CollectiveDeviceList::iota(2, 2)?;
+    assert_eq!(groups.group_size(), Some(2));
+    assert_eq!(groups.groups()[1].ids(), [2, 3]);
+    let g = |ids: Vec<i64>| ReplicaGroup::new(ids);
+    assert!(CollectiveDeviceList::new(vec![g(vec![0, 1])?, g(vec![2])?]).is_err());
+    assert!(CollectiveDeviceList::new(vec![g(vec![0, 1])?, g(vec![1, 2])?]).is_err());
+    assert_eq!(CollectiveDeviceList::all().group_size(), None);
+    Ok(())
+}
+
+#[test]
+fn single_replica_collectives() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let sum = {
+        let builder = xla::XlaBuilder::new("sum");
+        let x = builder.parameter(0, xla::ElementType::F32, &[], "x")?;
+        let y = builder.parameter(1, xla::ElementType::F32, &[], "y")?;
+        (x + y)?.build()?
+    };
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.constant_r1(&[1f32, 2., 3., 4.])?;
+    let all = CollectiveDeviceList::all();
+    let reduced = x.all_reduce(&sum, &all, None)?;
+    let gathered = x.all_gather(0, 1, &all, None)?;
+    let exe = client.compile(&builder.tuple(&[reduced, gathered])?.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?[0][0].to_literal_sync()?;
+    let (reduced, gathered) = result.to_tuple2()?;
+    assert_eq!(reduced.to_vec::<f32>()?, [1., 2., 3., 4.]);
+    assert_eq!(gathered.to_vec::<f32>()?, [1., 2., 3., 4.]);
+
+    let groups = CollectiveDeviceList::iota(1, 2)?;
+    assert!(x.all_gather(0, 1, &groups, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn auto_shard() -> Result<()> {
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[4, 8], "x")?;
+    let y = builder.parameter(1, xla::ElementType::F32, &[8, 2], "y")?;
+    let proto = x.matmul(&y)?.build()?.proto();
+    let options = xla::AutoShardingOptions { mesh_shape: vec![2], ..Default::default() };
+    let sharded = proto.auto_shard(&options)?;
+    let computations = sharded.computations()?;
+    let entry = computations.last().unwrap();
+    // Every instruction is annotated with a sharding over the two devices of the mesh.
+    for instruction in entry.instructions()? {
+        let sharding = instruction.sharding()?.unwrap();
+        assert!(sharding.tile_assignment_devices.iter().all(|d| *d == 0 || *d == 1));
+    }
+
+    let options = xla::AutoShardingOptions { mesh_shape: vec![2, 0], ..Default::default() };
+    assert!(proto.auto_shard(&options).is_err());
+    let options =
+        xla::AutoShardingOptions { mesh_shape: vec![2], mesh_ids: vec![0], ..Default::default() };
+    assert!(proto.auto_shard(&options).is_err());
+    Ok(())
+}
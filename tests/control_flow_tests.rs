@@ -1,3 +1,4 @@
+#![cfg(feature = "ffi")]
 use xla::{ArrayElement, Result};
 
 #[test]
@@ -54,8 +55,8 @@ fn while_op2() -> Result<()> {
     let computation = w.build()?;
     let result = client.compile(&computation)?;
     let result = result.execute::<xla::Literal>(&[])?;
-    let mut result = result[0][0].to_literal_sync()?;
-    let result = result.decompose_tuple()?;
+    let result = result[0][0].to_literal_sync()?;
+    let result = result.into_tuple()?;
     assert_eq!(result[0].element_count(), 1);
     assert_eq!(result[0].shape()?, xla::Shape::array::<i32>(vec![]));
     assert_eq!(result[0].to_vec::<i32>()?, [11]);
@@ -64,3 +65,192 @@ fn while_op2() -> Result<()> {
     assert_eq!(result[1].to_vec::<f32>()?, [1.2, 13.3]);
     Ok(())
 }
+
+#[test]
+fn beam_search() -> Result<()> {
+    use xla::decode::{beam_search, BeamSearchConfig};
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let prompt = builder.constant_r1(&[1i32, 0, 0, 0])?;
+    let prompt_len = builder.c0(1i32)?;
+    let config = BeamSearchConfig { beam_size: 2, max_len: 4, eos_token: Some(0) };
+    let (tokens, scores) = beam_search(&prompt, &prompt_len, &config, |tokens, _pos| {
+        let builder = tokens.builder();
+        let logits = builder.constant_r1(&[0f32, 1f32, 2f32])?;
+        logits.broadcast(&[2])
+    })?;
+    let computation = builder.tuple(&[tokens, scores])?.build()?;
+    let result = client.compile(&computation)?;
+    let result = result.execute::<xla::Literal>(&[])?;
+    let (tokens, scores) = result[0][0].to_literal_sync()?.to_tuple2()?;
+    assert_eq!(tokens.array_shape()?.dims(), [2, 4]);
+    assert_eq!(&tokens.to_vec::<i32>()?[..4], [1, 2, 2, 2]);
+    let scores = scores.to_vec::<f32>()?;
+    assert!(scores[0] >= scores[1]);
+    Ok(())
+}
+
+#[test]
+fn while_loop() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let init = [builder.c0(1i32)?, builder.constant_r1(&[1f32, 2f32])?];
+    let state = builder.while_loop(
+        |builder, state| state[0].lt(&builder.c0(100i32)?),
+        |builder, state| Ok(vec![(&state[0] * builder.c0(2i32)?)?, (&state[1] + &state[1])?]),
+        &init,
+    )?;
+    let computation = builder.tuple(state.as_slice())?.build()?;
+    let result = client.compile(&computation)?.execute::<xla::Literal>(&[])?;
+    let (x0, x1) = result[0][0].to_literal_sync()?.to_tuple2()?;
+    assert_eq!(x0.to_vec::<i32>()?, [128]);
+    assert_eq!(x1.to_vec::<f32>()?, [128., 256.]);
+
+    // The body has to preserve the shapes of the state.
+    let builder = xla::XlaBuilder::new("test");
+    let init = [builder.c0(1i32)?, builder.constant_r1(&[1f32, 2f32])?];
+    let err = builder
+        .while_loop(
+            |builder, state| state[0].lt(&builder.c0(100i32)?),
+            |builder, state| Ok(vec![state[0].convert(xla::PrimitiveType::F32)?, state[1].clone()]),
+            &init,
+        )
+        .unwrap_err();
+    assert!(matches!(err, xla::Error::ShapeMismatch { .. }), "{err:?}");
+    Ok(())
+}
+
+#[test]
+fn fori_loop() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let n = builder.parameter(0, i64::TY, &[], "n")?;
+    let state =
+        builder.fori_loop(&builder.c0(1i64)?, &n, &[builder.c0(1i64)?], |_, i, state| {
+            Ok(vec![(&state[0] * i)?])
+        })?;
+    let computation = state[0].build()?;
+    let exe = client.compile(&computation)?;
+    let result = exe.execute::<xla::Literal>(&[xla::Literal::scalar(6i64)])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<i64>()?, [120]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let n = builder.parameter(0, i64::TY, &[], "n")?;
+    let err = builder.fori_loop(&builder.c0(0f32)?, &builder.c0(1f32)?, &[], |_, _, _| Ok(vec![]));
+    assert!(matches!(err, Err(xla::Error::UnsupportedElementType { .. })));
+    let err = builder.fori_loop(&builder.c0(0i64)?, &n, &[n.clone()], |_, _, _| Ok(vec![]));
+    assert!(matches!(err, Err(xla::Error::UnexpectedNumberOfElemsInTuple { expected: 1, got: 0 })));
+    Ok(())
+}
+
+#[test]
+fn scan() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let xs = builder.c2(&[&[1f32, 2.], &[3., 4.], &[5., 6.]])?;
+    let init = builder.zero(f32::TY)?.broadcast(&[2])?;
+    let (carry, ys) = builder.scan(&[init], &[xs], |_, carry, x| {
+        let carry = (&carry[0] + &x[0])?;
+        let total = x[0].reduce_sum(&[0], false)?;
+        Ok((vec![carry.clone()], vec![carry, total]))
+    })?;
+    assert_eq!(ys[0].dims()?, [3, 2]);
+    assert_eq!(ys[1].dims()?, [3]);
+    let computation = builder.tuple(&[&carry[0], &ys[0], &ys[1]])?.build()?;
+    let result = client.compile(&computation)?.execute::<xla::Literal>(&[])?;
+    let (carry, cumsum, totals) = result[0][0].to_literal_sync()?.to_tuple3()?;
+    assert_eq!(carry.to_vec::<f32>()?, [9., 12.]);
+    assert_eq!(cumsum.to_vec::<f32>()?, [1., 2., 4., 6., 9., 12.]);
+    assert_eq!(totals.to_vec::<f32>()?, [3., 7., 11.]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let err = builder.scan(&[], &[], |_, _, _| Ok((vec![], vec![])));
+    assert!(matches!(err, Err(xla::Error::EmptyOpList { .. })));
+    let xs = [builder.c1(&[1f32, 2.])?, builder.c1(&[1f32, 2., 3.])?];
+    let err = builder.scan(&[], &xs, |_, _, _| Ok((vec![], vec![])));
+    assert!(matches!(err, Err(xla::Error::InvalidDims { .. })));
+    Ok(())
+}
+
+#[test]
+fn cond_and_switch() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let index = builder.parameter(0, i32::TY, &[], "index")?;
+    let pred = index.eq(&builder.c0(0i32)?)?;
+    let x = builder.c1(&[1f32, 2.])?;
+    let on_true = |_: &xla::XlaBuilder, ops: &[xla::XlaOp]| Ok(vec![(&ops[0] + &ops[0])?]);
+    let on_false = |_: &xla::XlaBuilder, ops: &[xla::XlaOp]| Ok(vec![ops[0].neg()?]);
+    let cond = builder.cond(&pred, on_true, on_false, &[x.clone()])?;
+    let zeros = |b: &xla::XlaBuilder, _: &[xla::XlaOp]| Ok(vec![b.c1(&[0f32, 0.])?]);
+    let switch = builder.switch(&index, &[&on_true, &on_false, &zeros], &[x.clone()])?;
+    let computation = builder.tuple(&[&cond[0], &switch[0]])?.build()?;
+    let exe = client.compile(&computation)?;
+    for (index, expected_cond, expected_switch) in [
+        (0i32, [2f32, 4.], [2f32, 4.]),
+        (1, [-1., -2.], [-1., -2.]),
+        (2, [-1., -2.], [0., 0.]),
+        // Out of bounds indexes select the last branch.
+        (7, [-1., -2.], [0., 0.]),
+    ] {
+        let result = exe.execute::<xla::Literal>(&[xla::Literal::scalar(index)])?;
+        let (c, s) = result[0][0].to_literal_sync()?.to_tuple2()?;
+        assert_eq!(c.to_vec::<f32>()?, expected_cond);
+        assert_eq!(s.to_vec::<f32>()?, expected_switch);
+    }
+
+    // The branches have to return values with the same shapes.
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.c1(&[1f32, 2.])?;
+    let pred = builder.c0(0i32)?.eq(&builder.c0(0i32)?)?;
+    let scalar = |b: &xla::XlaBuilder, _: &[xla::XlaOp]| Ok(vec![b.c0(0f32)?]);
+    let err = builder.cond(&pred, on_true, scalar, &[x.clone()]);
+    assert!(matches!(err, Err(xla::Error::ShapeMismatch { .. })));
+    let err = builder.cond(&x, on_true, on_false, &[x.clone()]);
+    assert!(matches!(err, Err(xla::Error::ShapeMismatch { .. })));
+    let err = builder.switch(&builder.c0(0i32)?, &[], &[x]);
+    assert!(matches!(err, Err(xla::Error::EmptyOpList { .. })));
+    Ok(())
+}
+
+#[derive(xla::XlaState)]
+struct Counter {
+    step: xla::XlaOp,
+    total: xla::XlaOp,
+}
+
+#[derive(xla::XlaState)]
+struct LoopState(xla::XlaOp, Counter);
+
+#[test]
+fn derived_state() -> Result<()> {
+    use xla::XlaState;
+    assert_eq!(LoopState::state_len(), 3);
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let counter = Counter { step: builder.c0(0i32)?, total: builder.c0(0i32)? };
+    let init = LoopState(builder.c1(&[1f32, 2.])?, counter);
+    let state = builder.while_state(
+        |b, s: &LoopState| s.1.step.lt(&b.c0(4i32)?),
+        |b, LoopState(x, counter)| {
+            let step = (&counter.step + b.c0(1i32)?)?;
+            let total = (&counter.total + &step)?;
+            Ok(LoopState((x + x)?, Counter { step, total }))
+        },
+        &init,
+    )?;
+    let computation = state.to_tuple(&builder)?.build()?;
+    let result = client.compile(&computation)?.execute::<xla::Literal>(&[])?;
+    let (x, step, total) = result[0][0].to_literal_sync()?.to_tuple3()?;
+    assert_eq!(x.to_vec::<f32>()?, [16., 32.]);
+    assert_eq!(step.to_vec::<i32>()?, [4]);
+    assert_eq!(total.to_vec::<i32>()?, [10]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let tuple = builder.tuple(&[builder.c0(1i32)?, builder.c0(2i32)?])?;
+    let counter = Counter::from_tuple(&tuple)?;
+    assert_eq!(counter.state_ops().len(), 2);
+    let err = LoopState::from_state_ops(&counter.state_ops());
+    assert!(matches!(err, Err(xla::Error::UnexpectedNumberOfElemsInTuple { expected: 3, got: 2 })));
+    Ok(())
+}
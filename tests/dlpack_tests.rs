@@ -0,0 +1,17 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+
+#[test]
+fn dlpack_roundtrip() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let buffer = client.buffer_from_host_buffer(&[1f32, 2., 3., 4., 5., 6.], &[2, 3], None)?;
+    let tensor = buffer.into_dlpack()?;
+    let dl_tensor = unsafe { &(*tensor).dl_tensor };
+    assert_eq!(dl_tensor.device.device_type, xla::dlpack::DL_CPU);
+    assert_eq!(dl_tensor.dtype.element_type()?, xla::ElementType::F32);
+    assert_eq!(unsafe { std::slice::from_raw_parts(dl_tensor.shape, 2) }, [2, 3]);
+
+    let buffer = unsafe { client.buffer_from_dlpack(tensor, None)? };
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, [1., 2., 3., 4., 5., 6.]);
+    Ok(())
+}
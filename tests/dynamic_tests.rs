@@ -0,0 +1,50 @@
+#![cfg(feature = "ffi")]
+use xla::dynamic::DynamicExecutable;
+use xla::{ElementType, Result};
+
+fn input(batch: i64) -> Result<xla::Literal> {
+    let values: Vec<f32> = (0..batch * 2).map(|v| v as f32).collect();
+    xla::Literal::vec1(&values).reshape(&[batch, 2])
+}
+
+#[test]
+fn dynamic_executable() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let parameters = vec![
+        (ElementType::F32, vec!["batch".into(), 2.into()]),
+        (ElementType::F32, vec!["batch".into()]),
+    ];
+    let mut exe = DynamicExecutable::new(&client, parameters, 2, |_, xs| {
+        xs[0].reduce_sum(&[1], false)?.add_(&xs[1])
+    });
+    assert!(exe.is_empty());
+
+    for (batch, compilations) in [(3, 1), (1, 2), (3, 2), (5, 3), (1, 4)] {
+        let offsets = xla::Literal::vec1(&vec![10f32; batch as usize]);
+        let result = exe.execute::<xla::Literal>(&[input(batch)?, offsets])?;
+        let result = result[0][0].to_literal_sync()?.to_vec::<f32>()?;
+        let expected: Vec<f32> = (0..batch).map(|i| (4 * i + 1) as f32 + 10.).collect();
+        assert_eq!(result, expected);
+        assert_eq!(exe.compilations(), compilations);
+        assert!(exe.len() <= exe.capacity());
+    }
+
+    // The inputs have to agree on the size of each symbol.
+    let offsets = xla::Literal::vec1(&[0f32; 2]);
+    let err = exe.execute::<xla::Literal>(&[input(3)?, offsets]);
+    assert!(matches!(err, Err(xla::Error::InvalidSymbol { .. })));
+    let offsets = xla::Literal::vec1(&[0f32; 3]);
+    let err = exe.execute::<xla::Literal>(&[input(3)?.reshape(&[2, 3])?, offsets]);
+    assert!(matches!(err, Err(xla::Error::InvalidDims { .. })));
+    let offsets = xla::Literal::vec1(&[0f64; 3]);
+    let err = exe.execute::<xla::Literal>(&[input(3)?, offsets]);
+    assert!(matches!(
+        err,
+        Err(xla::Error::ParameterTypeMismatch {
+            index: 1,
+            expected: ElementType::F32,
+            got: ElementType::F64
+        })
+    ));
+    Ok(())
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "ffi")]
+use std::backtrace::BacktraceStatus;
+use xla::{BacktraceCapture, Error};
+
+fn xla_error() -> Error {
+    match xla::HloModuleProto::parse_and_return_unverified_module(b"not an hlo module") {
+        Ok(_) => panic!("parsing an invalid module succeeded"),
+        Err(err) => err,
+    }
+}
+
+// This runs in its own test binary as the capture setting is process wide.
+#[test]
+fn backtrace_capture() {
+    xla::set_backtrace_capture(BacktraceCapture::Never);
+    assert_eq!(xla::backtrace_capture(), BacktraceCapture::Never);
+    match xla_error() {
+        Error::XlaError { backtrace, .. } => {
+            assert_eq!(backtrace.status(), BacktraceStatus::Disabled)
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+    xla::set_backtrace_capture(BacktraceCapture::Always);
+    match xla_error() {
+        Error::XlaError { backtrace, .. } => {
+            assert_eq!(backtrace.status(), BacktraceStatus::Captured)
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+    xla::set_backtrace_capture(BacktraceCapture::Env);
+}
@@ -0,0 +1,104 @@
+#![cfg(any(feature = "ffi", feature = "stub"))]
+use std::sync::mpsc;
+use std::time::Duration;
+use xla::executor::{Executor, ExecutorOptions};
+use xla::{Error, Result};
+
+#[test]
+fn executor_launch() -> Result<()> {
+    let executor = Executor::new(2, ExecutorOptions::default(), |worker| {
+        let client = xla::PjRtClient::cpu()?;
+        let builder = xla::XlaBuilder::new("test");
+        let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+        let scale = worker as f32 + 1.;
+        let exe = client.compile(&(x * builder.constant_r1(&[scale, scale])?)?.build()?)?;
+        Ok((client, exe))
+    })?;
+    assert_eq!(executor.num_workers(), 2);
+    let launches = (0..2)
+        .map(|worker| {
+            executor.launch(worker, |(_client, exe)| {
+                let outputs = exe.execute(&[xla::Literal::vec1(&[1f32, 2.])])?;
+                outputs[0][0].to_literal_sync()?.to_vec::<f32>()
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let results = launches.into_iter().map(|l| l.wait()).collect::<Result<Vec<_>>>()?;
+    assert_eq!(results, [vec![1., 2.], vec![2., 4.]]);
+    Ok(())
+}
+
+#[test]
+fn executor_backpressure() -> Result<()> {
+    let executor =
+        Executor::new(1, ExecutorOptions { queue_capacity: 1, ..Default::default() }, |_| Ok(()))?;
+    let (started_sender, started) = mpsc::channel();
+    let (release, release_receiver) = mpsc::channel::<()>();
+    let running = executor.launch(0, move |_| {
+        started_sender.send(()).unwrap();
+        release_receiver.recv().unwrap();
+        Ok(1)
+    })?;
+    started.recv().unwrap();
+    let queued = executor.try_launch(0, |_| Ok(2))?;
+    assert!(matches!(executor.try_launch(0, |_| Ok(3)), Err(Error::ExecutorQueueFull { .. })));
+    assert_eq!(executor.pending(0)?, 2);
+    release.send(()).unwrap();
+    assert_eq!(running.wait()?, 1);
+    assert_eq!(queued.wait()?, 2);
+    assert_eq!(executor.launch_any(|_| Ok(4))?.wait()?, 4);
+    Ok(())
+}
+
+#[test]
+fn executor_init_error() {
+    let executor = Executor::<()>::new(2, ExecutorOptions::default(), |worker| {
+        if worker == 1 {
+            Err(Error::ExecutorStopped { worker })
+        } else {
+            Ok(())
+        }
+    });
+    assert!(executor.is_err());
+}
+
+#[test]
+fn executor_deadline() -> Result<()> {
+    let options =
+        ExecutorOptions { deadline: Some(Duration::from_millis(50)), ..Default::default() };
+    let executor = Executor::new(1, options, |_| Ok(()))?;
+    let slow = executor.launch_named(0, "slow", |_| {
+        std::thread::sleep(Duration::from_millis(300));
+        Ok(1)
+    })?;
+    let fast = executor.launch(0, |_| Ok(2))?;
+    match slow.wait() {
+        Err(Error::ExecutionTimeout { name, worker, elapsed }) => {
+            assert_eq!(name, "slow");
+            assert_eq!(worker, 0);
+            assert!(elapsed >= Duration::from_millis(50));
+        }
+        result => panic!("unexpected result {result:?}"),
+    }
+    // The fast job only starts once the slow one has completed.
+    assert_eq!(fast.wait()?, 2);
+    Ok(())
+}
+
+#[test]
+fn executor_job_panics() -> Result<()> {
+    let executor = Executor::new(1, ExecutorOptions::default(), |_| Ok(()))?;
+    let launch = executor.launch_named(0, "panicking", |_| -> Result<i32> { panic!("boom") })?;
+    assert!(matches!(launch.wait(), Err(Error::ExecutorJobPanicked { .. })));
+    // The worker survives the panic and its pending count gets back to zero, the count is
+    // decremented right after the result has been sent.
+    assert_eq!(executor.launch(0, |_| Ok(1))?.wait()?, 1);
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while executor.pending(0)? != 0 && std::time::Instant::now() < deadline {
+        std::thread::yield_now()
+    }
+    assert_eq!(executor.pending(0)?, 0);
+    assert!(matches!(executor.pending(1), Err(Error::IndexOutOfBounds { .. })));
+    assert!(matches!(executor.launch(1, |_| Ok(1)), Err(Error::IndexOutOfBounds { .. })));
+    Ok(())
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+use xla::framework::{DeviceArray, TensorBackend};
+
+// A generic "framework" function running a computation through some backend.
+fn double<B: TensorBackend>(backend: &B, data: &[f32]) -> xla::Result<Vec<f32>> {
+    let builder = xla::XlaBuilder::new("double");
+    let x = builder.parameter(0, xla::ElementType::F32, &[data.len() as i64], "x")?;
+    let exe = backend.compile(&(&x + &x)?.build()?)?;
+    let x = backend.array_from_host(data, &[data.len()])?;
+    let outputs = backend.execute(&exe, &[&x])?;
+    outputs[0].to_vec::<f32>()
+}
+
+// A backend that keeps its arrays on the host and returns its arguments when executing.
+struct MockBackend;
+
+struct MockArray(xla::Literal);
+
+impl DeviceArray for MockArray {
+    fn array_shape(&self) -> xla::Result<xla::ArrayShape> {
+        self.0.array_shape()
+    }
+
+    fn to_literal(&self) -> xla::Result<xla::Literal> {
+        Ok(self.0.clone())
+    }
+}
+
+impl TensorBackend for MockBackend {
+    type Array = MockArray;
+    type Executable = ();
+
+    fn array_from_host<T: xla::ArrayElement>(
+        &self,
+        data: &[T],
+        dims: &[usize],
+    ) -> xla::Result<MockArray> {
+        let mut literal = xla::Literal::create_from_shape(T::TY.primitive_type(), dims)?;
+        literal.copy_raw_from(data)?;
+        Ok(MockArray(literal))
+    }
+
+    fn array_from_literal(&self, literal: &xla::Literal) -> xla::Result<MockArray> {
+        Ok(MockArray(literal.clone()))
+    }
+
+    fn compile(&self, _computation: &xla::XlaComputation) -> xla::Result<()> {
+        Ok(())
+    }
+
+    fn execute(&self, _exe: &(), args: &[&MockArray]) -> xla::Result<Vec<MockArray>> {
+        Ok(args.iter().map(|a| MockArray(a.0.clone())).collect())
+    }
+}
+
+#[test]
+fn pjrt_backend() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    assert_eq!(double(&client, &[1., 2., 3.])?, [2., 4., 6.]);
+    let array = client.array_from_literal(&xla::Literal::vec1(&[1i32, 2]))?;
+    assert_eq!(array.array_shape()?, xla::ArrayShape::new::<i32>(vec![2]));
+    Ok(())
+}
+
+#[test]
+fn mock_backend() -> Result<()> {
+    assert_eq!(double(&MockBackend, &[1., 2., 3.])?, [1., 2., 3.]);
+    Ok(())
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+use std::alloc::Layout;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator {
+    live_bytes: AtomicUsize,
+}
+
+unsafe impl xla::HostBufferAllocator for CountingAllocator {
+    fn allocate(&self, size: usize, align: usize) -> *mut u8 {
+        self.live_bytes.fetch_add(size, Ordering::SeqCst);
+        unsafe { std::alloc::alloc(Layout::from_size_align(size, align).unwrap()) }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize) {
+        self.live_bytes.fetch_sub(size, Ordering::SeqCst);
+        std::alloc::dealloc(ptr, Layout::from_size_align(size, align).unwrap())
+    }
+}
+
+static ALLOCATOR: CountingAllocator = CountingAllocator { live_bytes: AtomicUsize::new(0) };
+
+// This runs in its own test binary as the registered allocator is process wide.
+#[test]
+fn host_buffer_allocator() -> Result<()> {
+    let options =
+        xla::InitOptions { host_buffer_allocator: Some(&ALLOCATOR), ..Default::default() };
+    xla::init(options)?;
+    let client = xla::PjRtClient::cpu()?;
+    let mut host_buffer = client.allocate_host_buffer(4 * 4)?;
+    assert_eq!(ALLOCATOR.live_bytes.load(Ordering::SeqCst), 16);
+    assert!(host_buffer.iter().all(|v| *v == 0));
+    host_buffer.as_mut_slice_of::<f32>()?.copy_from_slice(&[1., 2., 3., 4.]);
+    let buffer =
+        client.buffer_from_pinned_host_buffer(&host_buffer, xla::ElementType::F32, &[4], None)?;
+    // The literals are also allocated by the registered allocator.
+    let literal = buffer.to_literal_sync()?;
+    assert_eq!(ALLOCATOR.live_bytes.load(Ordering::SeqCst), 32);
+    assert_eq!(literal.to_vec::<f32>()?, [1., 2., 3., 4.]);
+    drop(literal);
+    let literal = xla::Literal::from_bytes(xla::ElementType::F32, &[2], &[0; 8])?;
+    assert_eq!(ALLOCATOR.live_bytes.load(Ordering::SeqCst), 24);
+    let sum = client.compile(&{
+        let builder = xla::XlaBuilder::new("test");
+        let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+        (&x + &x)?.build()?
+    })?;
+    let result = sum.execute::<xla::Literal>(&[literal])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [0., 0.]);
+    assert_eq!(ALLOCATOR.live_bytes.load(Ordering::SeqCst), 16);
+
+    // Clearing the allocator does not affect the buffers that it has already allocated.
+    xla::clear_host_buffer_allocator();
+    let other_buffer = client.allocate_host_buffer(8)?;
+    assert_eq!(ALLOCATOR.live_bytes.load(Ordering::SeqCst), 16);
+    drop(other_buffer);
+    drop(host_buffer);
+    assert_eq!(ALLOCATOR.live_bytes.load(Ordering::SeqCst), 0);
+    drop(client);
+    xla::shutdown()?;
+    Ok(())
+}
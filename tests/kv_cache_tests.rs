@@ -0,0 +1,35 @@
+#![cfg(feature = "ffi")]
+use xla::kv_cache::KvCache;
+use xla::{ElementType, Result};
+
+#[test]
+fn kv_cache_append() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let mut cache = KvCache::new(&client, ElementType::F32, &[3, 2], 0, None)?;
+    assert_eq!(cache.capacity(), 3);
+    assert!(cache.is_empty());
+    let kv = client.buffer_from_host_buffer(&[1f32, 2., 3., 4.], &[2, 2], None)?;
+    cache.append(&kv, &kv)?;
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.valid_mask(), [true, true, false]);
+    let kv = client.buffer_from_host_buffer(&[5f32, 6.], &[1, 2], None)?;
+    cache.append(&kv, &kv)?;
+    let kv = client.buffer_from_host_buffer(&[7f32, 8.], &[1, 2], None)?;
+    cache.append(&kv, &kv)?;
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.position(), 4);
+    let keys = cache.keys().to_literal_sync()?.to_vec::<f32>()?;
+    assert_eq!(keys, [7., 8., 3., 4., 5., 6.]);
+    let kv = client.buffer_from_host_buffer(&[0f32; 6], &[3, 2], None)?;
+    assert!(cache.append(&kv, &kv).is_err());
+    cache.reset();
+    let keys = client.buffer_from_host_buffer(&[0f32; 2], &[1, 2], None)?;
+    let values = client.buffer_from_host_buffer(&[0f32; 3], &[1, 3], None)?;
+    assert!(matches!(cache.append(&keys, &values), Err(xla::Error::ShapeMismatch { .. })));
+    assert!(matches!(cache.append(&values, &values), Err(xla::Error::ShapeMismatch { .. })));
+    assert!(matches!(
+        KvCache::new(&client, ElementType::F32, &[0, 2], 0, None),
+        Err(xla::Error::InvalidDims { .. })
+    ));
+    Ok(())
+}
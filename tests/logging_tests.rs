@@ -0,0 +1,24 @@
+#![cfg(feature = "ffi")]
+use std::sync::{Arc, Mutex};
+use xla::Result;
+
+#[test]
+fn log_handler() -> Result<()> {
+    let records = Arc::new(Mutex::new(vec![]));
+    let records_ = records.clone();
+    xla::set_log_handler(move |record| {
+        let level = record.log_level();
+        records_.lock().unwrap().push((level, record.file.to_string(), record.message.to_string()))
+    });
+    // The cpu client logs a message from the C++ library when it is created.
+    let _client = xla::PjRtClient::cpu()?;
+    xla::clear_log_handler();
+    let records = records.lock().unwrap();
+    let (level, file, _) = records
+        .iter()
+        .find(|(_, _, message)| message.contains("TfrtCpuClient created"))
+        .expect("no log message received from the C++ library");
+    assert_eq!(*level, log::Level::Info);
+    assert!(file.contains("tfrt_cpu_pjrt_client"), "{file}");
+    Ok(())
+}
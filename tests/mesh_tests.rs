@@ -0,0 +1,138 @@
+#![cfg(feature = "ffi")]
+use xla::mesh::{assemble_from_shards, Mesh, OpSharding, OpShardingType, PartitionSpec};
+use xla::Result;
+
+fn mesh() -> Result<Mesh> {
+    Mesh::from_device_ids([("data", 2), ("model", 4)], (0..8).collect())
+}
+
+#[test]
+fn mesh_validation() -> Result<()> {
+    let mesh = mesh()?;
+    assert_eq!(mesh.shape(), [2, 4]);
+    assert_eq!(mesh.axis_size("model")?, 4);
+    assert!(mesh.axis_size("pipeline").is_err());
+    assert!(Mesh::from_device_ids([("data", 2)], vec![0, 1, 2]).is_err());
+    assert!(Mesh::from_device_ids([("data", 2), ("data", 1)], vec![0, 1]).is_err());
+    assert!(Mesh::from_device_ids([("data", 2)], vec![1, 1]).is_err());
+    Ok(())
+}
+
+#[test]
+fn op_sharding() -> Result<()> {
+    let mesh = mesh()?;
+    let sharding = mesh.op_sharding(&PartitionSpec::new([Some("data"), Some("model")]))?;
+    assert_eq!(sharding.ty, OpShardingType::Other);
+    assert_eq!(sharding.tile_assignment_dimensions, [2, 4]);
+    assert_eq!(sharding.tile_assignment_devices, (0..8).collect::<Vec<_>>());
+    assert_eq!(sharding.to_string(), "{devices=[2,4]0,1,2,3,4,5,6,7}");
+
+    let sharding = mesh.op_sharding(&PartitionSpec::new([Some("model"), None]))?;
+    assert_eq!(sharding.tile_assignment_dimensions, [4, 1, 2]);
+    assert_eq!(sharding.tile_assignment_devices, [0, 4, 1, 5, 2, 6, 3, 7]);
+    assert_eq!(sharding.tile_dims(), [4, 1]);
+    assert!(sharding.to_string().ends_with(" last_tile_dim_replicate}"));
+
+    let sharding = mesh.op_sharding(&PartitionSpec::with_axes([vec!["data", "model"]]))?;
+    assert_eq!(sharding.tile_assignment_dimensions, [8]);
+
+    let sharding = mesh.op_sharding(&PartitionSpec::replicated(2))?;
+    assert_eq!(sharding.ty, OpShardingType::Replicated);
+
+    assert!(mesh.op_sharding(&PartitionSpec::new([Some("data"), Some("data")])).is_err());
+    assert!(mesh.op_sharding(&PartitionSpec::new([Some("pipeline")])).is_err());
+    Ok(())
+}
+
+#[test]
+fn shard_literal() -> Result<()> {
+    let mesh = Mesh::from_device_ids([("data", 2), ("model", 2)], vec![3, 2, 1, 0])?;
+    let literal = xla::Literal::vec1(&(0..16).collect::<Vec<i32>>()).reshape(&[4, 4])?;
+    let shards = literal.shard(&mesh, &PartitionSpec::new([Some("data"), Some("model")]))?;
+    let ids: Vec<usize> = shards.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, [3, 2, 1, 0]);
+    assert_eq!(shards[0].1.to_vec::<i32>()?, [0, 1, 4, 5]);
+    assert_eq!(shards[1].1.to_vec::<i32>()?, [2, 3, 6, 7]);
+    assert_eq!(shards[3].1.to_vec::<i32>()?, [10, 11, 14, 15]);
+
+    // Replicated over the model axis.
+    let shards = literal.shard(&mesh, &PartitionSpec::new([None, Some("data")]))?;
+    assert_eq!(shards[0].1.array_shape()?.dims(), [4, 2]);
+    assert_eq!(shards[1].1.to_vec::<i32>()?, shards[0].1.to_vec::<i32>()?);
+    assert_eq!(shards[2].1.to_vec::<i32>()?, [2, 3, 6, 7, 10, 11, 14, 15]);
+
+    assert!(literal.shard(&mesh, &PartitionSpec::new([Some("data")])).is_err());
+    let literal = xla::Literal::vec1(&[0f32, 1., 2.]);
+    assert!(literal.shard(&mesh, &PartitionSpec::new([Some("data")])).is_err());
+    Ok(())
+}
+
+#[test]
+fn upload_sharded() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let mesh = Mesh::new([("data", 1)], &client.addressable_devices())?;
+    let literal = xla::Literal::vec1(&[1f32, 2., 3., 4.]);
+    let buffers = client.upload_sharded(&literal, &mesh, &PartitionSpec::new([Some("data")]))?;
+    assert_eq!(buffers.len(), 1);
+    assert_eq!(buffers[0].to_literal_sync()?.to_vec::<f32>()?, [1., 2., 3., 4.]);
+
+    let mesh = Mesh::from_device_ids([("data", 1)], vec![1000])?;
+    assert!(client.upload_sharded(&literal, &mesh, &PartitionSpec::replicated(1)).is_err());
+    Ok(())
+}
+
+#[test]
+fn unshard_literal() -> Result<()> {
+    let mesh = Mesh::from_device_ids([("data", 2), ("model", 2)], vec![3, 2, 1, 0])?;
+    let literal = xla::Literal::vec1(&(0..16).collect::<Vec<i32>>()).reshape(&[4, 4])?;
+    for spec in [
+        PartitionSpec::new([Some("data"), Some("model")]),
+        PartitionSpec::new([Some("model"), None]),
+        PartitionSpec::replicated(2),
+    ] {
+        let shards = literal.shard(&mesh, &spec)?;
+        let assembled = xla::Literal::unshard(&shards, &mesh, &spec)?;
+        assert_eq!(assembled.array_shape()?.dims(), [4, 4]);
+        assert_eq!(assembled.to_vec::<i32>()?, (0..16).collect::<Vec<_>>());
+    }
+
+    let spec = PartitionSpec::new([None, Some("data")]);
+    let mut shards = literal.shard(&mesh, &spec)?;
+    shards.swap(0, 1);
+    assert!(xla::Literal::unshard(&shards, &mesh, &spec).is_err());
+    shards.swap(0, 1);
+    shards[1].1 = xla::Literal::vec1(&[0i32; 8]).reshape(&[4, 2])?;
+    assert!(xla::Literal::unshard(&shards, &mesh, &spec).is_err());
+    Ok(())
+}
+
+#[test]
+fn attach_sharding() -> Result<()> {
+    let mesh = mesh()?;
+    let sharding = mesh.op_sharding(&PartitionSpec::new([Some("model"), None]))?;
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[8, 4], "x")?.with_sharding(&sharding)?;
+    let y = x.exp()?.with_sharding(&OpSharding::replicated())?;
+    let computation = (y + x)?.build()?;
+    let computations = computation.proto().computations()?;
+    let entry = computations.last().unwrap();
+    let root = entry.instruction_by_id(entry.root_id()).unwrap();
+    assert_eq!(root.sharding()?, None);
+    let exp = entry.instruction_by_id(root.operand_ids()[0]).unwrap();
+    assert_eq!(exp.sharding()?, Some(OpSharding::replicated()));
+    let param = entry.instruction_by_id(root.operand_ids()[1]).unwrap();
+    assert_eq!(param.sharding()?, Some(sharding));
+    Ok(())
+}
+
+#[test]
+fn assemble_buffers() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let mesh = Mesh::new([("data", 1)], &client.addressable_devices())?;
+    let spec = PartitionSpec::new([Some("data"), None]);
+    let literal = xla::Literal::vec1(&[1f32, 2., 3., 4.]).reshape(&[2, 2])?;
+    let buffers = client.upload_sharded(&literal, &mesh, &spec)?;
+    let assembled = assemble_from_shards(&buffers, &mesh, &spec)?;
+    assert_eq!(assembled.to_vec::<f32>()?, [1., 2., 3., 4.]);
+    Ok(())
+}
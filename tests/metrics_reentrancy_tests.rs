@@ -0,0 +1,25 @@
+#![cfg(feature = "ffi")]
+// The metrics hooks are process-global, this runs in its own test binary so that it does not
+// interfere with the other metrics tests.
+use std::sync::Arc;
+use xla::metrics::Metrics;
+use xla::Result;
+
+struct Reregister;
+
+impl Metrics for Reregister {
+    fn on_compile_finished(&self, _duration: std::time::Duration, _module_name: &str) {
+        // Hooks are not called under the registration lock so this does not deadlock.
+        xla::metrics::clear_metrics();
+    }
+}
+
+#[test]
+fn metrics_hooks_can_reregister() -> Result<()> {
+    xla::metrics::set_metrics(Arc::new(Reregister));
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let cst = builder.constant_r1c(42f32, 3)?;
+    client.compile(&cst.build()?)?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "ffi")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use xla::metrics::{Metrics, TransferDirection};
+use xla::Result;
+
+#[derive(Default)]
+struct Counters {
+    compiles: AtomicUsize,
+    executes: AtomicUsize,
+    to_host_bytes: AtomicUsize,
+}
+
+impl Metrics for Counters {
+    fn on_compile_finished(&self, _duration: std::time::Duration, _module_name: &str) {
+        self.compiles.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_execute_finished(&self, _duration: std::time::Duration) {
+        self.executes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_transfer(&self, bytes: usize, direction: TransferDirection) {
+        if direction == TransferDirection::DeviceToHost {
+            self.to_host_bytes.fetch_add(bytes, Ordering::SeqCst);
+        }
+    }
+}
+
+#[test]
+fn metrics_hooks() -> Result<()> {
+    let counters = Arc::new(Counters::default());
+    xla::metrics::set_metrics(counters.clone());
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let cst = builder.constant_r1c(42f32, 3)?;
+    let exe = client.compile(&cst.build()?)?;
+    let result = exe.execute::<xla::Literal>(&[])?;
+    let result = result[0][0].to_literal_sync()?;
+    xla::metrics::clear_metrics();
+    assert_eq!(result.to_vec::<f32>()?, [42., 42., 42.]);
+    assert_eq!(counters.compiles.load(Ordering::SeqCst), 1);
+    // The execution is reported by a callback once the outputs are ready, which may run on a
+    // runtime thread slightly after the result has been read.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while counters.executes.load(Ordering::SeqCst) == 0 && std::time::Instant::now() < deadline {
+        std::thread::yield_now()
+    }
+    assert_eq!(counters.executes.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.to_host_bytes.load(Ordering::SeqCst), 12);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+
+#[test]
+fn buffers_across_clients() -> Result<()> {
+    let client1 = xla::PjRtClient::cpu()?;
+    let client2 = xla::PjRtClient::cpu()?;
+    assert!(client1.is_same(&client1.clone()));
+    assert!(!client1.is_same(&client2));
+    assert_ne!(client1.id(), client2.id());
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let computation = (&x + &x)?.build()?;
+    let exe1 = client1.compile(&computation)?;
+    let buffer1 = client1.buffer_from_host_buffer(&[1f32, 2.], &[2], None)?;
+    let buffer2 = client2.buffer_from_host_buffer(&[1f32, 2.], &[2], None)?;
+    assert!(buffer2.client().is_same(&client2));
+
+    let result = exe1.execute_b(&[&buffer1])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [2., 4.]);
+    match exe1.execute_b(&[&buffer2]) {
+        Err(xla::Error::ClientMismatch { .. }) => {}
+        Err(err) => anyhow::bail!("unexpected error {err}"),
+        Ok(_) => anyhow::bail!("executing with a buffer from another client should fail"),
+    }
+
+    let devices2 = client2.addressable_devices();
+    let err = client1.buffer_from_host_buffer(&[1f32, 2.], &[2], Some(&devices2[0]));
+    assert!(matches!(err, Err(xla::Error::ClientMismatch { .. })));
+    Ok(())
+}
+
+#[test]
+fn copy_buffer_to_client() -> Result<()> {
+    let client1 = xla::PjRtClient::cpu()?;
+    let client2 = xla::PjRtClient::cpu()?;
+    let buffer1 = client1.buffer_from_host_buffer(&[1f32, 2.], &[2], None)?;
+    let buffer2 = buffer1.copy_to_client(&client2)?;
+    assert!(buffer2.client().is_same(&client2));
+    assert_eq!(buffer2.to_literal_sync()?.to_vec::<f32>()?, [1., 2.]);
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[2], "x")?;
+    let exe2 = client2.compile(&(&x + &x)?.build()?)?;
+    let result = exe2.execute_b(&[&buffer2])?;
+    assert_eq!(result[0][0].to_literal_sync()?.to_vec::<f32>()?, [2., 4.]);
+
+    let copy = buffer1.copy_to_client(&client1)?;
+    assert!(copy.client().is_same(&client1));
+    assert_eq!(copy.to_literal_sync()?.to_vec::<f32>()?, [1., 2.]);
+    Ok(())
+}
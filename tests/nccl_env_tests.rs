@@ -0,0 +1,41 @@
+#![cfg(feature = "ffi")]
+// This file only contains a single test as the NCCL settings are applied to the environment of
+// the process, which is not safe while other threads are running.
+use xla::{Error, GpuClientOptions, NcclConfig, Result};
+
+#[test]
+fn apply_nccl_config() -> Result<()> {
+    let timeout = Some(std::time::Duration::from_millis(500));
+    let options = GpuClientOptions {
+        nccl: NcclConfig { timeout, ..Default::default() },
+        ..Default::default()
+    };
+    let result = xla::PjRtClient::gpu_with_options(&options);
+    assert!(matches!(result, Err(Error::InvalidNcclConfig { .. })));
+
+    let nccl = NcclConfig {
+        comm_id: Some("127.0.0.1:4242".to_string()),
+        timeout: Some(std::time::Duration::from_secs(120)),
+        p2p_transport: Some(false),
+    };
+    unsafe { std::env::set_var("XLA_FLAGS", "--xla_cpu_enable_fast_math=false") }
+    // The settings are applied before creating the client, this may then fail when the xla
+    // extension has been built without GPU support.
+    let options = GpuClientOptions { nccl: nccl.clone(), ..Default::default() };
+    if let Err(err) = xla::PjRtClient::gpu_with_options(&options) {
+        assert!(!matches!(err, Error::InvalidNcclConfig { .. }), "{err:?}")
+    }
+    assert_eq!(std::env::var("NCCL_P2P_DISABLE").as_deref(), Ok("1"));
+    assert_eq!(std::env::var("NCCL_COMM_ID").as_deref(), Ok("127.0.0.1:4242"));
+    assert_eq!(
+        std::env::var("XLA_FLAGS").as_deref(),
+        Ok("--xla_cpu_enable_fast_math=false --xla_gpu_nccl_termination_timeout_seconds=120")
+    );
+
+    // The settings are process wide, a different config is rejected.
+    let other = NcclConfig { comm_id: Some("127.0.0.1:4343".to_string()), ..nccl };
+    let options = GpuClientOptions { nccl: other, ..Default::default() };
+    let result = xla::PjRtClient::gpu_with_options(&options);
+    assert!(matches!(result, Err(Error::InvalidNcclConfig { .. })));
+    Ok(())
+}
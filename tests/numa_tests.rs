@@ -0,0 +1,26 @@
+#![cfg(target_os = "linux")]
+use xla::numa::{NodeBinding, NumaMemoryPolicy};
+use xla::Result;
+
+fn current_affinity() -> Vec<usize> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    assert_eq!(unsafe { libc::sched_getaffinity(0, size, &mut set) }, 0);
+    (0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) }).collect()
+}
+
+#[test]
+fn bind_to_node() -> Result<()> {
+    let nodes = xla::numa::nodes()?;
+    let node = nodes[0];
+    let cpus = xla::numa::node_cpus(node)?;
+    let before = current_affinity();
+    {
+        let binding = NodeBinding::bind_current_thread(node, NumaMemoryPolicy::Preferred)?;
+        assert_eq!(binding.node(), node);
+        assert!(current_affinity().iter().all(|cpu| cpus.contains(cpu)));
+    }
+    assert_eq!(current_affinity(), before);
+    assert!(matches!(xla::numa::node_cpus(usize::MAX), Err(xla::Error::Numa(_))));
+    Ok(())
+}
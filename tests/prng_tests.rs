@@ -0,0 +1,46 @@
+#![cfg(feature = "ffi")]
+use xla::{ElementType, PrngKey, Result};
+
+fn sample(key: PrngKey, ty: ElementType) -> Result<xla::Literal> {
+    let client = xla::PjRtClient::cpu()?;
+    let builder = xla::XlaBuilder::new("test");
+    let values = key.uniform(&builder, ty, &[1000])?;
+    let exe = values.build()?.compile(&client)?;
+    let result = exe.execute::<xla::Literal>(&[])?;
+    result[0][0].to_literal_sync()
+}
+
+#[test]
+fn uniform_is_deterministic() -> Result<()> {
+    let key = PrngKey::new(42);
+    let v1 = sample(key, ElementType::F32)?.to_vec::<f32>()?;
+    let v2 = sample(key, ElementType::F32)?.to_vec::<f32>()?;
+    assert_eq!(v1, v2);
+    assert!(v1.iter().all(|v| (0.0..1.0).contains(v)));
+    let mean = v1.iter().sum::<f32>() / v1.len() as f32;
+    assert!((mean - 0.5).abs() < 0.05);
+
+    let (k1, k2) = key.split();
+    assert_ne!(k1, k2);
+    let v1 = sample(k1, ElementType::F64)?.to_vec::<f64>()?;
+    let v2 = sample(k2, ElementType::F64)?.to_vec::<f64>()?;
+    assert_ne!(v1, v2);
+    assert!(v1.iter().all(|v| (0.0..1.0).contains(v)));
+    Ok(())
+}
+
+#[test]
+fn split_and_fold_in_are_distinct() {
+    let key = PrngKey::new(42);
+    let (k1, k2) = key.split();
+    let keys = key.split_n(4);
+    assert_eq!(keys[..2], [k1, k2]);
+    let mut all = keys.clone();
+    all.extend((0..4).map(|i| key.fold_in(i)));
+    all.push(key);
+    for (i, k1) in all.iter().enumerate() {
+        for k2 in all[i + 1..].iter() {
+            assert_ne!(k1, k2);
+        }
+    }
+}
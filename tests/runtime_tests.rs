@@ -0,0 +1,20 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+
+// This runs in its own test binary as the configuration is process wide.
+#[test]
+fn init_and_shutdown() -> Result<()> {
+    let options = xla::InitOptions { log_output: xla::LogOutput::Discard, ..Default::default() };
+    xla::init(options)?;
+    xla::init(Default::default())?;
+    let client = xla::PjRtClient::cpu()?;
+    // The inner shutdown does not change anything.
+    xla::shutdown()?;
+    assert!(matches!(xla::shutdown(), Err(xla::Error::ClientsStillAlive { count: 1 })));
+    drop(client);
+    xla::init(Default::default())?;
+    xla::shutdown()?;
+    // Unmatched shutdown calls are no-ops.
+    xla::shutdown()?;
+    Ok(())
+}
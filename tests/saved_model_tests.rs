@@ -0,0 +1,115 @@
+#![cfg(feature = "ffi")]
+use anyhow::Result;
+use xla::saved_model;
+
+// Encode the protobuf messages used in saved_model.pb.
+fn varint(mut v: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    while v >= 0x80 {
+        bytes.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    bytes.push(v as u8);
+    bytes
+}
+
+fn bytes_field(number: u64, data: &[u8]) -> Vec<u8> {
+    let mut bytes = varint(number << 3 | 2);
+    bytes.extend(varint(data.len() as u64));
+    bytes.extend(data);
+    bytes
+}
+
+fn varint_field(number: u64, v: u64) -> Vec<u8> {
+    let mut bytes = varint(number << 3);
+    bytes.extend(varint(v));
+    bytes
+}
+
+fn map_entry(key: &str, value: &[u8]) -> Vec<u8> {
+    [bytes_field(1, key.as_bytes()), bytes_field(2, value)].concat()
+}
+
+// A f32 TensorInfo.
+fn tensor_info(name: &str, dims: &[i64]) -> Vec<u8> {
+    let shape: Vec<u8> =
+        dims.iter().flat_map(|d| bytes_field(2, &varint_field(1, *d as u64))).collect();
+    [bytes_field(1, name.as_bytes()), varint_field(2, 1), bytes_field(3, &shape)].concat()
+}
+
+fn saved_model_pb(inputs: &[&str]) -> Vec<u8> {
+    saved_model_pb_with_dims(inputs, &[2, 2])
+}
+
+fn saved_model_pb_with_dims(inputs: &[&str], dims: &[i64]) -> Vec<u8> {
+    let mut signature = vec![];
+    for input in inputs {
+        let info = tensor_info(&format!("serving_default_{input}:0"), dims);
+        signature.extend(bytes_field(1, &map_entry(input, &info)));
+    }
+    signature.extend(bytes_field(2, &map_entry("output_0", &tensor_info("out:0", &[-1, 2]))));
+    signature.extend(bytes_field(3, b"tensorflow/serving/predict"));
+    let meta_info = bytes_field(4, b"serve");
+    let meta_graph =
+        [bytes_field(1, &meta_info), bytes_field(5, &map_entry("serving_default", &signature))]
+            .concat();
+    [varint_field(1, 1), bytes_field(2, &meta_graph)].concat()
+}
+
+#[test]
+fn load_hlo_functions() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("xla-saved-model-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("assets"))?;
+    assert!(saved_model::load(&dir).is_err());
+    // A model without a serving meta graph.
+    std::fs::write(dir.join("saved_model.pb"), b"")?;
+    assert!(saved_model::load(&dir).is_err());
+    std::fs::write(dir.join("saved_model.pb"), saved_model_pb(&["y", "x"]))?;
+    assert!(saved_model::load(&dir).is_err());
+    std::fs::copy("examples/fn_hlo.txt", dir.join("assets/fn_text.hlo"))?;
+    std::fs::copy("examples/fn_hlo.pb", dir.join("assets/serving_default.hlo.pb"))?;
+
+    let model = saved_model::load(&dir)?;
+    assert_eq!(model.tags(), ["serve"]);
+    assert_eq!(model.function_names(), ["fn_text", "serving_default"]);
+    assert_eq!(model.signature_names(), ["serving_default"]);
+    let signature = model.signature("serving_default")?;
+    assert_eq!(signature.method_name, "tensorflow/serving/predict");
+    let keys: Vec<_> = signature.inputs.iter().map(|i| i.key.as_str()).collect();
+    assert_eq!(keys, ["x", "y"]);
+    assert_eq!(signature.inputs[0].name, "serving_default_x:0");
+    assert_eq!(signature.inputs[0].ty, Some(xla::ElementType::F32));
+    assert_eq!(signature.inputs[0].dims, Some(vec![2, 2]));
+    assert_eq!(signature.outputs[0].dims, Some(vec![-1, 2]));
+    assert!(model.signature("missing").is_err());
+    assert!(model.computation("missing").is_err());
+    let client = xla::PjRtClient::cpu()?;
+    for name in model.function_names() {
+        let exe = client.compile(&model.computation(name)?)?;
+        let x = xla::Literal::vec1(&[1f32, 2., 3., 4.]).reshape(&[2, 2])?;
+        let y = xla::Literal::vec1(&[1f32, 1., 1., 1.]).reshape(&[2, 2])?;
+        let result = exe.execute(&[x, y])?[0][0].to_literal_sync()?.to_tuple1()?;
+        assert_eq!(result.to_vec::<f32>()?, [1.5, 1.5, 3.5, 3.5]);
+    }
+
+    // The function does not match its signature.
+    std::fs::write(dir.join("saved_model.pb"), saved_model_pb(&["x"]))?;
+    assert!(saved_model::load(&dir).is_err());
+    std::fs::write(dir.join("saved_model.pb"), saved_model_pb_with_dims(&["x", "y"], &[3, 2]))?;
+    assert!(saved_model::load(&dir).is_err());
+    // Unknown dimensions match any size.
+    std::fs::write(dir.join("saved_model.pb"), saved_model_pb_with_dims(&["x", "y"], &[-1, 2]))?;
+    assert!(saved_model::load(&dir).is_ok());
+
+    // Parameters named after the signature inputs are mapped by name rather than by key order.
+    std::fs::remove_file(dir.join("assets/serving_default.hlo.pb"))?;
+    let hlo = std::fs::read_to_string("examples/fn_hlo.txt")?;
+    let hlo = hlo.replace("Arg_0.1", "y").replace("Arg_1.2", "serving_default_x");
+    std::fs::write(dir.join("assets/serving_default.hlo"), hlo)?;
+    std::fs::write(dir.join("saved_model.pb"), saved_model_pb(&["x", "y"]))?;
+    let model = saved_model::load(&dir)?;
+    let keys: Vec<_> = model.signature("serving_default")?.inputs.iter().map(|i| &i.key).collect();
+    assert_eq!(keys, ["y", "x"]);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "stub")]
+use xla::stub::{Literal, PjRtClient, XlaBuilder};
+use xla::{ElementType, PrimitiveType, Result};
+
+fn run(builder: &XlaBuilder, root: &xla::stub::XlaOp, args: &[Literal]) -> Result<Literal> {
+    let client = PjRtClient::cpu()?;
+    let exe = builder.build(root)?.compile(&client)?;
+    exe.execute::<Literal>(args)?[0][0].to_literal_sync()
+}
+
+#[test]
+fn add_op() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, ElementType::F32, &[2, 3], "x")?;
+    let y = builder.c0(1.5f32)?;
+    let sum = (x + y)?;
+    let arg = Literal::vec1(&[1f32, 2., 3., 4., 5., 6.]).reshape(&[2, 3])?;
+    let result = run(&builder, &sum, &[arg])?;
+    assert_eq!(result.array_shape()?.dims(), [2, 3]);
+    assert_eq!(result.to_vec::<f32>()?, [2.5, 3.5, 4.5, 5.5, 6.5, 7.5]);
+    Ok(())
+}
+
+#[test]
+fn integer_ops() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.c1(&[7i32, -7, 9])?;
+    let y = builder.c1(&[2i32, 2, 3])?;
+    let div = (x / y)?;
+    let result = run(&builder, &div, &[])?;
+    assert_eq!(result.to_vec::<i32>()?, [3, -3, 3]);
+    let converted = div.convert(PrimitiveType::F64)?;
+    let result = run(&builder, &converted, &[])?;
+    assert_eq!(result.to_vec::<f64>()?, [3., -3., 3.]);
+    Ok(())
+}
+
+#[test]
+fn broadcast_and_transpose() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.c1(&[1i64, 2, 3])?;
+    let b = x.broadcast_in_dim(&[3, 2], &[0])?;
+    let result = run(&builder, &b, &[])?;
+    assert_eq!(result.to_vec::<i64>()?, [1, 1, 2, 2, 3, 3]);
+    let t = b.transpose(&[1, 0])?;
+    let result = run(&builder, &t, &[])?;
+    assert_eq!(result.array_shape()?.dims(), [2, 3]);
+    assert_eq!(result.to_vec::<i64>()?, [1, 2, 3, 1, 2, 3]);
+    let b = x.broadcast(&[2])?;
+    assert_eq!(b.dims()?, [2, 3]);
+    Ok(())
+}
+
+#[test]
+fn reduce() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.c1(&[1f32, 5., 3., 4., 2., 6.])?.reshape(&[2, 3])?;
+    let sum = x.reduce_sum(&[1], false)?;
+    assert_eq!(run(&builder, &sum, &[])?.to_vec::<f32>()?, [9., 12.]);
+    let max = x.reduce_max(&[0], true)?;
+    let result = run(&builder, &max, &[])?;
+    assert_eq!(result.array_shape()?.dims(), [1, 3]);
+    assert_eq!(result.to_vec::<f32>()?, [4., 5., 6.]);
+    let mean = x.reduce_mean(&[0, 1], false)?;
+    assert_eq!(run(&builder, &mean, &[])?.to_vec::<f32>()?, [3.5]);
+    Ok(())
+}
+
+#[test]
+fn matmul_and_softmax() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let lhs = builder.parameter(0, ElementType::F64, &[2, 2], "lhs")?;
+    let rhs = builder.parameter(1, ElementType::F64, &[2, 3], "rhs")?;
+    let prod = lhs.matmul(&rhs)?;
+    let lhs_arg = Literal::vec1(&[1f64, 2., 3., 4.]).reshape(&[2, 2])?;
+    let rhs_arg = Literal::vec1(&[1f64, 0., 1., 0., 1., 1.]).reshape(&[2, 3])?;
+    let result = run(&builder, &prod, &[lhs_arg.clone(), rhs_arg.clone()])?;
+    assert_eq!(result.array_shape()?.dims(), [2, 3]);
+    assert_eq!(result.to_vec::<f64>()?, [1., 2., 3., 3., 4., 7.]);
+
+    let softmax = prod.softmax(1)?;
+    let result = run(&builder, &softmax, &[lhs_arg, rhs_arg])?.to_vec::<f64>()?;
+    for row in result.chunks(3) {
+        assert!((row.iter().sum::<f64>() - 1.).abs() < 1e-9);
+    }
+    assert!(result[2] > result[1] && result[1] > result[0]);
+    Ok(())
+}
+
+#[test]
+fn select_and_tuple() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.c1(&[1f32, -2., 3., -4.])?;
+    let zero = builder.zero(ElementType::F32)?;
+    let relu = x.gt(&zero)?.select(&x, &zero.broadcast(&[4])?)?;
+    let tuple = builder.tuple(&[&relu, &x.neg()?])?;
+    let (a, b) = run(&builder, &tuple, &[])?.to_tuple2()?;
+    assert_eq!(a.to_vec::<f32>()?, [1., 0., 3., 0.]);
+    assert_eq!(b.to_vec::<f32>()?, [-1., 2., -3., 4.]);
+    let elem = tuple.get_tuple_element(1)?;
+    assert_eq!(run(&builder, &elem, &[])?.to_vec::<f32>()?, [-1., 2., -3., 4.]);
+    Ok(())
+}
+
+#[test]
+fn errors() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, ElementType::F32, &[3], "x")?;
+    let y = builder.c1(&[1f32, 2.])?;
+    assert!((&x + &y).is_err());
+    assert!(x.add_(&builder.c1(&[1i32, 2, 3])?).is_err());
+    assert!(builder.parameter(0, ElementType::F32, &[3], "x").is_err());
+    assert!(builder.parameter(1, ElementType::F16, &[3], "x").is_err());
+
+    let other = XlaBuilder::new("other");
+    assert!(x.add_(&other.c0(1f32)?).is_err());
+
+    let exe = x.build()?.compile(&PjRtClient::cpu()?)?;
+    assert!(exe.execute::<Literal>(&[]).is_err());
+    assert!(exe.execute(&[Literal::vec1(&[1f32, 2.])]).is_err());
+    assert!(exe.execute(&[Literal::vec1(&[1i32, 2, 3])]).is_err());
+    assert!(exe.execute(&[Literal::vec1(&[1f32, 2., 3.])]).is_ok());
+    Ok(())
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "tch")]
+use anyhow::Result;
+use tch::{Device, Kind, Tensor};
+
+#[test]
+fn tensor_roundtrip() -> Result<()> {
+    let tensor = Tensor::arange(6, (Kind::Float, Device::Cpu)).reshape([2, 3]);
+    let literal = xla::tch::literal_from_tensor(&tensor)?;
+    assert_eq!(literal.array_shape()?, xla::ArrayShape::new::<f32>(vec![2, 3]));
+    assert_eq!(literal.to_vec::<f32>()?, [0., 1., 2., 3., 4., 5.]);
+
+    let client = xla::PjRtClient::cpu()?;
+    let buffer = xla::tch::buffer_from_tensor(&client, &tensor.tr(), None)?;
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<f32>()?, [0., 3., 1., 4., 2., 5.]);
+    drop(tensor);
+
+    let builder = xla::XlaBuilder::new("test");
+    let x = builder.parameter(0, xla::ElementType::F32, &[3, 2], "x")?;
+    let exe = client.compile(&(&x + &x)?.build()?)?;
+    let result = exe.execute_b(&[&buffer])?.remove(0).remove(0);
+    let result = xla::tch::tensor_from_buffer(&result, Device::Cpu)?;
+    assert_eq!(result.size(), [3, 2]);
+    assert_eq!(Vec::<f32>::try_from(result.flatten(0, -1))?, [0., 6., 2., 8., 4., 10.]);
+    Ok(())
+}
+
+#[test]
+fn tensor_views() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    // A narrowed tensor starts at an offset in its storage and is copied instead.
+    let tensor = Tensor::arange(7, (Kind::Int8, Device::Cpu)).narrow(0, 1, 6);
+    let buffer = xla::tch::buffer_from_tensor(&client, &tensor, None)?;
+    assert_eq!(buffer.to_literal_sync()?.to_vec::<i8>()?, [1, 2, 3, 4, 5, 6]);
+
+    let view = xla::tch::tensor_view_from_buffer(buffer)?;
+    assert_eq!(view.tensor().size(), [6]);
+    assert_eq!(Vec::<i8>::try_from(view.tensor())?, [1, 2, 3, 4, 5, 6]);
+    Ok(())
+}
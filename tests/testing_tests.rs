@@ -0,0 +1,56 @@
+#![cfg(feature = "ndarray")]
+use anyhow::Result;
+use ndarray::{ArrayD, Zip};
+use xla::testing::{array_to_literal, literal_to_array, Checker, Generator};
+use xla::ElementType;
+
+#[test]
+fn generator() -> Result<()> {
+    let mut generator = Generator::new(42);
+    for _ in 0..16 {
+        let dims = generator.dims();
+        assert!(dims.len() <= generator.max_rank);
+        assert!(dims.iter().all(|&d| d >= 1 && d <= generator.max_dim));
+        let ty = generator.element_type(&[ElementType::F32, ElementType::U8, ElementType::Pred])?;
+        let literal = generator.literal(ty, &dims)?;
+        assert_eq!(literal.ty()?, ty);
+        assert_eq!(literal.element_count(), dims.iter().product::<usize>());
+    }
+    let values = generator.literal(ElementType::U32, &[64])?.to_vec::<u32>()?;
+    assert!(values.iter().all(|&v| v < 10));
+    // The same seed generates the same values.
+    let lhs = Generator::new(1).literal(ElementType::F32, &[3, 2])?;
+    assert_eq!(lhs, Generator::new(1).literal(ElementType::F32, &[3, 2])?);
+    assert!(generator.element_type(&[]).is_err());
+
+    let array = literal_to_array::<f32>(&lhs)?;
+    assert_eq!(array.shape(), [3, 2]);
+    // Transposed arrays are converted in their logical order.
+    let transposed = array.t().to_owned();
+    let literal = array_to_literal(&transposed)?;
+    assert_eq!(literal.array_shape()?.dims(), [2, 3]);
+    assert_eq!(literal.to_vec::<f32>()?, transposed.iter().copied().collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn checker() -> Result<()> {
+    let client = xla::PjRtClient::cpu()?;
+    let mut checker = Checker::new(&client, 42);
+    checker.unary::<f32, _, _>(|x| x.exp(), |x| x.mapv(f32::exp))?;
+    checker.unary::<i32, _, _>(|x| x.neg(), |x| x.mapv(|v| -v))?;
+    checker.binary::<f32, _, _>(
+        |x, y| x.max(y),
+        |x, y| Zip::from(x).and(y).map_collect(|x, y| x.max(*y)),
+    )?;
+    checker.check::<f32, _, _>(
+        3,
+        |xs| (&xs[0] * &xs[1])? + &xs[2],
+        |xs: &[ArrayD<f32>]| &xs[0] * &xs[1] + &xs[2],
+    )?;
+
+    // A wrong reference is reported with the failing case.
+    let err = checker.unary::<f32, _, _>(|x| x.exp(), |x| x.mapv(f32::ln)).unwrap_err();
+    assert!(matches!(err, xla::Error::ReferenceMismatch { case: 0, .. }), "{err}");
+    Ok(())
+}
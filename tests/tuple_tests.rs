@@ -1,3 +1,4 @@
+#![cfg(feature = "ffi")]
 use xla::Result;
 
 #[test]
@@ -9,10 +10,14 @@ fn tuple_op() -> Result<()> {
     let computation = builder.tuple(&[cst42, cst43])?.build()?;
     let result = client.compile(&computation)?;
     let result = result.execute::<xla::Literal>(&[])?;
-    let mut result = result[0][0].to_literal_sync()?;
+    let result = result[0][0].to_literal_sync()?;
     assert_eq!(result.shape()?.tuple_size(), Some(2));
-    let as_tuple = result.decompose_tuple()?;
-    assert_eq!(result.shape()?.tuple_size(), Some(0));
+    let element = result.tuple_element(1)?;
+    assert_eq!(element.to_vec::<f32>()?, vec![43f32, 43f32]);
+    assert_eq!(result.shape()?.tuple_size(), Some(2));
+    assert!(result.tuple_element(2).is_err());
+    assert!(element.tuple_element(0).is_err());
+    let as_tuple = result.into_tuple()?;
     assert_eq!(as_tuple.len(), 2);
     assert_eq!(as_tuple[0].array_shape()?, xla::ArrayShape::new::<f32>(vec![]));
     assert_eq!(as_tuple[1].array_shape()?, xla::ArrayShape::new::<f32>(vec![2]));
@@ -0,0 +1,24 @@
+use xla::{ArrayShape, ElementType, PrimitiveType, Result, Shape};
+
+#[test]
+fn array_shape() -> Result<()> {
+    let shape = ArrayShape::new::<f32>(vec![2, 3]);
+    assert_eq!(shape.element_count(), 6);
+    assert_eq!(shape.ty(), ElementType::F32);
+    assert_eq!(shape.primitive_type(), PrimitiveType::F32);
+    let shape = Shape::Array(shape);
+    assert_eq!(shape.primitive_type(), PrimitiveType::F32);
+    let (d0, d1) = <(i64, i64)>::try_from(&shape)?;
+    assert_eq!((d0, d1), (2, 3));
+    let shape = Shape::tuple(vec![shape, Shape::array::<i64>(vec![4])]);
+    assert_eq!(shape.tuple_size(), Some(2));
+    assert!(ArrayShape::try_from(&shape).is_err());
+    Ok(())
+}
+
+#[test]
+fn element_types() {
+    assert_eq!(ElementType::F16.element_size_in_bytes(), 2);
+    assert_eq!(ElementType::Bf16.primitive_type(), PrimitiveType::Bf16);
+    assert_eq!(ElementType::C128.element_size_in_bytes(), 16);
+}
@@ -0,0 +1,78 @@
+//! Derive macros for the `xla` crate, these are re-exported by `xla` and should be used from
+//! there.
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+/// Derive `xla::XlaState` for a struct whose fields all implement `xla::XlaState`, e.g. `XlaOp`
+/// fields or other structs deriving `XlaState`. The fields are flattened in declaration order.
+#[proc_macro_derive(XlaState)]
+pub fn derive_xla_state(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match xla_state(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn xla_state(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => Err(syn::Error::new_spanned(input, "XlaState can only be derived for structs"))?,
+    };
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    // Member accessors, e.g. `self.foo` or `self.0`, and the local variables holding the fields
+    // when rebuilding the struct.
+    let members: Vec<_> = match fields {
+        Fields::Named(fields) => {
+            fields.named.iter().map(|f| f.ident.clone().unwrap()).map(|i| quote!(#i)).collect()
+        }
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|i| {
+                let i = Index::from(i);
+                quote!(#i)
+            })
+            .collect(),
+        Fields::Unit => vec![],
+    };
+    let vars: Vec<_> = (0..members.len()).map(|i| quote::format_ident!("__field{}", i)).collect();
+    let build = match fields {
+        Fields::Named(_) => quote!(Self { #(#members: #vars),* }),
+        Fields::Unnamed(_) => quote!(Self(#(#vars),*)),
+        Fields::Unit => quote!(Self),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::xla::XlaState for #name #ty_generics #where_clause {
+            fn state_len() -> usize {
+                0 #(+ <#types as ::xla::XlaState>::state_len())*
+            }
+
+            fn push_state_ops(&self, ops: &mut Vec<::xla::XlaOp>) {
+                #(::xla::XlaState::push_state_ops(&self.#members, ops);)*
+            }
+
+            fn from_state_ops(ops: &[::xla::XlaOp]) -> ::xla::Result<Self> {
+                let expected = <Self as ::xla::XlaState>::state_len();
+                if ops.len() != expected {
+                    return Err(::xla::Error::UnexpectedNumberOfElemsInTuple {
+                        expected,
+                        got: ops.len(),
+                    });
+                }
+                let mut offset = 0;
+                #(
+                    let len = <#types as ::xla::XlaState>::state_len();
+                    let #vars = <#types as ::xla::XlaState>::from_state_ops(
+                        &ops[offset..offset + len],
+                    )?;
+                    offset += len;
+                )*
+                let _ = offset;
+                Ok(#build)
+            }
+        }
+    })
+}
@@ -1,5 +1,7 @@
 #include "xla_rs.h"
 #include <cstdio>
+#include <mutex>
+#include <set>
 
 #define ASSIGN_OR_RETURN_STATUS(lhs, rexpr)                                    \
   ASSIGN_OR_RETURN_STATUS_IMPL(                                                \
@@ -33,12 +35,91 @@
         arg->builder()->ReportError(tsl::errors::Internal(e.what())));         \
   }
 
+class RustLogSink : public tsl::TFLogSink {
+public:
+  explicit RustLogSink(log_callback cb) : cb_(cb) {}
+  void Send(const tsl::TFLogEntry &entry) override {
+    std::string fname = entry.FName();
+    std::string msg = entry.ToString();
+    cb_(static_cast<int>(entry.log_severity()), verbosity(entry, fname),
+        fname.c_str(), entry.Line(), msg.c_str());
+  }
+
+private:
+  // TSL emits the VLOG messages with the INFO severity and does not record
+  // their level, an INFO message is reported with the highest VLOG level that
+  // is enabled for its file, so 0 unless verbose logging is on for this file.
+  static int verbosity(const tsl::TFLogEntry &entry, const std::string &fname) {
+    if (entry.log_severity() != absl::LogSeverity::kInfo) {
+      return 0;
+    }
+    int level = 0;
+    while (level < 10 && tsl::internal::LogMessage::VmoduleActivated(
+                             fname.c_str(), level + 1)) {
+      ++level;
+    }
+    return level;
+  }
+
+  log_callback cb_;
+};
+
+// Guards the two statics below, set_log_callback can be called concurrently.
+static std::mutex log_sink_mutex;
+static RustLogSink *rust_log_sink = nullptr;
+static std::vector<tsl::TFLogSink *> default_log_sinks;
+
+void set_log_callback(log_callback cb) {
+  std::lock_guard<std::mutex> lock(log_sink_mutex);
+  if (rust_log_sink != nullptr) {
+    tsl::TFRemoveLogSink(rust_log_sink);
+    delete rust_log_sink;
+    rust_log_sink = nullptr;
+  }
+  if (cb == nullptr) {
+    // Restore the sinks that were writing to stderr.
+    for (auto sink : default_log_sinks) {
+      tsl::TFAddLogSink(sink);
+    }
+    default_log_sinks.clear();
+    return;
+  }
+  if (default_log_sinks.empty()) {
+    default_log_sinks = tsl::TFGetLogSinks();
+    for (auto sink : default_log_sinks) {
+      tsl::TFRemoveLogSink(sink);
+    }
+  }
+  rust_log_sink = new RustLogSink(cb);
+  tsl::TFAddLogSink(rust_log_sink);
+}
+
+// Read the logging and the XLA_FLAGS settings from the environment now, these
+// are otherwise read lazily on the first log message or compilation.
+void tsl_runtime_init() {
+  tsl::internal::LogMessage::MaxVLogLevel();
+  xla::GetDebugOptionsFromFlags();
+}
+
+// The value in effect, XLA_FLAGS is only parsed once per process.
+int64_t xla_gpu_nccl_termination_timeout_seconds() {
+  return xla::GetDebugOptionsFromFlags()
+      .xla_gpu_nccl_termination_timeout_seconds();
+}
+
 status pjrt_cpu_client_create(pjrt_client *output) {
   ASSIGN_OR_RETURN_STATUS(client, xla::GetTfrtCpuClient(false));
   *output = new std::shared_ptr(std::move(client));
   return nullptr;
 }
 
+status pjrt_cpu_client_create_with_device_count(pjrt_client *output,
+                                                int device_count) {
+  ASSIGN_OR_RETURN_STATUS(client, xla::GetTfrtCpuClient(false, device_count));
+  *output = new std::shared_ptr(std::move(client));
+  return nullptr;
+}
+
 status pjrt_gpu_client_create(pjrt_client *output, double memory_fraction,
                               bool preallocate) {
   xla::GpuAllocatorConfig allocator = {.memory_fraction = memory_fraction,
@@ -50,6 +131,24 @@ status pjrt_gpu_client_create(pjrt_client *output, double memory_fraction,
   return nullptr;
 }
 
+status pjrt_gpu_client_create_with_options(pjrt_client *output,
+                                           double memory_fraction,
+                                           bool preallocate,
+                                           const int64_t *allowed_devices,
+                                           size_t num_allowed_devices) {
+  xla::GpuAllocatorConfig allocator = {.memory_fraction = memory_fraction,
+                                       .preallocate = preallocate};
+  xla::GpuClientOptions options;
+  options.allocator_config = allocator;
+  if (allowed_devices != nullptr) {
+    options.allowed_devices = std::set<int>(
+        allowed_devices, allowed_devices + num_allowed_devices);
+  }
+  ASSIGN_OR_RETURN_STATUS(client, xla::GetStreamExecutorGpuClient(options));
+  *output = new std::shared_ptr(std::move(client));
+  return nullptr;
+}
+
 /*
 status pjrt_tpu_client_create(pjrt_client *output,
                               int max_inflight_computations) {
@@ -59,6 +158,23 @@ status pjrt_tpu_client_create(pjrt_client *output,
 }
 */
 
+status pjrt_plugin_load(const char *device_type, const char *library_path) {
+  ASSIGN_OR_RETURN_STATUS(api, pjrt::LoadPjrtPlugin(device_type, library_path));
+  (void)api;
+  return nullptr;
+}
+
+status pjrt_plugin_initialize(const char *device_type) {
+  MAYBE_RETURN_STATUS(pjrt::InitializePjrtPlugin(device_type));
+  return nullptr;
+}
+
+status pjrt_plugin_client_create(pjrt_client *output, const char *device_type) {
+  ASSIGN_OR_RETURN_STATUS(client, xla::GetCApiClient(device_type));
+  *output = new std::shared_ptr(std::move(client));
+  return nullptr;
+}
+
 int pjrt_client_device_count(pjrt_client c) { return (*c)->device_count(); }
 
 int pjrt_client_addressable_device_count(pjrt_client c) {
@@ -88,10 +204,200 @@ char *pjrt_client_platform_version(pjrt_client c) {
   return strdup(std::string((*c)->platform_version()).c_str());
 }
 
+template <typename T> static std::string attribute_to_string(const T &value) {
+  return std::visit(
+      [](auto &&v) -> std::string {
+        using V = std::decay_t<decltype(v)>;
+        if constexpr (std::is_same_v<V, std::string>) {
+          return v;
+        } else if constexpr (std::is_same_v<V, bool>) {
+          return v ? "true" : "false";
+        } else if constexpr (std::is_same_v<V, std::vector<int64_t>>) {
+          std::string s;
+          for (size_t i = 0; i < v.size(); ++i) {
+            if (i > 0)
+              s += ",";
+            s += std::to_string(v[i]);
+          }
+          return s;
+        } else {
+          return std::to_string(v);
+        }
+      },
+      value);
+}
+
+static size_t
+attributes_to_c(const std::vector<std::pair<std::string, std::string>> &attrs,
+                char ***keys, char ***values) {
+  size_t n = attrs.size();
+  *keys = (char **)malloc(n * sizeof(char *));
+  *values = (char **)malloc(n * sizeof(char *));
+  for (size_t i = 0; i < n; ++i) {
+    (*keys)[i] = strdup(attrs[i].first.c_str());
+    (*values)[i] = strdup(attrs[i].second.c_str());
+  }
+  return n;
+}
+
+size_t pjrt_client_attributes(pjrt_client c, char ***keys, char ***values) {
+  std::vector<std::pair<std::string, std::string>> attrs;
+  auto plugin_attributes = (*c)->plugin_attributes();
+  if (plugin_attributes.has_value()) {
+    attrs.emplace_back(
+        "pjrt_c_api_version",
+        std::to_string(plugin_attributes->pjrt_c_api_major_version) + "." +
+            std::to_string(plugin_attributes->pjrt_c_api_minor_version));
+    for (const auto &[key, value] : plugin_attributes->attributes) {
+      attrs.emplace_back(key, attribute_to_string(value));
+    }
+  }
+  return attributes_to_c(attrs, keys, values);
+}
+
+static const size_t host_buffer_alignment = 64;
+
+bool pjrt_client_has_pinned_host_allocator(pjrt_client c) {
+  auto se_client = dynamic_cast<xla::PjRtStreamExecutorClient *>(c->get());
+  return se_client != nullptr && se_client->host_memory_allocator() != nullptr;
+}
+
+status pjrt_client_allocate_host_buffer(pjrt_client c, size_t len, void **ptr,
+                                        bool *pinned) {
+  auto se_client = dynamic_cast<xla::PjRtStreamExecutorClient *>(c->get());
+  if (se_client != nullptr && se_client->host_memory_allocator() != nullptr) {
+    *ptr = se_client->host_memory_allocator()->AllocateRaw(
+        host_buffer_alignment, std::max(len, (size_t)1));
+    *pinned = true;
+  } else {
+    // Clients without a page-locked memory allocator, e.g. the cpu client, get
+    // regular aligned host memory.
+    size_t size = (std::max(len, (size_t)1) + host_buffer_alignment - 1) /
+                  host_buffer_alignment * host_buffer_alignment;
+    *ptr = aligned_alloc(host_buffer_alignment, size);
+    *pinned = false;
+  }
+  if (*ptr == nullptr) {
+    return new Status(tsl::errors::ResourceExhausted(
+        "cannot allocate ", len, " bytes of host memory"));
+  }
+  return nullptr;
+}
+
+void pjrt_client_free_host_buffer(pjrt_client c, void *ptr, bool pinned) {
+  if (pinned) {
+    auto se_client = dynamic_cast<xla::PjRtStreamExecutorClient *>(c->get());
+    se_client->host_memory_allocator()->DeallocateRaw(ptr);
+  } else {
+    free(ptr);
+  }
+}
+
 void pjrt_client_free(pjrt_client b) { delete b; }
 
 void pjrt_loaded_executable_free(pjrt_loaded_executable b) { delete b; }
 
+int pjrt_loaded_executable_addressable_device_count(
+    const pjrt_loaded_executable exe) {
+  return exe->addressable_devices().size();
+}
+
+void pjrt_loaded_executable_addressable_devices(const pjrt_loaded_executable exe,
+                                                pjrt_device *outputs) {
+  size_t index = 0;
+  for (auto device : exe->addressable_devices()) {
+    outputs[index++] = device;
+  }
+}
+
+status pjrt_loaded_executable_fusions(const pjrt_loaded_executable exe,
+                                     size_t *n, char ***names, char ***kinds,
+                                     char ***fused, char ***backend_configs) {
+  ASSIGN_OR_RETURN_STATUS(modules, exe->GetHloModules());
+  std::vector<const HloInstruction *> fusions;
+  for (auto &module : modules) {
+    for (auto computation : module->MakeNonfusionComputations()) {
+      for (auto instr : computation->MakeInstructionPostOrder()) {
+        if (instr->opcode() == HloOpcode::kFusion) {
+          fusions.push_back(instr);
+        }
+      }
+    }
+  }
+  *n = fusions.size();
+  *names = (char **)malloc(*n * sizeof(char *));
+  *kinds = (char **)malloc(*n * sizeof(char *));
+  *fused = (char **)malloc(*n * sizeof(char *));
+  *backend_configs = (char **)malloc(*n * sizeof(char *));
+  for (size_t i = 0; i < *n; ++i) {
+    auto instr = fusions[i];
+    // The fused instructions are returned as a comma separated list, the
+    // parameters of the fused computation are skipped.
+    std::string s;
+    for (auto f : instr->fused_instructions_computation()
+                      ->MakeInstructionPostOrder()) {
+      if (f->opcode() == HloOpcode::kParameter)
+        continue;
+      if (!s.empty())
+        s += ",";
+      s += f->name();
+    }
+    (*names)[i] = strdup(instr->name().c_str());
+    (*kinds)[i] = strdup(ToString(instr->fusion_kind()).c_str());
+    (*fused)[i] = strdup(s.c_str());
+    (*backend_configs)[i] = strdup(instr->raw_backend_config_string().c_str());
+  }
+  return nullptr;
+}
+
+status pjrt_loaded_executable_cost_analysis(const pjrt_loaded_executable exe,
+                                           size_t *n, char ***keys,
+                                           char ***values) {
+  ASSIGN_OR_RETURN_STATUS(analysis, exe->GetCostAnalysis());
+  std::vector<std::pair<std::string, std::string>> attrs;
+  for (const auto &[key, value] : analysis) {
+    attrs.emplace_back(key, attribute_to_string(value));
+  }
+  *n = attributes_to_c(attrs, keys, values);
+  return nullptr;
+}
+
+// The kind values have to match the `BackendIrKind` enum on the rust side.
+status pjrt_loaded_executable_backend_ir(const pjrt_loaded_executable exe,
+                                        int kind, char **output) {
+  const Executable *executable = nullptr;
+  if (auto cpu_exe = dynamic_cast<TfrtCpuExecutable *>(exe)) {
+    executable = cpu_exe->cpu_executable().get();
+  } else if (auto se_exe =
+                 dynamic_cast<PjRtStreamExecutorLoadedExecutable *>(exe)) {
+    if (!se_exe->executables().empty()) {
+      executable = se_exe->executables()[0]->executable();
+    }
+  }
+  std::string ir;
+  if (auto cpu_exe = dynamic_cast<const cpu::CpuExecutable *>(executable)) {
+    // The object code of jit compiled cpu executables is not kept around, only
+    // the llvm ir can be retrieved.
+    if (kind == 0) {
+      ir = std::string(cpu_exe->ir_module_string());
+    }
+  } else if (auto gpu_exe =
+                 dynamic_cast<const gpu::GpuExecutable *>(executable)) {
+    if (kind == 1) {
+      ir = gpu_exe->text();
+    }
+  } else {
+    return new Status(tsl::errors::Unimplemented(
+        "backend ir is not available for this executable"));
+  }
+  if (ir.empty()) {
+    return new Status(tsl::errors::Unimplemented(
+        "this kind of backend ir is not available for this executable"));
+  }
+  *output = strdup(ir.c_str());
+  return nullptr;
+}
+
 status pjrt_buffer_from_host_buffer(const pjrt_client client,
                                     const pjrt_device device, const void *d,
                                     int pr_type, int dsize, const int64_t *ds,
@@ -107,6 +413,50 @@ status pjrt_buffer_from_host_buffer(const pjrt_client client,
   return nullptr;
 }
 
+status pjrt_buffer_from_device_pointer(const pjrt_client client,
+                                       const pjrt_device device, void *ptr,
+                                       int pr_type, int dsize,
+                                       const int64_t *ds,
+                                       void (*on_delete)(void *),
+                                       void *user_data, pjrt_buffer *output) {
+  PjRtDevice *device_ = device == nullptr ? (*client)->devices()[0] : device;
+  Shape shape = ShapeUtil::MakeShapeWithDescendingLayout(
+      (PrimitiveType)pr_type, absl::Span<const int64_t>(ds, dsize));
+  ASSIGN_OR_RETURN_STATUS(
+      buffer, (*client)->CreateViewOfDeviceBuffer(
+                  ptr, shape, device_,
+                  [on_delete, user_data]() { on_delete(user_data); }));
+  *output = buffer.release();
+  return nullptr;
+}
+
+// The address of the buffer in the device memory.
+status pjrt_buffer_unsafe_pointer(pjrt_buffer b, void **output) {
+  ASSIGN_OR_RETURN_STATUS(ptr, b->client()->UnsafeBufferPointer(b));
+  *output = reinterpret_cast<void *>(ptr);
+  return nullptr;
+}
+
+status pjrt_buffer_from_pinned_host_buffer(const pjrt_client client,
+                                           const pjrt_device device,
+                                           const void *d, int pr_type,
+                                           int dsize, const int64_t *ds,
+                                           pjrt_buffer *output) {
+  PjRtDevice *device_ = device == nullptr ? (*client)->devices()[0] : device;
+  // The device reads directly from the host memory, wait for the transfer to
+  // complete so that the caller can reuse the memory afterwards.
+  absl::Notification done;
+  ASSIGN_OR_RETURN_STATUS(
+      buffer,
+      (*client)->BufferFromHostBuffer(
+          d, (PrimitiveType)pr_type, absl::Span<const int64_t>(ds, dsize), {},
+          PjRtClient::HostBufferSemantics::kImmutableUntilTransferCompletes,
+          [&done]() { done.Notify(); }, device_));
+  done.WaitForNotification();
+  *output = buffer.release();
+  return nullptr;
+}
+
 status pjrt_buffer_from_host_literal(const pjrt_client client,
                                      const pjrt_device device, const literal l,
                                      pjrt_buffer *output) {
@@ -118,11 +468,25 @@ status pjrt_buffer_from_host_literal(const pjrt_client client,
 
 status pjrt_buffer_to_literal_sync(pjrt_buffer b, literal *output) {
   ASSIGN_OR_RETURN_STATUS(literal, b->ToLiteralSync());
-  *output = new Literal();
-  **output = std::move(*literal);
+  *output = new Literal(std::move(*literal));
+  return nullptr;
+}
+
+status pjrt_buffer_to_literal_into(pjrt_buffer b, literal output) {
+  MAYBE_RETURN_STATUS(b->ToLiteralSync(output));
+  return nullptr;
+}
+
+status pjrt_buffer_block_until_ready(pjrt_buffer b) {
+  MAYBE_RETURN_STATUS(b->GetReadyFuture().Await());
   return nullptr;
 }
 
+void pjrt_buffer_on_ready(pjrt_buffer b, void (*callback)(void *), void *ctx) {
+  b->GetReadyFuture().OnReady(
+      [callback, ctx](absl::Status) { callback(ctx); });
+}
+
 shape pjrt_buffer_on_device_shape(pjrt_buffer b) {
   return new Shape(b->on_device_shape());
 }
@@ -134,6 +498,27 @@ status pjrt_buffer_copy_to_device(pjrt_buffer b, pjrt_device device,
   return nullptr;
 }
 
+// PJRT does not support copying a buffer to its own device, instead an identity
+// computation is compiled for the device of the buffer and run there.
+status pjrt_buffer_duplicate(pjrt_buffer b, pjrt_buffer *output) {
+  auto device = b->device();
+  XlaBuilder builder("duplicate");
+  Parameter(&builder, 0, b->on_device_shape(), "arg");
+  ASSIGN_OR_RETURN_STATUS(computation, builder.Build());
+  CompileOptions options;
+  DeviceAssignment device_assignment(1, 1);
+  device_assignment(0, 0) = device->id();
+  options.executable_build_options.set_device_assignment(device_assignment);
+  ASSIGN_OR_RETURN_STATUS(exe, b->client()->Compile(computation, options));
+  ExecuteOptions execute_options;
+  std::optional<PjRtFuture<>> returned_future;
+  ASSIGN_OR_RETURN_STATUS(results,
+                          exe->ExecuteSharded({b}, device, execute_options,
+                                              returned_future, false));
+  *output = results[0].release();
+  return nullptr;
+}
+
 status pjrt_buffer_copy_raw_to_host_sync(pjrt_buffer b, void *dst,
                                          size_t offset, size_t transfer_size) {
   MAYBE_RETURN_STATUS(b->CopyRawToHost(dst, offset, transfer_size).Await());
@@ -142,6 +527,12 @@ status pjrt_buffer_copy_raw_to_host_sync(pjrt_buffer b, void *dst,
 
 void pjrt_buffer_free(pjrt_buffer b) { delete b; }
 
+void pjrt_buffer_delete(pjrt_buffer b) { b->Delete(); }
+
+bool pjrt_buffer_is_deleted(pjrt_buffer b) { return b->IsDeleted(); }
+
+pjrt_device pjrt_buffer_device(pjrt_buffer b) { return b->device(); }
+
 int pjrt_device_id(pjrt_device d) { return d->id(); }
 
 int pjrt_device_process_index(pjrt_device d) { return d->process_index(); }
@@ -172,6 +563,14 @@ char *pjrt_device_to_string(pjrt_device d) {
   return strdup(std::string(d->ToString()).c_str());
 }
 
+size_t pjrt_device_attributes(pjrt_device d, char ***keys, char ***values) {
+  std::vector<std::pair<std::string, std::string>> attrs;
+  for (const auto &[key, value] : d->Attributes()) {
+    attrs.emplace_back(key, attribute_to_string(value));
+  }
+  return attributes_to_c(attrs, keys, values);
+}
+
 xla_builder xla_builder_create(const char *name) {
   return new XlaBuilder(name);
 }
@@ -184,6 +583,18 @@ xla_op constant_literal(const xla_builder b, const literal l) {
   END_PROTECT_OP_B(b)
 }
 
+xla_op constant_literal_borrowed(const xla_builder b, int pr_type,
+                                 const int64_t *dims, size_t ndims,
+                                 const void *data) {
+  BEGIN_PROTECT_OP
+  auto shape = ShapeUtil::MakeShape((PrimitiveType)pr_type,
+                                    absl::Span<const int64_t>(dims, ndims));
+  // The literal only borrows the data, ConstantLiteral copies it in the graph.
+  BorrowingLiteral literal(static_cast<const char *>(data), shape);
+  return new XlaOp(ConstantLiteral(b, literal));
+  END_PROTECT_OP_B(b)
+}
+
 #define CONST_OP_R01(native_type, primitive_type)                              \
   xla_op constant_r0_##native_type(const xla_builder b, native_type f) {       \
     return new XlaOp(ConstantR0<native_type>(b, f));                           \
@@ -389,6 +800,24 @@ xla_op op_pow(const xla_op lhs, const xla_op rhs) {
   END_PROTECT_OP(lhs)
 }
 
+xla_op op_shift_left(const xla_op lhs, const xla_op rhs) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(ShiftLeft(*lhs, *rhs));
+  END_PROTECT_OP(lhs)
+}
+
+xla_op op_shift_right_arithmetic(const xla_op lhs, const xla_op rhs) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(ShiftRightArithmetic(*lhs, *rhs));
+  END_PROTECT_OP(lhs)
+}
+
+xla_op op_shift_right_logical(const xla_op lhs, const xla_op rhs) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(ShiftRightLogical(*lhs, *rhs));
+  END_PROTECT_OP(lhs)
+}
+
 xla_op op_dot(const xla_op lhs, const xla_op rhs) {
   BEGIN_PROTECT_OP
   return new XlaOp(Dot(*lhs, *rhs));
@@ -575,6 +1004,18 @@ xla_op op_is_finite(const xla_op arg) {
   END_PROTECT_OP(arg)
 }
 
+xla_op op_erf(const xla_op arg) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(Erf(*arg));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_erf_inv(const xla_op arg) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(ErfInv(*arg));
+  END_PROTECT_OP(arg)
+}
+
 xla_op op_neg(const xla_op arg) {
   BEGIN_PROTECT_OP
   return new XlaOp(Neg(*arg));
@@ -694,6 +1135,16 @@ xla_op op_rng_normal(const xla_op arg1, const xla_op arg2, int pr_type,
   END_PROTECT_OP(arg1)
 }
 
+xla_op op_rng_bit_generator(int algorithm, const xla_op initial_state,
+                            int pr_type, int dsize, const int64_t *ds) {
+  BEGIN_PROTECT_OP
+  auto shape = ShapeUtil::MakeShape((PrimitiveType)pr_type,
+                                    absl::Span<const int64_t>(ds, dsize));
+  return new XlaOp(
+      RngBitGenerator((RandomAlgorithm)algorithm, *initial_state, shape));
+  END_PROTECT_OP(initial_state)
+}
+
 xla_op op_slice_in_dim(const xla_op arg, int64_t start, int64_t stop,
                        int64_t stride, int64_t dim) {
   BEGIN_PROTECT_OP
@@ -713,6 +1164,44 @@ xla_op op_concat_in_dim(const xla_op arg, const xla_op *args, size_t nargs,
   END_PROTECT_OP(arg)
 }
 
+xla_op op_dynamic_slice(const xla_op arg, const xla_op *start_indices,
+                        size_t nstart, const int64_t *slice_sizes,
+                        size_t nsizes) {
+  BEGIN_PROTECT_OP
+  std::vector<XlaOp> start_indices_;
+  for (size_t i = 0; i < nstart; ++i) {
+    start_indices_.push_back(*start_indices[i]);
+  }
+  return new XlaOp(DynamicSlice(
+      *arg, start_indices_, absl::Span<const int64_t>(slice_sizes, nsizes)));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_dynamic_update_slice(const xla_op arg, const xla_op update,
+                               const xla_op *start_indices, size_t nstart) {
+  BEGIN_PROTECT_OP
+  std::vector<XlaOp> start_indices_;
+  for (size_t i = 0; i < nstart; ++i) {
+    start_indices_.push_back(*start_indices[i]);
+  }
+  return new XlaOp(DynamicUpdateSlice(*arg, *update, start_indices_));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_pad(const xla_op arg, const xla_op padding_value, const int64_t *low,
+              const int64_t *high, const int64_t *interior, size_t ndims) {
+  BEGIN_PROTECT_OP
+  PaddingConfig config;
+  for (size_t i = 0; i < ndims; ++i) {
+    auto dim = config.add_dimensions();
+    dim->set_edge_padding_low(low[i]);
+    dim->set_edge_padding_high(high[i]);
+    dim->set_interior_padding(interior[i]);
+  }
+  return new XlaOp(Pad(*arg, *padding_value, config));
+  END_PROTECT_OP(arg)
+}
+
 xla_op op_tuple(const xla_builder b, const xla_op *args, size_t nargs) {
   BEGIN_PROTECT_OP
   std::vector<XlaOp> args_;
@@ -729,6 +1218,19 @@ xla_op op_get_tuple_element(const xla_op arg, int64_t index) {
   END_PROTECT_OP(arg)
 }
 
+xla_op op_get_dimension_size(const xla_op arg, int64_t dim) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(GetDimensionSize(*arg, dim));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_set_dimension_size(const xla_op arg, const xla_op size,
+                             int64_t dim) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(SetDimensionSize(*arg, *size, dim));
+  END_PROTECT_OP(arg)
+}
+
 xla_op op_gather(const xla_op arg1, const xla_op arg2,
                  const int64_t *offset_dims, size_t noffset_dims,
                  const int64_t *collapsed_slice_dims,
@@ -754,12 +1256,44 @@ xla_op op_gather(const xla_op arg1, const xla_op arg2,
   END_PROTECT_OP(arg1)
 }
 
+xla_op op_scatter(const xla_op input, const xla_op scatter_indices,
+                  const xla_op updates, const xla_computation comp,
+                  const int64_t *update_window_dims, size_t nupdate_window_dims,
+                  const int64_t *inserted_window_dims,
+                  size_t ninserted_window_dims,
+                  const int64_t *scatter_dims_to_operand_dims,
+                  size_t nscatter_dims_to_operand_dims,
+                  int64_t index_vector_dim, bool indices_are_sorted,
+                  bool unique_indices) {
+  BEGIN_PROTECT_OP
+  ScatterDimensionNumbers dn;
+  for (size_t i = 0; i < nupdate_window_dims; ++i) {
+    dn.add_update_window_dims(update_window_dims[i]);
+  }
+  for (size_t i = 0; i < ninserted_window_dims; ++i) {
+    dn.add_inserted_window_dims(inserted_window_dims[i]);
+  }
+  for (size_t i = 0; i < nscatter_dims_to_operand_dims; ++i) {
+    dn.add_scatter_dims_to_operand_dims(scatter_dims_to_operand_dims[i]);
+  }
+  dn.set_index_vector_dim(index_vector_dim);
+  return new XlaOp(Scatter(*input, *scatter_indices, *updates, *comp, dn,
+                           indices_are_sorted, unique_indices));
+  END_PROTECT_OP(input)
+}
+
 xla_op op_convert_element_type(const xla_op arg, int pr_type) {
   BEGIN_PROTECT_OP
   return new XlaOp(ConvertElementType(*arg, (PrimitiveType)pr_type));
   END_PROTECT_OP(arg)
 }
 
+xla_op op_bitcast_convert_element_type(const xla_op arg, int pr_type) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(BitcastConvertType(*arg, (PrimitiveType)pr_type));
+  END_PROTECT_OP(arg)
+}
+
 xla_op op_dimensions_size(const xla_op arg, int64_t dim) {
   BEGIN_PROTECT_OP
   return new XlaOp(GetDimensionSize(*arg, dim));
@@ -775,6 +1309,97 @@ xla_op op_reduce(const xla_op arg, const xla_op init,
   END_PROTECT_OP(arg)
 }
 
+static std::vector<ReplicaGroup> replica_groups_from_c(const int64_t *ids,
+                                                       const size_t *sizes,
+                                                       size_t ngroups) {
+  std::vector<ReplicaGroup> groups;
+  for (size_t i = 0; i < ngroups; ++i) {
+    ReplicaGroup group;
+    for (size_t j = 0; j < sizes[i]; ++j) {
+      group.add_replica_ids(*ids++);
+    }
+    groups.push_back(group);
+  }
+  return groups;
+}
+
+// A non-positive handle means that the op does not use any channel.
+static std::optional<ChannelHandle> channel_handle_from_c(int64_t handle) {
+  if (handle <= 0) {
+    return std::nullopt;
+  }
+  ChannelHandle channel_handle;
+  channel_handle.set_handle(handle);
+  channel_handle.set_type(ChannelHandle::DEVICE_TO_DEVICE);
+  return channel_handle;
+}
+
+xla_op op_all_reduce(const xla_op arg, const xla_computation comp,
+                     const int64_t *ids, const size_t *sizes, size_t ngroups,
+                     int64_t channel_handle) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(AllReduce(*arg, *comp,
+                             replica_groups_from_c(ids, sizes, ngroups),
+                             channel_handle_from_c(channel_handle)));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_all_gather(const xla_op arg, int64_t dim, int64_t shard_count,
+                     const int64_t *ids, const size_t *sizes, size_t ngroups,
+                     int64_t channel_handle) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(AllGather(*arg, dim, shard_count,
+                             replica_groups_from_c(ids, sizes, ngroups),
+                             channel_handle_from_c(channel_handle)));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_all_to_all(const xla_op arg, int64_t split_dim, int64_t concat_dim,
+                     int64_t split_count, const int64_t *ids,
+                     const size_t *sizes, size_t ngroups,
+                     int64_t channel_handle) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(AllToAll(*arg, split_dim, concat_dim, split_count,
+                            replica_groups_from_c(ids, sizes, ngroups),
+                            /*layout=*/std::nullopt,
+                            channel_handle_from_c(channel_handle)));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_reduce_window(const xla_op arg, const xla_op init,
+                        const xla_computation comp, const int64_t *window_dims,
+                        size_t nwindow_dims, const int64_t *window_strides,
+                        size_t nwindow_strides, const int64_t *padding,
+                        size_t npadding) {
+  BEGIN_PROTECT_OP
+  std::vector<std::pair<int64_t, int64_t>> padding_;
+  for (size_t i = 0; i < npadding; ++i) {
+    padding_.push_back({padding[2 * i], padding[2 * i + 1]});
+  }
+  return new XlaOp(ReduceWindowWithGeneralPadding(
+      *arg, *init, *comp, absl::Span<const int64_t>(window_dims, nwindow_dims),
+      absl::Span<const int64_t>(window_strides, nwindow_strides), {}, {},
+      padding_));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_sort(const xla_op arg, const xla_op *others, size_t nothers,
+               const xla_computation comparator, int64_t dim, bool is_stable) {
+  BEGIN_PROTECT_OP
+  std::vector<XlaOp> args_ = {*arg};
+  for (size_t i = 0; i < nothers; ++i) {
+    args_.push_back(*others[i]);
+  }
+  return new XlaOp(Sort(args_, *comparator, dim, is_stable));
+  END_PROTECT_OP(arg)
+}
+
+xla_op op_top_k(const xla_op arg, int64_t k) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(TopK(*arg, k));
+  END_PROTECT_OP(arg)
+}
+
 xla_op op_internal_error(const xla_builder b, const char *error) {
   BEGIN_PROTECT_OP
   return new XlaOp(b->ReportError(tsl::errors::Internal(error)));
@@ -817,6 +1442,12 @@ xla_op op_max_value(const xla_builder b, int pr_type) {
   END_PROTECT_OP_B(b)
 }
 
+xla_op op_min_finite_value(const xla_builder b, int pr_type) {
+  BEGIN_PROTECT_OP
+  return new XlaOp(MinFiniteValue(b, (PrimitiveType)pr_type));
+  END_PROTECT_OP_B(b)
+}
+
 xla_op op_iota1(const xla_builder b, int pr_type, size_t sz) {
   BEGIN_PROTECT_OP
   return new XlaOp(Iota(b, (PrimitiveType)pr_type, (int64_t)sz));
@@ -848,6 +1479,19 @@ xla_op op_conditional(const xla_op pred, const xla_op true_op,
   END_PROTECT_OP(pred)
 }
 
+xla_op op_indexed_conditional(const xla_op index, const xla_computation *comps,
+                              const xla_op *ops, size_t n) {
+  BEGIN_PROTECT_OP
+  std::vector<const XlaComputation *> branches;
+  std::vector<XlaOp> operands;
+  for (size_t i = 0; i < n; ++i) {
+    branches.push_back(comps[i]);
+    operands.push_back(*ops[i]);
+  }
+  return new XlaOp(Conditional(*index, branches, operands));
+  END_PROTECT_OP(index)
+}
+
 xla_op op_triangular_solve(const xla_op a, const xla_op b, const bool left_side,
                            const bool lower, const bool unit_diagonal,
                            const int transpose_a) {
@@ -860,6 +1504,69 @@ xla_op op_triangular_solve(const xla_op a, const xla_op b, const bool left_side,
 
 xla_builder op_builder(const xla_op arg) { return arg->builder(); }
 
+status op_set_frontend_attribute(const xla_op arg, const char *key,
+                                 const char *value) {
+  MAYBE_RETURN_STATUS(
+      arg->builder()->SetInstructionFrontendAttribute(*arg, key, value));
+  return nullptr;
+}
+
+// XlaBuilder only gives mutable access to its instructions to subclasses, a
+// pointer to the inherited member can be used on any builder though.
+struct XlaBuilderInstructions : public XlaBuilder {
+  static absl::StatusOr<HloInstructionProto *> LookUp(XlaBuilder *b,
+                                                      XlaOp op) {
+    return (b->*&XlaBuilderInstructions::LookUpMutableInstruction)(op);
+  }
+};
+
+status op_set_backend_config(const xla_op arg, const char *config) {
+  ASSIGN_OR_RETURN_STATUS(
+      instr, XlaBuilderInstructions::LookUp(arg->builder(), *arg));
+  instr->set_backend_config(config);
+  return nullptr;
+}
+
+status op_set_sharding(const xla_op arg, int type, const int64_t *dims,
+                       size_t ndims, const int64_t *devices, size_t ndevices,
+                       bool replicate_on_last_tile_dim) {
+  ASSIGN_OR_RETURN_STATUS(
+      instr, XlaBuilderInstructions::LookUp(arg->builder(), *arg));
+  OpSharding sharding;
+  sharding.set_type(static_cast<OpSharding::Type>(type));
+  for (size_t i = 0; i < ndims; ++i) {
+    sharding.add_tile_assignment_dimensions(dims[i]);
+  }
+  for (size_t i = 0; i < ndevices; ++i) {
+    sharding.add_tile_assignment_devices(devices[i]);
+  }
+  sharding.set_replicate_on_last_tile_dim(replicate_on_last_tile_dim);
+  *instr->mutable_sharding() = sharding;
+  return nullptr;
+}
+
+status op_is_constant(const xla_op arg, bool *output) {
+  ASSIGN_OR_RETURN_STATUS(is_constant, arg->builder()->IsConstant(*arg));
+  *output = is_constant;
+  return nullptr;
+}
+
+// Evaluate the subgraph computing `arg` on the host, this fails if it depends
+// on some parameters.
+status op_evaluate_constant(const xla_op arg, literal *output) {
+  ASSIGN_OR_RETURN_STATUS(computation,
+                          arg->builder()->BuildConstantSubGraph(*arg));
+  ASSIGN_OR_RETURN_STATUS(
+      config, HloModule::CreateModuleConfigFromProto(computation.proto(), {}));
+  ASSIGN_OR_RETURN_STATUS(
+      module, HloModule::CreateFromProto(computation.proto(), config));
+  HloEvaluator evaluator;
+  ASSIGN_OR_RETURN_STATUS(
+      result, evaluator.Evaluate(*module, absl::Span<const Literal *const>()));
+  *output = new Literal(std::move(result));
+  return nullptr;
+}
+
 int xla_op_valid(const xla_op op) { return op->valid(); }
 
 void xla_op_free(xla_op o) { delete o; }
@@ -884,6 +1591,43 @@ status get_shape(const xla_builder b, const xla_op o, shape *out_shape) {
   return nullptr;
 }
 
+bool try_get_shape(const xla_builder b, const xla_op o, shape *out_shape) {
+  auto shape = b->GetShape(*o);
+  if (!shape.ok()) {
+    return false;
+  }
+  *out_shape = new Shape(shape.value());
+  return true;
+}
+
+// The opcodes are indexed in the same order as the Rust `BinaryOp` enum.
+bool infer_binary_op_shape(const xla_builder b, int op, const xla_op lhs,
+                           const xla_op rhs, shape *out_shape) {
+  static const HloOpcode opcodes[] = {
+      HloOpcode::kAdd,       HloOpcode::kSubtract, HloOpcode::kMultiply,
+      HloOpcode::kDivide,    HloOpcode::kRemainder, HloOpcode::kMaximum,
+      HloOpcode::kMinimum,   HloOpcode::kAnd,      HloOpcode::kOr,
+      HloOpcode::kXor,       HloOpcode::kAtan2,    HloOpcode::kPower,
+      HloOpcode::kCompare,   HloOpcode::kCompare,  HloOpcode::kCompare,
+      HloOpcode::kCompare,   HloOpcode::kCompare,  HloOpcode::kCompare,
+  };
+  if (op < 0 || op >= static_cast<int>(sizeof(opcodes) / sizeof(opcodes[0]))) {
+    return false;
+  }
+  auto lhs_shape = b->GetShapePtr(*lhs);
+  auto rhs_shape = b->GetShapePtr(*rhs);
+  if (!lhs_shape.ok() || !rhs_shape.ok()) {
+    return false;
+  }
+  auto shape = ShapeInference::InferBinaryOpShape(opcodes[op], **lhs_shape,
+                                                  **rhs_shape, {});
+  if (!shape.ok()) {
+    return false;
+  }
+  *out_shape = new Shape(shape.value());
+  return true;
+}
+
 status get_element_type(const xla_builder b, const xla_op o,
                         int *out_element_type) {
   ASSIGN_OR_RETURN_STATUS(shape, b->GetShapePtr(*o));
@@ -922,6 +1666,17 @@ status compile(const pjrt_client client, const xla_computation computation,
   return nullptr;
 }
 
+status compile_replicated(const pjrt_client client,
+                          const xla_computation computation, int num_replicas,
+                          pjrt_loaded_executable *output) {
+  CompileOptions options;
+  options.executable_build_options.set_num_replicas(num_replicas);
+  ASSIGN_OR_RETURN_STATUS(executable,
+                          (*client)->Compile(*computation, options));
+  *output = executable.release();
+  return nullptr;
+}
+
 status first_error(const xla_builder b) {
   MAYBE_RETURN_STATUS(b->first_error());
   return nullptr;
@@ -965,6 +1720,76 @@ status execute(const pjrt_loaded_executable exe, const literal *inputs,
   return nullptr;
 }
 
+static pjrt_buffer **execute_results_to_c(
+    std::vector<std::vector<std::unique_ptr<PjRtBuffer>>> &results) {
+  pjrt_buffer **out =
+      (pjrt_buffer **)malloc((results.size() + 1) * sizeof(pjrt_buffer *));
+  for (size_t i = 0; i < results.size(); ++i) {
+    auto &replica_results = results[i];
+    pjrt_buffer *per_replica_outputs = (pjrt_buffer *)malloc(
+        (replica_results.size() + 1) * sizeof(pjrt_buffer));
+    for (size_t j = 0; j < replica_results.size(); ++j) {
+      per_replica_outputs[j] = replica_results[j].release();
+    }
+    per_replica_outputs[replica_results.size()] = nullptr;
+    out[i] = per_replica_outputs;
+  }
+  out[results.size()] = nullptr;
+  return out;
+}
+
+// Run `nlaunches` independent executions, `inputs` holds the `ninputs`
+// arguments of each launch one after the other. All the host to device
+// transfers are started before waiting on any of them and the executions are
+// enqueued without waiting for the previous ones to complete.
+status execute_many(const pjrt_loaded_executable exe, const literal *inputs,
+                    int ninputs, int nlaunches, pjrt_buffer ****outputs) {
+  auto client = exe->client();
+  ExecuteOptions options;
+  options.strict_shape_checking = false;
+  PjRtDevice *device = client->devices()[0];
+  std::vector<std::unique_ptr<PjRtBuffer>> input_buffers;
+  for (int i = 0; i < ninputs * nlaunches; ++i) {
+    ASSIGN_OR_RETURN_STATUS(buffer,
+                            client->BufferFromHostLiteral(*inputs[i], device));
+    input_buffers.push_back(std::move(buffer));
+  }
+  // Wait for the transfers to have completed to avoid the literals potentially
+  // getting out of scope before they have been transfered.
+  for (auto &buffer : input_buffers) {
+    MAYBE_RETURN_STATUS(buffer->GetReadyFuture().Await());
+  }
+  std::vector<pjrt_buffer **> launch_outputs;
+  for (int l = 0; l < nlaunches; ++l) {
+    std::vector<PjRtBuffer *> input_buffer_ptrs;
+    for (int i = 0; i < ninputs; ++i) {
+      input_buffer_ptrs.push_back(input_buffers[l * ninputs + i].get());
+    }
+    auto results = exe->Execute({input_buffer_ptrs}, options);
+    if (!results.ok()) {
+      for (auto out : launch_outputs) {
+        for (size_t i = 0; out[i] != nullptr; ++i) {
+          for (size_t j = 0; out[i][j] != nullptr; ++j) {
+            delete out[i][j];
+          }
+          free(out[i]);
+        }
+        free(out);
+      }
+      return new Status(results.status());
+    }
+    launch_outputs.push_back(execute_results_to_c(results.value()));
+  }
+  pjrt_buffer ***out =
+      (pjrt_buffer ***)malloc((nlaunches + 1) * sizeof(pjrt_buffer **));
+  for (int l = 0; l < nlaunches; ++l) {
+    out[l] = launch_outputs[l];
+  }
+  out[nlaunches] = nullptr;
+  *outputs = out;
+  return nullptr;
+}
+
 status execute_b(const pjrt_loaded_executable exe, const pjrt_buffer *inputs,
                  int ninputs, pjrt_buffer ***outputs) {
   auto client = exe->client();
@@ -989,12 +1814,57 @@ status execute_b(const pjrt_loaded_executable exe, const pjrt_buffer *inputs,
   return nullptr;
 }
 
-literal literal_create_from_shape(int pr_type, const int64_t *dims,
-                                  size_t ndims) {
+// Run one execution per addressable device of the executable, the replicas
+// are launched together so that they can take part in the same collectives.
+// `inputs` holds the `ninputs` arguments of each replica one after the other,
+// in the order of the addressable devices.
+status execute_b_replicated(const pjrt_loaded_executable exe,
+                            const pjrt_buffer *inputs, int ninputs,
+                            int nreplicas, pjrt_buffer ***outputs) {
+  ExecuteOptions options;
+  options.strict_shape_checking = false;
+  std::vector<std::vector<PjRtBuffer *>> argument_handles(nreplicas);
+  for (int r = 0; r < nreplicas; ++r) {
+    argument_handles[r].assign(inputs + r * ninputs,
+                               inputs + (r + 1) * ninputs);
+  }
+  ASSIGN_OR_RETURN_STATUS(results, exe->Execute(argument_handles, options));
+  *outputs = execute_results_to_c(results);
+  return nullptr;
+}
+
+status literal_create_from_shape(int pr_type, const int64_t *dims,
+                                 size_t ndims, literal *output) {
+  ASSIGN_OR_RETURN_STATUS(
+      shape, ShapeUtil::MakeValidatedShape(
+                 (PrimitiveType)pr_type, absl::Span<const int64_t>(dims, ndims)));
+  *output = new Literal(Literal::CreateFromShape(shape));
+  return nullptr;
+}
+
+// An array literal using some storage allocated by the caller, the storage is
+// released through `free_` when the literal is deleted.
+class ExternalStorageLiteral : public MutableBorrowingLiteral {
+public:
+  ExternalStorageLiteral(char *data, const Shape &shape,
+                         literal_storage_free free, void *ctx)
+      : MutableBorrowingLiteral(data, shape), data_(data), free_(free),
+        ctx_(ctx) {}
+  ~ExternalStorageLiteral() override { free_(ctx_, data_); }
+
+private:
+  char *data_;
+  literal_storage_free free_;
+  void *ctx_;
+};
+
+literal literal_create_from_shape_in(int pr_type, const int64_t *dims,
+                                     size_t ndims, void *data,
+                                     literal_storage_free free, void *ctx) {
   auto shape = ShapeUtil::MakeShape((PrimitiveType)pr_type,
                                     absl::Span<const int64_t>(dims, ndims));
-  Literal l = Literal::CreateFromShape(shape);
-  return new Literal(std::move(l));
+  return new ExternalStorageLiteral(static_cast<char *>(data), shape, free,
+                                    ctx);
 }
 
 literal literal_create_from_shape_and_data(int pr_type, const int64_t *dims,
@@ -1037,31 +1907,47 @@ void literal_shape(const literal l, shape *out_shape) {
 }
 
 void literal_decompose_tuple(literal l, literal *outputs, size_t noutputs) {
-  auto tuple = l->DecomposeTuple();
+  // Tuple literals are always owning literals.
+  auto tuple = static_cast<Literal *>(l)->DecomposeTuple();
   for (int i = 0; i < std::min(noutputs, tuple.size()); ++i) {
     outputs[i] = new Literal(std::move(tuple[i]));
   }
 }
 
+literal literal_tuple_element(const literal l, int64_t index) {
+  return new Literal(LiteralSlice(*l, {index}).Clone());
+}
+
 int literal_element_type(const literal l) { return l->shape().element_type(); }
 
 void literal_copy_to(const literal l, void *dst, size_t size_in_bytes) {
   std::memcpy(dst, l->untyped_data(), size_in_bytes);
 }
 
+void *literal_untyped_data(literal l) { return l->untyped_data(); }
+
 void literal_copy_from(literal l, const void *src, size_t size_in_bytes) {
   std::memcpy(l->untyped_data(), src, size_in_bytes);
 }
 
 literal literal_make_tuple(const literal *l, size_t n) {
-  Literal out = LiteralUtil::MakeTuple(absl::MakeSpan(l, n));
+  std::vector<LiteralSlice> elems;
+  for (size_t i = 0; i < n; ++i) {
+    elems.emplace_back(*l[i]);
+  }
+  Literal out = LiteralUtil::MakeTupleFromSlices(elems);
   return new Literal(std::move(out));
 }
 
 literal literal_make_tuple_owned(const literal *l, size_t n) {
   std::vector<xla::Literal> elems;
   for (size_t i = 0; i < n; ++i) {
-    elems.push_back(std::move(*(l[i])));
+    // The storage of the literals that borrow it cannot be moved.
+    if (auto owned = dynamic_cast<Literal *>(l[i])) {
+      elems.push_back(std::move(*owned));
+    } else {
+      elems.push_back(l[i]->Clone());
+    }
   }
   Literal out = LiteralUtil::MakeTupleOwned(std::move(elems));
   return new Literal(std::move(out));
@@ -1118,6 +2004,172 @@ status hlo_module_proto_parse_proto(const char *d, size_t len, bool binary,
   return nullptr;
 }
 
+// Run dead code elimination and algebraic simplification until reaching a fixed
+// point.
+status hlo_module_proto_simplify(const hlo_module_proto p,
+                                 hlo_module_proto *output) {
+  ASSIGN_OR_RETURN_STATUS(config,
+                          HloModule::CreateModuleConfigFromProto(*p, {}));
+  ASSIGN_OR_RETURN_STATUS(module, HloModule::CreateFromProto(*p, config));
+  HloPassPipeline pipeline("simplify");
+  auto &fix = pipeline.AddPass<HloPassFix<HloPassPipeline>>("simplify-fix");
+  fix.AddPass<AlgebraicSimplifier>(AlgebraicSimplifierOptions());
+  fix.AddPass<HloDCE>();
+  MAYBE_RETURN_STATUS(pipeline.Run(module.get()).status());
+  *output = new HloModuleProto(module->ToProto());
+  return nullptr;
+}
+
+// Only run the auto-sharding pass, this annotates the instructions with the
+// shardings chosen for the mesh but does not partition the module.
+status hlo_module_proto_auto_shard(const hlo_module_proto p,
+                                   const int64_t *mesh_shape,
+                                   size_t nmesh_shape, const int64_t *mesh_ids,
+                                   size_t nmesh_ids, int64_t memory_budget_gb,
+                                   hlo_module_proto *output) {
+  AutoShardingOption option;
+  option.enable = true;
+  option.device_mesh_shape.assign(mesh_shape, mesh_shape + nmesh_shape);
+  int64_t num_partitions = 1;
+  for (auto d : option.device_mesh_shape) {
+    num_partitions *= d;
+  }
+  if (nmesh_ids == 0) {
+    for (int64_t i = 0; i < num_partitions; ++i) {
+      option.device_mesh_ids.push_back(i);
+    }
+  } else {
+    option.device_mesh_ids.assign(mesh_ids, mesh_ids + nmesh_ids);
+  }
+  if (memory_budget_gb > 0) {
+    option.memory_budget_per_device = memory_budget_gb * (1LL << 30);
+  }
+  ASSIGN_OR_RETURN_STATUS(config,
+                          HloModule::CreateModuleConfigFromProto(*p, {}));
+  config.set_num_partitions(num_partitions);
+  config.set_use_spmd_partitioning(true);
+  ASSIGN_OR_RETURN_STATUS(module, HloModule::CreateFromProto(*p, config));
+  MAYBE_RETURN_STATUS(AutoSharding(option).Run(module.get()).status());
+  *output = new HloModuleProto(module->ToProto());
+  return nullptr;
+}
+
+char *hlo_module_proto_entry_computation_name(const hlo_module_proto p) {
+  return strdup(p->entry_computation_name().c_str());
+}
+
+int64_t hlo_module_proto_entry_computation_id(const hlo_module_proto p) {
+  return p->entry_computation_id();
+}
+
+// Rename the entry computation, both in the module and in the computation
+// itself so that the two stay consistent.
+void hlo_module_proto_set_entry_computation_name(hlo_module_proto p,
+                                                 const char *name) {
+  p->set_entry_computation_name(name);
+  for (auto &computation : *p->mutable_computations()) {
+    if (computation.id() == p->entry_computation_id()) {
+      computation.set_name(name);
+    }
+  }
+}
+
+// Returns false if the module has no host program shape, otherwise the
+// parameters are returned as a tuple shape and `names` holds one string per
+// parameter.
+bool hlo_module_proto_host_program_shape(const hlo_module_proto p,
+                                         shape *parameters, shape *result,
+                                         char ***names) {
+  if (!p->has_host_program_shape()) {
+    return false;
+  }
+  auto &program_shape = p->host_program_shape();
+  std::vector<Shape> parameter_shapes;
+  *names = (char **)malloc(program_shape.parameters_size() * sizeof(char *));
+  for (int i = 0; i < program_shape.parameters_size(); ++i) {
+    parameter_shapes.push_back(Shape(program_shape.parameters(i)));
+    (*names)[i] = strdup(i < program_shape.parameter_names_size()
+                             ? program_shape.parameter_names(i).c_str()
+                             : "");
+  }
+  *parameters = new Shape(ShapeUtil::MakeTupleShape(parameter_shapes));
+  *result = new Shape(program_shape.result());
+  return true;
+}
+
+void hlo_module_proto_set_host_program_shape(hlo_module_proto p,
+                                             const shape *parameters,
+                                             const char **names,
+                                             size_t nparameters,
+                                             const shape result) {
+  ProgramShapeProto program_shape;
+  for (size_t i = 0; i < nparameters; ++i) {
+    *program_shape.add_parameters() = parameters[i]->ToProto();
+    program_shape.add_parameter_names(names[i]);
+  }
+  *program_shape.mutable_result() = result->ToProto();
+  *p->mutable_host_program_shape() = program_shape;
+}
+
+int hlo_module_proto_alias_count(const hlo_module_proto p) {
+  return p->input_output_alias().entries_size();
+}
+
+// The shape indexes are returned as arrays allocated with malloc.
+void hlo_module_proto_alias(const hlo_module_proto p, int i,
+                            int64_t **output_index, size_t *noutput_index,
+                            int64_t *parameter_number,
+                            int64_t **parameter_index,
+                            size_t *nparameter_index, int *kind) {
+  auto &entry = p->input_output_alias().entries(i);
+  *noutput_index = entry.output_shape_index_size();
+  *output_index = (int64_t *)malloc(*noutput_index * sizeof(int64_t));
+  for (size_t j = 0; j < *noutput_index; ++j) {
+    (*output_index)[j] = entry.output_shape_index(j);
+  }
+  *parameter_number = entry.parameter_number();
+  *nparameter_index = entry.parameter_shape_index_size();
+  *parameter_index = (int64_t *)malloc(*nparameter_index * sizeof(int64_t));
+  for (size_t j = 0; j < *nparameter_index; ++j) {
+    (*parameter_index)[j] = entry.parameter_shape_index(j);
+  }
+  *kind = entry.kind();
+}
+
+void hlo_module_proto_clear_aliases(hlo_module_proto p) {
+  p->mutable_input_output_alias()->clear_entries();
+}
+
+void hlo_module_proto_add_alias(hlo_module_proto p, const int64_t *output_index,
+                                size_t noutput_index, int64_t parameter_number,
+                                const int64_t *parameter_index,
+                                size_t nparameter_index, int kind) {
+  auto entry = p->mutable_input_output_alias()->add_entries();
+  for (size_t j = 0; j < noutput_index; ++j) {
+    entry->add_output_shape_index(output_index[j]);
+  }
+  entry->set_parameter_number(parameter_number);
+  for (size_t j = 0; j < nparameter_index; ++j) {
+    entry->add_parameter_shape_index(parameter_index[j]);
+  }
+  entry->set_kind((Kind)kind);
+}
+
+size_t hlo_module_proto_frontend_attributes(const hlo_module_proto p,
+                                            char ***keys, char ***values) {
+  std::vector<std::pair<std::string, std::string>> attrs;
+  for (const auto &[key, value] : p->frontend_attributes().map()) {
+    attrs.emplace_back(key, value);
+  }
+  return attributes_to_c(attrs, keys, values);
+}
+
+void hlo_module_proto_set_frontend_attribute(hlo_module_proto p,
+                                             const char *key,
+                                             const char *value) {
+  (*p->mutable_frontend_attributes()->mutable_map())[key] = value;
+}
+
 xla_computation
 xla_computation_from_hlo_module_proto(const hlo_module_proto p) {
   return new XlaComputation(*p);
@@ -1162,6 +2214,99 @@ char *hlo_instruction_proto_opcode(hlo_instruction_proto i) {
   return strdup(std::string(i->opcode()).c_str());
 }
 
+// The value of a constant instruction, `output` is set to null for other
+// instructions.
+status hlo_instruction_proto_literal(const hlo_instruction_proto i,
+                                     literal *output) {
+  *output = nullptr;
+  if (i->opcode() != "constant" || !i->has_literal()) {
+    return nullptr;
+  }
+  ASSIGN_OR_RETURN_STATUS(l, Literal::CreateFromProto(i->literal()));
+  *output = new Literal(std::move(l));
+  return nullptr;
+}
+
+int64_t hlo_instruction_proto_id(const hlo_instruction_proto i) {
+  return i->id();
+}
+
+char *hlo_instruction_proto_backend_config(const hlo_instruction_proto i) {
+  return strdup(i->backend_config().c_str());
+}
+
+size_t hlo_instruction_proto_frontend_attributes(const hlo_instruction_proto i,
+                                                 char ***keys, char ***values) {
+  std::vector<std::pair<std::string, std::string>> attrs;
+  for (const auto &[key, value] : i->frontend_attributes().map()) {
+    attrs.emplace_back(key, value);
+  }
+  return attributes_to_c(attrs, keys, values);
+}
+
+// The devices are always returned as an explicit list, including for the
+// shardings that are stored in the compact iota format.
+status hlo_instruction_proto_sharding(const hlo_instruction_proto i,
+                                      bool *has_sharding, int *type,
+                                      int64_t **dims, size_t *ndims,
+                                      int64_t **devices, size_t *ndevices,
+                                      bool *replicate_on_last_tile_dim) {
+  *has_sharding = i->has_sharding();
+  if (!*has_sharding) {
+    return nullptr;
+  }
+  auto &proto = i->sharding();
+  ASSIGN_OR_RETURN_STATUS(sharding, HloSharding::FromProto(proto));
+  std::vector<int64_t> device_list;
+  if (sharding.IsTiled()) {
+    sharding.tile_assignment().Each(
+        [&](absl::Span<const int64_t>, int64_t device) {
+          device_list.push_back(device);
+        });
+  } else {
+    device_list.assign(proto.tile_assignment_devices().begin(),
+                       proto.tile_assignment_devices().end());
+  }
+  *type = proto.type();
+  *ndims = proto.tile_assignment_dimensions_size();
+  *dims = (int64_t *)malloc(*ndims * sizeof(int64_t));
+  for (size_t j = 0; j < *ndims; ++j) {
+    (*dims)[j] = proto.tile_assignment_dimensions(j);
+  }
+  *ndevices = device_list.size();
+  *devices = (int64_t *)malloc(*ndevices * sizeof(int64_t));
+  std::copy(device_list.begin(), device_list.end(), *devices);
+  *replicate_on_last_tile_dim = proto.replicate_on_last_tile_dim();
+  return nullptr;
+}
+
+int hlo_instruction_proto_operand_count(const hlo_instruction_proto i) {
+  return i->operand_ids_size();
+}
+
+void hlo_instruction_proto_operand_ids(const hlo_instruction_proto i,
+                                       int64_t *out_ids) {
+  for (int j = 0; j < i->operand_ids_size(); ++j) {
+    out_ids[j] = i->operand_ids(j);
+  }
+}
+
+int64_t hlo_computation_proto_root_id(const hlo_computation_proto c) {
+  return c->root_id();
+}
+
+// A copy of the instruction with the given id, or null if there is none.
+hlo_instruction_proto
+hlo_computation_proto_instruction_by_id(const hlo_computation_proto c,
+                                        int64_t id) {
+  for (auto &instruction : c->instructions()) {
+    if (instruction.id() == id) {
+      return new HloInstructionProto(instruction);
+    }
+  }
+  return nullptr;
+}
+
 // void hlo_module_free(hlo_module m) { delete m; }
 // void hlo_computation_free(hlo_computation c) { delete c; }
 // void hlo_instruction_free(hlo_instruction i) { delete i; }
@@ -1171,3 +2316,37 @@ void hlo_module_proto_free(hlo_module_proto p) { delete p; }
 hlo_module_proto xla_computation_proto(const xla_computation c) {
   return new HloModuleProto(c->proto());
 }
+
+status xla_computation_fingerprint(const xla_computation c, char **output) {
+  ASSIGN_OR_RETURN_STATUS(
+      config, HloModule::CreateModuleConfigFromProto(c->proto(), {}));
+  ASSIGN_OR_RETURN_STATUS(module,
+                          HloModule::CreateFromProto(c->proto(), config));
+  // The fingerprint is computed on the canonical text representation of the
+  // module so it does not depend on the instruction names or ids.
+  *output = strdup(
+      module->GetFingerprint128(HloPrintOptions::Canonical()).c_str());
+  return nullptr;
+}
+
+status xla_computation_to_stablehlo(const xla_computation c, char **output) {
+  mlir::MLIRContext context;
+  context.loadDialect<mlir::func::FuncDialect, mlir::mhlo::MhloDialect,
+                      mlir::stablehlo::StablehloDialect>();
+  mlir::OwningOpRef<mlir::ModuleOp> module =
+      mlir::ModuleOp::create(mlir::UnknownLoc::get(&context));
+  MAYBE_RETURN_STATUS(ConvertHloToMlirHlo(*module, &c->proto(),
+                                          /*import_all_computations=*/true));
+  mlir::PassManager pm(&context);
+  pm.addPass(mlir::mhlo::createHloLegalizeToStablehloPass());
+  if (mlir::failed(pm.run(*module))) {
+    return new Status(
+        tsl::errors::Internal("failed to legalize mhlo to stablehlo"));
+  }
+  std::string s;
+  llvm::raw_string_ostream os(s);
+  module->print(os);
+  os.flush();
+  *output = strdup(s.c_str());
+  return nullptr;
+}
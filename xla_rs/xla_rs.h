@@ -10,18 +10,41 @@
 #pragma GCC diagnostic ignored "-Wreturn-type"
 #include "xla/client/client_library.h"
 #include "xla/client/lib/constants.h"
+#include "xla/client/lib/math.h"
 #include "xla/client/lib/matrix.h"
 #include "xla/client/xla_builder.h"
+#include "xla/debug_options_flags.h"
 #include "xla/literal_util.h"
 #include "xla/pjrt/gpu/gpu_helpers.h"
 #include "xla/pjrt/gpu/se_gpu_pjrt_client.h"
+#include "xla/pjrt/pjrt_api.h"
+#include "xla/pjrt/pjrt_c_api_client.h"
 #include "xla/pjrt/pjrt_client.h"
 #include "xla/pjrt/pjrt_stream_executor_client.h"
 #include "xla/pjrt/tfrt_cpu_pjrt_client.h"
+#include "absl/synchronization/notification.h"
+#include "mlir/IR/BuiltinOps.h"
+#include "mlir/IR/MLIRContext.h"
+#include "mlir/Pass/PassManager.h"
+#include "stablehlo/dialect/StablehloOps.h"
+#include "xla/mlir_hlo/mhlo/IR/hlo_ops.h"
+#include "xla/mlir_hlo/mhlo/transforms/passes.h"
+#include "xla/translate/hlo_to_mhlo/hlo_to_mlir_hlo.h"
+#include "xla/service/algebraic_simplifier.h"
+#include "xla/service/cpu/cpu_executable.h"
+#include "xla/service/gpu/gpu_executable.h"
+#include "xla/hlo/evaluator/hlo_evaluator.h"
+#include "xla/hlo/experimental/auto_sharding/auto_sharding.h"
+#include "xla/hlo/ir/hlo_sharding.h"
+#include "xla/service/hlo_dce.h"
 #include "xla/service/hlo_parser.h"
+#include "xla/service/hlo_pass_fix.h"
+#include "xla/service/hlo_pass_pipeline.h"
+#include "xla/service/shape_inference.h"
 #include "xla/shape_util.h"
 #include "xla/statusor.h"
 #include "xla/xla_data.pb.h"
+#include "tsl/platform/logging.h"
 #pragma GCC diagnostic pop
 using namespace xla;
 
@@ -34,7 +57,9 @@ typedef XlaBuilder *xla_builder;
 typedef XlaOp *xla_op;
 typedef Status *status;
 typedef Shape *shape;
-typedef Literal *literal;
+// Most literals are owning Literal objects, the ones created by
+// literal_create_from_shape_in borrow some storage allocated by the caller.
+typedef MutableLiteralBase *literal;
 typedef XlaComputation *xla_computation;
 typedef HloModuleProto *hlo_module_proto;
 typedef TriangularSolveOptions::Transpose triangularsolveoptions_transpose;
@@ -66,9 +91,22 @@ typedef struct _hlo_computation_proto *hlo_computation_proto;
 typedef struct _hlo_instruction_proto *hlo_instruction_proto;
 #endif
 
+// The arguments are the severity, the VLOG verbosity (0 for the messages that
+// are not verbose), the file name, the line, and the message.
+typedef void (*log_callback)(int, int, const char *, int, const char *);
+void set_log_callback(log_callback);
+void tsl_runtime_init();
+int64_t xla_gpu_nccl_termination_timeout_seconds();
+
 status pjrt_cpu_client_create(pjrt_client *);
+status pjrt_cpu_client_create_with_device_count(pjrt_client *, int);
 status pjrt_gpu_client_create(pjrt_client *, double, bool);
+status pjrt_gpu_client_create_with_options(pjrt_client *, double, bool,
+                                           const int64_t *, size_t);
 // status pjrt_tpu_client_create(pjrt_client *, int);
+status pjrt_plugin_load(const char *, const char *);
+status pjrt_plugin_initialize(const char *);
+status pjrt_plugin_client_create(pjrt_client *, const char *);
 void pjrt_client_free(pjrt_client);
 int pjrt_client_device_count(pjrt_client);
 int pjrt_client_addressable_device_count(pjrt_client);
@@ -76,8 +114,22 @@ void pjrt_client_devices(pjrt_client, pjrt_device *);
 void pjrt_client_addressable_devices(pjrt_client, pjrt_device *);
 char *pjrt_client_platform_name(pjrt_client);
 char *pjrt_client_platform_version(pjrt_client);
+size_t pjrt_client_attributes(pjrt_client, char ***, char ***);
+bool pjrt_client_has_pinned_host_allocator(pjrt_client);
+status pjrt_client_allocate_host_buffer(pjrt_client, size_t, void **, bool *);
+void pjrt_client_free_host_buffer(pjrt_client, void *, bool);
 
 void pjrt_loaded_executable_free(pjrt_loaded_executable);
+int pjrt_loaded_executable_addressable_device_count(
+    const pjrt_loaded_executable);
+void pjrt_loaded_executable_addressable_devices(const pjrt_loaded_executable,
+                                                pjrt_device *);
+status pjrt_loaded_executable_fusions(const pjrt_loaded_executable, size_t *,
+                                     char ***, char ***, char ***, char ***);
+status pjrt_loaded_executable_backend_ir(const pjrt_loaded_executable, int,
+                                        char **);
+status pjrt_loaded_executable_cost_analysis(const pjrt_loaded_executable,
+                                           size_t *, char ***, char ***);
 
 int pjrt_device_id(pjrt_device);
 int pjrt_device_process_index(pjrt_device);
@@ -87,22 +139,40 @@ status pjrt_device_transfer_from_outfeed(pjrt_device, literal);
 char *pjrt_device_kind(pjrt_device);
 char *pjrt_device_debug_string(pjrt_device);
 char *pjrt_device_to_string(pjrt_device);
+size_t pjrt_device_attributes(pjrt_device, char ***, char ***);
 
+status pjrt_buffer_unsafe_pointer(pjrt_buffer, void **);
+status pjrt_buffer_from_pinned_host_buffer(const pjrt_client, const pjrt_device,
+                                           const void *, int, int,
+                                           const int64_t *, pjrt_buffer *);
 status pjrt_buffer_from_host_literal(const pjrt_client, const pjrt_device,
                                      const literal, pjrt_buffer *);
 status pjrt_buffer_from_host_buffer(const pjrt_client, const pjrt_device,
                                     const void *, int, int, const int64_t *,
                                     pjrt_buffer *);
+status pjrt_buffer_from_device_pointer(const pjrt_client, const pjrt_device,
+                                       void *, int, int, const int64_t *,
+                                       void (*)(void *), void *,
+                                       pjrt_buffer *);
 status pjrt_buffer_to_literal_sync(pjrt_buffer, literal *);
+status pjrt_buffer_to_literal_into(pjrt_buffer, literal);
+status pjrt_buffer_block_until_ready(pjrt_buffer);
+void pjrt_buffer_on_ready(pjrt_buffer, void (*)(void *), void *);
 status pjrt_buffer_copy_raw_to_host_sync(pjrt_buffer, void *, size_t, size_t);
 shape pjrt_buffer_on_device_shape(pjrt_buffer);
 status pjrt_buffer_copy_to_device(pjrt_buffer, pjrt_device, pjrt_buffer *);
+status pjrt_buffer_duplicate(pjrt_buffer, pjrt_buffer *);
 void pjrt_buffer_free(pjrt_buffer);
+void pjrt_buffer_delete(pjrt_buffer);
+bool pjrt_buffer_is_deleted(pjrt_buffer);
+pjrt_device pjrt_buffer_device(pjrt_buffer);
 
 xla_builder xla_builder_create(const char *);
 void xla_builder_free(xla_builder);
 
 xla_op constant_literal(const xla_builder, const literal);
+xla_op constant_literal_borrowed(const xla_builder, int, const int64_t *,
+                                size_t, const void *);
 xla_op parameter(const xla_builder, int64_t, int, int, const int64_t *,
                  const char *);
 xla_op parameter_s(const xla_builder, int64_t, const shape, const char *);
@@ -122,6 +192,9 @@ xla_op op_or(const xla_op, const xla_op);
 xla_op op_xor(const xla_op, const xla_op);
 xla_op op_atan2(const xla_op, const xla_op);
 xla_op op_pow(const xla_op, const xla_op);
+xla_op op_shift_left(const xla_op, const xla_op);
+xla_op op_shift_right_arithmetic(const xla_op, const xla_op);
+xla_op op_shift_right_logical(const xla_op, const xla_op);
 xla_op op_dot(const xla_op, const xla_op);
 xla_op op_dot_general(const xla_op, const xla_op, const int64_t *, size_t,
                       const int64_t *, size_t, const int64_t *, size_t,
@@ -153,6 +226,8 @@ xla_op op_sqrt(const xla_op);
 xla_op op_rsqrt(const xla_op);
 xla_op op_cbrt(const xla_op);
 xla_op op_is_finite(const xla_op);
+xla_op op_erf(const xla_op);
+xla_op op_erf_inv(const xla_op);
 xla_op op_neg(const xla_op);
 xla_op op_lower_triangle(const xla_op);
 xla_op op_upper_triangle(const xla_op);
@@ -166,6 +241,7 @@ xla_op op_zero(const xla_builder, int);
 xla_op op_one(const xla_builder, int);
 xla_op op_min_value(const xla_builder, int);
 xla_op op_max_value(const xla_builder, int);
+xla_op op_min_finite_value(const xla_builder, int);
 xla_op op_reshape(const xla_op, size_t, const int64_t *);
 xla_op op_broadcast(const xla_op, size_t, const int64_t *);
 xla_op op_broadcast_in_dim(const xla_op, size_t, const int64_t *, size_t,
@@ -176,17 +252,43 @@ xla_op op_clamp(const xla_op, const xla_op, const xla_op);
 xla_op op_select(const xla_op, const xla_op, const xla_op);
 xla_op op_rng_uniform(const xla_op, const xla_op, int, int, const int64_t *);
 xla_op op_rng_normal(const xla_op, const xla_op, int, int, const int64_t *);
+xla_op op_rng_bit_generator(int, const xla_op, int, int, const int64_t *);
 xla_op op_slice_in_dim(const xla_op, int64_t, int64_t, int64_t, int64_t);
 xla_op op_concat_in_dim(const xla_op, const xla_op *, size_t, int64_t);
+xla_op op_dynamic_slice(const xla_op, const xla_op *, size_t, const int64_t *,
+                        size_t);
+xla_op op_dynamic_update_slice(const xla_op, const xla_op, const xla_op *,
+                               size_t);
+xla_op op_pad(const xla_op, const xla_op, const int64_t *, const int64_t *,
+              const int64_t *, size_t);
 xla_op op_tuple(const xla_builder, const xla_op *, size_t);
 xla_op op_get_tuple_element(const xla_op, int64_t);
+xla_op op_get_dimension_size(const xla_op, int64_t);
+xla_op op_set_dimension_size(const xla_op, const xla_op, int64_t);
 xla_op op_gather(const xla_op, const xla_op, const int64_t *, size_t,
                  const int64_t *, size_t, const int64_t *, size_t,
                  const int64_t *, const int64_t *, size_t);
+xla_op op_scatter(const xla_op, const xla_op, const xla_op,
+                  const xla_computation, const int64_t *, size_t,
+                  const int64_t *, size_t, const int64_t *, size_t, int64_t,
+                  bool, bool);
 xla_op op_convert_element_type(const xla_op, int);
+xla_op op_bitcast_convert_element_type(const xla_op, int);
 xla_op op_dimensions_size(const xla_op, int64_t);
 xla_op op_reduce(const xla_op, const xla_op, const xla_computation,
                  const int64_t *, size_t);
+xla_op op_all_reduce(const xla_op, const xla_computation, const int64_t *,
+                     const size_t *, size_t, int64_t);
+xla_op op_all_gather(const xla_op, int64_t, int64_t, const int64_t *,
+                     const size_t *, size_t, int64_t);
+xla_op op_all_to_all(const xla_op, int64_t, int64_t, int64_t, const int64_t *,
+                     const size_t *, size_t, int64_t);
+xla_op op_reduce_window(const xla_op, const xla_op, const xla_computation,
+                        const int64_t *, size_t, const int64_t *, size_t,
+                        const int64_t *, size_t);
+xla_op op_sort(const xla_op, const xla_op *, size_t, const xla_computation,
+               int64_t, bool);
+xla_op op_top_k(const xla_op, int64_t);
 xla_op op_internal_error(const xla_builder, const char *);
 xla_op op_unknown_error(const xla_builder, const char *);
 xla_op op_invalid_argument_error(const xla_builder, const char *);
@@ -195,9 +297,17 @@ xla_op op_iota(const xla_builder, int, size_t, const int64_t *, int64_t);
 xla_op op_while(const xla_computation, const xla_computation, const xla_op);
 xla_op op_conditional(const xla_op, const xla_op, const xla_computation,
                       const xla_op, const xla_computation);
+xla_op op_indexed_conditional(const xla_op, const xla_computation *,
+                              const xla_op *, size_t);
 xla_op op_triangular_solve(const xla_op, const xla_op, const bool, const bool,
                            const bool, const int);
 xla_builder op_builder(const xla_op);
+status op_set_frontend_attribute(const xla_op, const char *, const char *);
+status op_set_backend_config(const xla_op, const char *);
+status op_set_sharding(const xla_op, int, const int64_t *, size_t,
+                       const int64_t *, size_t, bool);
+status op_is_constant(const xla_op, bool *);
+status op_evaluate_constant(const xla_op, literal *);
 
 int xla_op_valid(const xla_op);
 void xla_op_free(xla_op);
@@ -212,6 +322,9 @@ shape make_shape_array(int, size_t, const int64_t *);
 shape make_shape_tuple(size_t, const shape *);
 
 status get_shape(const xla_builder, const xla_op, shape *);
+bool try_get_shape(const xla_builder, const xla_op, shape *);
+bool infer_binary_op_shape(const xla_builder, int, const xla_op, const xla_op,
+                           shape *);
 status get_element_type(const xla_builder, const xla_op, int *);
 status get_dimensions_size(const xla_builder, const xla_op, int *);
 status get_dimensions(const xla_builder, const xla_op, size_t *);
@@ -219,14 +332,24 @@ status get_dimensions(const xla_builder, const xla_op, size_t *);
 status build(const xla_builder, const xla_op, xla_computation *);
 status compile(const pjrt_client, const xla_computation,
                pjrt_loaded_executable *);
+status compile_replicated(const pjrt_client, const xla_computation, int,
+                          pjrt_loaded_executable *);
 status execute(const pjrt_loaded_executable, const literal *, int,
                pjrt_buffer ***);
+status execute_many(const pjrt_loaded_executable, const literal *, int, int,
+                    pjrt_buffer ****);
 status execute_b(const pjrt_loaded_executable, const pjrt_buffer *, int,
                  pjrt_buffer ***);
+status execute_b_replicated(const pjrt_loaded_executable, const pjrt_buffer *,
+                            int, int, pjrt_buffer ***);
 status first_error(const xla_builder);
 status get_current_status(const xla_builder);
 
-literal literal_create_from_shape(int, const int64_t *, size_t);
+status literal_create_from_shape(int, const int64_t *, size_t, literal *);
+// Called with the context and the storage pointer when the literal is freed.
+typedef void (*literal_storage_free)(void *, void *);
+literal literal_create_from_shape_in(int, const int64_t *, size_t, void *,
+                                     literal_storage_free, void *);
 literal literal_create_from_shape_and_data(int, const int64_t *, size_t,
                                            const void *, size_t);
 literal literal_clone(const literal);
@@ -236,8 +359,10 @@ int64_t literal_element_count(const literal);
 int literal_element_type(const literal);
 void literal_shape(const literal, shape *);
 void literal_decompose_tuple(literal, literal *, size_t);
+literal literal_tuple_element(const literal, int64_t);
 int64_t literal_size_bytes(const literal);
 void literal_copy_to(const literal, void *, size_t);
+void *literal_untyped_data(literal);
 void literal_copy_from(literal, const void *, size_t);
 literal literal_make_tuple(const literal *, size_t);
 literal literal_make_tuple_owned(const literal *, size_t);
@@ -247,6 +372,29 @@ status hlo_module_proto_parse_and_return_unverified_module(const char *, size_t,
                                                            hlo_module_proto *);
 status hlo_module_proto_parse_proto(const char *, size_t, bool,
                                     hlo_module_proto *);
+status hlo_module_proto_simplify(const hlo_module_proto, hlo_module_proto *);
+status hlo_module_proto_auto_shard(const hlo_module_proto, const int64_t *,
+                                   size_t, const int64_t *, size_t, int64_t,
+                                   hlo_module_proto *);
+char *hlo_module_proto_entry_computation_name(const hlo_module_proto);
+int64_t hlo_module_proto_entry_computation_id(const hlo_module_proto);
+void hlo_module_proto_set_entry_computation_name(hlo_module_proto,
+                                                 const char *);
+bool hlo_module_proto_host_program_shape(const hlo_module_proto, shape *,
+                                         shape *, char ***);
+void hlo_module_proto_set_host_program_shape(hlo_module_proto, const shape *,
+                                             const char **, size_t,
+                                             const shape);
+int hlo_module_proto_alias_count(const hlo_module_proto);
+void hlo_module_proto_alias(const hlo_module_proto, int, int64_t **, size_t *,
+                            int64_t *, int64_t **, size_t *, int *);
+void hlo_module_proto_clear_aliases(hlo_module_proto);
+void hlo_module_proto_add_alias(hlo_module_proto, const int64_t *, size_t,
+                                int64_t, const int64_t *, size_t, int);
+size_t hlo_module_proto_frontend_attributes(const hlo_module_proto, char ***,
+                                            char ***);
+void hlo_module_proto_set_frontend_attribute(hlo_module_proto, const char *,
+                                             const char *);
 xla_computation xla_computation_from_hlo_module_proto(const hlo_module_proto);
 void hlo_module_proto_free(hlo_module_proto);
 
@@ -260,12 +408,27 @@ status hlo_instruction_protos(const hlo_computation_proto,
 void hlo_instruction_proto_free(hlo_instruction_proto);
 
 char *hlo_instruction_proto_opcode(hlo_instruction_proto);
+status hlo_instruction_proto_literal(const hlo_instruction_proto, literal *);
+int64_t hlo_instruction_proto_id(const hlo_instruction_proto);
+char *hlo_instruction_proto_backend_config(const hlo_instruction_proto);
+size_t hlo_instruction_proto_frontend_attributes(const hlo_instruction_proto,
+                                                 char ***, char ***);
+status hlo_instruction_proto_sharding(const hlo_instruction_proto, bool *,
+                                      int *, int64_t **, size_t *, int64_t **,
+                                      size_t *, bool *);
+int hlo_instruction_proto_operand_count(const hlo_instruction_proto);
+void hlo_instruction_proto_operand_ids(const hlo_instruction_proto, int64_t *);
+int64_t hlo_computation_proto_root_id(const hlo_computation_proto);
+hlo_instruction_proto
+hlo_computation_proto_instruction_by_id(const hlo_computation_proto, int64_t);
 // void hlo_module_free(hlo_module);
 // void hlo_computation_free(hlo_computation);
 // void hlo_instruction_free(hlo_instruction);
 
 char *xla_computation_name(xla_computation);
 hlo_module_proto xla_computation_proto(const xla_computation);
+status xla_computation_to_stablehlo(const xla_computation, char **);
+status xla_computation_fingerprint(const xla_computation, char **);
 void xla_computation_free(xla_computation);
 
 void status_free(status);